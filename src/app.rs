@@ -5,6 +5,7 @@ use crate::{
   confirm_dlg::{Choice, ConfirmDlg, Hence},
   experience::Experience,
   farming::Farming,
+  game_data,
   offline::Offline,
   stats::{Stats, StatsFilter},
   util,
@@ -42,6 +43,9 @@ pub struct App {
   state: AppState,
   page: Page,
 
+  // Last seen state generation.
+  generation: u64,
+
   // Tab pages.
   chronometer: Chronometer,
   experience: Experience,
@@ -96,7 +100,7 @@ impl App {
       locale,
     );
     let farming = Farming::new(cc.egui_ctx.clone(), config.clone(), state.clone());
-    let offline = Offline::new(state.clone());
+    let offline = Offline::new(state.clone(), locale);
     let stats = Stats::new(log_path, threads, config.clone(), state.clone(), locale);
 
     if page == Page::Chronometer {
@@ -113,6 +117,7 @@ impl App {
       config,
       state,
       page,
+      generation: 0,
       chronometer,
       experience,
       farming,
@@ -258,9 +263,11 @@ impl App {
       return;
     };
 
-    let filter = Box::new({
-      let ext = Some(OsStr::new("sota"));
-      move |path: &Path| path.extension() == ext
+    // Also show the backups, but not files that aren't save-games.
+    let filter = Box::new(|path: &Path| {
+      let ext = path.extension();
+      (ext == Some(OsStr::new(game_data::SOTA_EXT)) || ext == Some(OsStr::new(game_data::BAK_EXT)))
+        && game_data::GameData::sniff(path).is_ok()
     });
 
     let available = ctx.available_rect().size();
@@ -309,6 +316,13 @@ impl eframe::App for App {
       self.choose_load_path(ctx);
     }
 
+    // Update the window title when the loaded file or its dirty state changes.
+    let generation = self.state.generation();
+    if generation != self.generation {
+      self.generation = generation;
+      ctx.send_viewport_cmd(ViewportCommand::Title(window_title(&self.state)));
+    }
+
     // Set the progress cursor if the app is busy.
     if self.state.is_busy() {
       ctx.output_mut(|output| output.cursor_icon = CursorIcon::Progress);
@@ -416,6 +430,11 @@ impl eframe::App for App {
       });
     });
 
+    // Remember the content hash of a newly stored save-game.
+    if let Some((path, hash)) = self.offline.take_stored_hash() {
+      self.config.set_save_game_hash(&path, hash);
+    }
+
     // Put the dialogs here so that they're anchored below the menu-bar.
     if let Some(file_dlg) = &mut self.file_dlg {
       if !file_dlg.show(ctx).visible() {
@@ -431,6 +450,7 @@ impl eframe::App for App {
                 let folder = path.with_file_name(String::default());
                 if self.offline.load(path.to_owned()) {
                   self.config.set_save_game_path(&folder);
+                  check_save_game_hash(&mut self.config, &mut self.offline, path);
                 }
               }
               egui_file::DialogType::SaveFile => self.offline.store_as(path.to_owned()),
@@ -534,6 +554,10 @@ impl eframe::App for App {
   }
 
   fn on_exit(&mut self, _: Option<&glow::Context>) {
+    if let Some((path, hash)) = self.offline.take_stored_hash() {
+      self.config.set_save_game_hash(&path, hash);
+    }
+
     self.chronometer.on_exit();
     self.experience.on_exit();
     self.farming.on_exit();
@@ -547,6 +571,28 @@ enum CloseStatus {
   CancelClose,
 }
 
+/// Check if the save-game was modified outside of CotA since it was last opened.
+fn check_save_game_hash(config: &mut Config, offline: &mut Offline, path: &Path) {
+  let Some(hash) = offline.content_hash() else {
+    return;
+  };
+
+  if let Some(prev) = config.get_save_game_hash(path) {
+    offline.set_modified(prev != hash);
+  }
+  config.set_save_game_hash(path, hash);
+}
+
+fn window_title(state: &AppState) -> String {
+  let Some(path) = state.current_file() else {
+    return util::APP_TITLE.into();
+  };
+
+  let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+  let dirty = if state.is_dirty() { "*" } else { "" };
+  format!("{} - {dirty}{file_name}", util::APP_TITLE)
+}
+
 fn top_panel<R>(ctx: &Context, contents: impl FnOnce(&mut Ui) -> R) {
   const MENU: &str = "Menu";
   TopBottomPanel::top(MENU)
@@ -0,0 +1,180 @@
+use crate::game_data::{self, GameData};
+use serde_json::Value;
+use std::{
+  borrow::Cow,
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// What's in a save-game, for telling backups apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveSummary {
+  pub avatar: String,
+  pub adv_lvl: i32,
+  pub prd_lvl: i32,
+  pub gold: i64,
+}
+
+impl SaveSummary {
+  fn new(game: &GameData) -> Self {
+    Self {
+      avatar: game.avatar_name().into(),
+      adv_lvl: game.get_adv_lvl(),
+      prd_lvl: game.get_prd_lvl(),
+      gold: game.total_gold(),
+    }
+  }
+}
+
+/// A backup of a save-game, from [`list`].
+#[derive(Clone, Debug)]
+pub struct BackupInfo {
+  pub path: PathBuf,
+
+  /// When the backup was made, from its name (`<name>.bak-<seconds>`) or else its modification
+  /// time.
+  pub timestamp: Option<SystemTime>,
+  pub size: u64,
+
+  /// The backup's contents, or why it couldn't be loaded.
+  pub summary: Result<SaveSummary, Cow<'static, str>>,
+}
+
+/// Differences between a save-game and one of its backups, from [`diff`].
+#[derive(Clone, Debug)]
+pub struct SaveDiff {
+  pub original: SaveSummary,
+  pub backup: SaveSummary,
+
+  /// JSON Patch that turns the original's records into the backup's. See [`GameData::diff`].
+  pub patch: Value,
+}
+
+/// List the backups of `original` in `dir`, newest first (see [`game_data::parse_backup_name`]).
+/// Backups that can't be loaded are still listed.
+pub fn list(dir: &Path, original: &Path) -> Vec<BackupInfo> {
+  let mut backups = Vec::new();
+  let Some(stem) = original.file_stem().and_then(|stem| stem.to_str()) else {
+    return backups;
+  };
+
+  let entries = ok!(dir.read_dir(), backups);
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+      continue;
+    };
+
+    let Some((name_stem, stamp)) = game_data::parse_backup_name(name) else {
+      continue;
+    };
+
+    let Ok(meta) = entry.metadata() else {
+      continue;
+    };
+
+    if name_stem != stem || !meta.is_file() {
+      continue;
+    }
+
+    let timestamp = match stamp {
+      Some(secs) => UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+      None => meta.modified().ok(),
+    };
+    let summary = GameData::peek(&path)
+      .map(|game| SaveSummary::new(&game))
+      .map_err(Cow::from);
+    backups.push(BackupInfo {
+      path,
+      timestamp,
+      size: meta.len(),
+      summary,
+    });
+  }
+
+  backups.sort_by(|a, b| {
+    b.timestamp
+      .cmp(&a.timestamp)
+      .then_with(|| a.path.cmp(&b.path))
+  });
+  backups
+}
+
+/// Compare a save-game with one of its backups.
+pub fn diff(original: &Path, backup: &Path) -> Result<SaveDiff, Cow<'static, str>> {
+  let original = GameData::load(original)?;
+  let backup = GameData::peek(backup)?;
+  Ok(SaveDiff {
+    original: SaveSummary::new(&original),
+    backup: SaveSummary::new(&backup),
+    patch: original.diff(&backup),
+  })
+}
+
+/// Replace a save-game with one of its backups. See [`game_data::restore_backup`].
+pub fn restore(backup: &Path, target: &Path) -> Result<(), game_data::Error> {
+  game_data::restore_backup(backup, target)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::save_builder::SaveBuilder;
+  use std::fs;
+
+  #[test]
+  fn test_backups() {
+    let dir = std::env::temp_dir().join("cota_test_backups");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let original = dir.join("SavedGame.sota");
+    let text = SaveBuilder::new().avatar("Barugon").gold(500).build();
+    fs::write(&original, &text).unwrap();
+    let older = SaveBuilder::new().avatar("Barugon").gold(100).build();
+    fs::write(dir.join("SavedGame.sota.bak-1000000000"), &older).unwrap();
+    fs::write(dir.join("SavedGame.sota.bak-1100000000"), &text).unwrap();
+    fs::write(dir.join("SavedGame.bak"), "corrupted").unwrap();
+    fs::write(dir.join("Other.sota.bak-1900000000"), &text).unwrap();
+    fs::write(dir.join("SavedGame.sota.bak-soon"), &text).unwrap();
+    fs::write(dir.join("SavedGame.Old.sota.bak"), &text).unwrap();
+
+    // Newest first, and the corrupted backup is still listed.
+    let backups = list(&dir, &original);
+    let names: Vec<_> = backups
+      .iter()
+      .map(|info| info.path.file_name().unwrap().to_str().unwrap())
+      .collect();
+    assert_eq!(names.len(), 3);
+    assert_eq!(
+      names[0..2],
+      ["SavedGame.bak", "SavedGame.sota.bak-1100000000"]
+    );
+    assert!(backups[0].summary.is_err());
+    let stamp = UNIX_EPOCH + Duration::from_secs(1000000000);
+    assert_eq!(backups[2].timestamp, Some(stamp));
+    let summary = backups[2].summary.as_ref().unwrap();
+    assert_eq!(summary.avatar, "Barugon");
+    assert_eq!(summary.gold, 100);
+    assert_eq!(backups[2].size, older.len() as u64);
+
+    let changes = diff(&original, &backups[2].path).unwrap();
+    assert_eq!((changes.original.gold, changes.backup.gold), (500, 100));
+    assert_eq!(changes.patch.as_array().unwrap().len(), 1);
+    assert!(diff(&original, &backups[0].path).is_err());
+
+    // Restoring checks the backup first.
+    assert!(restore(&backups[0].path, &original).is_err());
+    restore(&backups[2].path, &original).unwrap();
+    assert_eq!(fs::read_to_string(&original).unwrap(), older);
+
+    // Dots in the name are part of the stem.
+    let dotted = dir.join("SavedGame.Old.sota");
+    let backups = list(&dir, &dotted);
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].path, dir.join("SavedGame.Old.sota.bak"));
+
+    assert!(list(&dir.join("missing"), &original).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
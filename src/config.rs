@@ -1,7 +1,7 @@
 use crate::{
   plant_info::CropTimer,
   storage::Storage,
-  util::{Page, APP_NAME},
+  util::{self, Page, APP_NAME},
 };
 use std::{
   collections::{BTreeMap, BTreeSet, HashMap},
@@ -25,12 +25,17 @@ impl Config {
     dirs::config_dir().map(|path| path.join(APP_NAME).with_extension("ron"))
   }
 
+  /// Folder containing custom experience tables (for modified shards) and item names.
+  pub fn get_exp_tables_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|path| path.join(APP_NAME))
+  }
+
   fn get_sota_config_path() -> Option<PathBuf> {
     let path = dirs::config_dir()?;
     Some(path.join("Portalarium").join("Shroud of the Avatar"))
   }
 
-  fn get_default_log_path() -> Option<PathBuf> {
+  pub(crate) fn get_default_log_path() -> Option<PathBuf> {
     if let Some(path) = Self::get_sota_config_path() {
       let path = path.join("ChatLogs");
       if path.is_dir() {
@@ -41,6 +46,10 @@ impl Config {
   }
 
   fn get_default_save_game_path() -> Option<PathBuf> {
+    if let Some(dir) = util::default_save_dirs().into_iter().next() {
+      return Some(dir.path);
+    }
+
     if let Some(path) = Self::get_sota_config_path() {
       let path = path.join("SavedGames");
       if path.is_dir() {
@@ -208,8 +217,31 @@ impl Config {
     self.storage.persist();
   }
 
+  pub fn get_save_game_hash(&self, path: &Path) -> Option<u64> {
+    let hashes: HashMap<String, u64> = self.storage.get_as(Config::SAVE_HASHES_KEY)?;
+    hashes.get(path.to_str()?).copied()
+  }
+
+  pub fn set_save_game_hash(&mut self, path: &Path, hash: u64) {
+    let Some(path) = path.to_str() else {
+      println!("Invalid unicode in path: {path:?}");
+      return;
+    };
+
+    let mut hashes: HashMap<String, u64> = self
+      .storage
+      .get_as(Config::SAVE_HASHES_KEY)
+      .unwrap_or_default();
+    if hashes.get(path) != Some(&hash) {
+      hashes.insert(path.to_owned(), hash);
+      self.storage.set_as(Config::SAVE_HASHES_KEY, &hashes);
+      self.storage.persist();
+    }
+  }
+
   const LOG_PATH_KEY: &'static str = "log_path";
   const SAVE_PATH_KEY: &'static str = "save_path";
+  const SAVE_HASHES_KEY: &'static str = "save_hashes";
   const STATS_AVATAR_KEY: &'static str = "stats_avatar";
   const EXP_AVATAR_KEY: &'static str = "experience_avatar";
   const AVATAR_SKILLS: &'static str = "skills";
@@ -234,7 +234,7 @@ impl Experience {
                         let level = get_skill_lvl_mut(&mut self.level_info.skill_lvls, skill.id);
                         body.row(row_size, |mut row| {
                           row.col(|ui| {
-                            let text = RichText::from(skill.name);
+                            let text = RichText::from(skill.display_name());
                             let text = text.color(Color32::from_rgb(102, 154, 180));
                             let widget = Label::new(text).wrap_mode(TextWrapMode::Extend);
                             ui.add(widget);
@@ -287,8 +287,8 @@ impl Experience {
                   // Check if this group contains the selected skill.
                   if skill_group
                     .skills
-                    .binary_search_by(|skill| skill.name.cmp(self.selected.name))
-                    .is_ok()
+                    .iter()
+                    .any(|skill| skill.name == self.selected.name)
                   {
                     // Reset the selection.
                     self.selected = Default::default();
@@ -409,15 +409,9 @@ impl Experience {
   fn get_adv_info(&self) -> Option<AdvInfo> {
     let exp = self.level_info.adv_exp;
     if exp > 0 {
-      let lvl = util::floor_search(exp, LEVEL_EXP).unwrap() as i32 + 1;
-      if lvl < 200 {
-        return Some(AdvInfo {
-          lvl,
-          exp: Some(LEVEL_EXP[lvl as usize] - exp),
-        });
-      } else {
-        return Some(AdvInfo { lvl, exp: None });
-      }
+      let lvl = util::level_for_exp(exp, LEVEL_EXP);
+      let exp = util::exp_for_level(lvl + 1, LEVEL_EXP).map(|next| next - exp);
+      return Some(AdvInfo { lvl, exp });
     }
 
     None
@@ -463,7 +457,7 @@ fn get_needed_exp(level: &(i32, i32), mul: f64) -> Option<i64> {
   if level.0 > 0 || level.1 > 0 {
     let cur_lvl = level.0.max(1);
     let tgt_lvl = level.1.max(1);
-    let val = SKILL_EXP[tgt_lvl as usize - 1] - SKILL_EXP[cur_lvl as usize - 1];
+    let val = util::exp_for_level(tgt_lvl, SKILL_EXP)? - util::exp_for_level(cur_lvl, SKILL_EXP)?;
     return Some((val as f64 * mul).ceil() as i64);
   }
   None
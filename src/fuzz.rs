@@ -0,0 +1,329 @@
+use crate::{
+  game_data::{Error, GameData},
+  save_builder::SaveBuilder,
+};
+use regex::Regex;
+use std::{
+  fs,
+  ops::Range,
+  panic::{self, AssertUnwindSafe},
+  path::{Path, PathBuf},
+};
+
+/// Save-games that broke the parser before, checked by [`check`] in the tests.
+pub const CORPUS: [(&str, &[u8]); 8] = [
+  ("cdata", include_bytes!("../res/fuzz/cdata.sota")),
+  (
+    "duplicate_records",
+    include_bytes!("../res/fuzz/duplicate_records.sota"),
+  ),
+  (
+    "embedded_tags",
+    include_bytes!("../res/fuzz/embedded_tags.sota"),
+  ),
+  ("escaped", include_bytes!("../res/fuzz/escaped.sota")),
+  (
+    "mixed_quotes",
+    include_bytes!("../res/fuzz/mixed_quotes.sota"),
+  ),
+  ("reordered", include_bytes!("../res/fuzz/reordered.sota")),
+  ("truncated", include_bytes!("../res/fuzz/truncated.sota")),
+  ("utf16", include_bytes!("../res/fuzz/utf16.sota")),
+];
+
+/// Small deterministic random number generator (xorshift64*), so that failures can be reproduced
+/// from the seed.
+pub struct Rng(u64);
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    // Zero would only ever give zero.
+    Self(seed.max(1))
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  /// Get a number in `0..n`, which must not be empty.
+  pub fn below(&mut self, n: usize) -> usize {
+    (self.next_u64() % n as u64) as usize
+  }
+}
+
+/// An input that broke an invariant.
+#[derive(Clone, Debug)]
+pub struct Failure {
+  /// Iteration of [`run`], which with the seed reproduces the input.
+  pub iteration: usize,
+  pub message: String,
+  pub data: Vec<u8>,
+}
+
+/// Load `iterations` mutated save-games (see [`mutate`]) and [`check`] each one. The inputs are
+/// written to `dir`, which is removed afterwards.
+pub fn run(seed: u64, iterations: usize, dir: &Path) -> Vec<Failure> {
+  let _ = fs::create_dir_all(dir);
+  let mut rng = Rng::new(seed);
+  let bases = base_saves();
+  let mut failures = Vec::new();
+  for iteration in 0..iterations {
+    let base = &bases[rng.below(bases.len())];
+    let data = mutate(base, &mut rng);
+    if let Err(message) = check(&data, dir) {
+      failures.push(Failure {
+        iteration,
+        message,
+        data,
+      });
+    }
+  }
+
+  let _ = fs::remove_dir_all(dir);
+  failures
+}
+
+/// Valid save-games with a variety of contents.
+pub fn base_saves() -> Vec<String> {
+  let save = || {
+    SaveBuilder::new()
+      .avatar("Barugon")
+      .gold(1234)
+      .skill(3, 1440)
+      .item("IronOre", 50)
+  };
+
+  vec![
+    save().build(),
+    save().bank_gold(99).bag("Bag", &[("Longsword", 1)]).build(),
+    save()
+      .quoted_numbers()
+      .durable_item("Axe", 10.0, 20.0)
+      .build(),
+    save().ranked_skill(4, 100000, 2).without_backpack().build(),
+  ]
+}
+
+/// Apply one to three random mutations: truncation, byte flips, duplicated records, reordered
+/// collections, different quoting, embedded tags, CDATA sections and XML entities.
+pub fn mutate(text: &str, rng: &mut Rng) -> Vec<u8> {
+  let attr = Regex::new(r#"(Id|name)="([^"]*)""#).unwrap();
+  let mut text = text.to_owned();
+  let mut bytes = None;
+  for _ in 0..=rng.below(3) {
+    match rng.below(8) {
+      0 => {
+        let mut data = bytes.take().unwrap_or_else(|| text.clone().into_bytes());
+        data.truncate(rng.below(data.len() + 1));
+        bytes = Some(data);
+      }
+      1 => {
+        let mut data = bytes.take().unwrap_or_else(|| text.clone().into_bytes());
+        if !data.is_empty() {
+          let pos = rng.below(data.len());
+          data[pos] ^= 1 << rng.below(8);
+        }
+        bytes = Some(data);
+      }
+      _ if bytes.is_some() => (),
+      2 => {
+        if let Some(range) = pick(&spans(&text, "<record", "</record>"), rng) {
+          let record = text[range.clone()].to_owned();
+          text.insert_str(range.end, &record);
+        }
+      }
+      3 => {
+        let collections = spans(&text, "<collection", "</collection>");
+        if let (Some(a), Some(b)) = (pick(&collections, rng), pick(&collections, rng)) {
+          let (a, b) = if a.start < b.start { (a, b) } else { (b, a) };
+          if a.end <= b.start {
+            let (first, second) = (text[a.clone()].to_owned(), text[b.clone()].to_owned());
+            text.replace_range(b, &first);
+            text.replace_range(a, &second);
+          }
+        }
+      }
+      4 => {
+        let count = attr.find_iter(&text).count();
+        if count > 0 {
+          let nth = rng.below(count);
+          let found = attr.captures_iter(&text).nth(nth).unwrap();
+          let range = found.get(0).unwrap().range();
+          let quoted = format!("{}='{}'", &found[1], &found[2]);
+          text.replace_range(range, &quoted);
+        }
+      }
+      5 => {
+        const TAGS: [&str; 5] = [
+          "</record>",
+          r#"<record Id="000000000000000000000001">"#,
+          "</collection>",
+          r#"<collection name="User">"#,
+          "<![CDATA[",
+        ];
+        let pos = rng.below(text.len() + 1);
+        if text.is_char_boundary(pos) {
+          text.insert_str(pos, TAGS[rng.below(TAGS.len())]);
+        }
+      }
+      6 => {
+        if let Some(json) = pick(&record_json(&text), rng) {
+          text.insert_str(json.end, "]]>");
+          text.insert_str(json.start, "<![CDATA[");
+        }
+      }
+      _ => {
+        if let Some(json) = pick(&record_json(&text), rng) {
+          let escaped = text[json.clone()].replace('"', "&quot;");
+          text.replace_range(json, &escaped);
+        }
+      }
+    }
+  }
+
+  bytes.unwrap_or_else(|| text.into_bytes())
+}
+
+/// Check the invariants for one input, which is written to a file in `dir`:
+/// - Loading never panics, strictly or leniently.
+/// - Errors are typed, i.e. not [`Error::Other`].
+/// - A loaded save-game stored without changes loads again as the same save-game.
+pub fn check(data: &[u8], dir: &Path) -> Result<(), String> {
+  let path = dir.join("fuzz.sota");
+  fs::write(&path, data).map_err(|err| err.to_string())?;
+
+  let strict = catch(|| GameData::load(&path)).ok_or("load panicked")?;
+  let (lenient, _) = catch(|| GameData::load_lenient(&path)).ok_or("load_lenient panicked")?;
+  match &strict {
+    Ok(game) => round_trip(game, dir, false)?,
+    Err(Error::Other(err)) => return Err(format!("untyped error: {err}")),
+    Err(_) => (),
+  }
+
+  match lenient {
+    Some(game) => round_trip(&game, dir, true),
+    None if strict.is_ok() => Err(String::from("load worked but load_lenient didn't")),
+    None => Ok(()),
+  }
+}
+
+// Store without changes and load the result again.
+fn round_trip(game: &GameData, dir: &Path, lenient: bool) -> Result<(), String> {
+  let stored = catch(|| game.store_to_string()).ok_or("store_to_string panicked")?;
+  let text = stored.map_err(|err| format!("unable to store unchanged: {err}"))?;
+  let path: PathBuf = dir.join("fuzz_stored.sota");
+  fs::write(&path, &text).map_err(|err| err.to_string())?;
+
+  let reloaded = if lenient {
+    GameData::load_lenient(&path)
+      .0
+      .ok_or("the stored text doesn't load")?
+  } else {
+    GameData::load(&path).map_err(|err| format!("the stored text doesn't load: {err}"))?
+  };
+
+  let patch = game.diff(&reloaded);
+  if patch.as_array().is_some_and(|ops| !ops.is_empty()) {
+    return Err(format!("the stored text loads differently: {patch}"));
+  }
+
+  let again = reloaded.store_to_string().map_err(|err| err.to_string())?;
+  if again != text {
+    return Err(String::from("storing again changes the text"));
+  }
+  Ok(())
+}
+
+fn catch<T>(f: impl FnOnce() -> T) -> Option<T> {
+  panic::catch_unwind(AssertUnwindSafe(f)).ok()
+}
+
+fn pick(ranges: &[Range<usize>], rng: &mut Rng) -> Option<Range<usize>> {
+  (!ranges.is_empty()).then(|| ranges[rng.below(ranges.len())].clone())
+}
+
+// Find the spans from each `start` to the following `end`, inclusive.
+fn spans(text: &str, start: &str, end: &str) -> Vec<Range<usize>> {
+  let mut spans = Vec::new();
+  let mut from = 0;
+  while let Some(pos) = text[from..].find(start) {
+    let begin = from + pos;
+    let Some(len) = text[begin..].find(end) else {
+      break;
+    };
+    spans.push(begin..begin + len + end.len());
+    from = begin + len + end.len();
+  }
+  spans
+}
+
+// Find the JSON inside the records.
+fn record_json(text: &str) -> Vec<Range<usize>> {
+  let mut ranges = Vec::new();
+  for span in spans(text, "<record", "</record>") {
+    let end = span.end - "</record>".len();
+    if let Some(open) = text[span.start..end].find('>') {
+      ranges.push(span.start + open + 1..end);
+    }
+  }
+  ranges
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cota_test_fuzz_{name}"))
+  }
+
+  #[test]
+  fn test_corpus() {
+    let dir = dir("corpus");
+    fs::create_dir_all(&dir).unwrap();
+    for (name, data) in CORPUS {
+      if let Err(err) = check(data, &dir) {
+        panic!("{name}: {err}");
+      }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_fuzz() {
+    // Set COTA_FUZZ_ITERATIONS for a longer run, and COTA_FUZZ_SEED to try other inputs.
+    let var = |name, default| {
+      let val = std::env::var(name).ok();
+      val.and_then(|val| val.parse().ok()).unwrap_or(default)
+    };
+    let seed = var("COTA_FUZZ_SEED", 0x5eed);
+    let iterations = var("COTA_FUZZ_ITERATIONS", 300) as usize;
+
+    let failures = run(seed, iterations, &dir("run"));
+    if let Some(failure) = failures.first() {
+      panic!(
+        "{} of {iterations} inputs failed with seed {seed}, the first at iteration {}: {}\n{}",
+        failures.len(),
+        failure.iteration,
+        failure.message,
+        String::from_utf8_lossy(&failure.data)
+      );
+    }
+  }
+
+  #[test]
+  fn test_mutate() {
+    // The same seed gives the same inputs.
+    let text = &base_saves()[1];
+    let inputs = |seed| {
+      let mut rng = Rng::new(seed);
+      (0..20).map(|_| mutate(text, &mut rng)).collect::<Vec<_>>()
+    };
+    assert_eq!(inputs(1), inputs(1));
+    assert_ne!(inputs(1), inputs(2));
+    assert!(inputs(1).iter().any(|data| data != text.as_bytes()));
+  }
+}
@@ -1,8 +1,32 @@
-use crate::{skill_info, util};
+use crate::{
+  item_names::ItemNames,
+  journal::{
+    Change, ChangeCategory, ChangeDescription, ChangeId, ChangeValue, Field, History, Journal,
+  },
+  logs::StatsSnapshot,
+  skill_build::{Build, UnknownSkills},
+  skill_info, util,
+};
+use chrono::{DateTime, Utc};
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use skill_info::{SkillCategory, SkillInfo, SkillInfoGroup};
-use std::{borrow::Cow, fs::File, io::Write, ops::Range, path::PathBuf, sync::RwLock};
-use util::{LEVEL_EXP, LVL_RANGE, SKILL_EXP};
+use skill_info::{SkillCategory, SkillInfo, SkillInfoGroup, SkillRegistry};
+use std::{
+  borrow::Cow,
+  collections::{BTreeMap, HashMap, HashSet},
+  fmt,
+  fs::{File, OpenOptions},
+  io::{self, Read, Write},
+  ops::{Range, RangeInclusive},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+  },
+  time::SystemTime,
+};
+use util::{json_num, BatchError, DateRange, ExpTables, Limits, LineAnchor, ProgressSink, Search};
 
 // NOTE: UserKnowledge contains virtue.
 
@@ -12,7 +36,13 @@ pub struct GameData {
   path: RwLock<PathBuf>,
 
   // Full file text.
-  text: String,
+  text: Arc<String>,
+
+  // Hash of the file text at load.
+  hash: u64,
+
+  // The file as it was when loaded or last stored, to detect changes made by something else.
+  stamp: RwLock<Option<FileStamp>>,
 
   // Avatar ID.
   avatar: String,
@@ -23,519 +53,9157 @@ pub struct GameData {
   // Backpack ID.
   backpack: String,
 
-  // Parsed JSON sections.
-  character: Value,
-  inventory: Value,
-  gold: Value,
+  // Parsed JSON sections, shared with snapshots and copied on write.
+  character: Arc<Value>,
+  inventory: Arc<Value>,
+  gold: Arc<Value>,
 
-  // Save date.
-  date: Value,
-}
+  // Bank gold, which is not present in all saves.
+  bank: Option<Arc<Value>>,
 
-impl GameData {
-  pub fn load(path: PathBuf) -> Result<Self, Cow<'static, str>> {
-    match std::fs::read_to_string(&path) {
-      Ok(text) => {
-        // Get the avatar ID.
-        let avatar = get_avatar_id(&text)?;
+  // Distinct item names from the ItemStore, so that items with the same name share one string.
+  item_names: Arc<HashSet<Arc<str>>>,
 
-        // Get the avatar name.
-        let name = get_avatar_name(&text, &avatar)?;
+  // Display names for items.
+  display_names: Arc<ItemNames>,
 
-        // Get the backpack ID.
-        let backpack = get_backpack_id(&text, &avatar)?;
+  // Changed CharacterSheet records of the other avatars, by avatar ID.
+  sheets: Arc<BTreeMap<String, Value>>,
 
-        // Get the ItemStore JSON.
-        let inventory = get_json(&text, ITEM_STORE, &backpack)?;
+  // ItemStore records other than the backpack's that were changed or added, by ID.
+  stores: Arc<BTreeMap<String, Value>>,
 
-        // Get the CharacterSheet JSON.
-        let character = get_json(&text, CHARACTER_SHEET, &avatar)?;
+  // Save date, which is not available if no skills have been trained.
+  date: Option<Value>,
 
-        // Make sure adventurer experience is there.
-        if character.get(AE).and_then(|exp| exp.to_i64()).is_none() {
-          return Err(Cow::from("Unable to parse adventurer experience"));
-        }
+  // Experience tables for level calculations.
+  tables: ExpTables,
 
-        // Make sure producer experience is there.
-        if character.get(PE).and_then(|exp| exp.to_i64()).is_none() {
-          return Err(Cow::from("Unable to parse producer experience"));
-        }
+  // Level caps checked by the setters.
+  limits: Limits,
 
-        // Find a save date.
-        let date = match character.get(SK2) {
-          Some(val) if val.is_object() => find_date(val)?,
-          _ => return Err(Cow::from("Error reading skills")),
-        };
+  // How many timestamped backups to keep when storing over a file, if backups are enabled.
+  backup: Option<usize>,
 
-        // Get the UserGold JSON.
-        let gold = get_json(&text, USER_GOLD, USER_ID)?;
-
-        Ok(GameData {
-          path: RwLock::new(path),
-          text,
-          avatar,
-          name,
-          backpack,
-          character,
-          inventory,
-          gold,
-          date,
-        })
-      }
-      Err(err) => Err(Cow::from(format!("Unable to load file: {err}"))),
-    }
-  }
+  // Changes made since the save-game was loaded.
+  journal: Arc<Journal>,
 
-  pub fn store(&self) -> Result<(), Cow<'static, str>> {
-    self.store_as(self.get_file_path())
-  }
+  // Edits that can be undone and redone.
+  history: Arc<History>,
 
-  pub fn store_as(&self, path: PathBuf) -> Result<(), Cow<'static, str>> {
-    // Set CharacterSheet.
-    let text = set_json(&self.text, CHARACTER_SHEET, &self.avatar, &self.character)?;
+  // Bumped by every change, and the value when last loaded or stored. Storing is skipped if they
+  // match.
+  revision: u64,
+  stored: RwLock<u64>,
 
-    // Set ItemStore.
-    let text = set_json(&text, ITEM_STORE, &self.backpack, &self.inventory)?;
+  // Text encoding of the file and whether it started with a byte order mark.
+  encoding: Encoding,
+  bom: bool,
 
-    // Set UserGold.
-    let text = set_json(&text, USER_GOLD, USER_ID, &self.gold)?;
+  // The file had invalid UTF-8, which was replaced. Storing over it is refused until the user
+  // acknowledges that.
+  lossy: Option<PathBuf>,
 
-    // Create the save-game file and store the data.
-    match File::create(&path) {
-      Ok(mut file) => match file.write_all(text.as_bytes()) {
-        Ok(()) => {
-          // Change the path.
-          *self.path.write().unwrap() = path;
-          Ok(())
-        }
-        Err(err) => Err(Cow::from(err.to_string())),
-      },
-      Err(err) => Err(Cow::from(err.to_string())),
+  // Why the file can't be written, if it can't.
+  read_only: RwLock<Option<String>>,
+
+  // Sections that couldn't be loaded by `load_lenient`, which have placeholders.
+  unavailable: Vec<Section>,
+
+  // Save format version, if the save-game has one.
+  version: Option<u32>,
+}
+
+/// Clones share the unchanged data with the original, but changes to either one don't affect the
+/// other.
+impl Clone for GameData {
+  fn clone(&self) -> Self {
+    Self {
+      path: RwLock::new(self.get_file_path()),
+      text: self.text.clone(),
+      hash: self.hash,
+      stamp: RwLock::new(*self.stamp.read().unwrap()),
+      avatar: self.avatar.clone(),
+      name: self.name.clone(),
+      backpack: self.backpack.clone(),
+      character: self.character.clone(),
+      inventory: self.inventory.clone(),
+      gold: self.gold.clone(),
+      bank: self.bank.clone(),
+      item_names: self.item_names.clone(),
+      display_names: self.display_names.clone(),
+      sheets: self.sheets.clone(),
+      stores: self.stores.clone(),
+      date: self.date.clone(),
+      tables: self.tables.clone(),
+      limits: self.limits.clone(),
+      backup: self.backup,
+      journal: self.journal.clone(),
+      history: self.history.clone(),
+      revision: self.revision,
+      stored: RwLock::new(*self.stored.read().unwrap()),
+      encoding: self.encoding,
+      bom: self.bom,
+      lossy: self.lossy.clone(),
+      read_only: RwLock::new(self.read_only_reason()),
+      unavailable: self.unavailable.clone(),
+      version: self.version,
     }
   }
+}
 
-  pub fn avatar_name(&self) -> &str {
-    &self.name
+impl GameData {
+  /// Load a save-game file. UTF-8 and UTF-16 are supported, with or without a byte order mark.
+  /// Invalid characters are replaced (see [`GameData::lossy_warning`]). If more than one section
+  /// can't be loaded then they're all reported (see [`LoadError`]).
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+    let (game, _) = Self::load_with(path.as_ref(), false, None)?;
+    Ok(game)
   }
 
-  pub fn get_gold(&self) -> Option<i32> {
-    Some(self.gold.get(G)?.to_i64()? as i32)
+  /// Load a save-game file for one of its avatars (see [`GameData::list_avatars`]) rather than the
+  /// current one. Only that avatar's records are changed when storing.
+  pub fn load_avatar(path: impl AsRef<Path>, avatar: &str) -> Result<Self, Error> {
+    let (game, _) = Self::load_with(path.as_ref(), false, Some(avatar))?;
+    Ok(game)
   }
 
-  pub fn set_gold(&mut self, gold: i32) {
-    self.gold[G] = gold.into();
+  /// List the avatars in a save-game file, in file order.
+  pub fn list_avatars(path: impl AsRef<Path>) -> Result<Vec<AvatarInfo>, Error> {
+    let path = path.as_ref();
+    check_file_size(path, max_file_size())?;
+    let data = std::fs::read(path).map_err(Error::io(FileOp::Load, path))?;
+    let (encoding, bom) = Encoding::detect(&data);
+    let (text, _) = encoding.decode(&data, bom);
+    sniff_text(&text, true)?;
+    Ok(avatars(&text))
   }
 
-  pub fn get_adv_lvl(&self) -> i32 {
-    let ae = self.character.get(AE).unwrap();
-    let exp = ae.to_i64().unwrap();
-    util::floor_search(exp, LEVEL_EXP).unwrap() as i32 + 1
+  /// Load as much of a save-game file as possible. A missing or invalid CharacterSheet, ItemStore
+  /// or UserGold is reported instead of failing the load, and the features that need it fail with
+  /// a "not available" error (see [`GameData::available`]). Storing leaves those sections as they
+  /// are. An unsupported format version is reported but not fatal.
+  pub fn load_lenient(path: impl AsRef<Path>) -> (Option<Self>, Vec<LoadIssue>) {
+    match Self::load_with(path.as_ref(), true, None) {
+      Ok((game, issues)) => (Some(game), issues),
+      Err(err) => {
+        let issue = LoadIssue {
+          section: None,
+          offset: None,
+          message: err.to_string(),
+        };
+        (None, vec![issue])
+      }
+    }
   }
 
-  pub fn set_adv_lvl(&mut self, lvl: i32) {
-    assert!(LVL_RANGE.contains(&lvl));
-    self.character[AE] = LEVEL_EXP[lvl as usize - 1].into();
+  /// Quickly check if a file looks like a save-game, reading only the first few kilobytes. The
+  /// collection and record framing must be there, and so must the User collection if the whole
+  /// file was read.
+  pub fn sniff(path: impl AsRef<Path>) -> Result<(), NotASave> {
+    Self::sniff_limited(path.as_ref(), max_file_size())
   }
 
-  pub fn get_prd_lvl(&self) -> i32 {
-    let pe = self.character.get(PE).unwrap();
-    let exp = pe.to_i64().unwrap();
-    util::floor_search(exp, LEVEL_EXP).unwrap() as i32 + 1
+  fn sniff_limited(path: &Path, limit: u64) -> Result<(), NotASave> {
+    let unreadable = |err: io::Error| NotASave::Unreadable(err.to_string());
+    let file = File::open(path).map_err(unreadable)?;
+    let size = file.metadata().map_err(unreadable)?.len();
+    if size > limit {
+      return Err(NotASave::TooLarge { size, limit });
+    }
+
+    // Read one more byte than needed to know if the whole file was read.
+    let mut head = Vec::with_capacity(SNIFF_LEN + 1);
+    file
+      .take(SNIFF_LEN as u64 + 1)
+      .read_to_end(&mut head)
+      .map_err(unreadable)?;
+
+    let complete = head.len() <= SNIFF_LEN;
+    head.truncate(SNIFF_LEN);
+
+    let (encoding, bom) = Encoding::detect(&head);
+    let (text, _) = encoding.decode(&head, bom);
+    sniff_text(&text, complete)
   }
 
-  pub fn set_prd_lvl(&mut self, lvl: i32) {
-    assert!(LVL_RANGE.contains(&lvl));
-    self.character[PE] = LEVEL_EXP[lvl as usize - 1].into();
+  fn load_with(
+    path: &Path,
+    lenient: bool,
+    avatar: Option<&str>,
+  ) -> Result<(Self, Vec<LoadIssue>), Error> {
+    // Finish or undo a store that was interrupted.
+    let journal = suffixed(path, JOURNAL_SUFFIX);
+    if journal.exists() {
+      recover_store(&journal)?;
+    }
+
+    check_file_size(path, max_file_size())?;
+    let data = std::fs::read(path).map_err(Error::io(FileOp::Load, path))?;
+    let (encoding, bom) = Encoding::detect(&data);
+    let (text, lossy) = encoding.decode(&data, bom);
+
+    // Make sure that it's a save-game before complaining about what's missing.
+    sniff_text(&text, true)?;
+
+    let (mut game, issues) = Self::parse(text, lenient, avatar)?;
+    *game.path.write().unwrap() = path.to_owned();
+    game.hash = hash_bytes(&data);
+    *game.stamp.write().unwrap() = FileStamp::new(path, game.hash);
+    game.encoding = encoding;
+    game.bom = bom;
+    if lossy {
+      game.lossy = Some(path.to_owned());
+    }
+    *game.read_only.write().unwrap() = check_writable(path).err();
+    Ok((game, issues))
   }
 
-  pub fn get_file_path(&self) -> PathBuf {
-    self.path.read().unwrap().clone()
+  /// Get a warning if the file had invalid UTF-8 that was replaced when loading. Storing over the
+  /// original file fails until [`GameData::acknowledge_lossy`] is called.
+  pub fn lossy_warning(&self) -> Option<String> {
+    let path = self.lossy.as_ref()?;
+    Some(format!(
+      "'{}' has invalid characters, which were replaced",
+      path.display()
+    ))
   }
 
-  pub fn get_file_name(&self) -> String {
-    let path = self.path.read().unwrap();
-    path.file_name().unwrap().to_string_lossy().into()
+  /// Get the text encoding of the loaded file, which is also used when storing.
+  pub fn encoding(&self) -> Encoding {
+    self.encoding
   }
 
-  pub fn get_skills(&self, category: SkillCategory) -> Vec<SkillLvlGroup> {
-    let sk2 = self.character.get(SK2).unwrap();
-    let groups = skill_info::parse_skill_info_groups(category);
-    let mut skills = Vec::with_capacity(groups.len());
-    for group in groups {
-      skills.push(SkillLvlGroup::new(sk2, group));
-    }
+  /// Allow storing over a file that was loaded with invalid UTF-8 replaced.
+  pub fn acknowledge_lossy(&mut self) {
+    self.lossy = None;
+  }
 
-    skills
+  /// Load a save-game file only to look at it, e.g. a backup. Unlike [`GameData::load`], an
+  /// interrupted store isn't recovered and the file isn't checked for writing.
+  pub fn peek(path: impl AsRef<Path>) -> Result<Self, Error> {
+    let path = path.as_ref();
+    check_file_size(path, max_file_size())?;
+    let data = std::fs::read(path).map_err(Error::io(FileOp::Load, path))?;
+    let (encoding, bom) = Encoding::detect(&data);
+    let (text, _) = encoding.decode(&data, bom);
+    sniff_text(&text, true)?;
+    let (game, _) = Self::parse(text, false, None)?;
+    Ok(game)
   }
 
-  pub fn set_skills(&mut self, skills: &Vec<SkillLvlGroup>) {
-    let sk2 = self.character.get_mut(SK2).unwrap();
-    for group in skills {
-      for skill in &group.skills {
-        set_skill_lvl(sk2, &self.date, skill);
-      }
-    }
+  /// Parse save-game text. The file path is empty until the save-game is stored.
+  pub fn from_text(text: String) -> Result<Self, Error> {
+    let (game, _) = Self::parse(text, false, None)?;
+    Ok(game)
   }
 
-  pub fn get_inventory_items(&self) -> Vec<Item> {
-    let inv = self.inventory.get(IN).unwrap();
-    let items_map = inv.as_object().unwrap();
-    let mut items = Vec::with_capacity(items_map.len());
-    for (key, val) in items_map {
-      if let Some(item) = Item::new(val, key) {
-        items.push(item);
+  // Parse the text for an avatar, or the current one if it's `None`.
+  fn parse(
+    text: String,
+    lenient: bool,
+    avatar: Option<&str>,
+  ) -> Result<(Self, Vec<LoadIssue>), Error> {
+    let mut issues = Vec::new();
+
+    // An unknown format version is only a warning, the sections are still checked as usual.
+    let version = get_format_version(&text);
+    if let Some(warning) = unsupported_version(version) {
+      issues.push(LoadIssue {
+        section: None,
+        offset: None,
+        message: warning.to_string(),
+      });
+    }
+
+    // Get the avatar ID.
+    let avatar = match avatar {
+      Some(avatar) if get_json_range(&text, CHARACTER, avatar).is_none() => {
+        let id = avatar.into();
+        return Err(Error::MissingRecord {
+          collection: CHARACTER,
+          id,
+        });
+      }
+      Some(avatar) => avatar.to_owned(),
+      None => get_avatar_id(&text)?,
+    };
+
+    // Get the avatar name.
+    let name = get_avatar_name(&text, &avatar)?;
+
+    // Sections that can't be loaded are replaced with placeholders, so that all the problems can
+    // be reported at once. Only a lenient load goes on to use them.
+    let mut unavailable = Vec::new();
+    let mut errors = Vec::new();
+    let mut skip = |section: Section, id: &str, err: Error| {
+      errors.push(LoadIssue {
+        section: Some(section),
+        offset: json_error_offset(&text, section.collection(), id),
+        message: err.to_string(),
+      });
+      unavailable.push(section);
+      section.placeholder()
+    };
+
+    // Get the backpack ID and the ItemStore JSON.
+    let (backpack, inventory) = match get_backpack_id(&text, &avatar) {
+      Ok(backpack) => match get_json(&text, ITEM_STORE, &backpack) {
+        Ok(inventory) => (backpack, inventory),
+        Err(err) => {
+          let inventory = skip(Section::ItemStore, &backpack, err);
+          (backpack, inventory)
+        }
+      },
+      Err(err) => (String::new(), skip(Section::ItemStore, "", err)),
+    };
+
+    // Get the CharacterSheet JSON.
+    let character = match get_json(&text, CHARACTER_SHEET, &avatar).and_then(check_character) {
+      Ok(character) => character,
+      Err(err) => skip(Section::CharacterSheet, &avatar, err),
+    };
+
+    // Get the UserGold JSON. Some very old saves don't have it, in which case it's created when
+    // the gold is set.
+    let gold = match get_json(&text, USER_GOLD, USER_ID) {
+      Ok(gold) => gold,
+      Err(_) if lenient && get_json_range(&text, USER_GOLD, USER_ID).is_none() => {
+        issues.push(LoadIssue {
+          section: Some(Section::UserGold),
+          offset: None,
+          message: String::from("There's no UserGold record, one is added when the gold is set"),
+        });
+        Section::UserGold.placeholder()
       }
+      Err(err) => skip(Section::UserGold, USER_ID, err),
+    };
+
+    if !lenient && !errors.is_empty() {
+      return Err(Error::Load(LoadError { issues: errors }));
     }
+    issues.append(&mut errors);
 
-    items
-  }
+    // Find a save date.
+    let date = find_date(&character[SK2]);
 
-  pub fn set_inventory_items(&mut self, items: &Vec<Item>) {
-    let inv = self.inventory.get_mut(IN).unwrap();
-    for item in items {
-      let val = inv.get_mut(&item.id).unwrap();
-      let val = val.get_mut(IN).unwrap();
-      val[QN] = item.cnt.into();
-      if let Some(dur) = &item.dur {
-        val[HP] = dur.minor.into();
-        val[PHP] = dur.major.into();
+    // Get the UserBank JSON, if it's there.
+    let bank = get_json(&text, USER_BANK, USER_ID).ok();
+
+    // Report duplicates of the records that are used.
+    let records = [
+      (USER, USER_ID, None),
+      (CHARACTER_NAME, &avatar, None),
+      (CHARACTER, &avatar, None),
+      (CHARACTER_SHEET, &avatar, Some(Section::CharacterSheet)),
+      (ITEM_STORE, &backpack, Some(Section::ItemStore)),
+      (USER_GOLD, USER_ID, Some(Section::UserGold)),
+      (USER_BANK, USER_ID, None),
+    ];
+    for (collection, id, section) in records {
+      let found = find_records(&text, collection, id);
+      if let Some(last) = found.last().filter(|_| found.len() > 1) {
+        issues.push(LoadIssue {
+          section,
+          offset: Some(last.start),
+          message: format!(
+            "The {collection} record '{id}' appears {} times, the last one is used",
+            found.len()
+          ),
+        });
       }
     }
-  }
-}
 
-const USER_ID: &str = "000000000000000000000001";
-const CHARACTER_SHEET: &str = "CharacterSheet";
-const ITEM_STORE: &str = "ItemStore";
-const USER_GOLD: &str = "UserGold";
-const BAG: &str = "bag";
-const PHP: &str = "php";
-const SK2: &str = "sk2";
-const AE: &str = "ae";
-const AN: &str = "an";
-const DC: &str = "dc";
-const FN: &str = "fn";
-const HP: &str = "hp";
-const IN: &str = "in";
-const PE: &str = "pe";
-const QN: &str = "qn";
-const G: &str = "g";
-const M: &str = "m";
-const T: &str = "t";
-const X: &str = "x";
+    let hash = hash_bytes(text.as_bytes());
 
-#[derive(Clone)]
-pub struct SkillLvl {
-  pub info: SkillInfo,
-  pub level: i32,
-  comp: i32,
-}
+    let game = GameData {
+      path: RwLock::new(PathBuf::new()),
+      text: Arc::new(text),
+      hash,
+      stamp: RwLock::new(None),
+      avatar,
+      name,
+      backpack,
+      character: Arc::new(character),
+      item_names: Arc::new(intern_item_names(&inventory)),
+      display_names: Arc::default(),
+      inventory: Arc::new(inventory),
+      gold: Arc::new(gold),
+      bank: bank.map(Arc::new),
+      sheets: Arc::default(),
+      stores: Arc::default(),
+      date,
+      tables: ExpTables::default(),
+      limits: Limits::default(),
+      backup: None,
+      journal: Arc::default(),
+      history: Arc::default(),
+      revision: 0,
+      stored: RwLock::new(0),
+      encoding: Encoding::Utf8,
+      bom: false,
+      lossy: None,
+      read_only: RwLock::new(None),
+      unavailable,
+      version,
+    };
+    Ok((game, issues))
+  }
 
-impl SkillLvl {
-  fn new(sk2: &Value, info: SkillInfo) -> Self {
-    let level = get_skill_lvl(sk2, &info).unwrap_or(0);
-    let comp = level;
+  /// Get the save format version, if the save-game has one. See [`FORMAT_VERSIONS`].
+  pub fn format_version(&self) -> Option<u32> {
+    self.version
+  }
 
-    Self { info, level, comp }
+  /// Get a warning if the save format version is outside of [`FORMAT_VERSIONS`]. The save-game is
+  /// still loaded, but it might not be edited correctly.
+  pub fn version_warning(&self) -> Option<UnsupportedVersion> {
+    unsupported_version(self.version)
   }
 
-  fn accept(&mut self) {
-    self.comp = self.level;
+  /// Check that a section was loaded. Sections that [`GameData::load_lenient`] couldn't load are
+  /// not available.
+  pub fn available(&self, section: Section) -> Result<(), Error> {
+    if self.unavailable.contains(&section) {
+      return Err(Error::Unavailable(section));
+    }
+    Ok(())
   }
 
-  fn discard(&mut self) {
-    self.level = self.comp;
+  /// Store the changes. Nothing is written if there aren't any, unless [`StoreOptions::force`] is
+  /// set.
+  pub fn store(&self) -> Result<StoreOutcome, Error> {
+    self.store_as(self.get_file_path())
   }
 
-  pub fn changed(&self) -> bool {
-    self.level != self.comp
+  pub fn store_as(&self, path: impl AsRef<Path>) -> Result<StoreOutcome, Error> {
+    self.store_as_with(path, StoreOptions::default())
   }
-}
 
-pub struct SkillLvlGroup {
-  pub name: &'static str,
-  pub skills: Vec<SkillLvl>,
-}
+  pub fn store_as_with(
+    &self,
+    path: impl AsRef<Path>,
+    options: StoreOptions,
+  ) -> Result<StoreOutcome, Error> {
+    let path = path.as_ref();
 
-impl SkillLvlGroup {
-  fn new(sk2: &Value, group: SkillInfoGroup) -> Self {
-    let name = group.name;
-    let mut skills = Vec::with_capacity(group.skills.len());
-    for skill in group.skills {
-      skills.push(SkillLvl::new(sk2, skill));
+    // Rewriting the same file without changes would only bump its modification time.
+    if !options.force && !self.is_modified() && path == self.get_file_path() && path.is_file() {
+      return Ok(StoreOutcome::Unchanged);
     }
 
-    Self { name, skills }
-  }
+    self.write_file(path, options)?;
 
-  pub fn changed(&self) -> bool {
-    for skill in &self.skills {
-      if skill.changed() {
-        return true;
-      }
-    }
+    // Change the path and remember what was stored so that it's not seen as a conflict.
+    *self.path.write().unwrap() = path.to_owned();
+    *self.read_only.write().unwrap() = None;
+    let hash = hash_file(path).ok();
+    *self.stamp.write().unwrap() = hash.and_then(|hash| FileStamp::new(path, hash));
+    *self.stored.write().unwrap() = self.revision;
+    Ok(StoreOutcome::Written)
+  }
 
-    false
+  /// Check if the file could be written when it was loaded or last stored. See
+  /// [`GameData::read_only_reason`].
+  pub fn is_writable(&self) -> bool {
+    self.read_only.read().unwrap().is_none()
   }
 
-  pub fn accept(&mut self) {
-    for skill in &mut self.skills {
-      skill.accept();
-    }
+  /// Get the reason that the file can't be written, e.g. it's read-only or locked by the game.
+  pub fn read_only_reason(&self) -> Option<String> {
+    self.read_only.read().unwrap().clone()
   }
 
-  pub fn discard(&mut self) {
-    for skill in &mut self.skills {
-      skill.discard();
-    }
+  /// Check if anything changed since the save-game was loaded or last stored.
+  pub fn is_modified(&self) -> bool {
+    self.revision != *self.stored.read().unwrap()
   }
-}
 
-#[derive(PartialEq, Clone)]
-pub struct Durability {
-  pub minor: f64,
-  pub major: f64,
-}
+  /// Check if the file was changed by something else (e.g. the game) since it was loaded or last
+  /// stored. Storing over it fails in that case unless [`StoreOptions::force`] is set.
+  pub fn check_conflict(&self) -> Result<(), ConflictDetected> {
+    let Some(stamp) = *self.stamp.read().unwrap() else {
+      return Ok(());
+    };
 
-impl Durability {
-  fn new(val: &Value) -> Option<Self> {
-    let minor = val.get(HP)?.as_f64()?;
-    let major = val.get(PHP)?.as_f64()?;
+    // A file that's gone can't be clobbered.
+    let path = self.get_file_path();
+    let Ok(meta) = std::fs::metadata(&path) else {
+      return Ok(());
+    };
 
-    Some(Durability { minor, major })
+    if let Ok(false) = self.is_stale() {
+      return Ok(());
+    }
+
+    Err(ConflictDetected {
+      path,
+      loaded: stamp.modified,
+      modified: meta.modified().ok(),
+    })
   }
-}
 
-#[derive(Clone)]
-pub struct Item {
-  id: String,
-  name: String,
-  cnt_cmp: u64,
-  cnt: u64,
-  dur_cmp: Option<Durability>,
-  dur: Option<Durability>,
-  bag: bool,
-}
+  /// Check if the file's contents changed since it was loaded or last stored. The file is only
+  /// hashed if its size or modification time changed, so touching it isn't a change. Save-games
+  /// that weren't loaded from a file are never stale.
+  pub fn is_stale(&self) -> io::Result<bool> {
+    let Some(stamp) = *self.stamp.read().unwrap() else {
+      return Ok(false);
+    };
 
-impl Item {
-  fn new(val: &Value, id: &str) -> Option<Self> {
-    let val = val.get(IN)?;
-    let name = get_item_name(val)?;
-    let cnt = val.get(QN).and_then(|v| v.as_u64())?;
-    let dur = Durability::new(val);
-    let bag = val.get(BAG).is_some();
+    let path = self.get_file_path();
+    let meta = std::fs::metadata(&path)?;
+    if meta.len() == stamp.len && meta.modified().ok() == stamp.modified {
+      return Ok(false);
+    }
+    Ok(hash_file(&path)? != stamp.hash)
+  }
 
-    Some(Item {
-      id: id.into(),
-      name,
-      cnt_cmp: cnt,
-      cnt,
-      dur_cmp: dur.clone(),
-      dur,
-      bag,
-    })
+  /// Load the file again for the same avatar, dropping all changes and the undo history. The
+  /// experience tables, limits, backup setting and item names are kept. If the file can't be
+  /// loaded then nothing is changed.
+  pub fn reload(&mut self) -> Result<(), Error> {
+    let mut game = Self::load_avatar(self.get_file_path(), &self.avatar)?;
+    game.tables = self.tables.clone();
+    game.limits = self.limits.clone();
+    game.backup = self.backup;
+    game.display_names = self.display_names.clone();
+    *self = game;
+    Ok(())
   }
 
-  pub fn changed(&self) -> bool {
-    self.cnt != self.cnt_cmp || self.dur != self.dur_cmp
+  /// Store a copy of the save-game without changing the current path. Missing parent directories
+  /// are created.
+  pub fn store_copy_as(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+    let options = StoreOptions {
+      create_dirs: true,
+      ..Default::default()
+    };
+    self.write_file(path.as_ref(), options)
   }
 
-  pub fn name(&self) -> &str {
-    &self.name
+  /// Get the full save-game text with all the changes applied.
+  pub fn store_to_string(&self) -> Result<String, Error> {
+    self.snapshot().store_to_string()
   }
 
-  pub fn count_mut(&mut self) -> &mut u64 {
-    &mut self.cnt
+  /// Take a cheap copy of the current data that can be stored on another thread. Later changes to
+  /// this save-game don't affect the snapshot.
+  pub fn snapshot(&self) -> GameDataSnapshot {
+    GameDataSnapshot {
+      text: self.text.clone(),
+      avatar: self.avatar.clone(),
+      backpack: self.backpack.clone(),
+      character: self.character.clone(),
+      inventory: self.inventory.clone(),
+      gold: self.gold.clone(),
+      bank: self.bank.clone(),
+      sheets: self.sheets.clone(),
+      stores: self.stores.clone(),
+      journal: self.journal.clone(),
+      encoding: self.encoding,
+      bom: self.bom,
+      unavailable: self.unavailable.clone(),
+    }
   }
 
-  pub fn durability_mut(&mut self) -> Option<&mut Durability> {
-    if let Some(dur) = &mut self.dur {
-      return Some(dur);
+  fn write_file(&self, path: &Path, options: StoreOptions) -> Result<(), Error> {
+    if self.lossy.as_deref() == Some(path) {
+      let err = format!(
+        "'{}' was loaded with invalid characters replaced, store it elsewhere",
+        path.display()
+      );
+      return Err(Error::other(err));
     }
 
-    None
+    // Fail before anything is written if the file can't be replaced.
+    if path.exists() {
+      if let Err(reason) = check_writable(path) {
+        if path == self.get_file_path() {
+          *self.read_only.write().unwrap() = Some(reason.clone());
+        }
+
+        let err = ReadOnly {
+          path: path.to_owned(),
+          reason,
+        };
+        return Err(Error::ReadOnly(err));
+      }
+    }
+
+    if !options.force && path == self.get_file_path() {
+      self.check_conflict()?;
+    }
+
+    // Keep a copy of the file that's about to be replaced.
+    let backup = match self.backup.filter(|_| path.is_file()) {
+      Some(keep) => Some(backup_file(path, keep)?),
+      None => None,
+    };
+
+    self.snapshot().write_file(path, options, backup.as_deref())
   }
 
-  pub fn is_container(&self) -> bool {
-    self.bag
+  pub fn avatar_name(&self) -> &str {
+    &self.name
   }
 
-  pub fn accept(&mut self) {
-    self.cnt_cmp = self.cnt;
-    self.dur_cmp.clone_from(&self.dur);
+  /// ID of the avatar that was loaded.
+  pub fn avatar_id(&self) -> &str {
+    &self.avatar
   }
 
-  pub fn discard(&mut self) {
-    self.cnt = self.cnt_cmp;
-    self.dur.clone_from(&self.dur_cmp);
+  /// List the avatars in the save-game, in file order.
+  pub fn avatars(&self) -> Vec<AvatarInfo> {
+    avatars(&self.text)
   }
-}
 
-fn get_skill_lvl(sk2: &Value, info: &SkillInfo) -> Option<i32> {
-  let exp = sk2.get(format!("{}", info.id))?.get(X)?;
-  let exp = (exp.to_i64()? as f64 / info.mul) as i64;
-  Some(util::floor_search(exp, SKILL_EXP)? as i32 + 1)
-}
+  /// Hash of the file contents at load. See [`hash_file`].
+  pub fn content_hash(&self) -> u64 {
+    self.hash
+  }
 
-fn set_skill_lvl(sk2: &mut Value, date: &Value, skill: &SkillLvl) {
-  assert!((0..=200).contains(&skill.level));
-  if skill.level == 0 {
-    remove_skill(sk2, skill.info.id)
-  } else {
-    let exp = (SKILL_EXP[skill.level as usize - 1] as f64 * skill.info.mul).ceil() as i64;
-    let key = format!("{}", skill.info.id);
-    if let Some(skill) = sk2.get_mut(&key) {
-      // Set the skill's experience.
-      skill[X] = exp.into();
-    } else {
-      // Skill doesn't exist, so add it.
-      sk2[key] = serde_json::json!({
-        M: 0,
-        T: date,
-        X: exp,
-      });
+  pub fn get_gold(&self) -> Option<i64> {
+    json_num::to_i64_grouped(self.gold.get(G)?)
+  }
+
+  /// Set the gold, returns true if the value changed. The gold can't be negative or more than the
+  /// gold cap of [`GameData::limits`].
+  pub fn set_gold(&mut self, gold: i64) -> Result<bool, Error> {
+    self.available(Section::UserGold)?;
+    let old = self.get_gold();
+    if old == Some(gold) {
+      return Ok(false);
     }
+
+    self.check_gold(gold)?;
+    let prev = self.field_value(&Field::Gold);
+    let gold_val = Arc::make_mut(&mut self.gold);
+    gold_val[G] = encode_int(gold_val.get(G), gold);
+    let values = (int_value(old), ChangeValue::Int(gold));
+    self.record(ChangeCategory::Gold, "Gold", Field::Gold, prev, values);
+    Ok(true)
   }
-}
 
-fn remove_skill(sk2: &mut Value, id: u32) {
-  let skills = sk2.as_object_mut().unwrap();
-  skills.remove(&format!("{id}"));
-}
+  pub fn get_bank_gold(&self) -> Option<i64> {
+    json_num::to_i64_grouped(self.bank.as_ref()?.get(G)?)
+  }
 
-fn get_item_name(val: &Value) -> Option<String> {
-  let text = val.get(AN)?.as_str()?;
-  let pos = text.rfind('/')?;
+  /// Set the bank gold, returns true if the value changed. See [`GameData::set_gold`].
+  pub fn set_bank_gold(&mut self, gold: i64) -> Result<bool, Error> {
+    let old = self.get_bank_gold();
+    if old == Some(gold) {
+      return Ok(false);
+    }
 
-  Some(text[pos + 1..].into())
-}
+    self.check_gold(gold)?;
 
-trait ToI64 {
-  fn to_i64(&self) -> Option<i64>;
-}
+    // Create the record if the save doesn't have one.
+    let prev = self.field_value(&Field::Bank);
+    let bank = self
+      .bank
+      .get_or_insert_with(|| Arc::new(serde_json::json!({})));
+    let bank = Arc::make_mut(bank);
+    bank[G] = encode_int(bank.get(G), gold);
+    let values = (int_value(old), ChangeValue::Int(gold));
+    self.record(ChangeCategory::Gold, "Bank Gold", Field::Bank, prev, values);
+    Ok(true)
+  }
 
-impl ToI64 for Value {
-  fn to_i64(&self) -> Option<i64> {
-    match self {
-      Value::Number(val) => val.as_i64(),
-      Value::String(text) => text.parse().ok(),
-      _ => None,
+  fn check_gold(&self, gold: i64) -> Result<(), Error> {
+    let max = self.limits.gold;
+    if (0..=max).contains(&gold) {
+      return Ok(());
     }
+    Err(Error::InvalidGold { value: gold, max })
   }
-}
 
-fn get_avatar_id(text: &str) -> Result<String, Cow<'static, str>> {
-  // Get the User json.
-  let json = get_json(text, "User", USER_ID)?;
+  /// Gold carried plus gold in the bank.
+  pub fn total_gold(&self) -> i64 {
+    let gold = self.get_gold().unwrap_or(0);
+    let bank = self.get_bank_gold().unwrap_or(0);
+    gold.saturating_add(bank)
+  }
 
-  // Get the avatar ID.
-  if let Some(Value::String(id)) = json.get(DC) {
-    return Ok(id.clone());
+  /// Get the pooled adventurer experience.
+  pub fn get_adv_exp(&self) -> Option<i64> {
+    json_num::to_i64(self.character.get(AE)?)
   }
 
-  Err(Cow::from("Unable to determine the current avatar"))
-}
+  /// Set the pooled adventurer experience, returns true if it changed. See
+  /// [`GameData::exp_range`].
+  pub fn set_adv_exp(&mut self, exp: i64) -> Result<bool, Error> {
+    ExpOutOfRange::check("Adventurer", exp, self.exp_range())?;
+    let values = (int_value(self.get_adv_exp()), ChangeValue::Int(exp));
+    let name = "Adventurer Experience";
+    self.write_pooled_exp(Field::AdvExp, exp, name, values)
+  }
 
-fn get_avatar_name(text: &str, avatar: &str) -> Result<String, Cow<'static, str>> {
-  // Get the CharacterName json.
-  let json = get_json(text, "CharacterName", avatar)?;
+  /// Get the adventurer level. Negative experience is level 1.
+  pub fn get_adv_lvl(&self) -> i32 {
+    let exp = self.get_adv_exp().unwrap_or(0);
+    util::level_for_exp(exp, self.tables.level())
+  }
 
-  // Get the avatar name.
-  if let Some(Value::String(name)) = json.get(FN) {
-    return Ok(name.clone());
+  /// Get the adventurer level with the progress toward the next level, e.g. 99.72.
+  pub fn get_adv_lvl_f64(&self) -> f64 {
+    let exp = self.get_adv_exp().unwrap_or(0);
+    util::fractional_level(exp as f64, self.tables.level())
   }
 
-  Err(Cow::from("Unable to get the avatar name"))
-}
+  /// Get the adventurer experience debt, if the pooled experience is negative.
+  pub fn adv_exp_debt(&self) -> Option<i64> {
+    let exp = self.get_adv_exp()?;
+    (exp < 0).then_some(exp)
+  }
 
-fn get_backpack_id(text: &str, avatar: &str) -> Result<String, Cow<'static, str>> {
-  // Get the Character json.
-  let json = get_json(text, "Character", avatar)?;
+  /// Set negative pooled adventurer experience to zero, returns true if there was a debt.
+  pub fn clear_exp_debt(&mut self) -> bool {
+    let Some(debt) = self.adv_exp_debt() else {
+      return false;
+    };
 
-  // Get the backpack ID.
-  if let Some(Value::String(id)) = json.get("mainbp") {
-    return Ok(id.clone());
+    let values = (ChangeValue::Int(debt), ChangeValue::Int(0));
+    let name = "Adventurer Experience";
+    self
+      .write_pooled_exp(Field::AdvExp, 0, name, values)
+      .is_ok()
   }
 
-  Err(Cow::from("Unable to find the avatar's backpack"))
-}
+  /// Set the adventurer level, returns true if the experience changed. The level must be within
+  /// the level range of [`GameData::limits`].
+  pub fn set_adv_lvl(&mut self, lvl: i32) -> Result<bool, Error> {
+    OutOfRange::check("Adventurer", lvl, self.limits.level.clone())?;
+    let exp = util::exp_for_level(lvl, self.tables.level()).unwrap_or_default();
+    let old = self.get_adv_lvl();
+    let values = (ChangeValue::Int(old as i64), ChangeValue::Int(lvl as i64));
+    self.write_pooled_exp(Field::AdvExp, exp, "Adventurer Level", values)
+  }
+
+  /// Get the pooled producer experience.
+  pub fn get_prd_exp(&self) -> Option<i64> {
+    json_num::to_i64(self.character.get(PE)?)
+  }
+
+  /// Set the pooled producer experience, returns true if it changed. See [`GameData::exp_range`].
+  pub fn set_prd_exp(&mut self, exp: i64) -> Result<bool, Error> {
+    ExpOutOfRange::check("Producer", exp, self.exp_range())?;
+    let values = (int_value(self.get_prd_exp()), ChangeValue::Int(exp));
+    let name = "Producer Experience";
+    self.write_pooled_exp(Field::PrdExp, exp, name, values)
+  }
+
+  /// Get the producer level. Negative experience is level 1.
+  pub fn get_prd_lvl(&self) -> i32 {
+    let exp = self.get_prd_exp().unwrap_or(0);
+    util::level_for_exp(exp, self.tables.level())
+  }
+
+  /// Get the producer level with the progress toward the next level, e.g. 99.72.
+  pub fn get_prd_lvl_f64(&self) -> f64 {
+    let exp = self.get_prd_exp().unwrap_or(0);
+    util::fractional_level(exp as f64, self.tables.level())
+  }
+
+  /// Set the producer level, returns true if the experience changed. The level must be within
+  /// the level range of [`GameData::limits`].
+  pub fn set_prd_lvl(&mut self, lvl: i32) -> Result<bool, Error> {
+    OutOfRange::check("Producer", lvl, self.limits.level.clone())?;
+    let exp = util::exp_for_level(lvl, self.tables.level()).unwrap_or_default();
+    let old = self.get_prd_lvl();
+    let values = (ChangeValue::Int(old as i64), ChangeValue::Int(lvl as i64));
+    self.write_pooled_exp(Field::PrdExp, exp, "Producer Level", values)
+  }
+
+  /// Pooled experience that can be set, from none to the experience for the highest level of
+  /// [`GameData::limits`].
+  pub fn exp_range(&self) -> RangeInclusive<i64> {
+    let max = util::exp_for_level(*self.limits.level.end(), self.tables.level());
+    0..=max.unwrap_or_default()
+  }
+
+  // Write pooled adventurer or producer experience. Levels and experience are both set here.
+  fn write_pooled_exp(
+    &mut self,
+    field: Field,
+    exp: i64,
+    name: &'static str,
+    values: (ChangeValue, ChangeValue),
+  ) -> Result<bool, Error> {
+    self.available(Section::CharacterSheet)?;
+    let key = if field == Field::AdvExp { AE } else { PE };
+    let prev = self.field_value(&field);
+    let character = Arc::make_mut(&mut self.character);
+    if !set_exp(character, key, exp).ok_or_else(|| malformed_sheet("not an object"))? {
+      return Ok(false);
+    }
+
+    self.record(ChangeCategory::Other, name, field, prev, values);
+    Ok(true)
+  }
+
+  /// Use different experience tables for level calculations.
+  pub fn set_exp_tables(&mut self, tables: ExpTables) {
+    self.tables = tables;
+  }
+
+  /// Level and gold caps, which are [`Limits::default`] unless [`GameData::set_limits`] is called.
+  pub fn limits(&self) -> &Limits {
+    &self.limits
+  }
+
+  /// Use different level caps, e.g. from [`Limits::load_or_default`]. The experience tables must
+  /// have an entry for every level in the ranges.
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+  }
+
+  /// Copy the file to `<name>.bak-<seconds>` before storing over it, keeping the newest `keep`
+  /// backups (zero keeps them all). The store fails if the backup can't be made.
+  pub fn set_backup(&mut self, enabled: bool, keep: usize) {
+    self.backup = enabled.then_some(keep);
+  }
+
+  /// Use different display names for items.
+  pub fn set_item_names(&mut self, names: ItemNames) {
+    self.display_names = Arc::new(names);
+  }
+
+  pub fn get_file_path(&self) -> PathBuf {
+    self.path.read().unwrap().clone()
+  }
+
+  pub fn get_file_name(&self) -> String {
+    let path = self.path.read().unwrap();
+    let Some(name) = path.file_name() else {
+      return String::new();
+    };
+    name.to_string_lossy().into()
+  }
+
+  pub fn get_skills(&self, category: SkillCategory) -> Vec<SkillLvlGroup> {
+    let sk2 = self.character.get(SK2).unwrap();
+    let groups = skill_info::parse_skill_info_groups(category);
+    let mut skills = Vec::with_capacity(groups.len());
+    for group in groups {
+      skills.push(SkillLvlGroup::new(sk2, group, &self.tables));
+    }
+
+    skills
+  }
+
+  /// Set skill levels, returns true if any skill's experience changed. Nothing is changed if a
+  /// level isn't within the skill level range of [`GameData::limits`] (0 removes the skill).
+  pub fn set_skills(&mut self, skills: &[SkillLvlGroup]) -> Result<bool, Error> {
+    self.available(Section::CharacterSheet)?;
+
+    // Work on a copy so that a failure leaves the skills unchanged.
+    let mut sk2 = skills_mut(Arc::make_mut(&mut self.character))?.clone();
+    let date = self.date.get_or_insert_with(current_date);
+    let mut changes = Vec::new();
+    for group in skills {
+      for skill in &group.skills {
+        let old = get_skill_lvl(&sk2, &skill.info, &self.tables)
+          .level()
+          .unwrap_or(0);
+        let prev = sk2.get(skill.info.id.to_string()).cloned();
+        if set_skill_lvl(&mut sk2, date, skill, &self.tables, &self.limits)? {
+          changes.push((
+            skill.info.display_name(),
+            skill.info.id,
+            prev,
+            old,
+            skill.level,
+          ));
+        }
+      }
+    }
+
+    Arc::make_mut(&mut self.character)[SK2] = sk2;
+
+    let changed = !changes.is_empty();
+    self.apply_edit(|game| {
+      for (name, id, prev, old, new) in changes {
+        let values = (ChangeValue::Int(old as i64), ChangeValue::Int(new as i64));
+        game.record(ChangeCategory::Skill, name, Field::Skill(id), prev, values);
+      }
+    });
+    Ok(changed)
+  }
+
+  /// Set every skill in a group to the same level, with each skill's own multiplier. A level of 0
+  /// removes the skills. Returns the skills that changed. Nothing is changed if the level isn't
+  /// within the skill level range of [`GameData::limits`].
+  pub fn set_group_lvl(
+    &mut self,
+    group: &SkillInfoGroup,
+    lvl: i32,
+  ) -> Result<Vec<SkillAdjustment>, Error> {
+    self.set_groups_lvl(std::slice::from_ref(group), lvl)
+  }
+
+  /// Set every adventurer or producer skill to the same level. See [`GameData::set_group_lvl`].
+  pub fn set_category_lvl(
+    &mut self,
+    category: SkillCategory,
+    lvl: i32,
+  ) -> Result<Vec<SkillAdjustment>, Error> {
+    self.set_groups_lvl(&skill_info::parse_skill_info_groups(category), lvl)
+  }
+
+  fn set_groups_lvl(
+    &mut self,
+    groups: &[SkillInfoGroup],
+    lvl: i32,
+  ) -> Result<Vec<SkillAdjustment>, Error> {
+    let sk2 = self.character[SK2].clone();
+    let mut skills = Vec::with_capacity(groups.len());
+    for group in groups {
+      let skill = |info: &SkillInfo| SkillLvl {
+        level: lvl,
+        ..SkillLvl::new(&sk2, info.clone(), &self.tables)
+      };
+      skills.push(SkillLvlGroup {
+        name: group.name,
+        skills: group.skills.iter().map(skill).collect(),
+      });
+    }
+
+    self.set_skills(&skills)?;
+
+    // Report the skills whose experience changed.
+    let new = &self.character[SK2];
+    let mut changed = Vec::new();
+    for skill in skills.iter().flat_map(|group| &group.skills) {
+      let key = skill.info.id.to_string();
+      if sk2.get(&key) != new.get(&key) {
+        changed.push(SkillAdjustment {
+          id: skill.info.id,
+          name: skill.info.display_name(),
+          before: skill.comp,
+          after: skill.level,
+        });
+      }
+    }
+    Ok(changed)
+  }
+
+  /// Add `delta` levels to every trained skill, keeping the results within `clamp` (and the skill
+  /// level range of [`GameData::limits`]). Skills that aren't in the registry are left alone and
+  /// listed in the report.
+  pub fn adjust_all_skills(
+    &mut self,
+    registry: &SkillRegistry,
+    delta: i32,
+    clamp: RangeInclusive<i32>,
+  ) -> Result<AdjustReport, Error> {
+    if clamp.is_empty() {
+      let err = format!("Invalid level range {}-{}", clamp.start(), clamp.end());
+      return Err(Error::other(err));
+    }
+
+    let mut report = AdjustReport::default();
+    let ids = self.trained_skill_ids();
+
+    // Work on a copy so that a failure leaves the skills unchanged.
+    let mut sk2 = skills_mut(Arc::make_mut(&mut self.character))?.clone();
+    let date = self.date.get_or_insert_with(current_date);
+    let mut changes = Vec::new();
+    for id in ids {
+      let info = id.parse().ok().and_then(|id| registry.find_id(id));
+      let Some((_, _, info)) = info else {
+        report.unknown.push(id);
+        continue;
+      };
+
+      let before = get_skill_lvl(&sk2, info, &self.tables).level().unwrap_or(0);
+      let after = (before + delta).clamp(*clamp.start(), *clamp.end()).clamp(
+        *self.limits.skill_level.start(),
+        *self.limits.skill_level.end(),
+      );
+      let skill = SkillLvl {
+        info: info.clone(),
+        level: after,
+        comp: before,
+      };
+
+      let prev = sk2.get(&id).cloned();
+      if set_skill_lvl(&mut sk2, date, &skill, &self.tables, &self.limits)? {
+        changes.push((info.display_name(), info.id, prev, before, after));
+      }
+
+      report.skills.push(SkillAdjustment {
+        id: info.id,
+        name: info.display_name(),
+        before,
+        after,
+      });
+    }
+
+    Arc::make_mut(&mut self.character)[SK2] = sk2;
+    self.apply_edit(|game| {
+      for (name, id, prev, old, new) in changes {
+        let values = (ChangeValue::Int(old as i64), ChangeValue::Int(new as i64));
+        game.record(ChangeCategory::Skill, name, Field::Skill(id), prev, values);
+      }
+    });
+    Ok(report)
+  }
+
+  /// Make a template of the gold, levels and trained registry skills, which can be applied to
+  /// another avatar with [`GameData::apply_template`].
+  pub fn export_template(&self, registry: &SkillRegistry) -> CharacterTemplate {
+    let available = |section| self.available(section).is_ok();
+    let mut plan = BTreeMap::new();
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in registry.groups(category) {
+        for info in &group.skills {
+          if let Some(level) = self.skill_level(info).level() {
+            plan.insert(info.id, level);
+          }
+        }
+      }
+    }
+
+    let sheet = available(Section::CharacterSheet);
+    CharacterTemplate {
+      gold: self.get_gold(),
+      adv_lvl: sheet.then(|| self.get_adv_lvl()),
+      prd_lvl: sheet.then(|| self.get_prd_lvl()),
+      plan: sheet.then_some(plan),
+    }
+  }
+
+  /// Apply a template's gold, levels and skill levels. Values that the template doesn't have are
+  /// left alone, as are skills that aren't in the plan. Nothing is changed if any of it fails, but
+  /// if `progress` is canceled then what was applied before that is kept and reported.
+  pub fn apply_template(
+    &mut self,
+    template: &CharacterTemplate,
+    registry: &SkillRegistry,
+    progress: Option<&dyn ProgressSink>,
+  ) -> Result<ApplyReport, BatchError<ApplyReport>> {
+    self.apply_edit(|game| game.write_template(template, registry, progress))
+  }
+
+  fn write_template(
+    &mut self,
+    template: &CharacterTemplate,
+    registry: &SkillRegistry,
+    progress: Option<&dyn ProgressSink>,
+  ) -> Result<ApplyReport, BatchError<ApplyReport>> {
+    // Work on a copy so that a failure leaves everything unchanged.
+    let mut game = self.clone();
+    let mut report = ApplyReport::default();
+    if let Some(gold) = template.gold {
+      game.set_gold(gold).map_err(Cow::from)?;
+    }
+
+    if let Some(lvl) = template.adv_lvl {
+      game.set_adv_lvl(lvl)?;
+    }
+
+    if let Some(lvl) = template.prd_lvl {
+      game.set_prd_lvl(lvl)?;
+    }
+
+    if let Some(plan) = &template.plan {
+      let total = plan.len();
+      for (done, (&id, &level)) in plan.iter().enumerate() {
+        let label = registry.display_name(id);
+        if util::report_progress(progress, done, total, label) {
+          report.changes = game.journal.len() - self.journal.len();
+          *self = game;
+          return Err(BatchError::Canceled(report));
+        }
+
+        let Some((_, _, info)) = registry.find_id(id) else {
+          report.unknown.push(id);
+          continue;
+        };
+
+        // Skills are set one at a time so that a cancel can keep the ones already done.
+        let comp = game.skill_level(info).level().unwrap_or(0);
+        let info = info.clone();
+        let skills = vec![SkillLvl { info, level, comp }];
+        game.set_skills(&[SkillLvlGroup {
+          name: "Template",
+          skills,
+        }])?;
+      }
+    }
+
+    report.changes = game.journal.len() - self.journal.len();
+    *self = game;
+    Ok(report)
+  }
+
+  /// Make a build of the trained skills in a category, with the levels that
+  /// [`GameData::get_skills`] shows.
+  pub fn export_build(&self, category: SkillCategory) -> Build {
+    let sk2 = &self.character[SK2];
+    let mut build = Build::new(category);
+    for group in skill_info::parse_skill_info_groups(category) {
+      for info in &group.skills {
+        if let Some(level) = get_skill_lvl(sk2, info, &self.tables).level() {
+          build.skills.insert(info.id, level);
+        }
+      }
+    }
+    build
+  }
+
+  /// Set the skill levels in a build, returns true if any skill's experience changed. Skills at
+  /// level 0 are removed and skills that the build doesn't have are left alone. Nothing is changed
+  /// if the build has skills that aren't in the skill CSVs or levels that are out of range.
+  pub fn apply_build(&mut self, build: &Build) -> Result<bool, Error> {
+    build.validate()?;
+
+    let sk2 = &self.character[SK2];
+    let mut skills = Vec::with_capacity(build.skills.len());
+    for group in skill_info::parse_skill_info_groups(build.category) {
+      for info in group.skills {
+        if let Some(&level) = build.skills.get(&info.id) {
+          let mut skill = SkillLvl::new(sk2, info, &self.tables);
+          skill.level = level;
+          skills.push(skill);
+        }
+      }
+    }
+
+    self.set_skills(&[SkillLvlGroup {
+      name: "Build",
+      skills,
+    }])
+  }
+
+  /// Write the trained skills as CSV in the same format as the skill files, with the level in an
+  /// extra column: `group,name,mul,id,level`. Skills are ordered like the registry, adventurer
+  /// skills first. Skills that aren't in the registry are listed last in an "Unknown" group with
+  /// no name and a multiplier of 1.
+  pub fn export_skills_csv(&self, registry: &SkillRegistry, path: &Path) -> Result<(), Error> {
+    let sk2 = &self.character[SK2];
+    let mut known = HashSet::new();
+    let mut text = String::new();
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in registry.groups(category) {
+        for info in &group.skills {
+          let Some(level) = get_skill_lvl(sk2, info, &self.tables).level() else {
+            continue;
+          };
+
+          known.insert(info.id.to_string());
+          let (group, name, mul, id) = (group.name, info.name, info.mul, info.id);
+          text += &format!("{group},{name},{mul},{id},{level}\n");
+        }
+      }
+    }
+
+    if let Some(skills) = sk2.as_object() {
+      for id in skills.keys().filter(|id| !known.contains(*id)) {
+        let Ok(id) = id.parse::<u32>() else {
+          continue;
+        };
+
+        let info = SkillInfo {
+          mul: 1.0,
+          id,
+          ..Default::default()
+        };
+        let level = get_skill_lvl(sk2, &info, &self.tables).level().unwrap_or(0);
+        text += &format!("{UNKNOWN_GROUP},,1,{id},{level}\n");
+      }
+    }
+
+    if let Err(err) = std::fs::write(path, text) {
+      let err = format!("Unable to write '{}': {err}", path.display());
+      return Err(Error::other(err));
+    }
+
+    Ok(())
+  }
+
+  /// Exchange the levels of the skills in the registry between two avatars in this save-game.
+  /// Skills that aren't in the registry stay where they are. If `swap_pool` is true then the
+  /// pooled adventurer and producer experience is exchanged too.
+  pub fn swap_skills(
+    &mut self,
+    avatar_a: &str,
+    avatar_b: &str,
+    registry: &SkillRegistry,
+    swap_pool: bool,
+  ) -> Result<(), Error> {
+    if avatar_a == avatar_b {
+      return Err(Error::other(
+        "Unable to swap an avatar's skills with itself",
+      ));
+    }
+
+    let mut sheet_a = self.sheet(avatar_a)?;
+    let mut sheet_b = self.sheet(avatar_b)?;
+    let name = |avatar: &str| get_avatar_name(&self.text, avatar).unwrap_or_else(|_| avatar.into());
+    let (name_a, name_b) = (name(avatar_a), name(avatar_b));
+
+    // Take the known skills out of each sheet and put them in the other.
+    let skills_a = take_known_skills(&mut sheet_a, registry)?;
+    let skills_b = take_known_skills(&mut sheet_b, registry)?;
+    let (count_a, count_b) = (skills_a.len() as i64, skills_b.len() as i64);
+    skills_object_mut(&mut sheet_a)?.extend(skills_b);
+    skills_object_mut(&mut sheet_b)?.extend(skills_a);
+
+    // Each step is the sheets after it, which are recorded as separate changes.
+    let category = ChangeCategory::Skill;
+    let mut steps = Vec::new();
+    let name = format!("Skills from {name_b} ({name_a})");
+    let values = (ChangeValue::Int(count_a), ChangeValue::Int(count_b));
+    steps.push((avatar_a, sheet_a.clone(), category, name, values));
+    let name = format!("Skills from {name_a} ({name_b})");
+    let values = (ChangeValue::Int(count_b), ChangeValue::Int(count_a));
+    steps.push((avatar_b, sheet_b.clone(), category, name, values));
+
+    if swap_pool {
+      for (key, label) in [(AE, "Adventurer Experience"), (PE, "Producer Experience")] {
+        let (Some(obj_a), Some(obj_b)) = (sheet_a.as_object_mut(), sheet_b.as_object_mut()) else {
+          return Err(malformed_sheet("not an object").into());
+        };
+
+        // Keys that neither sheet has stay absent.
+        let exp_a = obj_a.get(key).cloned();
+        let exp_b = obj_b.get(key).cloned();
+        if exp_a.is_none() && exp_b.is_none() {
+          continue;
+        }
+
+        let int = |exp: &Option<Value>| int_value(exp.as_ref().and_then(json_num::to_i64));
+        let values_a = (int(&exp_a), int(&exp_b));
+        let values_b = (int(&exp_b), int(&exp_a));
+        for (obj, exp) in [(obj_a, exp_b), (obj_b, exp_a)] {
+          match exp {
+            Some(exp) => obj.insert(key.into(), exp),
+            None => obj.shift_remove(key),
+          };
+        }
+
+        let category = ChangeCategory::Other;
+        let name = format!("{label} ({name_a})");
+        steps.push((avatar_a, sheet_a.clone(), category, name, values_a));
+        let name = format!("{label} ({name_b})");
+        steps.push((avatar_b, sheet_b.clone(), category, name, values_b));
+      }
+    }
+
+    self.apply_edit(|game| {
+      for (avatar, sheet, category, name, values) in steps {
+        let field = Field::Sheet(avatar.to_owned());
+        let prev = game.field_value(&field);
+        game.set_sheet(avatar, sheet);
+        game.record(category, name, field, prev, values);
+      }
+    });
+
+    Ok(())
+  }
+
+  /// Compare two avatars in this save-game. Skills that have different levels are listed with
+  /// `before` as avatar `a`'s level and `after` as `b`'s, zero if untrained. Gold belongs to the
+  /// account rather than an avatar, so it's reported once as shared.
+  pub fn compare_avatars(
+    &self,
+    a: &str,
+    b: &str,
+    registry: &SkillRegistry,
+  ) -> Result<AvatarComparison, Error> {
+    let sheet_a = self.sheet(a)?;
+    let sheet_b = self.sheet(b)?;
+    let mut skills = Vec::new();
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in registry.groups(category) {
+        for info in &group.skills {
+          let level = |sheet: &Value| {
+            let level = get_skill_lvl(&sheet[SK2], info, &self.tables);
+            level.level().unwrap_or(0)
+          };
+
+          let (before, after) = (level(&sheet_a), level(&sheet_b));
+          if before != after {
+            skills.push(SkillAdjustment {
+              id: info.id,
+              name: info.name,
+              before,
+              after,
+            });
+          }
+        }
+      }
+    }
+
+    Ok(AvatarComparison {
+      a: self.avatar_summary(a, &sheet_a),
+      b: self.avatar_summary(b, &sheet_b),
+      skills,
+      shared_gold: self.get_gold(),
+    })
+  }
+
+  fn avatar_summary(&self, avatar: &str, sheet: &Value) -> AvatarSummary {
+    let level = |key| {
+      let exp = sheet.get(key).and_then(json_num::to_i64).unwrap_or(0);
+      util::level_for_exp(exp, self.tables.level())
+    };
+
+    // Each avatar has its own backpack.
+    let backpack = get_backpack_id(&self.text, avatar).ok();
+    let items = backpack
+      .and_then(|id| self.item_store(&id).ok())
+      .and_then(|store| Some(store.get(IN)?.as_object()?.len()));
+
+    AvatarSummary {
+      id: avatar.into(),
+      name: get_avatar_name(&self.text, avatar).unwrap_or_else(|_| avatar.to_owned()),
+      adv_lvl: level(AE),
+      prd_lvl: level(PE),
+      items,
+    }
+  }
+
+  /// Get the IDs of the trained skills, as they appear in the save-game.
+  pub fn trained_skill_ids(&self) -> Vec<String> {
+    match self.character.get(SK2).and_then(|sk2| sk2.as_object()) {
+      Some(sk2) => sk2.keys().cloned().collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// Get the skills in the save-game with their experience, level and when they were last trained,
+  /// in save-game order. If `category` is `None` then skills that aren't in the registry are
+  /// included too, with no name or category.
+  pub fn trained_skills(
+    &self,
+    category: Option<SkillCategory>,
+    registry: &SkillRegistry,
+  ) -> Vec<TrainedSkill> {
+    let mut index = HashMap::new();
+    for cat in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in registry.groups(cat) {
+        for info in &group.skills {
+          index.insert(info.id, (cat, group.name, info));
+        }
+      }
+    }
+
+    let sk2 = &self.character[SK2];
+    let Some(entries) = sk2.as_object() else {
+      return Vec::new();
+    };
+
+    let mut skills = Vec::with_capacity(entries.len());
+    for (key, val) in entries {
+      let Ok(id) = key.parse::<u32>() else {
+        continue;
+      };
+
+      let found = index.get(&id).copied();
+      if category.is_some() && found.map(|(cat, _, _)| cat) != category {
+        continue;
+      }
+
+      // Unknown skills have a multiplier of 1.
+      let unknown = SkillInfo {
+        mul: 1.0,
+        id,
+        ..Default::default()
+      };
+      let info = found.map_or(&unknown, |(_, _, info)| info);
+      let time = val
+        .get(T)
+        .and_then(|date| json_num::to_i64(date.get(DATE)?));
+      skills.push(TrainedSkill {
+        id,
+        category: found.map(|(cat, _, _)| cat),
+        group: found.map(|(_, group, _)| group),
+        name: found.map(|(_, _, info)| info.display_name()),
+        exp: val.get(X).and_then(json_num::to_i64),
+        level: get_skill_lvl(sk2, info, &self.tables),
+        time: time.and_then(DateTime::from_timestamp_millis),
+      });
+    }
+    skills
+  }
+
+  /// Get a skill's experience as it is in the save-game, i.e. with the skill's multiplier.
+  pub fn get_skill_exp(&self, id: u32) -> Option<i64> {
+    json_num::to_i64(self.character.get(SK2)?.get(id.to_string())?.get(X)?)
+  }
+
+  /// Set a skill's experience (with its multiplier), training the skill if it isn't. Returns true
+  /// if it changed. The experience can't be negative or more than the experience for the highest
+  /// skill level of [`GameData::limits`].
+  pub fn set_skill_exp(&mut self, id: u32, exp: i64) -> Result<bool, Error> {
+    let info = [SkillCategory::Adventurer, SkillCategory::Producer]
+      .into_iter()
+      .flat_map(skill_info::parse_skill_info_groups)
+      .flat_map(|group| group.skills)
+      .find(|info| info.id == id);
+    let Some(info) = info else {
+      return Err(Error::other(format!("Unknown skill ID {id}")));
+    };
+
+    let max = util::exp_for_level(*self.limits.skill_level.end(), self.tables.skill());
+    let max = (max.unwrap_or_default() as f64 * info.mul).ceil() as i64;
+    ExpOutOfRange::check(info.display_name(), exp, 0..=max)?;
+    self.available(Section::CharacterSheet)?;
+
+    let old = self.get_skill_exp(id);
+    let prev = self.character[SK2].get(id.to_string()).cloned();
+    let date = self.date.get_or_insert_with(current_date).clone();
+    let sk2 = skills_mut(Arc::make_mut(&mut self.character))?;
+    if !write_skill_exp(sk2, &date, id, exp)? {
+      return Ok(false);
+    }
+
+    let name = format!("{} Experience", info.display_name());
+    let values = (int_value(old), ChangeValue::Int(exp));
+    self.record(ChangeCategory::Skill, name, Field::Skill(id), prev, values);
+    Ok(true)
+  }
+
+  /// Get a skill's level, telling untrained skills apart from bad experience values.
+  pub fn skill_level(&self, info: &SkillInfo) -> SkillLevel {
+    get_skill_lvl(&self.character[SK2], info, &self.tables)
+  }
+
+  /// Get a skill's level with the progress toward the next level, e.g. 99.72. Returns `None` if the
+  /// skill isn't trained or its experience is invalid.
+  pub fn get_skill_lvl_f64(&self, id: u32, mul: f64) -> Option<f64> {
+    let exp = self.character.get(SK2)?.get(id.to_string())?.get(X)?;
+    let exp = json_num::to_i64(exp)? as f64 / mul;
+    (exp as i64 >= 0).then(|| util::fractional_level(exp, self.tables.skill()))
+  }
+
+  pub fn get_inventory_items(&self) -> Vec<Item> {
+    let inv = self.inventory.get(IN).unwrap();
+    let items_map = inv.as_object().unwrap();
+    let mut items = Vec::with_capacity(items_map.len());
+    for (key, val) in items_map {
+      if let Some(item) = Item::new(val, key, &self.item_names, &self.display_names) {
+        items.push(item);
+      }
+    }
+
+    items
+  }
+
+  /// Get the items in the backpack and in the bags inside it, with each bag followed by its
+  /// contents. Items in bags have [`Item::parent_id`] set. Bags whose contents are missing or were
+  /// already listed (e.g. a bag inside itself) are skipped, with a warning for each.
+  pub fn get_inventory_tree(&self) -> (Vec<Item>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut warnings = Vec::new();
+    let mut visited = HashSet::from([self.backpack.clone()]);
+    let store = (None, &self.inventory as &Value);
+    self.collect_items(store, None, &mut visited, &mut items, &mut warnings);
+    (items, warnings)
+  }
+
+  // Add the items in an ItemStore to the tree, followed by the contents of any bags.
+  fn collect_items(
+    &self,
+    (store_id, store): (Option<&str>, &Value),
+    parent: Option<&str>,
+    visited: &mut HashSet<String>,
+    items: &mut Vec<Item>,
+    warnings: &mut Vec<String>,
+  ) {
+    let Some(store_items) = store.get(IN).and_then(Value::as_object) else {
+      let id = store_id.unwrap_or(&self.backpack);
+      warnings.push(format!("The contents of {id} are malformed"));
+      return;
+    };
+
+    for (id, val) in store_items {
+      let Some(mut item) = Item::new(val, id, &self.item_names, &self.display_names) else {
+        continue;
+      };
+
+      item.parent = parent.map(String::from);
+      item.store = store_id.map(String::from);
+      let name = item.display_name.clone();
+      items.push(item);
+
+      let Some(bag) = val[IN].get(BAG).and_then(Value::as_str) else {
+        continue;
+      };
+
+      if !visited.insert(bag.to_owned()) {
+        warnings.push(format!(
+          "Skipped the contents of {name} ({id}): already listed"
+        ));
+        continue;
+      }
+
+      match self.item_store(bag) {
+        Ok(contents) => {
+          let store = (Some(bag), contents.as_ref());
+          self.collect_items(store, Some(id), visited, items, warnings);
+        }
+        Err(err) => warnings.push(format!("Skipped the contents of {name} ({id}): {err}")),
+      }
+    }
+  }
+
+  /// Sum the counts of the items whose name matches, grouped by name (ignoring case) and sorted by
+  /// count, largest first. If `recursive` is true then the contents of bags are included.
+  pub fn count_by_name(
+    &self,
+    search: &Search,
+    recursive: bool,
+    kind: ItemNameKind,
+  ) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, (String, u64)> = HashMap::new();
+    self.visit_items(recursive, |_, _, val| {
+      let Some(name) = item_name(val) else {
+        return;
+      };
+      let name = match kind {
+        ItemNameKind::Display => self.display_names.display_name(name).into_owned(),
+        ItemNameKind::Raw => name.to_owned(),
+      };
+      let Some(cnt) = val.get(QN).and_then(json_num::to_u64) else {
+        return;
+      };
+
+      if search.find_in(&name).is_none() {
+        return;
+      }
+
+      let entry = counts.entry(name.to_lowercase()).or_insert((name, 0));
+      entry.1 += cnt;
+    });
+
+    let mut counts: Vec<(String, u64)> = counts.into_values().collect();
+    counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+  }
+
+  /// Find items with the same asset that are in more than one stack. If `recursive` is true then
+  /// the contents of bags are included.
+  pub fn duplicate_items(&self, recursive: bool) -> Vec<DuplicateGroup> {
+    let mut groups: BTreeMap<String, Vec<ItemInstance>> = BTreeMap::new();
+    self.visit_items(recursive, |container, id, val| {
+      let Some(asset) = val.get(AN).and_then(|asset| asset.as_str()) else {
+        return;
+      };
+      let Some(count) = val.get(QN).and_then(json_num::to_u64) else {
+        return;
+      };
+
+      // Bags are never duplicates.
+      if val.get(BAG).is_some() {
+        return;
+      }
+
+      groups.entry(asset.into()).or_default().push(ItemInstance {
+        container: container.into(),
+        id: id.into(),
+        count,
+        durability: Durability::new(val),
+      });
+    });
+
+    groups
+      .into_iter()
+      .filter(|(_, instances)| instances.len() > 1)
+      .map(|(asset, instances)| DuplicateGroup { asset, instances })
+      .collect()
+  }
+
+  /// Merge the group's stacks into the first one, including stacks in bags. Returns the number of
+  /// stacks removed. Nothing is changed if any of the stacks can't be found.
+  pub fn merge_stacks(&mut self, group: &DuplicateGroup) -> Result<usize, Error> {
+    self.available(Section::ItemStore)?;
+    if !group.mergeable() {
+      return Err(Error::other(format!("Unable to merge {}", group.asset)));
+    }
+
+    let Some((first, rest)) = group.instances.split_first() else {
+      return Ok(0);
+    };
+
+    // Change copies of the ItemStore records, so that an error leaves everything as it was.
+    let mut stores: Vec<(String, Value)> = Vec::new();
+    for instance in &group.instances {
+      if !stores.iter().any(|(id, _)| *id == instance.container) {
+        let store = self.item_store(&instance.container)?.into_owned();
+        stores.push((instance.container.clone(), store));
+      }
+    }
+
+    let mut total = 0;
+    for instance in rest {
+      let val = stack_mut(&mut stores, instance)?;
+      total += val.get(QN).and_then(json_num::to_u64).unwrap_or(0);
+    }
+
+    let val = stack_mut(&mut stores, first)?;
+    let count = val.get(QN).and_then(json_num::to_u64).unwrap_or(0);
+    set_value(val, QN, (count + total).into());
+    let name = format!("{} stacks", get_item_name(val).unwrap_or_default());
+
+    for instance in rest {
+      let store = stores.iter_mut().find(|(id, _)| *id == instance.container);
+      if let Some(items) = store.and_then(|(_, store)| store[IN].as_object_mut()) {
+        items.shift_remove(&instance.id);
+      }
+    }
+
+    self.apply_edit(|game| {
+      for (id, store) in stores {
+        let stacks = group.instances.iter().filter(|inst| inst.container == id);
+        let old = ChangeValue::Int(stacks.count() as i64);
+        let new = ChangeValue::Int((id == first.container) as i64);
+        let field = game.store_field(&id);
+        let prev = game.field_value(&field);
+        game.set_field_value(&field, Some(store));
+        game.record(ChangeCategory::Item, &name, field, prev, (old, new));
+      }
+    });
+    Ok(rest.len())
+  }
+
+  // The journal field for an ItemStore record.
+  fn store_field(&self, id: &str) -> Field {
+    if id == self.backpack {
+      Field::Inventory
+    } else {
+      Field::Store(id.to_owned())
+    }
+  }
+
+  // Call `visit` with the container ID, item ID and item JSON of each item.
+  fn visit_items(&self, recursive: bool, mut visit: impl FnMut(&str, &str, &Value)) {
+    let mut visited = HashSet::from([self.backpack.clone()]);
+    let mut stores = vec![(
+      self.backpack.clone(),
+      Cow::Borrowed(self.inventory.as_ref()),
+    )];
+
+    while let Some((container, store)) = stores.pop() {
+      let Some(items) = store.get(IN).and_then(|items| items.as_object()) else {
+        continue;
+      };
+
+      for (id, val) in items {
+        let Some(val) = val.get(IN) else { continue };
+
+        // Queue the bag's contents.
+        if recursive {
+          if let Some(id) = val.get(BAG).and_then(|id| id.as_str()) {
+            if visited.insert(id.to_owned()) {
+              if let Ok(bag) = self.item_store(id) {
+                stores.push((id.to_owned(), bag));
+              }
+            }
+          }
+        }
+
+        visit(&container, id, val);
+      }
+    }
+  }
+
+  // Get an ItemStore record's JSON, with any changes that haven't been stored.
+  fn item_store(&self, id: &str) -> Result<Cow<'_, Value>, Error> {
+    if id == self.backpack {
+      self.available(Section::ItemStore)?;
+      return Ok(Cow::Borrowed(self.inventory.as_ref()));
+    }
+
+    match self.stores.get(id) {
+      Some(Value::Null) => Err(Error::MissingRecord {
+        collection: ITEM_STORE,
+        id: id.into(),
+      }),
+      Some(store) => Ok(Cow::Borrowed(store)),
+      None => Ok(Cow::Owned(get_json(&self.text, ITEM_STORE, id)?)),
+    }
+  }
+
+  // Find an item in the backpack or any bag in it. Returns the ID of the ItemStore that it's in and
+  // the item's JSON.
+  fn find_item(&self, id: &str) -> Option<(String, Value)> {
+    let mut found = None;
+    self.visit_items(true, |container, item_id, _| {
+      if found.is_none() && item_id == id {
+        found = Some(container.to_owned());
+      }
+    });
+
+    let container = found?;
+    let item = self.item_store(&container).ok()?[IN].get(id)?.clone();
+    Some((container, item))
+  }
+
+  /// Write a container (a bag or chest) and everything in it, including bags inside it, to a JSON
+  /// file that [`GameData::import_container`] can add to another save-game.
+  pub fn export_container(&self, id: &str, path: &Path) -> Result<(), Error> {
+    let Some((_, item)) = self.find_item(id) else {
+      return Err(Error::other(format!("Unable to find item {id}")));
+    };
+
+    let Some(bag) = item.get(IN).and_then(|val| val.get(BAG)?.as_str()) else {
+      return Err(Error::other(format!("Item {id} is not a container")));
+    };
+
+    // Collect the container's ItemStore and the ones of the bags inside it.
+    let mut stores = BTreeMap::new();
+    let mut pending = vec![bag.to_owned()];
+    while let Some(store_id) = pending.pop() {
+      if stores.contains_key(&store_id) {
+        continue;
+      }
+
+      let Ok(store) = self.item_store(&store_id).map(Cow::into_owned) else {
+        let err = ContainerError::MissingStore(store_id);
+        return Err(Error::other(err.to_string()));
+      };
+
+      pending.extend(store_bags(&store).into_iter().map(String::from));
+      stores.insert(store_id, store);
+    }
+
+    let file = ContainerFile {
+      version: CONTAINER_VERSION,
+      id: id.into(),
+      item,
+      stores,
+    };
+
+    let text = serde_json::to_string_pretty(&file).unwrap();
+    if let Err(err) = std::fs::write(path, text) {
+      let err = format!("Unable to write '{}': {err}", path.display());
+      return Err(Error::other(err));
+    }
+
+    Ok(())
+  }
+
+  /// Add a container written by [`GameData::export_container`]. All the IDs are replaced with new
+  /// ones so that the same file can be imported more than once. Returns the container's new ID.
+  pub fn import_container(&mut self, path: &Path, into: &ContainerTarget) -> Result<String, Error> {
+    self.apply_edit(|game| game.write_container(path, into))
+  }
+
+  fn write_container(&mut self, path: &Path, into: &ContainerTarget) -> Result<String, Error> {
+    let text = std::fs::read_to_string(path);
+    let text = text.map_err(Error::io(FileOp::Load, path))?;
+    let file = parse_container(&text).map_err(|err| Cow::from(err.to_string()))?;
+
+    // Find the ItemStore to put the container in.
+    let target = match into {
+      ContainerTarget::Backpack => {
+        self.available(Section::ItemStore)?;
+        self.backpack.clone()
+      }
+      ContainerTarget::Container(id) => {
+        let bag = self.find_item(id).and_then(|(_, item)| {
+          let bag = item.get(IN)?.get(BAG)?.as_str()?;
+          Some(bag.to_owned())
+        });
+        bag.ok_or_else(|| Cow::from(format!("Item {id} is not a container")))?
+      }
+    };
+
+    // Make new IDs for the stores and every item in them.
+    let items = file
+      .stores
+      .values()
+      .map(|store| store[IN].as_object().unwrap().len());
+    let mut ids = self
+      .new_ids(file.stores.len() + items.sum::<usize>() + 1)
+      .into_iter();
+    let mut new_id = HashMap::new();
+    for (store_id, store) in &file.stores {
+      new_id.insert(store_id.as_str(), ids.next().unwrap());
+      let items = store[IN].as_object().unwrap();
+      for item_id in items.keys() {
+        new_id.insert(item_id.as_str(), ids.next().unwrap());
+      }
+    }
+    new_id.insert(file.id.as_str(), ids.next().unwrap());
+
+    let remap = |item: &Value| {
+      let mut item = item.clone();
+      if let Some(bag) = item[IN].get(BAG).and_then(|bag| bag.as_str()) {
+        let bag = new_id[bag].clone();
+        item[IN][BAG] = bag.into();
+      }
+      item
+    };
+
+    // Add the stores, contents first.
+    let mut changes = Vec::new();
+    for (store_id, store) in &file.stores {
+      let mut items = serde_json::Map::new();
+      for (item_id, item) in store[IN].as_object().unwrap() {
+        items.insert(new_id[item_id.as_str()].clone(), remap(item));
+      }
+
+      let mut store = store.clone();
+      let count = items.len() as i64;
+      store[IN] = Value::Object(items);
+      changes.push((new_id[store_id.as_str()].clone(), store, count));
+    }
+
+    let asset = item_name(&file.item[IN]).unwrap_or_default();
+    let name = self.display_names.display_name(asset).into_owned();
+    for (id, store, count) in changes {
+      Arc::make_mut(&mut self.stores).insert(id.clone(), store);
+      let values = (ChangeValue::None, ChangeValue::Int(count));
+      let field = Field::Store(id);
+      self.record(
+        ChangeCategory::Item,
+        format!("{name} Contents"),
+        field,
+        None,
+        values,
+      );
+    }
+
+    // Add the container.
+    let id = new_id[file.id.as_str()].clone();
+    let item = remap(&file.item);
+    let values = (ChangeValue::None, ChangeValue::Int(1));
+    let name = format!("Imported {name}");
+    if target == self.backpack {
+      let prev = self.field_value(&Field::Inventory);
+      let inventory = Arc::make_mut(&mut self.inventory);
+      inventory[IN]
+        .as_object_mut()
+        .unwrap()
+        .insert(id.clone(), item);
+      self.record(ChangeCategory::Item, name, Field::Inventory, prev, values);
+    } else {
+      let field = Field::Store(target.clone());
+      let prev = self.field_value(&field);
+      let mut store = self.item_store(&target)?.into_owned();
+      let Some(items) = store.get_mut(IN).and_then(|items| items.as_object_mut()) else {
+        return Err(Error::other(format!(
+          "The contents of {target} are malformed"
+        )));
+      };
+
+      items.insert(id.clone(), item);
+      Arc::make_mut(&mut self.stores).insert(target, store);
+      self.record(ChangeCategory::Item, name, field, prev, values);
+    }
+
+    Ok(id)
+  }
+
+  // Make IDs that aren't used in the save-game, in the same 24 digit hex form as the game's.
+  fn new_ids(&self, count: usize) -> Vec<String> {
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+    let (secs, nanos) = now.map_or((0, 0), |now| (now.as_secs(), now.subsec_nanos()));
+    let start = self.hash ^ hash_bytes(&nanos.to_le_bytes());
+    let taken = |id: &str| {
+      self.text.contains(id)
+        || self.inventory[IN].get(id).is_some()
+        || self
+          .stores
+          .iter()
+          .any(|(store_id, store)| store_id == id || store[IN].get(id).is_some())
+    };
+
+    (0..u64::MAX)
+      .map(|idx| format!("{:08x}{:016x}", secs as u32, start.wrapping_add(idx)))
+      .filter(|id| !taken(id))
+      .take(count)
+      .collect()
+  }
+
+  /// Set the durability of the listed items to a percentage of their maximum. The items can be in
+  /// the backpack or in bags in it. Items without durability are skipped. Returns the number of
+  /// items that changed.
+  pub fn set_durability_percent(&mut self, ids: &[&str], percent: f64) -> Result<usize, Error> {
+    if !(0.0..=100.0).contains(&percent) {
+      return Err(Error::other(format!(
+        "Invalid durability percentage: {percent}"
+      )));
+    }
+
+    self.available(Section::ItemStore)?;
+
+    // Find the items and check all the new values before changing anything.
+    let mut found = Vec::new();
+    let mut invalid = Vec::new();
+    for id in ids {
+      let Some((container, item)) = self.find_item(id) else {
+        continue;
+      };
+
+      let Some(dur) = item.get(IN).and_then(Durability::new) else {
+        continue;
+      };
+
+      let minor = dur.major * percent / 100.0;
+      let new = Durability { minor, ..dur };
+      if let Err(err) = new.validate(DurabilityPolicy::Reject) {
+        invalid.push(format!("{id} ({err})"));
+        continue;
+      }
+      found.push((container, id.to_string(), item, dur.minor, minor));
+    }
+    if !invalid.is_empty() {
+      return Err(Error::Other(invalid_durability(&invalid)));
+    }
+
+    // Items in bags are changed in a copy of the bag's ItemStore, and each change records the
+    // whole store.
+    let mut stores = HashMap::new();
+    for (container, ..) in &found {
+      if *container != self.backpack && !stores.contains_key(container) {
+        let store = self.item_store(container)?.into_owned();
+        stores.insert(container.clone(), store);
+      }
+    }
+
+    self.apply_edit(|game| {
+      let mut count = 0;
+      for (container, id, mut item, old, new) in found {
+        // Always written as floating point, the same as the game does.
+        if !set_value(&mut item[IN], HP, new.into()) {
+          continue;
+        }
+
+        let name = get_item_name(&item[IN]).unwrap_or_default();
+        let (field, val) = match stores.get_mut(&container) {
+          Some(store) => {
+            store[IN][&id] = item;
+            (Field::Store(container), store.clone())
+          }
+          None => (Field::Item(id), item),
+        };
+
+        let prev = game.field_value(&field);
+        game.set_field_value(&field, Some(val));
+        let values = (ChangeValue::Float(old), ChangeValue::Float(new));
+        game.record(ChangeCategory::Item, name, field, prev, values);
+        count += 1;
+      }
+      Ok(count)
+    })
+  }
+
+  /// Set item counts and durability, returns true if any item changed. Nothing is changed if any
+  /// of the durability values are invalid (see [`Durability::validate`]).
+  pub fn set_inventory_items(&mut self, items: &[Item]) -> Result<bool, Error> {
+    self.set_inventory_items_with(items, DurabilityPolicy::Reject)
+  }
+
+  /// Set item counts and durability, with a choice of what to do about minor durability that's
+  /// more than major.
+  pub fn set_inventory_items_with(
+    &mut self,
+    items: &[Item],
+    policy: DurabilityPolicy,
+  ) -> Result<bool, Error> {
+    self.available(Section::ItemStore)?;
+
+    // Check all the durability values before changing anything.
+    let mut durs = Vec::with_capacity(items.len());
+    let mut invalid = Vec::new();
+    for item in items {
+      match item
+        .dur
+        .as_ref()
+        .map(|dur| dur.validate(policy))
+        .transpose()
+      {
+        Ok(dur) => durs.push(dur),
+        Err(err) => invalid.push(format!("{} ({err})", item.id)),
+      }
+    }
+
+    if !invalid.is_empty() {
+      return Err(Error::Other(invalid_durability(&invalid)));
+    }
+
+    let mut changes = Vec::new();
+    for (item, dur) in items.iter().zip(durs) {
+      let Some(store_id) = &item.store else {
+        let inv = Arc::make_mut(&mut self.inventory).get_mut(IN).unwrap();
+
+        // Skip items that were removed since they were listed.
+        let Some(val) = inv.get_mut(&item.id) else {
+          continue;
+        };
+
+        for (prev, values) in update_item(val, item, dur) {
+          changes.push((item, Field::Item(item.id.clone()), prev, values));
+        }
+        continue;
+      };
+
+      // Items in bags are changed in a copy of the bag's ItemStore, and each change records the
+      // whole store.
+      let Ok(store) = self.item_store(store_id) else {
+        continue;
+      };
+
+      let mut store = store.into_owned();
+      let Some(val) = store.get_mut(IN).and_then(|items| items.get_mut(&item.id)) else {
+        continue;
+      };
+
+      let updates = update_item(val, item, dur);
+      if updates.is_empty() {
+        continue;
+      }
+
+      for (prev, values) in updates {
+        let mut prev_store = store.clone();
+        prev_store[IN][&item.id] = prev;
+        changes.push((item, Field::Store(store_id.clone()), prev_store, values));
+      }
+      Arc::make_mut(&mut self.stores).insert(store_id.clone(), store);
+    }
+
+    let changed = !changes.is_empty();
+    self.apply_edit(|game| {
+      for (item, field, prev, values) in changes {
+        let name = item.display_name();
+        game.record(ChangeCategory::Item, name, field, Some(prev), values);
+      }
+    });
+    Ok(changed)
+  }
+
+  /// Add an item to the backpack, returns its ID. `template_path` is the item's asset path, e.g.
+  /// "ItemTemplates/Crafting/CraftingOreIronOre". If the save-game already has an item with the
+  /// same path and durability then the new one gets full durability too.
+  pub fn add_inventory_item(&mut self, template_path: &str, count: u64) -> Result<String, Error> {
+    let mut durability = None;
+    self.visit_items(true, |_, _, val| {
+      if durability.is_none() && val.get(AN).and_then(Value::as_str) == Some(template_path) {
+        durability = Durability::new(val).map(|dur| Durability {
+          minor: dur.major,
+          ..dur
+        });
+      }
+    });
+    self.add_inventory_item_with(template_path, count, durability)
+  }
+
+  /// Add an item to the backpack with the given durability, returns its ID.
+  pub fn add_inventory_item_with(
+    &mut self,
+    template_path: &str,
+    count: u64,
+    durability: Option<Durability>,
+  ) -> Result<String, Error> {
+    self.available(Section::ItemStore)?;
+    if !(1..=self.limits.stack).contains(&count) {
+      return Err(Error::other(format!("Invalid item count: {count}")));
+    }
+
+    let mut item = serde_json::json!({ AN: template_path, QN: count });
+    let Some(name) = get_item_name(&item).filter(|name| !name.is_empty()) else {
+      return Err(Error::other(format!(
+        "Invalid item path: '{template_path}'"
+      )));
+    };
+
+    if let Some(dur) = durability {
+      let dur = dur.validate(DurabilityPolicy::Reject);
+      let dur = dur.map_err(|err| invalid_durability(&[format!("{name} ({err})")]))?;
+      item[HP] = dur.minor.into();
+      item[PHP] = dur.major.into();
+    }
+
+    let id = self.new_ids(1).remove(0);
+    let inv = Arc::make_mut(&mut self.inventory).get_mut(IN).unwrap();
+    inv[&id] = serde_json::json!({ IN: item });
+    Arc::make_mut(&mut self.item_names).insert(Arc::from(name.as_str()));
+
+    let name = self.display_names.display_name(&name).into_owned();
+    let values = (ChangeValue::None, ChangeValue::Int(count as i64));
+    let field = Field::Item(id.clone());
+    self.record(ChangeCategory::Item, name, field, None, values);
+    Ok(id)
+  }
+
+  /// Remove an item from the backpack. Containers must be empty, and their ItemStore records are
+  /// removed with them.
+  pub fn remove_inventory_item(&mut self, id: &str) -> Result<(), Error> {
+    self.available(Section::ItemStore)?;
+    let Some(prev) = self.inventory[IN].get(id).cloned() else {
+      return Err(Error::other(format!(
+        "There's no item '{id}' in the backpack"
+      )));
+    };
+
+    let bag = prev[IN].get(BAG).and_then(Value::as_str).map(str::to_owned);
+    if let Some(bag) = &bag {
+      let store = self.item_store(bag);
+      if store.is_ok_and(|store| store[IN].as_object().is_some_and(|items| !items.is_empty())) {
+        return Err(Error::other(format!(
+          "Item '{id}' is a container with items in it"
+        )));
+      }
+    }
+
+    let count = prev[IN].get(QN).and_then(json_num::to_i64).unwrap_or(0);
+    let name = item_name(&prev[IN]).unwrap_or(id);
+    let name = self.display_names.display_name(name).into_owned();
+    self.apply_edit(|game| {
+      let inv = Arc::make_mut(&mut game.inventory).get_mut(IN).unwrap();
+      inv.as_object_mut().unwrap().shift_remove(id);
+
+      let values = (ChangeValue::Int(count), ChangeValue::None);
+      let field = Field::Item(id.to_owned());
+      game.record(ChangeCategory::Item, &name, field, Some(prev), values);
+
+      // Leave a removed marker in place of the container's store, so that the record is deleted
+      // when the save-game is stored.
+      if let Some(bag) = bag {
+        let field = Field::Store(bag);
+        let prev = game.field_value(&field);
+        game.set_field_value(&field, Some(Value::Null));
+        let values = (ChangeValue::Int(0), ChangeValue::None);
+        game.record(
+          ChangeCategory::Item,
+          format!("{name} Contents"),
+          field,
+          prev,
+          values,
+        );
+      }
+    });
+    Ok(())
+  }
+
+  /// Set the count of the top-level items whose display names match. Containers and items with
+  /// durability are skipped. With `opts.dry_run` the changes are worked out but not made.
+  pub fn set_items_count_by_name(
+    &mut self,
+    name: &str,
+    count: u64,
+    opts: MatchOpts,
+  ) -> Result<CountChanges, Error> {
+    self.available(Section::ItemStore)?;
+    if !(1..=self.limits.stack).contains(&count) {
+      return Err(Error::other(format!("Invalid item count: {count}")));
+    }
+
+    let search = Search::String {
+      find: name.into(),
+      ignore_case: opts.ignore_case,
+      anchor: match opts.mode {
+        NameMatch::Exact => LineAnchor::WholeLine,
+        NameMatch::Substring => LineAnchor::None,
+      },
+    };
+
+    let mut changes = CountChanges::default();
+    let mut items = self.get_inventory_items();
+    items.retain_mut(|item| {
+      if search.find_in(item.display_name()).is_none() {
+        return false;
+      }
+
+      let id = item.id.clone();
+      if item.is_container() {
+        changes.skipped.push((id, SkipReason::Container));
+        return false;
+      }
+
+      if item.dur.is_some() {
+        changes.skipped.push((id, SkipReason::NotStackable));
+        return false;
+      }
+
+      changes.items.push((id, item.cnt, count));
+      item.cnt = count;
+      true
+    });
+
+    if !opts.dry_run {
+      self.set_inventory_items(&items)?;
+    }
+    Ok(changes)
+  }
+
+  /// Describe the changes made since the save-game was loaded, oldest first.
+  pub fn pending_changes(&self) -> Vec<ChangeDescription> {
+    self.journal.describe(&util::get_locale())
+  }
+
+  /// Undo a single pending change. This fails if a later change affects the same data.
+  pub fn revert_change(&mut self, id: ChangeId) -> Result<(), Error> {
+    let locale = util::get_locale();
+    let change = match Arc::make_mut(&mut self.journal).remove(id, &locale) {
+      Ok(change) => change,
+      Err(Some(later)) => {
+        return Err(Error::other(format!(
+          "Conflicts with a later change: {later}"
+        )))
+      }
+      Err(None) => return Err(Error::other("Unable to find the change")),
+    };
+
+    self.set_field_value(&change.field, change.old);
+    Arc::make_mut(&mut self.history).forget(id);
+    self.revision += 1;
+    Ok(())
+  }
+
+  /// Undo the last edit, returns false if there's nothing to undo. An edit is everything changed by
+  /// one call, e.g. every skill set by [`GameData::set_skills`]. Only the last
+  /// [`journal::MAX_UNDO`](crate::journal::MAX_UNDO) edits can be undone.
+  pub fn undo(&mut self) -> bool {
+    let Some(ids) = Arc::make_mut(&mut self.history).pop_undo() else {
+      return false;
+    };
+
+    // Restore the fields newest change first, keeping their current JSON for redo.
+    let changes = Arc::make_mut(&mut self.journal).take(&ids);
+    let mut undone = Vec::with_capacity(changes.len());
+    for change in changes.into_iter().rev() {
+      let new = self.field_value(&change.field);
+      self.set_field_value(&change.field, change.old.clone());
+      undone.push((change, new));
+    }
+
+    undone.reverse();
+    Arc::make_mut(&mut self.history).push_redo(undone);
+    self.revision += 1;
+    true
+  }
+
+  /// Redo the last undone edit, returns false if there's nothing to redo. Any other change after
+  /// an undo means that nothing can be redone.
+  pub fn redo(&mut self) -> bool {
+    let Some(edit) = Arc::make_mut(&mut self.history).pop_redo() else {
+      return false;
+    };
+
+    let mut ids = Vec::with_capacity(edit.len());
+    for (change, new) in edit {
+      self.set_field_value(&change.field, new);
+      ids.push(change.id);
+      Arc::make_mut(&mut self.journal).push(change);
+    }
+
+    Arc::make_mut(&mut self.history).push_undo(ids);
+    self.revision += 1;
+    true
+  }
+
+  pub fn can_undo(&self) -> bool {
+    self.history.can_undo()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    self.history.can_redo()
+  }
+
+  /// Describe the differences from the loaded save-game as an RFC 6902 JSON Patch. Paths start
+  /// with the collection name and record ID, e.g. `/CharacterSheet/<avatar>/ae`.
+  pub fn pending_patch(&self) -> Value {
+    let mut ops = Vec::new();
+    for (collection, id, val) in self.patch_records() {
+      let path = format!("/{collection}/{}", escape_pointer(&id));
+      match (get_json(&self.text, collection, &id).ok(), val) {
+        (Some(old), Some(new)) => diff_json(&path, &old, new, &mut ops),
+        (None, Some(new)) => {
+          ops.push(serde_json::json!({ "op": "add", "path": path, "value": new }))
+        }
+        (Some(_), None) => ops.push(serde_json::json!({ "op": "remove", "path": path })),
+        (None, None) => (),
+      }
+    }
+    Value::Array(ops)
+  }
+
+  /// Describe the differences between this save-game and another as a JSON Patch, in the same form
+  /// as [`GameData::pending_patch`]. Applying the patch to this save-game's records gives the
+  /// other's.
+  pub fn diff(&self, other: &GameData) -> Value {
+    let mut ops = Vec::new();
+    let records = self.patch_records();
+    let other_records = other.patch_records();
+    let find = |records: &[(&str, String, Option<&'_ Value>)], collection: &str, id: &str| {
+      let found = records
+        .iter()
+        .find(|(col, rid, _)| *col == collection && rid == id);
+      found.and_then(|(.., val)| val.cloned())
+    };
+
+    for (collection, id, val) in &records {
+      let path = format!("/{collection}/{}", escape_pointer(id));
+      match (val, find(&other_records, collection, id)) {
+        (Some(old), Some(new)) => diff_json(&path, old, &new, &mut ops),
+        (Some(_), None) => ops.push(serde_json::json!({ "op": "remove", "path": path })),
+        (None, Some(new)) => {
+          ops.push(serde_json::json!({ "op": "add", "path": path, "value": new }))
+        }
+        (None, None) => (),
+      }
+    }
+
+    // Records that only the other save-game has.
+    for (collection, id, val) in &other_records {
+      let listed = records
+        .iter()
+        .any(|(col, rid, _)| col == collection && rid == id);
+      if let (Some(new), false) = (val, listed) {
+        let path = format!("/{collection}/{}", escape_pointer(id));
+        ops.push(serde_json::json!({ "op": "add", "path": path, "value": new }));
+      }
+    }
+    Value::Array(ops)
+  }
+
+  /// Apply an RFC 6902 JSON Patch as produced by [`GameData::pending_patch`]. Nothing is changed if
+  /// any of the operations fail. Each patched record is a pending change, and the whole patch is
+  /// one edit that can be undone.
+  pub fn apply_patch(&mut self, patch: &Value) -> Result<(), Error> {
+    let Some(ops) = patch.as_array() else {
+      return Err(Error::other("The patch must be an array"));
+    };
+
+    // Patch copies of the records so that a failure leaves everything unchanged.
+    let mut records: Vec<(Field, String, Option<Value>)> = Vec::new();
+    for op in ops {
+      let invalid = || Error::other(format!("Invalid patch operation: {op}"));
+      let Some(path) = op.get("path").and_then(|path| path.as_str()) else {
+        return Err(invalid());
+      };
+
+      let mut segments = parse_pointer(path)?;
+      if segments.len() < 2 {
+        return Err(Error::other(format!("Invalid patch path: {path}")));
+      }
+
+      let rest = segments.split_off(2);
+      let (collection, id) = (segments[0].as_str(), segments[1].as_str());
+      let field = match collection {
+        CHARACTER_SHEET => Field::Sheet(id.to_owned()),
+        ITEM_STORE if id == self.backpack => Field::Inventory,
+        ITEM_STORE => Field::Store(id.to_owned()),
+        USER_GOLD if id == USER_ID => Field::GoldRecord,
+        USER_BANK if id == USER_ID => Field::Bank,
+        _ => return Err(Error::other(format!("Unsupported patch path: {path}"))),
+      };
+
+      let pos = match records.iter().position(|(other, ..)| *other == field) {
+        Some(pos) => pos,
+        None => {
+          let record = self.patch_record(&field)?;
+          records.push((field, format!("{collection} {id}"), record));
+          records.len() - 1
+        }
+      };
+
+      let (field, _, record) = &mut records[pos];
+      if rest.is_empty() {
+        // Add or replace a whole record, e.g. an imported container's store. Only the bank and
+        // stores can be removed.
+        *record = match (op.get("op").and_then(|op| op.as_str()), &*field) {
+          (Some("add" | "replace"), _) => Some(op.get("value").cloned().ok_or_else(invalid)?),
+          (Some("remove"), Field::Bank) => None,
+          (Some("remove"), Field::Store(_)) => Some(Value::Null),
+          _ => return Err(invalid()),
+        };
+        continue;
+      }
+
+      let record = match (record, &*field) {
+        (Some(record), _) => record,
+        (record, Field::Bank) => record.insert(serde_json::json!({})),
+        _ => return Err(Error::other(format!("Invalid patch path: {path}"))),
+      };
+      apply_patch_op(record, &rest, op)?;
+    }
+
+    self.apply_edit(|game| {
+      for (field, name, record) in records {
+        let old = game.field_value(&field);
+        let category = match field {
+          Field::GoldRecord | Field::Bank => ChangeCategory::Gold,
+          Field::Inventory | Field::Store(_) => ChangeCategory::Item,
+          _ => ChangeCategory::Other,
+        };
+        let values = (ChangeValue::None, ChangeValue::None);
+        game.set_field_value(&field, record);
+        game.record(category, format!("Patched {name}"), field, old, values);
+      }
+    });
+    Ok(())
+  }
+
+  // Get the JSON of a record that's being patched.
+  fn patch_record(&self, field: &Field) -> Result<Option<Value>, Error> {
+    match field {
+      Field::Sheet(avatar) if *avatar == self.avatar => self.sheet(avatar).map(Some),
+      Field::Sheet(avatar) => match self.sheets.get(avatar) {
+        Some(sheet) => Ok(Some(sheet.clone())),
+        None => Ok(Some(get_json(&self.text, CHARACTER_SHEET, avatar)?)),
+      },
+      Field::Inventory => {
+        self.available(Section::ItemStore)?;
+        Ok(Some(self.inventory.as_ref().clone()))
+      }
+      Field::GoldRecord => {
+        self.available(Section::UserGold)?;
+        Ok(Some(self.gold.as_ref().clone()))
+      }
+      Field::Store(id) => Ok(self.item_store(id).ok().map(Cow::into_owned)),
+      _ => Ok(self.field_value(field)),
+    }
+  }
+
+  // The records that can be patched, with their current values.
+  fn patch_records(&self) -> Vec<(&'static str, String, Option<&Value>)> {
+    let mut records = vec![
+      (
+        CHARACTER_SHEET,
+        self.avatar.clone(),
+        Some(self.character.as_ref()),
+      ),
+      (
+        ITEM_STORE,
+        self.backpack.clone(),
+        Some(self.inventory.as_ref()),
+      ),
+      (USER_GOLD, USER_ID.into(), Some(self.gold.as_ref())),
+    ];
+
+    // Sections that weren't loaded don't have changes.
+    records.retain(|(collection, ..)| {
+      !self
+        .unavailable
+        .iter()
+        .any(|section| section.collection() == *collection)
+    });
+    records.push((USER_BANK, USER_ID.into(), self.bank.as_deref()));
+
+    for (avatar, sheet) in self.sheets.iter() {
+      records.push((CHARACTER_SHEET, avatar.clone(), Some(sheet)));
+    }
+
+    // Removed stores are marked with null.
+    for (id, store) in self.stores.iter() {
+      records.push((
+        ITEM_STORE,
+        id.clone(),
+        Some(store).filter(|store| !store.is_null()),
+      ));
+    }
+    records
+  }
+
+  // Get the current JSON for a journal field.
+  fn field_value(&self, field: &Field) -> Option<Value> {
+    match field {
+      Field::Gold => self.gold.get(G).cloned(),
+      Field::GoldRecord => Some(self.gold.as_ref().clone()),
+      Field::Bank => self.bank.as_ref().map(|bank| bank.as_ref().clone()),
+      Field::AdvExp => self.character.get(AE).cloned(),
+      Field::PrdExp => self.character.get(PE).cloned(),
+      Field::Skill(id) => self.character[SK2].get(id.to_string()).cloned(),
+      Field::Item(id) => self.inventory[IN].get(id).cloned(),
+      Field::Inventory => Some(self.inventory.as_ref().clone()),
+      Field::Sheet(avatar) => self.sheet(avatar).ok(),
+      Field::Store(id) => self.stores.get(id).cloned(),
+    }
+  }
+
+  // Replace the JSON for a journal field, removing it if `val` is `None`.
+  fn set_field_value(&mut self, field: &Field, val: Option<Value>) {
+    fn set(obj: &mut Value, key: &str, val: Option<Value>) {
+      let obj = obj.as_object_mut().unwrap();
+      match val {
+        Some(val) => obj.insert(key.into(), val),
+        None => obj.shift_remove(key),
+      };
+    }
+
+    match field {
+      Field::Gold => set(Arc::make_mut(&mut self.gold), G, val),
+      Field::GoldRecord => {
+        if let Some(val) = val {
+          self.gold = Arc::new(val);
+        }
+      }
+      Field::Bank => self.bank = val.map(Arc::new),
+      Field::AdvExp => set(Arc::make_mut(&mut self.character), AE, val),
+      Field::PrdExp => set(Arc::make_mut(&mut self.character), PE, val),
+      Field::Skill(id) => {
+        let sk2 = Arc::make_mut(&mut self.character).get_mut(SK2).unwrap();
+        set(sk2, &id.to_string(), val);
+      }
+      Field::Item(id) => {
+        let inv = Arc::make_mut(&mut self.inventory).get_mut(IN).unwrap();
+        set(inv, id, val);
+      }
+      Field::Inventory => {
+        if let Some(val) = val {
+          self.inventory = Arc::new(val);
+        }
+      }
+      Field::Sheet(avatar) => {
+        if let Some(val) = val {
+          self.set_sheet(avatar, val);
+        }
+      }
+      Field::Store(id) => {
+        let stores = Arc::make_mut(&mut self.stores);
+        match val {
+          Some(val) => stores.insert(id.clone(), val),
+          None => stores.remove(id),
+        };
+      }
+    }
+  }
+
+  // Get an avatar's CharacterSheet JSON.
+  fn sheet(&self, avatar: &str) -> Result<Value, Error> {
+    if avatar == self.avatar {
+      self.available(Section::CharacterSheet)?;
+      return Ok(self.character.as_ref().clone());
+    }
+
+    if let Some(sheet) = self.sheets.get(avatar) {
+      return Ok(sheet.clone());
+    }
+
+    match get_json(&self.text, CHARACTER_SHEET, avatar) {
+      Ok(sheet) if sheet.get(SK2).is_some_and(|sk2| sk2.is_object()) => Ok(sheet),
+      _ => Err(Error::other(format!("Unable to find avatar '{avatar}'"))),
+    }
+  }
+
+  fn set_sheet(&mut self, avatar: &str, sheet: Value) {
+    if avatar == self.avatar {
+      self.character = Arc::new(sheet);
+    } else {
+      Arc::make_mut(&mut self.sheets).insert(avatar.to_owned(), sheet);
+    }
+  }
+
+  // Make the changes recorded by `edit` one edit for undo. Changes recorded outside of an edit are
+  // undone one at a time.
+  fn apply_edit<T>(&mut self, edit: impl FnOnce(&mut Self) -> T) -> T {
+    Arc::make_mut(&mut self.history).begin();
+    let result = edit(self);
+    Arc::make_mut(&mut self.history).end();
+    result
+  }
+
+  // Add a change to the journal if the field's JSON changed.
+  fn record(
+    &mut self,
+    category: ChangeCategory,
+    name: impl Into<String>,
+    field: Field,
+    old: Option<Value>,
+    values: (ChangeValue, ChangeValue),
+  ) {
+    let new = self.field_value(&field);
+    if new == old {
+      return;
+    }
+
+    self.revision += 1;
+    let journal = Arc::make_mut(&mut self.journal);
+    let id = journal.next_id();
+    Arc::make_mut(&mut self.history).push(id);
+    journal.push(Change {
+      id,
+      category,
+      name: name.into(),
+      field,
+      old,
+      old_value: values.0,
+      new_value: values.1,
+    });
+  }
+
+  /// Check the save-game for values that the game or this editor can't handle.
+  pub fn validate(&self, registry: &SkillRegistry) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity, location: String, message: String| {
+      issues.push(ValidationIssue {
+        severity,
+        location,
+        message,
+      });
+    };
+
+    // Gold.
+    let records = [
+      (USER_GOLD, Some(self.gold.as_ref())),
+      (USER_BANK, self.bank.as_deref()),
+    ];
+    for (collection, record) in records {
+      let Some(gold) = record.and_then(|record| record.get(G)) else {
+        continue;
+      };
+      let location = format!("{collection}/{USER_ID}/{G}");
+      match json_num::to_i64_grouped(gold) {
+        Some(gold) if gold < 0 => {
+          issue(Severity::Error, location, format!("Negative gold: {gold}"))
+        }
+        Some(gold) if gold > self.limits.gold => issue(
+          Severity::Warning,
+          location,
+          format!("Too much gold: {gold}"),
+        ),
+        Some(_) => (),
+        None => issue(Severity::Error, location, format!("Invalid gold: {gold}")),
+      }
+    }
+
+    if let Some(debt) = self.adv_exp_debt() {
+      let location = format!("{CHARACTER_SHEET}/{}/{AE}", self.avatar);
+      let message = format!("Adventurer experience is negative: {debt}");
+      issue(Severity::Warning, location, message);
+    }
+
+    // Skills.
+    let max_lvl = *self.limits.skill_level.end();
+    let max_exp = util::exp_for_level(max_lvl, self.tables.skill());
+    if let Some(sk2) = self.character.get(SK2).and_then(|sk2| sk2.as_object()) {
+      for (id, skill) in sk2 {
+        let location = format!("{CHARACTER_SHEET}/{}/{SK2}/{id}", self.avatar);
+        let info = id.parse().ok().and_then(|id| registry.find_id(id));
+        let Some((_, _, info)) = info else {
+          issue(
+            Severity::Warning,
+            location.clone(),
+            format!("Unknown skill: {id}"),
+          );
+          continue;
+        };
+
+        match get_skill_lvl(&self.character[SK2], info, &self.tables) {
+          SkillLevel::Level(_) => {
+            // More experience than the highest level needs.
+            let exp = json_num::to_i64(&skill[X]).unwrap_or(0);
+            if max_exp.is_some_and(|max_exp| exp as f64 > max_exp as f64 * info.mul) {
+              let name = info.display_name();
+              let message = format!("{name} is above level {max_lvl}: {exp}");
+              issue(Severity::Warning, location, message);
+            }
+          }
+          SkillLevel::Invalid(err) => {
+            issue(
+              Severity::Error,
+              location,
+              format!("{} has {err}", info.display_name()),
+            );
+          }
+          SkillLevel::Untrained => (),
+        }
+      }
+    }
+
+    // Items.
+    self.visit_items(true, |container, id, val| {
+      let location = format!("{ITEM_STORE}/{container}/{IN}/{id}");
+      let name = get_item_name(val).unwrap_or_else(|| id.to_owned());
+      if let Some(count) = val.get(QN).and_then(json_num::to_i64) {
+        if count < 0 {
+          let message = format!("{name} has a negative count: {count}");
+          issue(Severity::Error, location.clone(), message);
+        }
+      }
+
+      let minor = val.get(HP).and_then(json_num::to_f64);
+      let major = val.get(PHP).and_then(json_num::to_f64);
+      if let (Some(minor), Some(major)) = (minor, major) {
+        if minor > major {
+          let message = format!("{name} has more durability than its maximum: {minor} > {major}");
+          issue(Severity::Warning, location.clone(), message);
+        }
+      }
+
+      if let Some(bag) = val.get(BAG).and_then(|bag| bag.as_str()) {
+        if get_json_range(&self.text, ITEM_STORE, bag).is_none() {
+          let message = format!("{name}'s contents are missing ({ITEM_STORE} {bag})");
+          issue(Severity::Error, location, message);
+        }
+      }
+    });
+
+    for skill in self.skill_consistency(registry) {
+      let location = format!("{CHARACTER_SHEET}/{}/{SK2}/{}", self.avatar, skill.id);
+      let message = format!(
+        "{} is level {} from its experience but has rank {}",
+        skill.name, skill.level, skill.rank
+      );
+      issue(Severity::Warning, location, message);
+    }
+
+    for collection in duplicate_collections(&self.text) {
+      let message = format!("The {collection} collection appears more than once");
+      issue(Severity::Warning, collection.to_owned(), message);
+    }
+
+    for (collection, id) in duplicate_records(&self.text) {
+      let message = format!("The {collection} record '{id}' appears more than once");
+      issue(Severity::Warning, format!("{collection}/{id}"), message);
+    }
+
+    if let Some(warning) = self.lossy_warning() {
+      issue(Severity::Warning, self.get_file_name(), warning);
+    }
+
+    if let Some(warning) = self.version_warning() {
+      issue(Severity::Warning, self.get_file_name(), warning.to_string());
+    }
+
+    // Make sure that the changes can be stored and loaded again.
+    let stored = self.store_to_string().and_then(GameData::from_text);
+    if let Err(err) = stored {
+      issue(
+        Severity::Error,
+        self.get_file_name(),
+        format!("Unable to store: {err}"),
+      );
+    }
+
+    issues
+  }
+
+  /// Summarize the trained skills of each skill tree. Trees without trained skills are left out.
+  pub fn tree_summary(&self, registry: &SkillRegistry) -> Vec<TreeSummary> {
+    let mut summaries = Vec::new();
+    let Some(sk2) = self.character.get(SK2) else {
+      return summaries;
+    };
+
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in registry.groups(category) {
+        let mut trained_by_tier = Vec::new();
+        let mut exp = 0;
+        for info in &group.skills {
+          if get_skill_lvl(sk2, info, &self.tables).level().is_none() {
+            continue;
+          }
+
+          let tier = registry.tier(info) as usize;
+          if trained_by_tier.len() < tier {
+            trained_by_tier.resize(tier, 0);
+          }
+          trained_by_tier[tier - 1] += 1;
+
+          let skill = sk2.get(info.id.to_string());
+          exp += skill
+            .and_then(|skill| json_num::to_i64(skill.get(X)?))
+            .unwrap_or(0);
+        }
+
+        if !trained_by_tier.is_empty() {
+          summaries.push(TreeSummary {
+            category,
+            group: group.name,
+            highest_tier: trained_by_tier.len() as u32,
+            trained_by_tier,
+            exp,
+          });
+        }
+      }
+    }
+    summaries
+  }
+
+  /// Compare the level from each skill's experience with the rank stored in the save-game. Skills
+  /// whose levels differ by more than one are returned, which usually means that the skill's
+  /// multiplier is wrong.
+  pub fn skill_consistency(&self, registry: &SkillRegistry) -> Vec<SkillInconsistency> {
+    let mut found = Vec::new();
+    let Some(sk2) = self.character.get(SK2).and_then(|sk2| sk2.as_object()) else {
+      return found;
+    };
+
+    for (id, skill) in sk2 {
+      let Some((_, _, info)) = id.parse().ok().and_then(|id| registry.find_id(id)) else {
+        continue;
+      };
+
+      // A rank of zero means it's not stored.
+      let rank = skill.get(M).and_then(json_num::to_i64).unwrap_or(0);
+      if rank <= 0 {
+        continue;
+      }
+
+      let Some(level) = get_skill_lvl(&self.character[SK2], info, &self.tables).level() else {
+        continue;
+      };
+
+      if (level as i64 - rank).abs() > SKILL_RANK_TOLERANCE {
+        found.push(SkillInconsistency {
+          id: info.id,
+          name: info.display_name(),
+          level,
+          rank: rank as i32,
+        });
+      }
+    }
+
+    found
+  }
+
+  /// Compare the save-game with a `/stats` dump, returning the values that differ.
+  pub fn compare_with_stats(
+    &self,
+    stats: &StatsSnapshot,
+    registry: &SkillRegistry,
+  ) -> Vec<StatDiff> {
+    let mut diffs = Vec::new();
+    let mut push = |name: &str, save: i64, server: i64| {
+      if save != server {
+        diffs.push(StatDiff {
+          name: name.into(),
+          save,
+          stats: server,
+        });
+      }
+    };
+
+    if let Some(lvl) = stats.adv_lvl {
+      push("Adventurer Level", self.get_adv_lvl() as i64, lvl as i64);
+    }
+
+    if let Some(lvl) = stats.prd_lvl {
+      push("Producer Level", self.get_prd_lvl() as i64, lvl as i64);
+    }
+
+    if let (Some(gold), Some(save)) = (stats.gold, self.get_gold()) {
+      push("Gold", save, gold);
+    }
+
+    // Values that aren't skills are ignored.
+    let sk2 = self.character.get(SK2).unwrap();
+    for (name, value) in &stats.values {
+      if let Some((_, _, info)) = registry.find_stat(name) {
+        let lvl = get_skill_lvl(sk2, info, &self.tables).level().unwrap_or(0);
+        push(info.display_name(), lvl as i64, value.floor() as i64);
+      }
+    }
+
+    diffs
+  }
+
+  /// Render a character report for sharing.
+  pub fn report(&self, registry: &SkillRegistry, format: ReportFormat) -> String {
+    self.report_with_locale(registry, format, &util::get_locale())
+  }
+
+  fn report_with_locale(
+    &self,
+    registry: &SkillRegistry,
+    format: ReportFormat,
+    locale: &Locale,
+  ) -> String {
+    let sk2 = self.character.get(SK2).unwrap();
+    let date = self.date.as_ref().and_then(|date| {
+      let ms = json_num::to_i64(date.get(DATE)?)?;
+      let date = DateTime::from_timestamp_millis(ms)?;
+      Some(date.format("%Y-%m-%d").to_string())
+    });
+    let gold = self.get_gold().unwrap_or(0);
+    let adv_lvl = self.get_adv_lvl();
+    let prd_lvl = self.get_prd_lvl();
+
+    // Trained skills as (category, group, skill, level).
+    let mut skills = Vec::new();
+    for (category, title) in [
+      (SkillCategory::Adventurer, "Adventurer"),
+      (SkillCategory::Producer, "Producer"),
+    ] {
+      for group in registry.groups(category) {
+        for info in &group.skills {
+          if let Some(lvl) = get_skill_lvl(sk2, info, &self.tables).level() {
+            skills.push((title, group.name, info.display_name(), lvl));
+          }
+        }
+      }
+    }
+
+    let trees = self.tree_summary(registry);
+
+    // Top-level inventory counts.
+    let items: Vec<(String, u64)> = self
+      .get_inventory_items()
+      .into_iter()
+      .map(|item| (item.name.to_string(), item.cnt))
+      .collect();
+
+    match format {
+      ReportFormat::Markdown => {
+        let mut text = format!("# {}\n\n", self.name);
+        if let Some(date) = &date {
+          text += &format!("- Date: {date}\n");
+        }
+        text += &format!("- Gold: {}\n", gold.to_formatted_string(locale));
+        if let Some(bank) = self.get_bank_gold() {
+          text += &format!("- Bank: {}\n", bank.to_formatted_string(locale));
+        }
+        text += &format!("- Adventurer Level: {adv_lvl}\n");
+        text += &format!("- Producer Level: {prd_lvl}\n");
+
+        for title in ["Adventurer", "Producer"] {
+          let mut iter = skills.iter().filter(|(cat, ..)| *cat == title).peekable();
+          if iter.peek().is_none() {
+            continue;
+          }
+
+          text +=
+            &format!("\n## {title} Skills\n\n| Tree | Skill | Level |\n| --- | --- | ---: |\n");
+          for (_, group, name, lvl) in iter {
+            text += &format!("| {group} | {name} | {lvl} |\n");
+          }
+        }
+
+        if !trees.is_empty() {
+          text += "\n## Skill Trees\n\n| Tree | Skills | Highest Tier | Experience |\n";
+          text += "| --- | ---: | ---: | ---: |\n";
+          for tree in &trees {
+            text += &format!(
+              "| {} | {} | {} | {} |\n",
+              tree.group,
+              tree.trained(),
+              tree.highest_tier,
+              tree.exp.to_formatted_string(locale)
+            );
+          }
+        }
+
+        if !items.is_empty() {
+          text += "\n## Inventory\n\n| Item | Count |\n| --- | ---: |\n";
+          for (name, cnt) in &items {
+            text += &format!("| {name} | {} |\n", cnt.to_formatted_string(locale));
+          }
+        }
+        text
+      }
+      ReportFormat::Json => {
+        let skills: Vec<Value> = skills
+          .iter()
+          .map(|(cat, group, name, lvl)| {
+            serde_json::json!({
+              "category": cat,
+              "group": group,
+              "name": name,
+              "level": lvl,
+            })
+          })
+          .collect();
+        let trees: Vec<Value> = trees
+          .iter()
+          .map(|tree| {
+            serde_json::json!({
+              "category": format!("{:?}", tree.category),
+              "group": tree.group,
+              "trained_by_tier": tree.trained_by_tier,
+              "highest_tier": tree.highest_tier,
+              "exp": tree.exp,
+            })
+          })
+          .collect();
+        let items: Vec<Value> = items
+          .iter()
+          .map(|(name, cnt)| serde_json::json!({ "name": name, "count": cnt }))
+          .collect();
+        let summary = serde_json::json!({
+          "name": self.name,
+          "date": date,
+          "gold": gold,
+          "bank": self.get_bank_gold(),
+          "adventurer_level": adv_lvl,
+          "producer_level": prd_lvl,
+          "skills": skills,
+          "skill_trees": trees,
+          "inventory": items,
+        });
+        serde_json::to_string_pretty(&summary).unwrap()
+      }
+    }
+  }
+}
+
+/// A point-in-time copy of a save-game's data. See [`GameData::snapshot`].
+#[derive(Clone)]
+pub struct GameDataSnapshot {
+  text: Arc<String>,
+  avatar: String,
+  backpack: String,
+  character: Arc<Value>,
+  inventory: Arc<Value>,
+  gold: Arc<Value>,
+  bank: Option<Arc<Value>>,
+  sheets: Arc<BTreeMap<String, Value>>,
+  stores: Arc<BTreeMap<String, Value>>,
+  journal: Arc<Journal>,
+  encoding: Encoding,
+  bom: bool,
+  unavailable: Vec<Section>,
+}
+
+impl GameDataSnapshot {
+  pub fn store_as(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+    self.write_file(path.as_ref(), StoreOptions::default(), None)
+  }
+
+  pub fn store_as_with(&self, path: impl AsRef<Path>, options: StoreOptions) -> Result<(), Error> {
+    self.write_file(path.as_ref(), options, None)
+  }
+
+  /// Get the full save-game text with all the changes applied.
+  pub fn store_to_string(&self) -> Result<String, Error> {
+    // Sections that weren't loaded are left as they are.
+    let loaded = |section| !self.unavailable.contains(&section);
+    let mut text = self.text.as_ref().clone();
+
+    // Set CharacterSheet.
+    if loaded(Section::CharacterSheet) {
+      text = set_json(&text, CHARACTER_SHEET, &self.avatar, &self.character)?;
+    }
+
+    // Set the other avatars' CharacterSheets.
+    for (avatar, sheet) in self.sheets.iter() {
+      text = set_json(&text, CHARACTER_SHEET, avatar, sheet)?;
+    }
+
+    // Set ItemStore.
+    if loaded(Section::ItemStore) {
+      text = set_json(&text, ITEM_STORE, &self.backpack, &self.inventory)?;
+    }
+
+    // Set the other ItemStores, adding the ones for imported containers and removing the ones for
+    // removed containers.
+    for (id, store) in self.stores.iter() {
+      if store.is_null() {
+        text = remove_json(&text, ITEM_STORE, id);
+      } else if get_json_range(&text, ITEM_STORE, id).is_some() {
+        text = set_json(&text, ITEM_STORE, id, store)?;
+      } else {
+        text = add_json(&text, ITEM_STORE, id, store)?;
+      }
+    }
+
+    // Set UserGold, adding the record if the save doesn't have one and the gold was set.
+    if loaded(Section::UserGold) {
+      if get_json_range(&text, USER_GOLD, USER_ID).is_some() {
+        text = set_json(&text, USER_GOLD, USER_ID, &self.gold)?;
+      } else if self.gold.get(G).is_some() {
+        text = add_json(&text, USER_GOLD, USER_ID, &self.gold)?;
+      }
+    }
+
+    // Set UserBank, but only if it was loaded or created.
+    let text = match &self.bank {
+      Some(bank) if get_json_range(&text, USER_BANK, USER_ID).is_some() => {
+        set_json(&text, USER_BANK, USER_ID, bank)?
+      }
+      Some(bank) => add_json(&text, USER_BANK, USER_ID, bank)?,
+      None => text,
+    };
+
+    Ok(text)
+  }
+
+  // Store to `path`. `backup` is the copy of the old file, if one was made, and the pending changes
+  // are written next to it.
+  fn write_file(
+    &self,
+    path: &Path,
+    options: StoreOptions,
+    backup: Option<&Path>,
+  ) -> Result<(), Error> {
+    let text = self.store_to_string()?;
+
+    if options.create_dirs {
+      if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(Error::io(FileOp::Store, dir))?;
+      }
+    }
+
+    // Store the data in its original encoding.
+    // The new file is checked before it replaces the old one.
+    let data = self.encoding.encode(&text, self.bom);
+    write_journaled(path, &data, |temp| {
+      if !options.verify {
+        return Ok(());
+      }
+      self.verify_file(temp).map_err(|reason| {
+        Error::Verification(VerificationFailed {
+          path: path.to_owned(),
+          reason,
+        })
+      })
+    })?;
+
+    if options.write_changes {
+      let path = suffixed(backup.unwrap_or(path), CHANGES_SUFFIX);
+      let mut text = String::new();
+      for change in self.journal.describe(&util::get_locale()) {
+        text += &format!("{change}\n");
+      }
+
+      std::fs::write(&path, text).map_err(Error::io(FileOp::Store, &path))?;
+    }
+
+    Ok(())
+  }
+
+  // Read a stored file back and check it against this snapshot.
+  fn verify_file(&self, path: &Path) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|err| format!("unable to read it back: {err}"))?;
+    let (text, _) = self.encoding.decode(&data, self.bom);
+    self.verify_text(text)
+  }
+
+  // Parse stored text and compare the important values with this snapshot.
+  fn verify_text(&self, text: String) -> Result<(), String> {
+    // Saves without gold can only be loaded leniently.
+    let lenient = !self.unavailable.is_empty() || self.gold.get(G).is_none();
+    let (game, _) = GameData::parse(text, lenient, Some(&self.avatar))
+      .map_err(|err| format!("it doesn't load: {err}"))?;
+    let len = |val: Option<&Value>| val.and_then(|val| val.as_object()).map(|obj| obj.len());
+    let checks = [
+      ("gold", self.gold.get(G), game.gold.get(G)),
+      (
+        "adventurer experience",
+        self.character.get(AE),
+        game.character.get(AE),
+      ),
+    ];
+    for (name, expected, found) in checks {
+      if expected != found {
+        let show =
+          |val: Option<&Value>| val.map_or_else(|| String::from("missing"), Value::to_string);
+        return Err(format!(
+          "{name} is {} instead of {}",
+          show(found),
+          show(expected)
+        ));
+      }
+    }
+
+    let counts = [
+      (
+        "skill count",
+        len(self.character.get(SK2)),
+        len(game.character.get(SK2)),
+      ),
+      (
+        "item count",
+        len(self.inventory.get(IN)),
+        len(game.inventory.get(IN)),
+      ),
+    ];
+    for (name, expected, found) in counts {
+      if expected != found {
+        let (expected, found) = (expected.unwrap_or(0), found.unwrap_or(0));
+        return Err(format!("{name} is {found} instead of {expected}"));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Stacks of the same item. See [`GameData::duplicate_items`].
+#[derive(Clone, Debug)]
+pub struct DuplicateGroup {
+  /// Asset path of the item.
+  pub asset: String,
+  pub instances: Vec<ItemInstance>,
+}
+
+impl DuplicateGroup {
+  /// Stacks can only be merged if none of them have durability. Items with durability are
+  /// equipment, which doesn't stack in the game.
+  pub fn mergeable(&self) -> bool {
+    let stackable = |instance: &ItemInstance| instance.durability.is_none();
+    self.instances.len() > 1 && self.instances.iter().all(stackable)
+  }
+}
+
+/// A stack of items in a container.
+#[derive(Clone, Debug)]
+pub struct ItemInstance {
+  /// ID of the ItemStore record that holds the item.
+  pub container: String,
+  pub id: String,
+  pub count: u64,
+  pub durability: Option<Durability>,
+}
+
+/// A value that differs between the save-game and a `/stats` dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatDiff {
+  pub name: String,
+  pub save: i64,
+  pub stats: i64,
+}
+
+/// A skill in the save-game, from [`GameData::trained_skills`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrainedSkill {
+  pub id: u32,
+
+  /// The skill's category, group and name, if it's in the registry.
+  pub category: Option<SkillCategory>,
+  pub group: Option<&'static str>,
+  pub name: Option<&'static str>,
+
+  /// Experience as it is in the save-game, before the skill's multiplier is taken out.
+  pub exp: Option<i64>,
+  pub level: SkillLevel,
+
+  /// When the skill was last trained.
+  pub time: Option<DateTime<Utc>>,
+}
+
+/// A skill's level in the save-game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkillLevel {
+  Untrained,
+  Level(i32),
+
+  /// The skill is in the save-game but its experience can't be used.
+  Invalid(String),
+}
+
+impl SkillLevel {
+  /// Get the level, if the skill is trained and valid.
+  pub fn level(&self) -> Option<i32> {
+    match self {
+      SkillLevel::Level(level) => Some(*level),
+      _ => None,
+    }
+  }
+}
+
+/// Result of [`GameData::adjust_all_skills`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdjustReport {
+  pub skills: Vec<SkillAdjustment>,
+
+  /// IDs of trained skills that aren't in the registry.
+  pub unknown: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkillAdjustment {
+  pub id: u32,
+  pub name: &'static str,
+  pub before: i32,
+  pub after: i32,
+}
+
+/// An avatar in a save-game, from [`GameData::list_avatars`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvatarInfo {
+  pub id: String,
+
+  /// The avatar's name, or the ID if it has no CharacterName record.
+  pub name: String,
+}
+
+/// Differences between two avatars in a save-game, from [`GameData::compare_avatars`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvatarComparison {
+  pub a: AvatarSummary,
+  pub b: AvatarSummary,
+
+  /// Skills with different levels, `before` being `a`'s and `after` being `b`'s.
+  pub skills: Vec<SkillAdjustment>,
+
+  /// The account's gold, which all the avatars share.
+  pub shared_gold: Option<i64>,
+}
+
+/// One avatar's side of an [`AvatarComparison`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvatarSummary {
+  pub id: String,
+  pub name: String,
+  pub adv_lvl: i32,
+  pub prd_lvl: i32,
+
+  /// Number of item stacks in the avatar's backpack, if it could be found.
+  pub items: Option<usize>,
+}
+
+/// Gold, levels and skill levels that can be applied to an avatar. Values that are `None` are left
+/// alone when applying. See [`GameData::export_template`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterTemplate {
+  pub gold: Option<i64>,
+  pub adv_lvl: Option<i32>,
+  pub prd_lvl: Option<i32>,
+
+  /// Skill levels by skill ID.
+  pub plan: Option<BTreeMap<u32, i32>>,
+}
+
+/// Result of [`GameData::apply_template`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+  /// Number of pending changes that were added.
+  pub changes: usize,
+
+  /// IDs of planned skills that aren't in the registry, which were skipped.
+  pub unknown: Vec<u32>,
+}
+
+/// A save-game section that [`GameData::load_lenient`] can do without.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+  CharacterSheet,
+  ItemStore,
+  UserGold,
+}
+
+impl Section {
+  /// Name of the section's collection.
+  pub fn collection(self) -> &'static str {
+    match self {
+      Section::CharacterSheet => CHARACTER_SHEET,
+      Section::ItemStore => ITEM_STORE,
+      Section::UserGold => USER_GOLD,
+    }
+  }
+
+  // Stand-in JSON for a section that couldn't be loaded.
+  fn placeholder(self) -> Value {
+    match self {
+      Section::CharacterSheet => serde_json::json!({ AE: 0, PE: 0, SK2: {} }),
+      Section::ItemStore => serde_json::json!({ IN: {} }),
+      Section::UserGold => serde_json::json!({}),
+    }
+  }
+}
+
+impl fmt::Display for Section {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.collection())
+  }
+}
+
+/// Error from loading or storing a save-game.
+#[derive(Debug)]
+pub enum Error {
+  /// A file couldn't be read or written.
+  Io {
+    op: FileOp,
+    path: PathBuf,
+    err: io::Error,
+  },
+
+  /// The file isn't a save-game, or is too large to load.
+  NotASave(NotASave),
+  TooLarge(FileTooLarge),
+
+  /// A collection that's needed isn't in the save-game.
+  MissingCollection(&'static str),
+
+  /// A record that's needed isn't in its collection.
+  MissingRecord {
+    collection: &'static str,
+    id: String,
+  },
+
+  /// A record's JSON can't be parsed.
+  InvalidJson(Box<InvalidJson>),
+  Json(serde_json::Error),
+
+  /// A value that's needed is missing from its record, or is the wrong type.
+  MissingField(&'static str),
+
+  /// All the sections that couldn't be loaded.
+  Load(LoadError),
+
+  /// A section that [`GameData::load_lenient`] couldn't load is needed.
+  Unavailable(Section),
+
+  /// The file couldn't be backed up before storing over it. See [`GameData::set_backup`].
+  Backup {
+    path: PathBuf,
+    err: io::Error,
+  },
+  ReadOnly(ReadOnly),
+
+  /// A backup that [`restore_backup`] was given doesn't load.
+  InvalidBackup {
+    path: PathBuf,
+    err: Box<Error>,
+  },
+
+  /// A skill build has skills that aren't in its category.
+  UnknownSkills(UnknownSkills),
+
+  /// A section's JSON isn't shaped as expected, e.g. a skill that isn't an object.
+  MalformedSection(MalformedSection),
+
+  /// A level or experience that's outside of its allowed range.
+  OutOfRange(OutOfRange),
+  ExpOutOfRange(ExpOutOfRange),
+
+  /// Gold that's negative or more than the cap.
+  InvalidGold {
+    value: i64,
+    max: i64,
+  },
+  Conflict(ConflictDetected),
+
+  /// A stored save-game that didn't read back the same, so it wasn't replaced.
+  Verification(VerificationFailed),
+  Other(Cow<'static, str>),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Io { op, path, err } => write!(f, "Unable to {op} '{}': {err}", path.display()),
+      Error::NotASave(err) => write!(f, "{err}"),
+      Error::TooLarge(err) => write!(f, "{err}"),
+      Error::MissingCollection(collection) => {
+        write!(f, "The save-game has no '{collection}' collection")
+      }
+      Error::MissingRecord { collection, id } => {
+        write!(f, "The '{collection}' collection has no record '{id}'")
+      }
+      Error::InvalidJson(err) => write!(f, "{err}"),
+      Error::Json(err) => write!(f, "Invalid JSON: {err}"),
+      Error::MissingField(field) => {
+        let name = match *field {
+          AE => "adventurer experience",
+          PE => "producer experience",
+          SK2 => "skills",
+          DC => "current avatar",
+          FN => "avatar name",
+          MAINBP => "avatar's backpack",
+          _ => "value",
+        };
+        write!(f, "Unable to read the {name} ('{field}')")
+      }
+      Error::Load(err) => write!(f, "{err}"),
+      Error::Unavailable(section) => write!(f, "{section} is not available"),
+      Error::Backup { path, err } => write!(
+        f,
+        "Unable to back up '{}', it wasn't stored: {err}",
+        path.display()
+      ),
+      Error::ReadOnly(err) => write!(f, "{err}"),
+      Error::InvalidBackup { path, err } => {
+        write!(f, "Backup '{}' is not valid: {err}", path.display())
+      }
+      Error::UnknownSkills(err) => write!(f, "{err}"),
+      Error::MalformedSection(err) => write!(f, "{err}"),
+      Error::OutOfRange(err) => write!(f, "{err}"),
+      Error::ExpOutOfRange(err) => write!(f, "{err}"),
+      Error::InvalidGold { value, max } => write!(f, "Gold {value} is not within 0-{max}"),
+      Error::Conflict(err) => write!(f, "{err}"),
+      Error::Verification(err) => write!(f, "{err}"),
+      Error::Other(err) => write!(f, "{err}"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Io { err, .. } | Error::Backup { err, .. } => Some(err),
+      Error::Json(err) => Some(err),
+      Error::InvalidBackup { err, .. } => Some(err.as_ref()),
+      _ => None,
+    }
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Self {
+    Error::Json(err)
+  }
+}
+
+impl From<NotASave> for Error {
+  fn from(err: NotASave) -> Self {
+    Error::NotASave(err)
+  }
+}
+
+impl From<FileTooLarge> for Error {
+  fn from(err: FileTooLarge) -> Self {
+    Error::TooLarge(err)
+  }
+}
+
+impl From<ConflictDetected> for Error {
+  fn from(err: ConflictDetected) -> Self {
+    Error::Conflict(err)
+  }
+}
+
+impl From<Cow<'static, str>> for Error {
+  fn from(err: Cow<'static, str>) -> Self {
+    Error::Other(err)
+  }
+}
+
+impl<R> From<Error> for BatchError<R> {
+  fn from(err: Error) -> Self {
+    BatchError::Failed(Cow::from(err))
+  }
+}
+
+impl From<UnknownSkills> for Error {
+  fn from(err: UnknownSkills) -> Self {
+    Error::UnknownSkills(err)
+  }
+}
+
+impl From<Error> for Cow<'static, str> {
+  fn from(err: Error) -> Self {
+    match err {
+      Error::Other(err) => err,
+      err => Cow::from(err.to_string()),
+    }
+  }
+}
+
+impl Error {
+  fn other(err: impl Into<Cow<'static, str>>) -> Self {
+    Error::Other(err.into())
+  }
+
+  // Make an I/O error for a file operation, e.g. `.map_err(Error::io(FileOp::Store, path))`.
+  fn io(op: FileOp, path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+    move |err| Error::Io {
+      op,
+      path: path.to_owned(),
+      err,
+    }
+  }
+}
+
+/// What was being done with a file when it failed. See [`Error::Io`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileOp {
+  Load,
+  Store,
+  Restore,
+  Recover,
+}
+
+impl fmt::Display for FileOp {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let text = match self {
+      FileOp::Load => "load",
+      FileOp::Store => "store",
+      FileOp::Restore => "restore",
+      FileOp::Recover => "recover",
+    };
+    write!(f, "{text}")
+  }
+}
+
+/// Error from [`GameData::sniff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotASave {
+  /// The file couldn't be read.
+  Unreadable(String),
+
+  /// The file isn't a save-game, for the given reason.
+  Unrecognized(&'static str),
+
+  /// The file is larger than [`max_file_size`].
+  TooLarge { size: u64, limit: u64 },
+}
+
+impl fmt::Display for NotASave {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NotASave::Unreadable(err) => write!(f, "Unable to load file: {err}"),
+      NotASave::Unrecognized(reason) => write!(f, "Not a SotA save-game: {reason}"),
+      NotASave::TooLarge { size, limit } => write!(
+        f,
+        "Not a SotA save-game: it's {size} bytes, which is more than the limit of {limit}"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for NotASave {}
+
+/// A save-game whose format version is outside of [`FORMAT_VERSIONS`]. See
+/// [`GameData::version_warning`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+  pub found: u32,
+}
+
+impl fmt::Display for UnsupportedVersion {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let found = self.found;
+    if found > *FORMAT_VERSIONS.end() {
+      let max = FORMAT_VERSIONS.end();
+      write!(
+        f,
+        "This save-game is from a newer game version ({found}) than cota supports ({max})"
+      )
+    } else {
+      let min = FORMAT_VERSIONS.start();
+      write!(
+        f,
+        "This save-game is from an older game version ({found}) than cota supports ({min})"
+      )
+    }
+  }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+/// A problem found when loading. See [`GameData::load_lenient`] and [`LoadError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadIssue {
+  /// The section with the problem, or `None` for problems with the whole file or other records.
+  pub section: Option<Section>,
+
+  /// Byte offset of the problem in the save-game text, if it could be found.
+  pub offset: Option<usize>,
+  pub message: String,
+}
+
+impl fmt::Display for LoadIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.offset {
+      Some(offset) => write!(f, "{} (at byte {offset})", self.message),
+      None => write!(f, "{}", self.message),
+    }
+  }
+}
+
+/// Everything that stopped a save-game from loading.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadError {
+  pub issues: Vec<LoadIssue>,
+}
+
+impl fmt::Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let [issue] = self.issues.as_slice() {
+      return write!(f, "{issue}");
+    }
+
+    write!(f, "The save-game has {} problems:", self.issues.len())?;
+    for issue in &self.issues {
+      write!(f, "\n- {issue}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Trained skills in a skill tree, from [`GameData::tree_summary`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeSummary {
+  pub category: SkillCategory,
+  pub group: &'static str,
+
+  /// Number of trained skills in each tier, starting with tier 1.
+  pub trained_by_tier: Vec<usize>,
+
+  /// The highest tier that has a trained skill.
+  pub highest_tier: u32,
+
+  /// Total experience in the tree's skills.
+  pub exp: i64,
+}
+
+impl TreeSummary {
+  /// Number of trained skills in the tree.
+  pub fn trained(&self) -> usize {
+    self.trained_by_tier.iter().sum()
+  }
+}
+
+/// A skill whose level doesn't match its stored rank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkillInconsistency {
+  pub id: u32,
+  pub name: &'static str,
+
+  /// Level from the skill's experience.
+  pub level: i32,
+
+  /// Rank stored in the save-game.
+  pub rank: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+  Warning,
+  Error,
+}
+
+/// A problem found by [`GameData::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationIssue {
+  pub severity: Severity,
+
+  /// Path to the JSON value, starting with the collection name and record ID.
+  pub location: String,
+  pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{:?}: {} ({})",
+      self.severity, self.message, self.location
+    )
+  }
+}
+
+/// Output format for [`GameData::report`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+  Markdown,
+  Json,
+}
+
+/// Hash a file's contents without loading it as a save-game. The result matches
+/// [`GameData::content_hash`] for the same file.
+///
+/// This is a 64 bit FNV-1a hash, which is stable across platforms and sessions but is not
+/// cryptographic; it's only meant for detecting changes.
+pub fn hash_file(path: impl AsRef<Path>) -> io::Result<u64> {
+  let data = std::fs::read(path)?;
+  Ok(hash_bytes(&data))
+}
+
+fn hash_bytes(data: &[u8]) -> u64 {
+  const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+  const PRIME: u64 = 0x100000001b3;
+  data.iter().fold(OFFSET_BASIS, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(PRIME)
+  })
+}
+
+/// Which of an item's names to search or sort by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ItemNameKind {
+  /// The name that's shown, such as "Iron Ore".
+  #[default]
+  Display,
+
+  /// The asset name, such as "CraftingOreIronOre".
+  Raw,
+}
+
+/// Sort items by name, ignoring case. If `natural` is true then numbers in the names are compared
+/// by value (see [`util::natural_cmp`]).
+pub fn sort_items(items: &mut [Item], natural: bool, kind: ItemNameKind) {
+  if natural {
+    items.sort_by(|a, b| util::natural_cmp(a.name_of(kind), b.name_of(kind)));
+  } else {
+    items.sort_by_cached_key(|item| item.name_of(kind).to_lowercase());
+  }
+}
+
+/// List the save-games in a folder along with their backups (see [`parse_backup_name`]), keeping
+/// the files dated within `dates`. The date is the timestamp in a backup's name or else the file's
+/// modification time. Backups without a save-game are listed under the save-game's expected path.
+pub fn scan_save_dir(dir: &Path, dates: &DateRange) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+  let mut saves: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+  let entries = ok!(dir.read_dir(), saves);
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if !path.is_file() {
+      continue;
+    }
+
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+      continue;
+    };
+
+    if let Some((stem, stamp)) = parse_backup_name(name) {
+      let stamp = stamp.and_then(|secs| DateTime::from_timestamp(i64::try_from(secs).ok()?, 0));
+      let ts = stamp.map(|stamp| stamp.naive_utc());
+      if dates.accepts(ts.or_else(|| util::file_mtime(&path))) {
+        let save = dir.join(format!("{stem}.{SOTA_EXT}"));
+        saves.entry(save).or_default().push(path);
+      }
+    } else if path.extension().and_then(|ext| ext.to_str()) == Some(SOTA_EXT)
+      && dates.accepts(util::file_mtime(&path))
+    {
+      saves.entry(path).or_default();
+    }
+  }
+
+  for backups in saves.values_mut() {
+    backups.sort_unstable();
+  }
+
+  saves
+}
+
+/// Replace a save-game with one of its backups. The backup must load as a save-game and the
+/// current target is first copied to `<target>.cota.bak`. The backup is restored as is, in its
+/// own encoding.
+pub fn restore_backup(backup: &Path, target: &Path) -> Result<(), Error> {
+  let data = std::fs::read(backup).map_err(Error::io(FileOp::Restore, backup))?;
+  let (encoding, bom) = Encoding::detect(&data);
+  let (text, _) = encoding.decode(&data, bom);
+  if let Err(err) = GameData::from_text(text) {
+    let path = backup.to_owned();
+    let err = Box::new(err);
+    return Err(Error::InvalidBackup { path, err });
+  }
+
+  if target.exists() {
+    let mut safety = target.as_os_str().to_owned();
+    safety.push(format!(".cota.{BAK_EXT}"));
+    if let Err(err) = std::fs::copy(target, &safety) {
+      let path = target.to_owned();
+      return Err(Error::Backup { path, err });
+    }
+  }
+
+  write_journaled(target, &data, |_| Ok(())).map_err(|err| match err {
+    Error::Io { path, err, .. } => Error::Io {
+      op: FileOp::Restore,
+      path,
+      err,
+    },
+    err => err,
+  })
+}
+
+/// Copy a file to `<name>.bak-<seconds>` next to it and remove the oldest of those beyond `keep`.
+/// Zero keeps them all. Returns the backup's path.
+fn backup_file(path: &Path, keep: usize) -> Result<PathBuf, Error> {
+  let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+  let mut secs = now.map_or(0, |time| time.as_secs());
+
+  // Don't replace a backup from the same second, it has an older version of the file.
+  let mut backup = suffixed(path, &format!(".{BAK_EXT}-{secs}"));
+  while backup.exists() {
+    secs += 1;
+    backup = suffixed(path, &format!(".{BAK_EXT}-{secs}"));
+  }
+
+  if let Err(err) = std::fs::copy(path, &backup) {
+    let path = path.to_owned();
+    return Err(Error::Backup { path, err });
+  }
+
+  if keep > 0 {
+    // Failing to remove old backups only leaves them there.
+    for old in timestamped_backups(path).into_iter().skip(keep) {
+      let _ = std::fs::remove_file(old);
+    }
+  }
+  Ok(backup)
+}
+
+/// Check if a file name is a save-game backup's. Backups are named `<stem>.bak`,
+/// `<stem>.sota.bak`, `<stem>.sota.cota.bak` (see [`restore_backup`]) or
+/// `<stem>.sota.bak-<seconds>`, where `<stem>` is the save-game's name without its extension.
+/// Returns the stem and the timestamp in the name, if it has one.
+pub fn parse_backup_name(name: &str) -> Option<(&str, Option<u64>)> {
+  let (rest, stamp) = match name.rsplit_once('.')? {
+    (rest, BAK_EXT) => (rest, None),
+    (rest, ext) => {
+      let secs = ext.strip_prefix(BAK_EXT)?.strip_prefix('-')?;
+      if secs.is_empty() || !secs.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+      }
+      (rest, Some(secs.parse().ok()?))
+    }
+  };
+
+  let stem = rest.strip_suffix(".cota").unwrap_or(rest);
+  let stem = stem
+    .strip_suffix(SOTA_EXT)
+    .and_then(|stem| stem.strip_suffix('.'))
+    .unwrap_or(stem);
+  (!stem.is_empty()).then_some((stem, stamp))
+}
+
+// Get the backups made by `backup_file`, newest first.
+fn timestamped_backups(path: &Path) -> Vec<PathBuf> {
+  let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+    return Vec::new();
+  };
+
+  let prefix = format!("{name}.{BAK_EXT}-");
+  let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+  let entries = ok!(dir.unwrap_or(Path::new(".")).read_dir(), Vec::new());
+  let mut backups = Vec::new();
+  for entry in entries.flatten() {
+    let backup = entry.path();
+    let secs = backup
+      .file_name()
+      .and_then(|name| name.to_str()?.strip_prefix(&prefix)?.parse::<u64>().ok());
+    if let Some(secs) = secs {
+      backups.push((secs, backup));
+    }
+  }
+
+  backups.sort_unstable_by(|a, b| b.cmp(a));
+  backups.into_iter().map(|(_, backup)| backup).collect()
+}
+
+/// Suffix for the file that describes a store in progress. See [`recover_interrupted`].
+pub const JOURNAL_SUFFIX: &str = ".cota-journal";
+
+/// Suffix for the new file while it's being written.
+const TEMP_SUFFIX: &str = ".cota-tmp";
+
+/// Suffix for the copy of the old file while it's being replaced.
+const OLD_SUFFIX: &str = ".cota-old";
+
+/// Suffix for the list of changes written with [`StoreOptions::write_changes`].
+const CHANGES_SUFFIX: &str = ".changes.txt";
+
+/// What was intended by a store, so that it can be finished or undone if it's interrupted.
+#[derive(Serialize, Deserialize)]
+struct StoreJournal {
+  target: PathBuf,
+  temp: PathBuf,
+
+  /// Where the target is copied to before it's replaced, if it exists.
+  backup: Option<PathBuf>,
+
+  /// Hash of the new contents (see [`hash_file`]).
+  hash: u64,
+}
+
+/// What was done with an interrupted store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recovery {
+  /// The new file was complete, so the store was finished.
+  RolledForward(PathBuf),
+
+  /// The new file was incomplete, so the old file was kept.
+  RolledBack(PathBuf),
+}
+
+/// Finish or undo the stores in a folder that were interrupted, e.g. by a crash. This is also done
+/// for a single save-game when it's loaded.
+pub fn recover_interrupted(dir: &Path) -> Result<Vec<Recovery>, Error> {
+  let entries = dir.read_dir().map_err(Error::io(FileOp::Recover, dir))?;
+  let mut journals: Vec<PathBuf> = entries
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|path| path.to_string_lossy().ends_with(JOURNAL_SUFFIX))
+    .collect();
+  journals.sort_unstable();
+
+  let mut recovered = Vec::with_capacity(journals.len());
+  for journal in journals {
+    recovered.push(recover_store(&journal)?);
+  }
+  Ok(recovered)
+}
+
+/// Write a file so that it's either fully replaced or left as it was, even if the write is
+/// interrupted. The steps are described in a journal until they're done. `verify` is given the
+/// written temporary file and can stop the store before anything is replaced.
+fn write_journaled(
+  path: &Path,
+  data: &[u8],
+  verify: impl FnOnce(&Path) -> Result<(), Error>,
+) -> Result<(), Error> {
+  let unable = |path: &Path, err| Error::io(FileOp::Store, path)(err);
+
+  let journal_path = suffixed(path, JOURNAL_SUFFIX);
+  let journal = StoreJournal {
+    target: path.to_owned(),
+    temp: suffixed(path, TEMP_SUFFIX),
+    backup: path.exists().then(|| suffixed(path, OLD_SUFFIX)),
+    hash: hash_bytes(data),
+  };
+
+  let text = serde_json::to_string(&journal)?;
+  std::fs::write(&journal_path, text).map_err(|err| unable(&journal_path, err))?;
+
+  // Undo the store. The target is only ever replaced by a rename, so it's still the original.
+  let temp = &journal.temp;
+  let undo = || {
+    let _ = std::fs::remove_file(temp);
+    if let Some(backup) = &journal.backup {
+      let _ = std::fs::remove_file(backup);
+    }
+    let _ = std::fs::remove_file(&journal_path);
+  };
+
+  if let Some(backup) = &journal.backup {
+    if let Err(err) = std::fs::copy(path, backup) {
+      undo();
+      return Err(unable(path, err));
+    }
+  }
+
+  let result = File::create(temp).and_then(|mut file| {
+    file.write_all(data)?;
+    file.sync_all()?;
+    check_step("write")
+  });
+  if let Err(err) = result {
+    undo();
+    return Err(unable(temp, err));
+  }
+
+  if let Err(err) = verify(temp) {
+    undo();
+    return Err(err);
+  }
+
+  if let Err(err) = check_step("replace").and_then(|_| std::fs::rename(temp, path)) {
+    undo();
+    return Err(unable(path, err));
+  }
+
+  if let Some(backup) = &journal.backup {
+    let _ = std::fs::remove_file(backup);
+  }
+  let _ = std::fs::remove_file(&journal_path);
+  Ok(())
+}
+
+#[cfg(test)]
+thread_local! {
+  // A step of `write_journaled` to fail, for testing that the original file survives.
+  static FAIL_STEP: std::cell::Cell<Option<&'static str>> = const { std::cell::Cell::new(None) };
+}
+
+#[cfg(test)]
+fn check_step(step: &'static str) -> io::Result<()> {
+  if FAIL_STEP.with(|fail| fail.get()) == Some(step) {
+    return Err(io::Error::other(format!("the {step} step was failed")));
+  }
+  Ok(())
+}
+
+#[cfg(not(test))]
+fn check_step(_step: &'static str) -> io::Result<()> {
+  Ok(())
+}
+
+/// Finish a store if its new file is complete, otherwise put the old file back.
+fn recover_store(journal_path: &Path) -> Result<Recovery, Error> {
+  let text = std::fs::read_to_string(journal_path);
+  let text = text.map_err(Error::io(FileOp::Recover, journal_path))?;
+
+  let journal_str = journal_path.to_string_lossy();
+  let target = PathBuf::from(
+    journal_str
+      .strip_suffix(JOURNAL_SUFFIX)
+      .unwrap_or(&journal_str),
+  );
+  let journal = match serde_json::from_str::<StoreJournal>(&text) {
+    Ok(journal) => journal,
+
+    // The journal itself wasn't finished, so nothing else was done.
+    Err(_) => StoreJournal {
+      temp: suffixed(&target, TEMP_SUFFIX),
+      target,
+      backup: None,
+      hash: 0,
+    },
+  };
+
+  let complete = |path: &Path| hash_file(path).is_ok_and(|hash| hash == journal.hash);
+  let unable = |err| Error::io(FileOp::Recover, &journal.target)(err);
+
+  let recovery = if journal.temp.is_file() && complete(&journal.temp) {
+    std::fs::rename(&journal.temp, &journal.target).map_err(unable)?;
+    Recovery::RolledForward(journal.target.clone())
+  } else if complete(&journal.target) {
+    // Only the clean up was interrupted.
+    Recovery::RolledForward(journal.target.clone())
+  } else {
+    if let Some(backup) = &journal.backup {
+      if !journal.target.exists() && backup.is_file() {
+        std::fs::rename(backup, &journal.target).map_err(unable)?;
+      }
+    }
+    let _ = std::fs::remove_file(&journal.temp);
+    Recovery::RolledBack(journal.target.clone())
+  };
+
+  if let Some(backup) = &journal.backup {
+    let _ = std::fs::remove_file(backup);
+  }
+  let _ = std::fs::remove_file(journal_path);
+  Ok(recovery)
+}
+
+// Find an item's JSON in copies of ItemStore records.
+fn stack_mut<'a>(
+  stores: &'a mut [(String, Value)],
+  instance: &ItemInstance,
+) -> Result<&'a mut Value, Error> {
+  let store = stores.iter_mut().find(|(id, _)| *id == instance.container);
+  let item = store.and_then(|(_, store)| store.get_mut(IN)?.get_mut(&instance.id)?.get_mut(IN));
+  item.ok_or_else(|| Error::other(format!("Unable to find item {}", instance.id)))
+}
+
+/// Append a suffix to a path's file name.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+  let mut path = path.as_os_str().to_owned();
+  path.push(suffix);
+  PathBuf::from(path)
+}
+
+/// Text encoding of a save-game file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+  #[default]
+  Utf8,
+  Utf16Le,
+  Utf16Be,
+}
+
+impl Encoding {
+  /// Detect the encoding from the byte order mark or, failing that, from where the null bytes are
+  /// in the first kilobyte. Also returns whether there's a byte order mark.
+  fn detect(data: &[u8]) -> (Self, bool) {
+    if data.starts_with(UTF8_BOM) {
+      return (Encoding::Utf8, true);
+    }
+    if data.starts_with(&[0xFF, 0xFE]) {
+      return (Encoding::Utf16Le, true);
+    }
+    if data.starts_with(&[0xFE, 0xFF]) {
+      return (Encoding::Utf16Be, true);
+    }
+
+    // ASCII text in UTF-16 has a null byte in every pair.
+    let head = &data[..data.len().min(1024)];
+    let pairs = head.len() / 2;
+    let count = |offset| {
+      head
+        .iter()
+        .skip(offset)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count()
+    };
+    if pairs > 0 && count(1) * 2 >= pairs {
+      (Encoding::Utf16Le, false)
+    } else if pairs > 0 && count(0) * 2 >= pairs {
+      (Encoding::Utf16Be, false)
+    } else {
+      (Encoding::Utf8, false)
+    }
+  }
+
+  /// Decode file data, replacing anything invalid. Also returns whether anything was replaced.
+  fn decode(self, data: &[u8], bom: bool) -> (String, bool) {
+    match self {
+      Encoding::Utf8 => {
+        let data = if bom { &data[UTF8_BOM.len()..] } else { data };
+        match std::str::from_utf8(data) {
+          Ok(text) => (text.to_owned(), false),
+          Err(_) => (String::from_utf8_lossy(data).into_owned(), true),
+        }
+      }
+      Encoding::Utf16Le | Encoding::Utf16Be => {
+        let data = if bom { &data[2..] } else { data };
+        let units = data.chunks(2).map(|pair| match (self, pair) {
+          (Encoding::Utf16Le, [lo, hi]) => u16::from_le_bytes([*lo, *hi]),
+          (_, [hi, lo]) => u16::from_be_bytes([*hi, *lo]),
+          // An odd byte out at the end.
+          _ => 0xFFFD,
+        });
+
+        let mut lossy = data.len() % 2 != 0;
+        let text = char::decode_utf16(units)
+          .map(|ch| {
+            ch.unwrap_or_else(|_| {
+              lossy = true;
+              char::REPLACEMENT_CHARACTER
+            })
+          })
+          .collect();
+        (text, lossy)
+      }
+    }
+  }
+
+  /// Encode text for storing, with a byte order mark if `bom` is true.
+  fn encode(self, text: &str, bom: bool) -> Vec<u8> {
+    match self {
+      Encoding::Utf8 if bom => [UTF8_BOM, text.as_bytes()].concat(),
+      Encoding::Utf8 => text.as_bytes().to_vec(),
+      Encoding::Utf16Le | Encoding::Utf16Be => {
+        let bom = bom.then_some(0xFEFF);
+        let units = bom.into_iter().chain(text.encode_utf16());
+        let mut data = Vec::with_capacity(text.len() * 2 + 2);
+        for unit in units {
+          if self == Encoding::Utf16Le {
+            data.extend(unit.to_le_bytes());
+          } else {
+            data.extend(unit.to_be_bytes());
+          }
+        }
+        data
+      }
+    }
+  }
+}
+
+/// Options for storing a save-game.
+#[derive(Clone, Copy)]
+pub struct StoreOptions {
+  /// Create missing parent directories.
+  pub create_dirs: bool,
+
+  /// Also write the pending changes to `<name>.changes.txt`, where `<name>` is the file name of the
+  /// backup that was made (see [`GameData::set_backup`]) or of the save-game if there isn't one.
+  pub write_changes: bool,
+
+  /// Read the new file back before it replaces the old one and make sure that it has the same
+  /// gold, adventurer experience, skills and items. This is on by default.
+  pub verify: bool,
+
+  /// Store over the loaded file even if something else changed it (see
+  /// [`GameData::check_conflict`]), or if there are no changes.
+  pub force: bool,
+}
+
+/// What [`GameData::store`] did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreOutcome {
+  Written,
+
+  /// There were no changes, so the file was left alone.
+  Unchanged,
+}
+
+impl Default for StoreOptions {
+  fn default() -> Self {
+    Self {
+      create_dirs: false,
+      write_changes: false,
+      verify: true,
+      force: false,
+    }
+  }
+}
+
+/// A save-game file that was changed by something else since it was loaded. See
+/// [`GameData::check_conflict`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConflictDetected {
+  pub path: PathBuf,
+
+  /// Modification time when the file was loaded or last stored.
+  pub loaded: Option<SystemTime>,
+
+  /// Current modification time.
+  pub modified: Option<SystemTime>,
+}
+
+impl fmt::Display for ConflictDetected {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let show = |time: Option<SystemTime>| match time {
+      Some(time) => DateTime::<Utc>::from(time)
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string(),
+      None => String::from("unknown"),
+    };
+
+    write!(
+      f,
+      "'{}' was changed by something else since it was loaded ({}, now {})",
+      self.path.display(),
+      show(self.loaded),
+      show(self.modified)
+    )
+  }
+}
+
+impl std::error::Error for ConflictDetected {}
+
+/// A section that doesn't have the expected structure, e.g. after a bad patch or an edit by
+/// another tool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MalformedSection {
+  pub section: Section,
+  pub reason: String,
+}
+
+impl fmt::Display for MalformedSection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} is malformed: {}", self.section, self.reason)
+  }
+}
+
+impl std::error::Error for MalformedSection {}
+
+/// A record whose JSON can't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidJson {
+  pub collection: String,
+  pub id: String,
+
+  /// One-based position in the record's JSON.
+  pub line: usize,
+  pub column: usize,
+
+  /// Byte offset in the file text. This is the start of the record if the JSON is escaped.
+  pub offset: usize,
+
+  /// The JSON around the problem.
+  pub context: String,
+  pub message: String,
+}
+
+impl InvalidJson {
+  // How much of the JSON to show.
+  const CONTEXT_LEN: usize = 80;
+
+  fn new(
+    collection: &str,
+    id: &str,
+    json: &str,
+    start: usize,
+    style: RecordStyle,
+    err: &serde_json::Error,
+  ) -> Self {
+    let pos = line_col_pos(json, err.line(), err.column());
+
+    // Escaped JSON is a different length to the text in the file.
+    let offset = if style.escaped { start } else { start + pos };
+
+    // The error message ends with the position, which is shown separately.
+    let message = err.to_string();
+    let message = match message.rsplit_once(" at line ") {
+      Some((message, _)) => message.to_owned(),
+      None => message,
+    };
+
+    Self {
+      collection: collection.into(),
+      id: id.into(),
+      line: err.line(),
+      column: err.column(),
+      offset,
+      context: text_around(json, pos, Self::CONTEXT_LEN).into(),
+      message,
+    }
+  }
+}
+
+impl fmt::Display for InvalidJson {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Invalid JSON in the {} record '{}' at line {}, column {}: {} near `{}`",
+      self.collection, self.id, self.line, self.column, self.message, self.context
+    )
+  }
+}
+
+impl std::error::Error for InvalidJson {}
+
+impl From<MalformedSection> for Error {
+  fn from(err: MalformedSection) -> Self {
+    Error::MalformedSection(err)
+  }
+}
+
+/// A level outside of its allowed range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutOfRange {
+  /// What the level is for, e.g. "Adventurer" or a skill name.
+  pub name: &'static str,
+  pub value: i32,
+  pub range: RangeInclusive<i32>,
+}
+
+impl OutOfRange {
+  fn check(name: &'static str, value: i32, range: RangeInclusive<i32>) -> Result<(), Self> {
+    if range.contains(&value) {
+      return Ok(());
+    }
+    Err(Self { name, value, range })
+  }
+}
+
+impl fmt::Display for OutOfRange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (start, end) = (self.range.start(), self.range.end());
+    write!(
+      f,
+      "{} level {} is not within {start}-{end}",
+      self.name, self.value
+    )
+  }
+}
+
+impl std::error::Error for OutOfRange {}
+
+impl From<OutOfRange> for Error {
+  fn from(err: OutOfRange) -> Self {
+    Error::OutOfRange(err)
+  }
+}
+
+/// Experience outside of its allowed range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpOutOfRange {
+  /// What the experience is for, e.g. "Adventurer" or a skill name.
+  pub name: &'static str,
+  pub value: i64,
+  pub range: RangeInclusive<i64>,
+}
+
+impl ExpOutOfRange {
+  fn check(name: &'static str, value: i64, range: RangeInclusive<i64>) -> Result<(), Self> {
+    if range.contains(&value) {
+      return Ok(());
+    }
+    Err(Self { name, value, range })
+  }
+}
+
+impl fmt::Display for ExpOutOfRange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (start, end) = (self.range.start(), self.range.end());
+    write!(
+      f,
+      "{} experience {} is not within {start}-{end}",
+      self.name, self.value
+    )
+  }
+}
+
+impl std::error::Error for ExpOutOfRange {}
+
+impl From<ExpOutOfRange> for Error {
+  fn from(err: ExpOutOfRange) -> Self {
+    Error::ExpOutOfRange(err)
+  }
+}
+
+// Size, modification time and hash of a file.
+#[derive(Clone, Copy, Debug)]
+struct FileStamp {
+  modified: Option<SystemTime>,
+  len: u64,
+  hash: u64,
+}
+
+impl FileStamp {
+  fn new(path: &Path, hash: u64) -> Option<Self> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some(Self {
+      modified: meta.modified().ok(),
+      len: meta.len(),
+      hash,
+    })
+  }
+}
+
+/// A save-game that didn't read back the same before it was stored. See [`StoreOptions::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationFailed {
+  pub path: PathBuf,
+  pub reason: String,
+}
+
+impl fmt::Display for VerificationFailed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "'{}' wasn't stored because it didn't verify: {}",
+      self.path.display(),
+      self.reason
+    )
+  }
+}
+
+impl std::error::Error for VerificationFailed {}
+
+/// A file that's larger than [`max_file_size`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileTooLarge {
+  pub path: PathBuf,
+  pub size: u64,
+  pub limit: u64,
+}
+
+impl fmt::Display for FileTooLarge {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "'{}' is too large to load ({} bytes, the limit is {})",
+      self.path.display(),
+      self.size,
+      self.limit
+    )
+  }
+}
+
+impl std::error::Error for FileTooLarge {}
+
+/// The default for [`max_file_size`].
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+static MAX_FILE_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_MAX_FILE_SIZE);
+
+/// Get the size of the largest file that will be loaded or sniffed. Larger files can still be
+/// read with [`scan_records`].
+pub fn max_file_size() -> u64 {
+  MAX_FILE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Set the size of the largest file that will be loaded or sniffed.
+pub fn set_max_file_size(limit: u64) {
+  MAX_FILE_SIZE.store(limit, Ordering::Relaxed);
+}
+
+fn check_file_size(path: &Path, limit: u64) -> Result<(), FileTooLarge> {
+  // Unreadable files are reported when they're read.
+  let size = std::fs::metadata(path).map_or(0, |meta| meta.len());
+  if size > limit {
+    return Err(FileTooLarge {
+      path: path.to_owned(),
+      size,
+      limit,
+    });
+  }
+  Ok(())
+}
+
+/// A record's JSON, as found by [`scan_records`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordText {
+  pub collection: String,
+  pub id: String,
+  pub json: String,
+}
+
+/// Read the records in some collections without loading the whole file, which works for files
+/// that are too large to load. Only one record at a time is kept in memory, and records larger
+/// than [`max_file_size`] fail. Only UTF-8 files can be scanned.
+pub fn scan_records(
+  path: impl AsRef<Path>,
+  collections: &[&str],
+) -> Result<Vec<RecordText>, Error> {
+  let path = path.as_ref();
+  let file = File::open(path).map_err(Error::io(FileOp::Load, path))?;
+  let reader = io::BufReader::new(file);
+  let records = scan_records_from(reader, collections, max_file_size());
+  records.map_err(Error::other)
+}
+
+fn scan_records_from(
+  mut reader: impl Read,
+  collections: &[&str],
+  limit: u64,
+) -> Result<Vec<RecordText>, String> {
+  const CHUNK_LEN: usize = 64 * 1024;
+  let record_end = format!("</{RECORD}>");
+  let find = |data: &[u8], find: &[u8]| data.windows(find.len()).position(|win| win == find);
+  let tag_body = |tag: &str, name: &str| -> Option<String> {
+    let body = tag.strip_prefix(name)?;
+    (body.is_empty() || body.starts_with(char::is_whitespace)).then(|| body.to_owned())
+  };
+  let attr = |body: &str, name: &str| -> Option<String> {
+    let attrs = parse_attrs(body)?;
+    let (_, val, _) = attrs.into_iter().find(|(key, _, _)| *key == name)?;
+    Some(val.to_owned())
+  };
+
+  let mut records = Vec::new();
+  let mut collection: Option<String> = None;
+  let mut buf = Vec::new();
+  let mut chunk = vec![0; CHUNK_LEN];
+  let mut first = true;
+  let mut eof = false;
+  loop {
+    // Handle the complete tags and records in the buffer.
+    let mut pos = 0;
+    while let Some(start) = find(&buf[pos..], b"<").map(|start| pos + start) {
+      pos = start;
+      let Some(end) = find(&buf[start..], b">").map(|end| start + end) else {
+        break;
+      };
+
+      let tag = String::from_utf8_lossy(&buf[start + 1..end]);
+      if let Some(body) = tag_body(&tag, COLLECTION) {
+        collection = attr(&body, NAME);
+      } else if tag_body(&tag, &format!("/{COLLECTION}")).is_some() {
+        collection = None;
+      } else if let Some(body) = tag_body(&tag, RECORD) {
+        let json_start = end + 1;
+        let Some(len) = find(&buf[json_start..], record_end.as_bytes()) else {
+          break;
+        };
+
+        let name = collection
+          .as_deref()
+          .filter(|name| collections.contains(name));
+        if let (Some(name), Some(id)) = (name, attr(&body, ID)) {
+          let raw = String::from_utf8_lossy(&buf[json_start..json_start + len]);
+          let json = raw.trim();
+          let json = json
+            .strip_prefix(CDATA_START)
+            .and_then(|json| json.strip_suffix(CDATA_END))
+            .unwrap_or(json);
+          records.push(RecordText {
+            collection: name.to_owned(),
+            id,
+            json: json.to_owned(),
+          });
+        }
+
+        pos = json_start + len + record_end.len();
+        continue;
+      }
+      pos = end + 1;
+    }
+
+    if find(&buf[pos..], b"<").is_none() {
+      pos = buf.len();
+    }
+    buf.drain(..pos);
+
+    if eof {
+      return Ok(records);
+    }
+
+    if buf.len() as u64 > limit {
+      return Err(format!(
+        "A record is larger than the limit of {limit} bytes"
+      ));
+    }
+
+    let len = reader.read(&mut chunk).map_err(|err| err.to_string())?;
+    let mut data = &chunk[..len];
+    if first {
+      first = false;
+      match Encoding::detect(data) {
+        (Encoding::Utf8, true) => data = &data[UTF8_BOM.len()..],
+        (Encoding::Utf8, false) => (),
+        _ => return Err(String::from("Only UTF-8 save-games can be scanned")),
+      }
+    }
+
+    eof = len == 0;
+    buf.extend_from_slice(data);
+  }
+}
+
+/// A save-game file that can't be written. See [`GameData::is_writable`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadOnly {
+  pub path: PathBuf,
+  pub reason: String,
+}
+
+impl fmt::Display for ReadOnly {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "'{}' can't be written: {}",
+      self.path.display(),
+      self.reason
+    )
+  }
+}
+
+impl std::error::Error for ReadOnly {}
+
+/// Check if an existing file can be written. Permissions aren't reliable on network shares, so
+/// the file is also opened for appending, which fails if it's locked (e.g. by the game).
+fn check_writable(path: &Path) -> Result<(), String> {
+  let meta = std::fs::metadata(path).map_err(|err| err.to_string())?;
+  if meta.permissions().readonly() {
+    return Err(String::from("the file is read-only"));
+  }
+
+  match OpenOptions::new().append(true).open(path) {
+    Ok(_) => Ok(()),
+    Err(err) => Err(format!("it can't be opened for writing ({err})")),
+  }
+}
+
+/// Extension for save-games.
+pub const SOTA_EXT: &str = "sota";
+
+/// Save format versions that are known to edit correctly. Save-games without a version are always
+/// accepted, others are loaded with a warning.
+pub const FORMAT_VERSIONS: RangeInclusive<u32> = 1..=1;
+
+/// Extension for save-game backups.
+pub const BAK_EXT: &str = "bak";
+
+const USER_ID: &str = "000000000000000000000001";
+const SKILL_RANK_TOLERANCE: i64 = 1;
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const SNIFF_LEN: usize = 4096;
+const UNKNOWN_GROUP: &str = "Unknown";
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+const DATA: &str = "Data";
+const VERSION: &str = "version";
+const COLLECTION: &str = "collection";
+const RECORD: &str = "record";
+const NAME: &str = "name";
+const ID: &str = "Id";
+const USER: &str = "User";
+const CHARACTER_NAME: &str = "CharacterName";
+const CHARACTER: &str = "Character";
+const CHARACTER_SHEET: &str = "CharacterSheet";
+const ITEM_STORE: &str = "ItemStore";
+const USER_GOLD: &str = "UserGold";
+// Guessed from UserGold; not yet checked against a real save with banked gold.
+const USER_BANK: &str = "UserBank";
+const BAG: &str = "bag";
+const PHP: &str = "php";
+const SK2: &str = "sk2";
+const AE: &str = "ae";
+const AN: &str = "an";
+const DC: &str = "dc";
+const FN: &str = "fn";
+const MAINBP: &str = "mainbp";
+const HP: &str = "hp";
+const IN: &str = "in";
+const PE: &str = "pe";
+const QN: &str = "qn";
+const G: &str = "g";
+const M: &str = "m";
+const T: &str = "t";
+const DATE: &str = "$date";
+const X: &str = "x";
+
+#[derive(Clone)]
+pub struct SkillLvl {
+  pub info: SkillInfo,
+  pub level: i32,
+  comp: i32,
+}
+
+impl SkillLvl {
+  fn new(sk2: &Value, info: SkillInfo, tables: &ExpTables) -> Self {
+    let level = get_skill_lvl(sk2, &info, tables).level().unwrap_or(0);
+    let comp = level;
+
+    Self { info, level, comp }
+  }
+
+  fn accept(&mut self) {
+    self.comp = self.level;
+  }
+
+  fn discard(&mut self) {
+    self.level = self.comp;
+  }
+
+  pub fn changed(&self) -> bool {
+    self.level != self.comp
+  }
+}
+
+pub struct SkillLvlGroup {
+  pub name: &'static str,
+  pub skills: Vec<SkillLvl>,
+}
+
+impl SkillLvlGroup {
+  fn new(sk2: &Value, group: SkillInfoGroup, tables: &ExpTables) -> Self {
+    let name = group.name;
+    let mut skills = Vec::with_capacity(group.skills.len());
+    for skill in group.skills {
+      skills.push(SkillLvl::new(sk2, skill, tables));
+    }
+
+    Self { name, skills }
+  }
+
+  pub fn changed(&self) -> bool {
+    for skill in &self.skills {
+      if skill.changed() {
+        return true;
+      }
+    }
+
+    false
+  }
+
+  pub fn accept(&mut self) {
+    for skill in &mut self.skills {
+      skill.accept();
+    }
+  }
+
+  pub fn discard(&mut self) {
+    for skill in &mut self.skills {
+      skill.discard();
+    }
+  }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Durability {
+  pub minor: f64,
+  pub major: f64,
+}
+
+impl Durability {
+  fn new(val: &Value) -> Option<Self> {
+    let minor = json_num::to_f64(val.get(HP)?)?;
+    let major = json_num::to_f64(val.get(PHP)?)?;
+
+    Some(Durability { minor, major })
+  }
+
+  /// Check that the values are finite and not negative, and that minor isn't more than major.
+  /// Returns the durability to write, which has minor lowered to major if `policy` is
+  /// [`DurabilityPolicy::Clamp`].
+  pub fn validate(&self, policy: DurabilityPolicy) -> Result<Durability, Cow<'static, str>> {
+    if !self.minor.is_finite() || !self.major.is_finite() {
+      return Err(Cow::from("not a number"));
+    }
+
+    if self.minor < 0.0 || self.major < 0.0 {
+      return Err(Cow::from("negative"));
+    }
+
+    if self.minor > self.major {
+      if policy == DurabilityPolicy::Reject {
+        let err = format!("minor {} is more than major {}", self.minor, self.major);
+        return Err(Cow::from(err));
+      }
+
+      return Ok(Durability {
+        minor: self.major,
+        major: self.major,
+      });
+    }
+
+    Ok(self.clone())
+  }
+}
+
+/// How [`GameData::set_items_count_by_name`] matches names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameMatch {
+  /// The whole name.
+  #[default]
+  Exact,
+
+  /// Part of the name.
+  Substring,
+}
+
+/// Options for [`GameData::set_items_count_by_name`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchOpts {
+  pub mode: NameMatch,
+  pub ignore_case: bool,
+
+  /// Work out the changes without making them.
+  pub dry_run: bool,
+}
+
+/// Why a matching item's count wasn't set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+  Container,
+
+  /// The item has durability, so each one is separate.
+  NotStackable,
+}
+
+impl fmt::Display for SkipReason {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SkipReason::Container => write!(f, "it's a container"),
+      SkipReason::NotStackable => write!(f, "it doesn't stack"),
+    }
+  }
+}
+
+/// Result of [`GameData::set_items_count_by_name`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CountChanges {
+  /// Item ID, old count and new count of each item that was set.
+  pub items: Vec<(String, u64, u64)>,
+
+  /// Items that matched but were left alone.
+  pub skipped: Vec<(String, SkipReason)>,
+}
+
+/// Version of the files written by [`GameData::export_container`].
+pub const CONTAINER_VERSION: u32 = 1;
+
+/// Where [`GameData::import_container`] puts a container.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ContainerTarget {
+  #[default]
+  Backpack,
+
+  /// Inside another container, by item ID.
+  Container(String),
+}
+
+/// A container file that can't be imported.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ContainerError {
+  Invalid(String),
+
+  /// The file was written by a different version.
+  Version {
+    found: u32,
+  },
+
+  /// An ID is used for more than one item or ItemStore in the file.
+  IdCollision(String),
+
+  /// A bag's contents aren't there.
+  MissingStore(String),
+}
+
+impl fmt::Display for ContainerError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ContainerError::Invalid(err) => write!(f, "Invalid container file: {err}"),
+      ContainerError::Version { found } => write!(
+        f,
+        "Container file version {found} is not supported, expected {CONTAINER_VERSION}"
+      ),
+      ContainerError::IdCollision(id) => write!(f, "Container file has more than one '{id}'"),
+      ContainerError::MissingStore(id) => write!(f, "Unable to find the contents of bag {id}"),
+    }
+  }
+}
+
+impl std::error::Error for ContainerError {}
+
+/// A container and everything in it. See [`GameData::export_container`].
+#[derive(Serialize, Deserialize)]
+struct ContainerFile {
+  version: u32,
+
+  /// The container's item ID and JSON.
+  id: String,
+  item: Value,
+
+  /// ItemStore records of the container and the bags inside it, by ID.
+  stores: BTreeMap<String, Value>,
+}
+
+/// Parse a container file, checking that its IDs are unique and that every bag's contents are
+/// there.
+fn parse_container(text: &str) -> Result<ContainerFile, ContainerError> {
+  let invalid = |err: String| ContainerError::Invalid(err);
+  let file: Value = serde_json::from_str(text).map_err(|err| invalid(err.to_string()))?;
+
+  // Check the version before anything else can fail because of it.
+  let version = file.get("version").and_then(json_num::to_u64);
+  let Some(version) = version.and_then(|version| u32::try_from(version).ok()) else {
+    return Err(invalid(String::from("there's no version")));
+  };
+
+  if version != CONTAINER_VERSION {
+    return Err(ContainerError::Version { found: version });
+  }
+
+  let file: ContainerFile = serde_json::from_value(file).map_err(|err| invalid(err.to_string()))?;
+  let Some(bag) = file.item.get(IN).and_then(|item| item.get(BAG)?.as_str()) else {
+    return Err(invalid(String::from("the item is not a container")));
+  };
+
+  // Every ID must be unique, and every bag's contents must be there.
+  let mut ids = HashSet::from([file.id.as_str()]);
+  let mut bags = vec![bag];
+  for (store_id, store) in &file.stores {
+    let Some(items) = store.get(IN).and_then(|items| items.as_object()) else {
+      return Err(invalid(format!("the contents of {store_id} are malformed")));
+    };
+
+    for id in [store_id].into_iter().chain(items.keys()) {
+      if !ids.insert(id) {
+        return Err(ContainerError::IdCollision(id.clone()));
+      }
+    }
+
+    if let Some((id, _)) = items.iter().find(|(_, item)| !item[IN].is_object()) {
+      return Err(invalid(format!("item {id} is malformed")));
+    }
+    bags.extend(store_bags(store));
+  }
+
+  if let Some(bag) = bags.into_iter().find(|bag| !file.stores.contains_key(*bag)) {
+    return Err(ContainerError::MissingStore(bag.to_owned()));
+  }
+
+  Ok(file)
+}
+
+/// Get the IDs of the ItemStores of the bags in an ItemStore.
+fn store_bags(store: &Value) -> Vec<&str> {
+  let items = store.get(IN).and_then(|items| items.as_object());
+  let items = items.into_iter().flat_map(|items| items.values());
+  items
+    .filter_map(|item| item.get(IN)?.get(BAG)?.as_str())
+    .collect()
+}
+
+/// What to do about minor durability that's more than major. See [`Durability::validate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DurabilityPolicy {
+  #[default]
+  Reject,
+  Clamp,
+}
+
+#[derive(Clone)]
+pub struct Item {
+  id: String,
+  name: Arc<str>,
+  display_name: Arc<str>,
+  cnt_cmp: u64,
+  cnt: u64,
+  dur_cmp: Option<Durability>,
+  dur: Option<Durability>,
+  bag: bool,
+  parent: Option<String>,
+
+  // The ItemStore that the item is in, if it's not the backpack.
+  store: Option<String>,
+}
+
+impl Item {
+  fn new(
+    val: &Value,
+    id: &str,
+    names: &HashSet<Arc<str>>,
+    display_names: &ItemNames,
+  ) -> Option<Self> {
+    let val = val.get(IN)?;
+    let name = item_name(val)?;
+    let display_name = Arc::from(display_names.display_name(name));
+    let name = names.get(name).cloned().unwrap_or_else(|| Arc::from(name));
+    let cnt = val.get(QN).and_then(json_num::to_u64)?;
+    let dur = Durability::new(val);
+    let bag = val.get(BAG).is_some();
+
+    Some(Item {
+      id: id.into(),
+      name,
+      display_name,
+      cnt_cmp: cnt,
+      cnt,
+      dur_cmp: dur.clone(),
+      dur,
+      bag,
+      parent: None,
+      store: None,
+    })
+  }
+
+  pub fn changed(&self) -> bool {
+    self.cnt != self.cnt_cmp || self.dur != self.dur_cmp
+  }
+
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  /// The asset name, such as "CraftingOreIronOre".
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// The name to show, such as "Iron Ore".
+  pub fn display_name(&self) -> &str {
+    &self.display_name
+  }
+
+  /// Get the display or asset name.
+  pub fn name_of(&self, kind: ItemNameKind) -> &str {
+    match kind {
+      ItemNameKind::Display => self.display_name(),
+      ItemNameKind::Raw => self.name(),
+    }
+  }
+
+  pub fn count_mut(&mut self) -> &mut u64 {
+    &mut self.cnt
+  }
+
+  pub fn durability_mut(&mut self) -> Option<&mut Durability> {
+    if let Some(dur) = &mut self.dur {
+      return Some(dur);
+    }
+
+    None
+  }
+
+  pub fn is_container(&self) -> bool {
+    self.bag
+  }
+
+  /// The ID of the bag that the item is in, from [`GameData::get_inventory_tree`].
+  pub fn parent_id(&self) -> Option<&str> {
+    self.parent.as_deref()
+  }
+
+  pub fn accept(&mut self) {
+    self.cnt_cmp = self.cnt;
+    self.dur_cmp.clone_from(&self.dur);
+  }
+
+  pub fn discard(&mut self) {
+    self.cnt = self.cnt_cmp;
+    self.dur.clone_from(&self.dur_cmp);
+  }
+}
+
+fn get_skill_lvl(sk2: &Value, info: &SkillInfo, tables: &ExpTables) -> SkillLevel {
+  let Some(skill) = sk2.get(format!("{}", info.id)) else {
+    return SkillLevel::Untrained;
+  };
+
+  let Some(exp) = skill.get(X) else {
+    return SkillLevel::Invalid(String::from("no experience"));
+  };
+
+  let Some(exp) = json_num::to_i64(exp) else {
+    return SkillLevel::Invalid(format!("invalid experience: {exp}"));
+  };
+
+  let scaled = (exp as f64 / info.mul) as i64;
+  if scaled < 0 {
+    return SkillLevel::Invalid(format!("negative experience: {exp}"));
+  }
+  SkillLevel::Level(util::level_for_exp(scaled, tables.skill()))
+}
+
+/// Set a skill's experience for its level, or remove it if the level is 0. Returns true if the
+/// skill changed.
+fn set_skill_lvl(
+  sk2: &mut Value,
+  date: &Value,
+  skill: &SkillLvl,
+  tables: &ExpTables,
+  limits: &Limits,
+) -> Result<bool, Error> {
+  OutOfRange::check(
+    skill.info.display_name(),
+    skill.level,
+    limits.skill_level.clone(),
+  )?;
+  if skill.level == 0 {
+    Ok(remove_skill(sk2, skill.info.id)?)
+  } else {
+    let exp = util::exp_for_level(skill.level, tables.skill()).unwrap_or_default();
+    let exp = exp as f64 * skill.info.mul;
+    Ok(write_skill_exp(
+      sk2,
+      date,
+      skill.info.id,
+      exp.ceil() as i64,
+    )?)
+  }
+}
+
+/// Set a skill's experience, adding the skill if it isn't there. Returns true if it changed.
+fn write_skill_exp(
+  sk2: &mut Value,
+  date: &Value,
+  id: u32,
+  exp: i64,
+) -> Result<bool, MalformedSection> {
+  let key = format!("{id}");
+  let Some(skills) = sk2.as_object_mut() else {
+    return Err(malformed_sheet("sk2 is not an object"));
+  };
+
+  if let Some(val) = skills.get_mut(&key) {
+    // Set the skill's experience.
+    let changed = set_exp(val, X, exp);
+    changed.ok_or_else(|| malformed_sheet(format!("skill {key} is not an object")))
+  } else {
+    // Skill doesn't exist, so add it.
+    let val = serde_json::json!({
+      M: 0,
+      T: date,
+      X: exp,
+    });
+    skills.insert(key, val);
+    Ok(true)
+  }
+}
+
+fn remove_skill(sk2: &mut Value, id: u32) -> Result<bool, MalformedSection> {
+  let Some(skills) = sk2.as_object_mut() else {
+    return Err(malformed_sheet("sk2 is not an object"));
+  };
+  Ok(skills.shift_remove(&format!("{id}")).is_some())
+}
+
+// Get a CharacterSheet's skills, which must be an object.
+fn skills_mut(sheet: &mut Value) -> Result<&mut Value, MalformedSection> {
+  match sheet.get_mut(SK2) {
+    Some(sk2) if sk2.is_object() => Ok(sk2),
+    Some(_) => Err(malformed_sheet("sk2 is not an object")),
+    None => Err(malformed_sheet("sk2 is missing")),
+  }
+}
+
+fn skills_object_mut(
+  sheet: &mut Value,
+) -> Result<&mut serde_json::Map<String, Value>, MalformedSection> {
+  let sk2 = skills_mut(sheet)?;
+  sk2
+    .as_object_mut()
+    .ok_or_else(|| malformed_sheet("sk2 is not an object"))
+}
+
+// Remove the skills that are in the registry from a CharacterSheet and return them.
+fn take_known_skills(
+  sheet: &mut Value,
+  registry: &SkillRegistry,
+) -> Result<Vec<(String, Value)>, MalformedSection> {
+  let sk2 = skills_object_mut(sheet)?;
+  let known = |id: &String| {
+    id.parse()
+      .ok()
+      .and_then(|id| registry.find_id(id))
+      .is_some()
+  };
+  let ids: Vec<String> = sk2.keys().filter(|id| known(id)).cloned().collect();
+  Ok(
+    ids
+      .into_iter()
+      .filter_map(|id| sk2.remove_entry(&id))
+      .collect(),
+  )
+}
+
+fn malformed_sheet(reason: impl Into<String>) -> MalformedSection {
+  MalformedSection {
+    section: Section::CharacterSheet,
+    reason: reason.into(),
+  }
+}
+
+/// Set an experience value, returns true if it changed. Returns `None` if `val` isn't an object.
+fn set_exp(val: &mut Value, key: &str, exp: i64) -> Option<bool> {
+  let obj = val.as_object_mut()?;
+  if obj.get(key).and_then(json_num::to_i64) == Some(exp) {
+    return Some(false);
+  }
+
+  let new = encode_int(obj.get(key), exp);
+  obj.insert(key.into(), new);
+  Some(true)
+}
+
+/// Make a JSON integer, quoted if the value that it replaces was quoted.
+fn encode_int(old: Option<&Value>, val: i64) -> Value {
+  match old {
+    Some(Value::String(_)) => Value::String(val.to_string()),
+    _ => val.into(),
+  }
+}
+
+/// Set a value, returns true if it changed.
+fn set_value(val: &mut Value, key: &str, new: Value) -> bool {
+  let new = match val.get(key) {
+    Some(old) if same_value(old, &new) => return false,
+
+    // Keep quoted numbers quoted.
+    Some(Value::String(_)) if new.is_number() => Value::String(new.to_string()),
+    _ => new,
+  };
+
+  val[key] = new;
+  true
+}
+
+/// Compare values, with numbers compared by value. Numbers keep their original text, so `40` and
+/// `40.0` are different JSON but the same number. Quoted numbers are the same as unquoted ones.
+fn same_value(a: &Value, b: &Value) -> bool {
+  match (a, b) {
+    (Value::String(_), Value::Number(_)) | (Value::Number(_), Value::String(_)) => {
+      json_num::to_f64(a).is_some_and(|val| Some(val) == json_num::to_f64(b))
+    }
+    (Value::Number(a), Value::Number(b)) => {
+      if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        a == b
+      } else if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+        a == b
+      } else {
+        a == b || a.as_f64() == b.as_f64()
+      }
+    }
+    _ => a == b,
+  }
+}
+
+// Set an item's count and durability. Returns the item's JSON from before each change, with the
+// change's values. Durability changes are separate from count changes.
+fn update_item(
+  val: &mut Value,
+  item: &Item,
+  dur: Option<Durability>,
+) -> Vec<(Value, (ChangeValue, ChangeValue))> {
+  let mut changes = Vec::new();
+  let Some(old_cnt) = val.get(IN).and_then(|val| json_num::to_u64(val.get(QN)?)) else {
+    return changes;
+  };
+
+  let old_dur = val.get(IN).and_then(Durability::new);
+  let prev = val.clone();
+  if set_value(&mut val[IN], QN, item.cnt.into()) {
+    let values = (
+      ChangeValue::Int(old_cnt as i64),
+      ChangeValue::Int(item.cnt as i64),
+    );
+    changes.push((prev, values));
+  }
+
+  if let Some(dur) = dur {
+    let prev = val.clone();
+    let minor = set_value(&mut val[IN], HP, dur.minor.into());
+    let major = set_value(&mut val[IN], PHP, dur.major.into());
+    if minor || major {
+      let old = old_dur.map_or(ChangeValue::None, |dur| ChangeValue::Float(dur.minor));
+      changes.push((prev, (old, ChangeValue::Float(dur.minor))));
+    }
+  }
+  changes
+}
+
+fn invalid_durability(items: &[String]) -> Cow<'static, str> {
+  Cow::from(format!("Invalid durability for {}", items.join(", ")))
+}
+
+fn get_item_name(val: &Value) -> Option<String> {
+  item_name(val).map(String::from)
+}
+
+fn item_name(val: &Value) -> Option<&str> {
+  let text = val.get(AN)?.as_str()?;
+  let pos = text.rfind('/')?;
+
+  Some(&text[pos + 1..])
+}
+
+/// Collect the distinct item names in an ItemStore.
+fn intern_item_names(inventory: &Value) -> HashSet<Arc<str>> {
+  let mut names = HashSet::new();
+  let Some(items) = inventory.get(IN).and_then(Value::as_object) else {
+    return names;
+  };
+
+  for val in items.values() {
+    if let Some(name) = val.get(IN).and_then(item_name) {
+      if !names.contains(name) {
+        names.insert(Arc::from(name));
+      }
+    }
+  }
+  names
+}
+
+// Get the format version from the root element's `version` attribute.
+fn get_format_version(text: &str) -> Option<u32> {
+  let tag = find_tag(text, 0, DATA, VERSION, None)?;
+  tag.value.trim().parse().ok()
+}
+
+fn unsupported_version(version: Option<u32>) -> Option<UnsupportedVersion> {
+  let found = version.filter(|version| !FORMAT_VERSIONS.contains(version))?;
+  Some(UnsupportedVersion { found })
+}
+
+// Get the avatars from the Character collection.
+fn avatars(text: &str) -> Vec<AvatarInfo> {
+  let mut avatars: Vec<AvatarInfo> = Vec::new();
+  let mut from = 0;
+  while let Some(tag) = find_tag(text, from, COLLECTION, NAME, Some(CHARACTER)) {
+    let start = tag.range.end;
+    let end = text[start..]
+      .find(collection_end())
+      .map_or(text.len(), |len| start + len);
+
+    let mut pos = start;
+    while let Some(record) = find_tag(&text[..end], pos, RECORD, ID, None) {
+      let id = record.value;
+      if !avatars.iter().any(|avatar| avatar.id == id) {
+        let name = get_avatar_name(text, id).unwrap_or_else(|_| id.to_owned());
+        let id = id.to_owned();
+        avatars.push(AvatarInfo { id, name });
+      }
+      pos = record.range.end;
+    }
+    from = end;
+  }
+  avatars
+}
+
+fn get_avatar_id(text: &str) -> Result<String, Error> {
+  // Get the User json.
+  let json = get_json(text, USER, USER_ID)?;
+
+  // Get the avatar ID.
+  if let Some(Value::String(id)) = json.get(DC) {
+    return Ok(id.clone());
+  }
+
+  Err(Error::MissingField(DC))
+}
+
+fn get_avatar_name(text: &str, avatar: &str) -> Result<String, Error> {
+  // Get the CharacterName json.
+  let json = get_json(text, CHARACTER_NAME, avatar)?;
+
+  // Get the avatar name.
+  if let Some(Value::String(name)) = json.get(FN) {
+    return Ok(name.clone());
+  }
+
+  Err(Error::MissingField(FN))
+}
+
+fn get_backpack_id(text: &str, avatar: &str) -> Result<String, Error> {
+  // Get the Character json.
+  let json = get_json(text, CHARACTER, avatar)?;
+
+  // Get the backpack ID.
+  if let Some(Value::String(id)) = json.get(MAINBP) {
+    return Ok(id.clone());
+  }
+
+  Err(Error::MissingField(MAINBP))
+}
+
+fn collection_tag(collection: &str, quote: char) -> String {
+  format!("<collection name={quote}{collection}{quote}>")
+}
+
+fn record_tag(id: &str, quote: char) -> String {
+  format!("<record Id={quote}{id}{quote}>")
+}
+
+const fn record_end() -> &'static str {
+  "</record>"
+}
+
+const fn collection_end() -> &'static str {
+  "</collection>"
+}
+
+/// A start tag found by [`find_tag`].
+struct Tag<'a> {
+  range: Range<usize>,
+
+  // Value of the matched attribute.
+  value: &'a str,
+
+  // Quote character used for the matched attribute.
+  quote: char,
+}
+
+/// Find the first `<name ...>` start tag, at or after `from`, with the attribute `attr` set to
+/// `value` (or set to anything if `value` is `None`). Attributes can be in any order, quoted with
+/// either `"` or `'` and separated by any amount of whitespace.
+fn find_tag<'a>(
+  text: &'a str,
+  mut from: usize,
+  name: &str,
+  attr: &str,
+  value: Option<&str>,
+) -> Option<Tag<'a>> {
+  let open = format!("<{name}");
+  loop {
+    let start = from + text[from..].find(&open)?;
+    let body_start = start + open.len();
+    let end = body_start + text[body_start..].find('>')?;
+    from = body_start;
+
+    // Make sure that it's not just the start of a longer name.
+    let body = &text[body_start..end];
+    if !body.is_empty() && !body.starts_with(char::is_whitespace) {
+      continue;
+    }
+
+    let Some(attrs) = parse_attrs(body) else {
+      continue;
+    };
+
+    let found = attrs
+      .into_iter()
+      .find(|(key, val, _)| *key == attr && value.is_none_or(|value| value == *val));
+    if let Some((_, value, quote)) = found {
+      return Some(Tag {
+        range: start..end + 1,
+        value,
+        quote,
+      });
+    }
+  }
+}
+
+/// Parse the attributes of a start tag into (name, value, quote) tuples.
+fn parse_attrs(mut body: &str) -> Option<Vec<(&str, &str, char)>> {
+  let mut attrs = Vec::new();
+  loop {
+    body = body.trim_start();
+    if body.is_empty() || body == "/" {
+      return Some(attrs);
+    }
+
+    let (key, rest) = body.split_once('=')?;
+    let rest = rest.trim_start();
+    let quote = rest.chars().next().filter(|ch| *ch == '"' || *ch == '\'')?;
+    let (val, rest) = rest[1..].split_once(quote)?;
+    attrs.push((key.trim_end(), val, quote));
+    body = rest;
+  }
+}
+
+/// How a record's JSON is stored in the XML.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct RecordStyle {
+  // Wrapped in a CDATA section (the range excludes the wrapper).
+  cdata: bool,
+
+  // XML entities are used for special characters.
+  escaped: bool,
+}
+
+impl RecordStyle {
+  /// The five standard XML entities. `&amp;` is last so that it's unescaped last.
+  const ENTITIES: [(&'static str, &'static str); 5] = [
+    ("&lt;", "<"),
+    ("&gt;", ">"),
+    ("&quot;", "\""),
+    ("&apos;", "'"),
+    ("&amp;", "&"),
+  ];
+
+  fn detect(raw: &str) -> Self {
+    let trimmed = raw.trim();
+    if trimmed.starts_with(CDATA_START) && trimmed.ends_with(CDATA_END) {
+      return Self {
+        cdata: true,
+        escaped: false,
+      };
+    }
+
+    let escaped = Self::ENTITIES
+      .iter()
+      .any(|(entity, _)| raw.contains(entity));
+    Self {
+      cdata: false,
+      escaped,
+    }
+  }
+
+  /// Get the JSON text from the raw record text.
+  fn decode(self, raw: &str) -> Cow<'_, str> {
+    if !self.escaped {
+      return Cow::Borrowed(raw);
+    }
+
+    let mut text = raw.to_owned();
+    for (entity, ch) in Self::ENTITIES {
+      text = text.replace(entity, ch);
+    }
+    Cow::Owned(text)
+  }
+
+  /// Convert JSON text to the raw record text.
+  fn encode(self, json: String) -> String {
+    if self.cdata && json.contains(CDATA_END) {
+      // Keep the CDATA section from ending early; `>` can be escaped in JSON strings.
+      return json.replace(CDATA_END, "]]\\u003e");
+    }
+
+    if self.escaped {
+      // Only the characters that can't appear as is. The quotes are JSON syntax.
+      return json
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    }
+
+    json
+  }
+}
+
+fn get_json_range(text: &str, collection: &str, id: &str) -> Option<(Range<usize>, RecordStyle)> {
+  // The game uses the last record if there's more than one.
+  let Range { start, end } = find_records(text, collection, id).pop()?;
+  let raw = &text[start..end];
+  let style = RecordStyle::detect(raw);
+  if style.cdata {
+    let inner_start = raw.find(CDATA_START)? + CDATA_START.len();
+    let inner_end = raw.rfind(CDATA_END)?;
+    return Some((start + inner_start..start + inner_end, style));
+  }
+  Some((start..end, style))
+}
+
+/// Find the contents of all the records with the ID, in file order.
+fn find_records(text: &str, collection: &str, id: &str) -> Vec<Range<usize>> {
+  let mut records = Vec::new();
+
+  // A collection can appear more than once, so look in each of them.
+  let mut from = 0;
+  while let Some(tag) = find_tag(text, from, COLLECTION, NAME, Some(collection)) {
+    let start = tag.range.end;
+    let Some(len) = text[start..].find(collection_end()) else {
+      break;
+    };
+
+    // Find the record tags within the collection.
+    let end = start + len;
+    let text = &text[..end];
+    let mut pos = start;
+    while let Some(tag) = find_tag(text, pos, RECORD, ID, Some(id)) {
+      let start = tag.range.end;
+
+      // Find the record end tag.
+      let Some(len) = text[start..].find(record_end()) else {
+        break;
+      };
+      records.push(start..start + len);
+      pos = start + len;
+    }
+
+    from = end;
+  }
+
+  records
+}
+
+/// Get the collection names and IDs of records that appear more than once.
+fn duplicate_records(text: &str) -> Vec<(&str, &str)> {
+  let mut counts = BTreeMap::new();
+  let mut from = 0;
+  while let Some(tag) = find_tag(text, from, COLLECTION, NAME, None) {
+    let start = tag.range.end;
+    let end = text[start..]
+      .find(collection_end())
+      .map_or(text.len(), |len| start + len);
+
+    let mut pos = start;
+    while let Some(record) = find_tag(&text[..end], pos, RECORD, ID, None) {
+      *counts.entry((tag.value, record.value)).or_insert(0) += 1;
+      pos = record.range.end;
+    }
+    from = end;
+  }
+
+  counts
+    .into_iter()
+    .filter_map(|(key, count)| (count > 1).then_some(key))
+    .collect()
+}
+
+/// Get the names of collections that appear more than once.
+fn duplicate_collections(text: &str) -> Vec<&str> {
+  let mut counts = BTreeMap::new();
+  let mut from = 0;
+  while let Some(tag) = find_tag(text, from, COLLECTION, NAME, None) {
+    *counts.entry(tag.value).or_insert(0) += 1;
+    from = tag.range.end;
+  }
+
+  counts
+    .into_iter()
+    .filter_map(|(name, count)| (count > 1).then_some(name))
+    .collect()
+}
+
+fn get_json(text: &str, collection: &'static str, id: &str) -> Result<Value, Error> {
+  let Some((range, style)) = get_json_range(text, collection, id) else {
+    return Err(missing_record(text, collection, id));
+  };
+
+  // Records are always objects, so parse them as maps to report anything else as invalid.
+  let json = style.decode(&text[range.clone()]);
+  match serde_json::from_str::<serde_json::Map<String, Value>>(&json) {
+    Ok(map) => Ok(Value::Object(map)),
+    Err(err) => {
+      let err = InvalidJson::new(collection, id, &json, range.start, style, &err);
+      Err(Error::InvalidJson(Box::new(err)))
+    }
+  }
+}
+
+// Get the error for a record that can't be found.
+fn missing_record(text: &str, collection: &'static str, id: &str) -> Error {
+  if find_tag(text, 0, COLLECTION, NAME, Some(collection)).is_none() {
+    return Error::MissingCollection(collection);
+  }
+
+  Error::MissingRecord {
+    collection,
+    id: id.into(),
+  }
+}
+
+fn set_json(text: &str, collection: &'static str, id: &str, val: &Value) -> Result<String, Error> {
+  if let Some((range, style)) = get_json_range(text, collection, id) {
+    // Convert the value to JSON text, in the same style as the original record.
+    let json = style.encode(val.to_string());
+
+    // Concatenate the XML with the new JSON.
+    let parts = [&text[..range.start], &json, &text[range.end..]];
+    let mut result = String::with_capacity(parts[0].len() + parts[1].len() + parts[2].len());
+    result.push_str(parts[0]);
+    result.push_str(parts[1]);
+    result.push_str(parts[2]);
+    return Ok(result);
+  }
+
+  Err(missing_record(text, collection, id))
+}
+
+/// Add a new record, and the collection if necessary.
+fn add_json(text: &str, collection: &'static str, id: &str, val: &Value) -> Result<String, Error> {
+  // Use the same quotes as the existing records.
+  let quote = find_tag(text, 0, RECORD, ID, None).map_or('"', |tag| tag.quote);
+  let record = format!("{}{val}{}", record_tag(id, quote), record_end());
+  if let Some(tag) = find_tag(text, 0, COLLECTION, NAME, Some(collection)) {
+    // Insert the record at the start of the collection.
+    let pos = tag.range.end;
+    return Ok(format!("{}{record}{}", &text[..pos], &text[pos..]));
+  }
+
+  let tag = collection_tag(collection, quote);
+  if let Some(pos) = text.rfind(collection_end()) {
+    // Insert the collection after the last one.
+    let pos = pos + collection_end().len();
+    let end = collection_end();
+    return Ok(format!(
+      "{}{tag}{record}{end}{}",
+      &text[..pos],
+      &text[pos..]
+    ));
+  }
+
+  let err = format!("Unable to add '{collection}' collection");
+  Err(Error::other(err))
+}
+
+/// Remove all the records with the ID.
+fn remove_json(text: &str, collection: &str, id: &str) -> String {
+  let mut text = text.to_owned();
+
+  // Remove from the end, so that the earlier ranges stay valid.
+  for Range { start, end } in find_records(&text, collection, id).into_iter().rev() {
+    let start = text[..start].rfind(&format!("<{RECORD}")).unwrap_or(start);
+    text.replace_range(start..end + record_end().len(), "");
+  }
+  text
+}
+
+// Check for the save-game structure. The User collection is only looked for if the text is
+// complete.
+fn sniff_text(text: &str, complete: bool) -> Result<(), NotASave> {
+  if !text.trim_start().starts_with('<') {
+    return Err(NotASave::Unrecognized("not XML"));
+  }
+
+  let Some(tag) = find_tag(text, 0, COLLECTION, NAME, None) else {
+    return Err(NotASave::Unrecognized("no collections"));
+  };
+
+  if find_tag(text, tag.range.end, RECORD, ID, None).is_none() {
+    return Err(NotASave::Unrecognized("no records"));
+  }
+
+  if complete && find_tag(text, 0, COLLECTION, NAME, Some(USER)).is_none() {
+    return Err(NotASave::Unrecognized("no User collection"));
+  }
+
+  Ok(())
+}
+
+// Make sure that a CharacterSheet has what's needed.
+fn check_character(character: Value) -> Result<Value, Error> {
+  // Make sure adventurer experience is there.
+  if character.get(AE).and_then(json_num::to_i64).is_none() {
+    return Err(Error::MissingField(AE));
+  }
+
+  // Make sure producer experience is there.
+  if character.get(PE).and_then(json_num::to_i64).is_none() {
+    return Err(Error::MissingField(PE));
+  }
+
+  if !character.get(SK2).is_some_and(|val| val.is_object()) {
+    return Err(Error::MissingField(SK2));
+  }
+
+  Ok(character)
+}
+
+// Find where a record goes wrong, for reporting. This is the position of the JSON error if there
+// is one, otherwise the start of the record.
+fn json_error_offset(text: &str, collection: &str, id: &str) -> Option<usize> {
+  let (range, style) = get_json_range(text, collection, id)?;
+  let json = style.decode(&text[range.clone()]);
+  match serde_json::from_str::<Value>(&json) {
+    Err(err) => Some(InvalidJson::new(collection, id, &json, range.start, style, &err).offset),
+    Ok(_) => Some(range.start),
+  }
+}
+
+// Get the byte position of a one-based line and column.
+fn line_col_pos(text: &str, line: usize, column: usize) -> usize {
+  let start: usize = text
+    .split_inclusive('\n')
+    .take(line.saturating_sub(1))
+    .map(str::len)
+    .sum();
+  (start + column.saturating_sub(1)).min(text.len())
+}
+
+// Get up to `len` bytes of text around a position, on character boundaries.
+fn text_around(text: &str, pos: usize, len: usize) -> &str {
+  let mut start = pos.saturating_sub(len / 2);
+  while !text.is_char_boundary(start) {
+    start -= 1;
+  }
+
+  let mut end = (start + len).min(text.len());
+  while !text.is_char_boundary(end) {
+    end -= 1;
+  }
+  &text[start..end]
+}
+
+fn find_date(val: &Value) -> Option<Value> {
+  if let Value::Object(obj) = val {
+    for (_, val) in obj {
+      if let Some(val) = val.get(T) {
+        return Some(val.clone());
+      }
+    }
+  }
+
+  None
+}
+
+/// Append JSON Patch operations that turn `old` into `new`.
+fn diff_json(path: &str, old: &Value, new: &Value, ops: &mut Vec<Value>) {
+  match (old, new) {
+    (Value::Object(old), Value::Object(new)) => {
+      for (key, old_val) in old {
+        let path = format!("{path}/{}", escape_pointer(key));
+        match new.get(key) {
+          Some(new_val) => diff_json(&path, old_val, new_val, ops),
+          None => ops.push(serde_json::json!({ "op": "remove", "path": path })),
+        }
+      }
+
+      for (key, new_val) in new {
+        if !old.contains_key(key) {
+          let path = format!("{path}/{}", escape_pointer(key));
+          ops.push(serde_json::json!({ "op": "add", "path": path, "value": new_val }));
+        }
+      }
+    }
+    _ if old != new => {
+      ops.push(serde_json::json!({ "op": "replace", "path": path, "value": new }));
+    }
+    _ => (),
+  }
+}
+
+/// Apply a single JSON Patch operation to `target` at the path `segments`.
+fn apply_patch_op(
+  target: &mut Value,
+  segments: &[String],
+  op: &Value,
+) -> Result<(), Cow<'static, str>> {
+  let invalid = || Cow::from(format!("Invalid patch operation: {op}"));
+  let Some((last, parents)) = segments.split_last() else {
+    return Err(invalid());
+  };
+
+  let mut parent = target;
+  for segment in parents {
+    parent = match parent {
+      Value::Object(obj) => obj.get_mut(segment),
+      Value::Array(arr) => segment
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| arr.get_mut(index)),
+      _ => None,
+    }
+    .ok_or_else(invalid)?;
+  }
+
+  let value = op.get("value").cloned();
+  match (op.get("op").and_then(|op| op.as_str()), parent) {
+    (Some("add"), Value::Object(obj)) => {
+      obj.insert(last.clone(), value.ok_or_else(invalid)?);
+    }
+    (Some("replace"), Value::Object(obj)) if obj.contains_key(last) => {
+      obj.insert(last.clone(), value.ok_or_else(invalid)?);
+    }
+    (Some("remove"), Value::Object(obj)) => {
+      obj.shift_remove(last).ok_or_else(invalid)?;
+    }
+    (Some("test"), Value::Object(obj)) => {
+      if obj.get(last) != value.as_ref() {
+        return Err(Cow::from(format!("Patch test failed: {op}")));
+      }
+    }
+    (Some(kind), Value::Array(arr)) => {
+      let index = if last == "-" {
+        Some(arr.len())
+      } else {
+        last.parse().ok()
+      };
+      let index = index.ok_or_else(invalid)?;
+      match kind {
+        "add" if index <= arr.len() => arr.insert(index, value.ok_or_else(invalid)?),
+        "replace" if index < arr.len() => arr[index] = value.ok_or_else(invalid)?,
+        "remove" if index < arr.len() => {
+          arr.remove(index);
+        }
+        "test" if arr.get(index) == value.as_ref() => (),
+        _ => return Err(invalid()),
+      }
+    }
+    _ => return Err(invalid()),
+  }
+  Ok(())
+}
+
+/// Escape a JSON Pointer segment.
+fn escape_pointer(segment: &str) -> String {
+  segment.replace('~', "~0").replace('/', "~1")
+}
+
+/// Split a JSON Pointer into unescaped segments.
+fn parse_pointer(path: &str) -> Result<Vec<String>, Cow<'static, str>> {
+  let Some(path) = path.strip_prefix('/') else {
+    return Err(Cow::from(format!("Invalid patch path: {path}")));
+  };
+  Ok(
+    path
+      .split('/')
+      .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+      .collect(),
+  )
+}
+
+fn int_value(val: Option<i64>) -> ChangeValue {
+  val.map_or(ChangeValue::None, ChangeValue::Int)
+}
+
+/// The current date/time as a save date (milliseconds since the Unix epoch).
+fn current_date() -> Value {
+  serde_json::json!({ DATE: Utc::now().timestamp_millis() })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::save_builder::SaveBuilder;
+  use std::fs;
+
+  fn save() -> SaveBuilder {
+    SaveBuilder::new()
+      .avatar("Barugon")
+      .adv_exp(4641)
+      .prd_exp(1000)
+      .gold(1234)
+      .skill(3, 1440)
+      .item("Items/Crafting/IronOre", 50)
+      .durable_item("Items/Weapons/Longsword", 40.0, 50.0)
+  }
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cota_test_{name}.sota"))
+  }
+
+  fn load_text(name: &str, text: &str) -> GameData {
+    let path = temp_path(name);
+    fs::write(&path, text).unwrap();
+    GameData::load(path).unwrap()
+  }
+
+  #[test]
+  fn test_bank_gold() {
+    // Saves without bank gold are stored unchanged.
+    let text = save().build();
+    let mut game = load_text("bank_none", &text);
+    assert_eq!(game.get_gold(), Some(1234));
+    assert_eq!(game.get_bank_gold(), None);
+    assert_eq!(game.total_gold(), 1234);
+    game.store().unwrap();
+    assert_eq!(fs::read_to_string(game.get_file_path()).unwrap(), text);
+
+    // Setting bank gold creates the record.
+    assert!(!game.set_gold(1234).unwrap());
+    assert!(game.set_bank_gold(5000).unwrap());
+    assert!(!game.set_bank_gold(5000).unwrap());
+    game.store().unwrap();
+    let game = GameData::load(game.get_file_path()).unwrap();
+    assert_eq!(game.get_gold(), Some(1234));
+    assert_eq!(game.get_bank_gold(), Some(5000));
+    assert_eq!(game.total_gold(), 6234);
+
+    // Existing bank records are updated in place.
+    let text = save().bank_gold(5000).build();
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.store_to_string().unwrap(), text);
+    assert!(game.set_bank_gold(6000).unwrap());
+    let game = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    assert_eq!(game.get_bank_gold(), Some(6000));
+  }
+
+  #[test]
+  fn test_large_gold() {
+    let text = save()
+      .build()
+      .replace(r#"{"g":1234}"#, r#"{"g":"3,000,000,000"}"#);
+    let mut game = GameData::from_text(text).unwrap();
+    assert_eq!(game.get_gold(), Some(3_000_000_000));
+    assert_eq!(game.total_gold(), 3_000_000_000);
+
+    // Gold can't be negative or more than the cap.
+    let err = game.set_gold(-1).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!("Gold -1 is not within 0-{}", util::MAX_GOLD)
+    );
+    assert!(game.set_gold(util::MAX_GOLD + 1).is_err());
+    assert!(game.set_bank_gold(-5).is_err());
+    assert!(!game.set_gold(3_000_000_000).unwrap());
+    assert!(!game.is_modified());
+
+    let limits = Limits {
+      gold: 10_000_000_000,
+      ..game.limits().clone()
+    };
+    game.set_limits(limits);
+    assert!(game.set_gold(5_000_000_000).unwrap());
+    let game = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    assert_eq!(game.get_gold(), Some(5_000_000_000));
+  }
+
+  #[test]
+  fn test_store_copy_as() {
+    let text = save().build();
+    let game = load_text("store_copy_as", &text);
+    let dir = std::env::temp_dir().join("cota_test_store_copy_as");
+    let _ = fs::remove_dir_all(&dir);
+
+    // Plain store_as does not create folders.
+    let path = dir.join("backups").join("2024").join("SaveGame.sota");
+    assert!(game.store_as(&path).is_err());
+
+    game.store_copy_as(&path).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    assert_eq!(game.get_file_path(), temp_path("store_copy_as"));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_backups() {
+    let dir = std::env::temp_dir().join("cota_test_backups");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let text = save().build();
+    let target = dir.join("SavedGame.sota");
+    fs::write(&target, "current").unwrap();
+    fs::write(dir.join("SavedGame.sota.bak"), &text).unwrap();
+    fs::write(dir.join("SavedGame.bak"), "corrupted").unwrap();
+    fs::write(dir.join("Other.bak"), &text).unwrap();
+    fs::write(dir.join("notes.txt"), "").unwrap();
+    fs::write(dir.join("My.Save.sota"), &text).unwrap();
+    fs::write(dir.join("My.Save.sota.bak-1000"), &text).unwrap();
+    fs::write(dir.join("My.Save.sota.bak-soon"), &text).unwrap();
+
+    let saves = scan_save_dir(&dir, &DateRange::default());
+    assert_eq!(saves.len(), 3);
+    assert_eq!(
+      saves[&target],
+      [dir.join("SavedGame.bak"), dir.join("SavedGame.sota.bak")]
+    );
+    assert_eq!(saves[&dir.join("Other.sota")], [dir.join("Other.bak")]);
+    assert_eq!(
+      saves[&dir.join("My.Save.sota")],
+      [dir.join("My.Save.sota.bak-1000")]
+    );
+
+    // Backups are dated by the timestamp in their names, the rest by modification time.
+    let today = Utc::now().date_naive();
+    let dates = DateRange::new(today.and_hms_opt(0, 0, 0), None);
+    let recent = scan_save_dir(&dir, &dates);
+    assert_eq!(recent.len(), 3);
+    assert!(recent[&dir.join("My.Save.sota")].is_empty());
+    let dates = DateRange::new(None, today.pred_opt().unwrap().and_hms_opt(0, 0, 0));
+    let old = scan_save_dir(&dir, &dates);
+    assert_eq!(old.len(), 1);
+    assert_eq!(
+      old[&dir.join("My.Save.sota")],
+      [dir.join("My.Save.sota.bak-1000")]
+    );
+
+    // Backups load like save-games.
+    let game = GameData::load(&saves[&target][1]).unwrap();
+    assert_eq!(game.avatar_name(), "Barugon");
+
+    // A corrupted backup is reported and not restored.
+    assert!(restore_backup(&saves[&target][0], &target).is_err());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "current");
+
+    restore_backup(&saves[&target][1], &target).unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), text);
+    let safety = dir.join("SavedGame.sota.cota.bak");
+    assert_eq!(fs::read_to_string(&safety).unwrap(), "current");
+    assert!(scan_save_dir(&dir, &DateRange::default())[&target].contains(&safety));
+
+    // UTF-16 backups are restored as they are.
+    let utf16 = Encoding::Utf16Le.encode(&text, true);
+    fs::write(&saves[&target][1], &utf16).unwrap();
+    restore_backup(&saves[&target][1], &target).unwrap();
+    assert_eq!(fs::read(&target).unwrap(), utf16);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_tag_styles() {
+    // Single quotes, extra whitespace and other attributes.
+    let text = save()
+      .build()
+      .replace(r#"<collection name=""#, "<collection  name = '")
+      .replace(r#"<record Id=""#, "<record\n Id='")
+      .replace(r#"">"#, "'>")
+      .replace("<record\n Id='0000", "<record kind='user' Id='0000");
+    assert!(!text.contains(r#"">"#));
+
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.avatar_name(), "Barugon");
+    assert_eq!(game.get_gold(), Some(1234));
+    assert_eq!(game.store_to_string().unwrap(), text);
+
+    // Only the JSON changes and added records use the same quotes.
+    assert!(game.set_gold(99).unwrap());
+    assert!(game.set_bank_gold(5).unwrap());
+    let stored = game.store_to_string().unwrap();
+    let text = text.replace(r#"{"g":1234}"#, r#"{"g":99}"#);
+    let bank = format!(
+      "<collection name='UserBank'><record Id='{USER_ID}'>{{\"g\":5}}</record></collection>"
+    );
+    assert_eq!(stored, text.replace("</Data>", &format!("{bank}</Data>")));
+  }
+
+  #[test]
+  fn test_split_collection() {
+    // The backpack is in a second ItemStore collection.
+    let backpack = SaveBuilder::backpack_id();
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/Flax", 5)])
+      .build();
+    let start = text.find(&format!(r#"<record Id="{backpack}">"#)).unwrap();
+    let end = start + text[start..].find("</record>").unwrap() + "</record>".len();
+    let record = &text[start..end];
+    let text = text.replacen(record, "", 1).replacen(
+      "</Data>",
+      &format!(r#"<collection name="ItemStore">{record}</collection></Data>"#),
+      1,
+    );
+
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.get_inventory_items().len(), 3);
+
+    // The record is changed where it was found.
+    let mut items = game.get_inventory_items();
+    *items[0].count_mut() = 60;
+    assert!(game.set_inventory_items(&items).unwrap());
+    let stored = game.store_to_string().unwrap();
+    assert_eq!(stored.len(), text.len());
+    assert!(stored.find(r#""qn":60"#).unwrap() > text.rfind("<collection").unwrap());
+
+    let issues = game.validate(&SkillRegistry::new());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].location, "ItemStore");
+  }
+
+  #[test]
+  fn test_record_styles() {
+    let text = save()
+      .item("Items/Crafting/Salt & Pepper", 5)
+      .gold(100)
+      .build();
+
+    // Records wrapped in CDATA sections.
+    let cdata = text
+      .replace(r#"">{"#, r#""><![CDATA[{"#)
+      .replace("}</record>", "}]]></record>");
+    let mut game = GameData::from_text(cdata.clone()).unwrap();
+    assert_eq!(game.avatar_name(), "Barugon");
+    assert_eq!(game.get_gold(), Some(100));
+    assert_eq!(game.store_to_string().unwrap(), cdata);
+    assert!(game.set_gold(200).unwrap());
+    let stored = game.store_to_string().unwrap();
+    assert_eq!(stored, cdata.replace(r#""g":100"#, r#""g":200"#));
+
+    // CDATA sections can't be ended early by the JSON.
+    let (range, style) = get_json_range(&cdata, USER_GOLD, USER_ID).unwrap();
+    assert_eq!(style.encode(r#"{"x":"]]>"}"#.into()), r#"{"x":"]]\u003e"}"#);
+    assert!(cdata[range].starts_with(r#"{"g":"#));
+
+    // Records with XML entities.
+    let escaped = text.replace('&', "&amp;");
+    let mut game = GameData::from_text(escaped.clone()).unwrap();
+    let mut items = game.get_inventory_items();
+    let item = items
+      .iter_mut()
+      .find(|item| item.name().contains('&'))
+      .unwrap();
+    assert!(item.name().contains("Salt & Pepper"));
+    assert_eq!(game.store_to_string().unwrap(), escaped);
+    *item.count_mut() = 6;
+    assert!(game.set_inventory_items(&items).unwrap());
+    let stored = game.store_to_string().unwrap();
+    assert!(stored.contains("Salt &amp; Pepper"));
+    assert!(!stored.contains("Salt & Pepper"));
+    let game = GameData::from_text(stored).unwrap();
+    let mut items = game.get_inventory_items();
+    let item = items
+      .iter_mut()
+      .find(|item| item.name().contains('&'))
+      .unwrap();
+    assert_eq!(*item.count_mut(), 6);
+  }
+
+  #[test]
+  fn test_format_version() {
+    let text = save().build();
+    let game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.format_version(), None);
+
+    let versioned =
+      |version: u32| text.replace("<Data>", &format!(r#"<Data version="{version}">"#));
+    let game = GameData::from_text(versioned(1)).unwrap();
+    assert_eq!(game.format_version(), Some(1));
+
+    assert_eq!(game.version_warning(), None);
+
+    // Other versions still load, with a warning.
+    let newer = *FORMAT_VERSIONS.end() + 1;
+    let game = GameData::from_text(versioned(newer)).unwrap();
+    let warning = game.version_warning().unwrap();
+    assert_eq!(warning, UnsupportedVersion { found: newer });
+    let max = FORMAT_VERSIONS.end();
+    assert_eq!(
+      warning.to_string(),
+      format!("This save-game is from a newer game version ({newer}) than cota supports ({max})")
+    );
+    let game = GameData::from_text(versioned(0)).unwrap();
+    let warning = game.version_warning().unwrap().to_string();
+    assert!(warning.contains("older game version (0)"));
+
+    let (game, issues) = GameData::parse(versioned(newer), true, None).unwrap();
+    assert_eq!(game.format_version(), Some(newer));
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, None);
+    assert!(issues[0].message.contains("newer game version"));
+  }
+
+  #[test]
+  fn test_sniff() {
+    let dir = std::env::temp_dir().join("cota_test_sniff");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("file.sota");
+    assert!(matches!(
+      GameData::sniff(&path),
+      Err(NotASave::Unreadable(_))
+    ));
+
+    let not_saves: [(&[u8], &str); 4] = [
+      (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "not XML"),
+      (b"<html><body>Hello</body></html>", "no collections"),
+      (
+        br#"<Data><collection name="User"></collection></Data>"#,
+        "no records",
+      ),
+      (
+        br#"<Data><collection name="Other"><record Id="1">{}</record></collection></Data>"#,
+        "no User collection",
+      ),
+    ];
+    for (data, reason) in not_saves {
+      fs::write(&path, data).unwrap();
+      assert_eq!(GameData::sniff(&path), Err(NotASave::Unrecognized(reason)));
+      let err = GameData::load(&path).err().unwrap();
+      assert!(matches!(err, Error::NotASave(NotASave::Unrecognized(_))));
+      assert_eq!(err.to_string(), format!("Not a SotA save-game: {reason}"));
+    }
+
+    // A save-game that can't be parsed isn't reported as something else.
+    let text = save().build();
+    fs::write(
+      &path,
+      text.replace(r#"name="UserGold""#, r#"name="Missing""#),
+    )
+    .unwrap();
+    assert_eq!(GameData::sniff(&path), Ok(()));
+    let err = GameData::load(&path).err().unwrap();
+    assert!(matches!(err, Error::Load(_)));
+    assert!(!err.to_string().starts_with("Not a SotA save-game"));
+
+    // Only the start of big files is read.
+    let user = r#"<collection name="User">"#;
+    let other = r#"<collection name="Other"><record Id="1">{}</record></collection>"#;
+    let padding = "<!-- padding -->".repeat(SNIFF_LEN);
+    let big = text.replacen(user, &format!("{other}{padding}{user}"), 1);
+    fs::write(&path, &big).unwrap();
+    assert_eq!(GameData::sniff(&path), Ok(()));
+
+    let utf16 = Encoding::Utf16Le.encode(&text, true);
+    fs::write(&path, utf16).unwrap();
+    assert_eq!(GameData::sniff(&path), Ok(()));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_load_lenient() {
+    let dir = std::env::temp_dir().join("cota_test_load_lenient");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lenient.sota");
+    let text = save().gold(100).item("Items/Crafting/Flax", 5).build();
+    let (game, issues) = GameData::load_lenient(&path);
+    assert!(game.is_none());
+    assert_eq!(issues[0].section, None);
+
+    // Missing CharacterSheet.
+    let data = text.replace(r#"name="CharacterSheet""#, r#"name="Missing""#);
+    fs::write(&path, &data).unwrap();
+    assert!(GameData::load(&path).is_err());
+    let (game, issues) = GameData::load_lenient(&path);
+    let mut game = game.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, Some(Section::CharacterSheet));
+    assert_eq!(issues[0].offset, None);
+    assert!(game.available(Section::CharacterSheet).is_err());
+    assert!(game.set_adv_lvl(10).is_err());
+    assert!(game.set_gold(200).unwrap());
+    game.store().unwrap();
+    let stored = fs::read_to_string(&path).unwrap();
+    assert_eq!(stored, data.replace(r#""g":100"#, r#""g":200"#));
+
+    // Invalid ItemStore JSON.
+    let (range, _) = get_json_range(&text, ITEM_STORE, &SaveBuilder::backpack_id()).unwrap();
+    let offset = range.start + 1;
+    let data = format!("{}x{}", &text[..offset], &text[offset..]);
+    fs::write(&path, &data).unwrap();
+    assert!(GameData::load(&path).is_err());
+    let (game, issues) = GameData::load_lenient(&path);
+    let mut game = game.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, Some(Section::ItemStore));
+    assert_eq!(issues[0].offset, Some(offset));
+    assert!(issues[0]
+      .to_string()
+      .ends_with(&format!("(at byte {offset})")));
+    assert!(game.get_inventory_items().is_empty());
+    assert_eq!(
+      game.available(Section::ItemStore).unwrap_err().to_string(),
+      "ItemStore is not available"
+    );
+    assert!(game.set_adv_lvl(10).unwrap());
+    game.store().unwrap();
+    let stored = fs::read_to_string(&path).unwrap();
+    assert!(stored.contains(&data[range.start..range.end + 1]));
+
+    // Invalid UserGold JSON.
+    let data = text.replace(r#"{"g":100}"#, r#"{"g":}"#);
+    fs::write(&path, &data).unwrap();
+    assert!(GameData::load(&path).is_err());
+    let (game, issues) = GameData::load_lenient(&path);
+    let mut game = game.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, Some(Section::UserGold));
+    assert_eq!(game.get_gold(), None);
+    assert!(game.set_gold(200).is_err());
+    assert!(game.pending_patch().as_array().unwrap().is_empty());
+    assert!(game.set_adv_lvl(10).unwrap());
+    game.store().unwrap();
+    let (game, _) = GameData::load_lenient(&path);
+    assert_eq!(game.unwrap().get_adv_lvl(), 10);
+    assert!(fs::read_to_string(&path).unwrap().contains(r#"{"g":}"#));
+
+    // Missing UserGold, which is added when the gold is set.
+    let data = text.replace(r#"name="UserGold""#, r#"name="Missing""#);
+    fs::write(&path, &data).unwrap();
+    assert!(GameData::load(&path).is_err());
+    let (game, issues) = GameData::load_lenient(&path);
+    let mut game = game.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, Some(Section::UserGold));
+    assert!(game.available(Section::UserGold).is_ok());
+    assert_eq!(game.get_gold(), None);
+    assert_eq!(game.store_to_string().unwrap(), data);
+    assert!(game.set_gold(200).unwrap());
+    game.store().unwrap();
+    let game = GameData::load(&path).unwrap();
+    assert_eq!(game.get_gold(), Some(200));
+    assert!(fs::read_to_string(&path)
+      .unwrap()
+      .contains(r#"name="Missing""#));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let text = save()
+      .build()
+      .replace(r#""qn":50"#, r#""qn":x50"#)
+      .replace(r#""g":1234"#, r#""g":"#)
+      .replace(r#""sk2":{"#, r#""sk2":[{"#);
+    let err = GameData::from_text(text.clone()).err().unwrap();
+    let Error::Load(LoadError { issues }) = &err else {
+      panic!("{err}");
+    };
+    assert_eq!(issues.len(), 3);
+
+    let err = err.to_string();
+    let mut lines = err.lines();
+    assert_eq!(lines.next(), Some("The save-game has 3 problems:"));
+    assert!(lines
+      .next()
+      .unwrap()
+      .starts_with("- Invalid JSON in the ItemStore record"));
+    assert!(lines
+      .next()
+      .unwrap()
+      .starts_with("- Invalid JSON in the CharacterSheet record"));
+    assert!(lines
+      .next()
+      .unwrap()
+      .starts_with("- Invalid JSON in the UserGold record"));
+    assert_eq!(lines.next(), None);
+
+    // The same problems don't stop a lenient load.
+    let (game, issues) = GameData::parse(text, true, None).unwrap();
+    assert_eq!(issues.len(), 3);
+    for section in [
+      Section::ItemStore,
+      Section::CharacterSheet,
+      Section::UserGold,
+    ] {
+      assert!(game.available(section).is_err());
+    }
+  }
+
+  #[test]
+  fn test_shared_item_names() {
+    let mut builder = save();
+    for index in 0..10_000 {
+      builder = builder.item(&format!("Items/Ore/Ore{}", index % 50), 1);
+    }
+    let game = GameData::from_text(builder.build()).unwrap();
+    let items = game.get_inventory_items();
+    assert_eq!(items.len(), 10_002);
+
+    // Only one copy of each name is kept.
+    let copied: usize = items.iter().map(|item| item.name.len()).sum();
+    let mut shared = HashMap::new();
+    for item in &items {
+      shared.insert(item.name.as_ptr(), item.name.len());
+    }
+    assert_eq!(shared.len(), 52);
+    assert!(shared.values().sum::<usize>() * 100 < copied);
+
+    assert_eq!(
+      game.clone().get_inventory_items()[5].name(),
+      items[5].name()
+    );
+
+    // The backpack stack and the bag stack of the same item share the name.
+    let text = save()
+      .item("Items/Ore/Ore7", 1)
+      .bag("Items/Bags/Pouch", &[("Items/Ore/Ore7", 3)])
+      .build();
+    let game = GameData::from_text(text).unwrap();
+    let (items, _) = game.get_inventory_tree();
+    let ores: Vec<&Item> = items.iter().filter(|item| item.name() == "Ore7").collect();
+    assert_eq!(ores.len(), 2);
+    assert_eq!(ores[0].parent_id(), None);
+    assert!(ores[1].parent_id().is_some());
+    assert!(Arc::ptr_eq(&ores[0].name, &ores[1].name));
+  }
+
+  #[test]
+  fn test_file_size_limit() {
+    let path = temp_path("size_limit");
+    let text = save().build();
+    fs::write(&path, &text).unwrap();
+    let size = text.len() as u64;
+    assert!(check_file_size(&path, size).is_ok());
+    assert!(GameData::sniff_limited(&path, size).is_ok());
+
+    let err = check_file_size(&path, size - 1).err().unwrap();
+    assert_eq!(err.size, size);
+    assert!(err
+      .to_string()
+      .ends_with(&format!("({size} bytes, the limit is {})", size - 1)));
+    assert!(matches!(
+      GameData::sniff_limited(&path, 10),
+      Err(NotASave::TooLarge { limit: 10, .. })
+    ));
+
+    // Large files can still be scanned.
+    let records = scan_records(&path, &[ITEM_STORE, USER_GOLD]).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].collection, ITEM_STORE);
+    assert_eq!(records[0].id, SaveBuilder::backpack_id());
+    assert_eq!(records[1].json, r#"{"g":1234}"#);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_scan_records() {
+    // Read a few bytes at a time, so that tags and records are split across reads.
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+      fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.0.len().min(buf.len()).min(5);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+      }
+    }
+
+    let text = concat!(
+      "\u{feff}<?xml version=\"1.0\"?><Data>\n",
+      "<collection name=\"UserGold\"><record Id=\"1\">{\"g\":5}</record></collection>\n",
+      "<collection name='Other'><record Id='2'>{\"t\":\"</a>\"}</record></collection>\n",
+      "<collection name=\"UserGold\"><record Id=\"3\"> <![CDATA[{\"g\":\"<6>\"}]]> </record>\n",
+      "</collection></Data>",
+    );
+    let records = scan_records_from(Trickle(text.as_bytes()), &[USER_GOLD], 1000).unwrap();
+    let found: Vec<(&str, &str)> = records
+      .iter()
+      .map(|record| (record.id.as_str(), record.json.as_str()))
+      .collect();
+    assert_eq!(found, [("1", r#"{"g":5}"#), ("3", r#"{"g":"<6>"}"#)]);
+
+    // Unfinished records are limited.
+    let err = scan_records_from(Trickle(text.as_bytes()), &[USER_GOLD], 20).err();
+    assert!(err.unwrap().starts_with("A record is larger"));
+
+    let data = Encoding::Utf16Le.encode(text, true);
+    assert!(scan_records_from(data.as_slice(), &[USER_GOLD], 1000).is_err());
+  }
+
+  #[test]
+  fn test_read_only() {
+    let path = temp_path("read_only");
+    fs::write(&path, save().build()).unwrap();
+    let game = GameData::load(&path).unwrap();
+    assert!(game.is_writable());
+    assert_eq!(game.read_only_reason(), None);
+
+    let set_read_only = |read_only| {
+      let mut permissions = fs::metadata(&path).unwrap().permissions();
+      permissions.set_readonly(read_only);
+      fs::set_permissions(&path, permissions).unwrap();
+    };
+
+    set_read_only(true);
+    let mut game = GameData::load(&path).unwrap();
+    assert!(!game.is_writable());
+    assert_eq!(game.read_only_reason().unwrap(), "the file is read-only");
+
+    // Storing fails without touching anything.
+    game.set_gold(1).unwrap();
+    let err = game.store().err().unwrap();
+    assert!(matches!(err, Error::ReadOnly(_)));
+    assert!(err
+      .to_string()
+      .ends_with("can't be written: the file is read-only"));
+    assert!(!suffixed(&path, JOURNAL_SUFFIX).exists());
+    assert_eq!(GameData::load(&path).unwrap().get_gold(), Some(1234));
+
+    // Storing elsewhere works.
+    let copy = temp_path("read_only_copy");
+    game.store_as(&copy).unwrap();
+    assert!(game.is_writable());
+
+    set_read_only(false);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&copy).unwrap();
+  }
+
+  #[test]
+  fn test_store_backup() {
+    let dir = std::env::temp_dir().join("cota_test_store_backup");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("SavedGame.sota");
+    fs::write(&path, save().build()).unwrap();
+    fs::write(dir.join("SavedGame.sota.bak-1000"), "oldest").unwrap();
+    fs::write(dir.join("SavedGame.sota.bak"), "other").unwrap();
+
+    // Storing a new file doesn't back anything up.
+    let mut game = GameData::load(&path).unwrap();
+    game.set_backup(true, 2);
+    let copy = dir.join("Copy.sota");
+    game.store_copy_as(&copy).unwrap();
+    assert!(timestamped_backups(&copy).is_empty());
+
+    // Each store backs up the previous version and only the newest two are kept.
+    for gold in 1..=3 {
+      game.set_gold(gold).unwrap();
+      game.store().unwrap();
+    }
+    let backups = timestamped_backups(&path);
+    assert_eq!(backups.len(), 2);
+    assert_eq!(GameData::load(&backups[0]).unwrap().get_gold(), Some(2));
+    assert_eq!(GameData::load(&backups[1]).unwrap().get_gold(), Some(1));
+    assert!(dir.join("SavedGame.sota.bak").exists());
+
+    // Zero keeps them all.
+    game.set_backup(true, 0);
+    game.set_gold(4).unwrap();
+    game.store().unwrap();
+    assert_eq!(timestamped_backups(&path).len(), 3);
+
+    game.set_backup(false, 0);
+    game.set_gold(5).unwrap();
+    game.store().unwrap();
+    assert_eq!(timestamped_backups(&path).len(), 3);
+
+    // A backup that can't be made stops the store.
+    let set_read_only = |read_only| {
+      let mut permissions = fs::metadata(&dir).unwrap().permissions();
+      permissions.set_readonly(read_only);
+      fs::set_permissions(&dir, permissions).unwrap();
+    };
+
+    set_read_only(true);
+    game.set_backup(true, 2);
+    game.set_gold(6).unwrap();
+    let result = game.store();
+
+    // Permissions don't stop some users (e.g. root) from writing.
+    if fs::write(dir.join("probe"), "").is_err() {
+      let err = result.err().unwrap();
+      assert!(matches!(&err, Error::Backup { path: backup, .. } if backup == &path));
+      assert!(err.to_string().starts_with("Unable to back up"));
+      assert_eq!(GameData::load(&path).unwrap().get_gold(), Some(5));
+    }
+
+    set_read_only(false);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_store_failure() {
+    let dir = std::env::temp_dir().join("cota_test_store_failure");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("SavedGame.sota");
+    let original = save().build();
+    fs::write(&path, &original).unwrap();
+    let mut game = GameData::load(&path).unwrap();
+    game.set_gold(1).unwrap();
+
+    // The original is untouched and nothing is left behind, whichever step fails.
+    let copy = dir.join("Copy.sota");
+    for step in ["write", "replace"] {
+      FAIL_STEP.with(|fail| fail.set(Some(step)));
+      let err = game.store().err().unwrap();
+      assert!(matches!(
+        err,
+        Error::Io {
+          op: FileOp::Store,
+          ..
+        }
+      ));
+      assert!(err.to_string().starts_with("Unable to store"));
+      assert!(err
+        .to_string()
+        .ends_with(&format!("the {step} step was failed")));
+      assert_eq!(fs::read_to_string(&path).unwrap(), original);
+      assert!(game.is_modified());
+
+      // The path only changes if the store works.
+      assert!(game.store_as(&copy).is_err());
+      assert_eq!(game.get_file_path(), path);
+      assert!(!copy.exists());
+
+      let files = fs::read_dir(&dir).unwrap().count();
+      assert_eq!(files, 1);
+    }
+
+    FAIL_STEP.with(|fail| fail.set(None));
+    game.store_as(&copy).unwrap();
+    assert_eq!(game.get_file_path(), copy);
+    assert_eq!(GameData::load(&copy).unwrap().get_gold(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_recover_interrupted() {
+    let dir = std::env::temp_dir().join("cota_test_recover");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let old = save().gold(1).build();
+    let new = save().gold(2).build();
+    let target = dir.join("SavedGame.sota");
+    let journal = suffixed(&target, JOURNAL_SUFFIX);
+    let temp = suffixed(&target, TEMP_SUFFIX);
+    let backup = suffixed(&target, OLD_SUFFIX);
+
+    // Set up the files as they'd be if a store was interrupted.
+    let interrupt = |files: &[(&Path, &str)]| {
+      for path in [&target, &temp, &backup] {
+        let _ = fs::remove_file(path);
+      }
+      for (path, text) in files {
+        fs::write(path, text).unwrap();
+      }
+      let journal_text = serde_json::json!({
+        "target": target,
+        "temp": temp,
+        "backup": backup,
+        "hash": hash_bytes(new.as_bytes()),
+      });
+      fs::write(&journal, journal_text.to_string()).unwrap();
+    };
+    let gold = || GameData::load(&target).unwrap().get_gold();
+    let clean = || !journal.exists() && !temp.exists() && !backup.exists();
+
+    // The new file was partly written.
+    interrupt(&[(&target, &old), (&backup, &old), (&temp, &new[..100])]);
+    let recovered = recover_interrupted(&dir).unwrap();
+    assert_eq!(recovered, [Recovery::RolledBack(target.clone())]);
+    assert!(clean());
+    assert_eq!(gold(), Some(1));
+
+    // The new file was written but didn't replace the old one.
+    interrupt(&[(&target, &old), (&backup, &old), (&temp, &new)]);
+    let recovered = recover_interrupted(&dir).unwrap();
+    assert_eq!(recovered, [Recovery::RolledForward(target.clone())]);
+    assert!(clean());
+    assert_eq!(gold(), Some(2));
+
+    // Only the clean up is left.
+    interrupt(&[(&target, &new), (&backup, &old)]);
+    assert_eq!(gold(), Some(2));
+    assert!(clean());
+
+    // The target is gone and the new file is incomplete, so the copy is put back.
+    interrupt(&[(&backup, &old), (&temp, &new[..100])]);
+    assert_eq!(gold(), Some(1));
+    assert!(clean());
+
+    // The journal itself is incomplete.
+    interrupt(&[(&target, &old), (&temp, &new)]);
+    fs::write(&journal, r#"{"target":"#).unwrap();
+    let recovered = recover_interrupted(&dir).unwrap();
+    assert_eq!(recovered, [Recovery::RolledBack(target.clone())]);
+    assert!(clean());
+    assert_eq!(gold(), Some(1));
+
+    // A finished store doesn't leave anything behind.
+    let mut game = GameData::load(&target).unwrap();
+    game.set_gold(3).unwrap();
+    game.store().unwrap();
+    assert!(clean());
+    assert_eq!(gold(), Some(3));
+    assert!(recover_interrupted(&dir).unwrap().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_error_variants() {
+    let err = GameData::load(temp_path("missing")).err().unwrap();
+    let not_found = |err: &io::Error| err.kind() == io::ErrorKind::NotFound;
+    assert!(matches!(&err, Error::Io { op: FileOp::Load, err, .. } if not_found(err)));
+
+    // Records and collections that are needed to find the avatar.
+    let text = save().build();
+    let avatar = SaveBuilder::avatar_id();
+    let missing = text.replace(r#"name="CharacterName""#, r#"name="Other""#);
+    let err = GameData::from_text(missing).err().unwrap();
+    assert!(matches!(err, Error::MissingCollection(CHARACTER_NAME)));
+
+    let missing = text.replace(r#""mainbp""#, r#""bp""#);
+    let err = get_backpack_id(&missing, &avatar).err().unwrap();
+    assert!(matches!(err, Error::MissingField(MAINBP)));
+    assert_eq!(
+      err.to_string(),
+      "Unable to read the avatar's backpack ('mainbp')"
+    );
+
+    let err = get_json(&text, CHARACTER_SHEET, "other").err().unwrap();
+    assert!(
+      matches!(&err, Error::MissingRecord { collection: CHARACTER_SHEET, id } if id == "other")
+    );
+    assert!(set_json(&text, USER_BANK, USER_ID, &Value::Null).is_err());
+    assert!(get_json(&text, CHARACTER_SHEET, &avatar).is_ok());
+
+    // Records that aren't objects are invalid.
+    let text = text.replace(r#"{"g":1234}"#, "1234");
+    let err = get_json(&text, USER_GOLD, USER_ID).err().unwrap();
+    assert!(matches!(err, Error::InvalidJson(_)));
+    assert!(Cow::from(err).starts_with("Invalid JSON in the UserGold record"));
+  }
+
+  #[test]
+  fn test_invalid_json() {
+    let text = save().build().replace(r#""qn":50"#, r#""qn":x50"#);
+    let backpack = SaveBuilder::backpack_id();
+    let (range, _) = get_json_range(&text, ITEM_STORE, &backpack).unwrap();
+    let pos = text.find("x50").unwrap();
+    let column = pos - range.start + 1;
+    let err = GameData::from_text(text.clone()).err().unwrap().to_string();
+    assert!(err.starts_with(&format!(
+      "Invalid JSON in the ItemStore record '{backpack}' at line 1, column {column}: expected"
+    )));
+
+    let context = text_around(&text, pos, InvalidJson::CONTEXT_LEN);
+    assert!(context.contains(r#""qn":x50"#));
+    assert_eq!(context.len(), InvalidJson::CONTEXT_LEN);
+    assert!(err.ends_with(&format!("near `{context}` (at byte {pos})")));
+    assert_eq!(json_error_offset(&text, ITEM_STORE, &backpack), Some(pos));
+
+    // Lines are counted, and context is cut on character boundaries.
+    assert_eq!(line_col_pos("ab\ncd\nef", 3, 2), 7);
+    assert_eq!(line_col_pos("ab", 5, 5), 2);
+    assert_eq!(text_around("\u{e9}\u{e9}x\u{e9}\u{e9}", 4, 4), "\u{e9}x");
+  }
+
+  #[test]
+  fn test_durability_validation() {
+    let text = save().durable_item("Items/Armor/Helm", 10.0, 80.0).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let original = game.store_to_string().unwrap();
+    let items = game.get_inventory_items();
+    let (sword, helm) = (1, 2);
+
+    let cases = [
+      (f64::NAN, 50.0, "not a number"),
+      (10.0, f64::INFINITY, "not a number"),
+      (-1.0, 50.0, "negative"),
+      (10.0, -50.0, "negative"),
+      (60.0, 50.0, "minor 60 is more than major 50"),
+    ];
+    for (minor, major, reason) in cases {
+      let dur = Durability { minor, major };
+      assert_eq!(dur.validate(DurabilityPolicy::Reject).unwrap_err(), reason);
+
+      // Nothing is changed, and the item is named.
+      let mut items = items.clone();
+      *items[sword].durability_mut().unwrap() = dur;
+      let err = game.set_inventory_items(&items).unwrap_err();
+      assert_eq!(
+        err.to_string(),
+        format!("Invalid durability for {} ({reason})", items[sword].id())
+      );
+    }
+    assert_eq!(game.store_to_string().unwrap(), original);
+
+    // Too high minor durability can be clamped instead.
+    let mut items = items.clone();
+    items[sword].durability_mut().unwrap().minor = 60.0;
+    items[helm].durability_mut().unwrap().minor = 90.0;
+    assert!(game
+      .set_inventory_items_with(&items, DurabilityPolicy::Clamp)
+      .unwrap());
+    let text = game.store_to_string().unwrap();
+    assert!(text.contains(r#""an":"Items/Weapons/Longsword","hp":50.0,"php":50.0"#));
+    assert!(text.contains(r#""an":"Items/Armor/Helm","hp":80.0,"php":80.0"#));
+
+    // Percentages of invalid durability are refused.
+    let text = save().build().replace(r#""php":50.0"#, r#""php":-50.0"#);
+    let mut game = GameData::from_text(text).unwrap();
+    let items = game.get_inventory_items();
+    let id = items[sword].id();
+    let err = game.set_durability_percent(&[id], 50.0).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!("Invalid durability for {id} (negative)")
+    );
+  }
+
+  #[test]
+  fn test_duplicate_records() {
+    let backpack = SaveBuilder::backpack_id();
+    let text = save().build();
+    let tag = format!(r#"<record Id="{backpack}">"#);
+    let start = text.find(&tag).unwrap();
+    let end = start + text[start..].find("</record>").unwrap() + "</record>".len();
+    let stale = text[start..end].replace(r#""qn":50"#, r#""qn":7"#);
+    let text = format!("{}{stale}{}", &text[..start], &text[start..]);
+
+    // The last record is used and reported.
+    let (mut game, issues) = GameData::parse(text.clone(), false, None).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].section, Some(Section::ItemStore));
+    assert_eq!(issues[0].offset, Some(start + stale.len() + tag.len()));
+    let mut items = game.get_inventory_items();
+    assert_eq!(*items[0].count_mut(), 50);
+
+    // The same record is changed.
+    *items[0].count_mut() = 60;
+    assert!(game.set_inventory_items(&items).unwrap());
+    let stored = game.store_to_string().unwrap();
+    assert!(stored.contains(r#""qn":7"#));
+    assert!(stored.contains(r#""qn":60"#));
+    assert!(stored.find(r#""qn":7"#) < stored.find(r#""qn":60"#));
+
+    let issues = game.validate(&SkillRegistry::new());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].location, format!("ItemStore/{backpack}"));
+  }
+
+  #[test]
+  fn test_load_encoding() {
+    let dir = std::env::temp_dir().join("cota_test_load_encoding");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let text = save().build();
+
+    // A byte order mark is skipped and kept when storing.
+    let path = dir.join("bom.sota");
+    let data = [UTF8_BOM, text.as_bytes()].concat();
+    fs::write(&path, &data).unwrap();
+    let game = GameData::load(&path).unwrap();
+    assert_eq!(game.avatar_name(), "Barugon");
+    assert_eq!(game.content_hash(), hash_file(&path).unwrap());
+    assert_eq!(game.lossy_warning(), None);
+    let options = StoreOptions {
+      force: true,
+      ..Default::default()
+    };
+    game.store_as_with(&path, options).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), data);
+
+    // Invalid UTF-8 in an unrelated record.
+    let path = dir.join("lossy.sota");
+    let mut data = text.replace(r#"{"dc""#, r#"{"x":"@","dc""#).into_bytes();
+    let pos = data.iter().position(|ch| *ch == b'@').unwrap();
+    data[pos] = 0xFF;
+    fs::write(&path, &data).unwrap();
+    let mut game = GameData::load(&path).unwrap();
+    assert_eq!(game.avatar_name(), "Barugon");
+    assert!(game.lossy_warning().is_some());
+    assert_eq!(game.validate(&SkillRegistry::new()).len(), 1);
+
+    // Storing over the original needs to be acknowledged.
+    assert!(game.set_gold(5).unwrap());
+    assert!(game.store().is_err());
+    assert_eq!(fs::read(&path).unwrap(), data);
+    game.store_as(dir.join("copy.sota")).unwrap();
+    game.acknowledge_lossy();
+    game.store_as(&path).unwrap();
+    assert!(fs::read_to_string(&path).unwrap().contains('\u{FFFD}'));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_utf16() {
+    let dir = std::env::temp_dir().join("cota_test_utf16");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let text = save().avatar("Bärugon").build();
+
+    for (encoding, bom) in [
+      (Encoding::Utf16Le, true),
+      (Encoding::Utf16Be, true),
+      (Encoding::Utf16Le, false),
+      (Encoding::Utf16Be, false),
+    ] {
+      let path = dir.join("utf16.sota");
+      let data = encoding.encode(&text, bom);
+      fs::write(&path, &data).unwrap();
+
+      let mut game = GameData::load(&path).unwrap();
+      assert_eq!(game.encoding(), encoding);
+      assert_eq!(game.avatar_name(), "Bärugon");
+      assert_eq!(game.lossy_warning(), None);
+
+      // Unmodified saves are stored byte for byte.
+      game.store().unwrap();
+      assert_eq!(fs::read(&path).unwrap(), data);
+
+      // Changes are stored in the same encoding.
+      assert!(game.set_gold(99).unwrap());
+      game.store().unwrap();
+      let game = GameData::load(&path).unwrap();
+      assert_eq!(game.encoding(), encoding);
+      assert_eq!(game.get_gold(), Some(99));
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_set_lvl_changed() {
+    let mut game = GameData::from_text(save().build()).unwrap();
+    assert_eq!(game.get_adv_lvl(), 5);
+    assert!(!game.set_adv_lvl(5).unwrap());
+    assert!(game.set_adv_lvl(6).unwrap());
+    assert_eq!(game.get_adv_lvl(), 6);
+
+    let mut items = game.get_inventory_items();
+    assert!(!game.set_inventory_items(&items).unwrap());
+    *items[0].count_mut() = 60;
+    assert!(game.set_inventory_items(&items).unwrap());
+  }
+
+  #[test]
+  fn test_raised_limits() {
+    let dir = std::env::temp_dir().join("cota_test_raised_limits");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let table: Vec<i64> = (0..250).map(|val| val * 1000).collect();
+    let json = serde_json::to_string(&table).unwrap();
+    fs::write(dir.join("level_exp.json"), &json).unwrap();
+    fs::write(dir.join("skill_exp.json"), &json).unwrap();
+
+    // The default limits reject levels past 200.
+    let mut game = GameData::from_text(save().build()).unwrap();
+    let err = game.set_adv_lvl(250).unwrap_err();
+    assert_eq!(err.to_string(), "Adventurer level 250 is not within 1-200");
+
+    let tables = ExpTables::load_or_default(&dir).unwrap();
+    game.set_limits(Limits::load_or_default(&dir, &tables).unwrap());
+    game.set_exp_tables(tables);
+    assert_eq!(game.limits().level, 1..=250);
+    assert!(game.set_adv_lvl(250).unwrap());
+    assert!(game.set_prd_lvl(250).unwrap());
+    assert_eq!((game.get_adv_lvl(), game.get_prd_lvl()), (250, 250));
+    assert!(game.set_adv_lvl(251).is_err());
+
+    // Skills are clamped to the raised cap.
+    let registry = SkillRegistry::new();
+    let report = game
+      .adjust_all_skills(&registry, 300, 0..=i32::MAX)
+      .unwrap();
+    assert!(report.unknown.is_empty());
+    let (_, _, info) = registry.find_id(3).unwrap();
+    assert_eq!(game.skill_level(info).level(), Some(250));
+
+    // Reloading keeps the limits.
+    let path = dir.join("SavedGame.sota");
+    game.store_as(&path).unwrap();
+    game.reload().unwrap();
+    assert_eq!(game.get_adv_lvl(), 250);
+    assert_eq!(game.limits().level, 1..=250);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_exp_debt() {
+    let mut game = GameData::from_text(save().adv_exp(-5000).build()).unwrap();
+    assert_eq!(game.get_adv_lvl(), 1);
+    assert_eq!(game.adv_exp_debt(), Some(-5000));
+
+    let issues = game.validate(&SkillRegistry::new());
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].location.ends_with("/ae"));
+
+    assert!(game.clear_exp_debt());
+    assert!(!game.clear_exp_debt());
+    assert_eq!(game.adv_exp_debt(), None);
+    assert_eq!(game.get_adv_lvl(), 1);
+    assert_eq!(game.validate(&SkillRegistry::new()), []);
+
+    let changes = game.pending_changes();
+    assert_eq!(
+      changes[0].to_string(),
+      "[Other] Adventurer Experience: -5,000 -> 0"
+    );
+  }
+
+  #[test]
+  fn test_exp() {
+    let text = save().adv_exp(1000).prd_exp(2000).build();
+    let mut game = GameData::from_text(text).unwrap();
+    assert_eq!(game.get_adv_exp(), Some(1000));
+    assert_eq!(game.get_prd_exp(), Some(2000));
+    assert_eq!(game.get_skill_exp(3), Some(1440));
+    assert_eq!(game.get_skill_exp(5), None);
+
+    // Experience must be within the range for the level caps.
+    let max = *game.exp_range().end();
+    assert_eq!(util::level_for_exp(max, game.tables.level()), 200);
+    assert!(game.set_adv_exp(-1).is_err());
+    let err = game.set_prd_exp(max + 1).unwrap_err();
+    assert!(matches!(&err, Error::ExpOutOfRange(err) if err.name == "Producer"));
+    assert_eq!(
+      err.to_string(),
+      format!("Producer experience {} is not within 0-{max}", max + 1)
+    );
+    assert!(!game.set_adv_exp(1000).unwrap());
+    assert!(game.set_adv_exp(123_456).unwrap());
+    assert!(game.set_prd_exp(max).unwrap());
+    assert_eq!(game.get_adv_exp(), Some(123_456));
+    assert_eq!(game.get_prd_lvl(), 200);
+
+    // Levels are set through the same experience.
+    assert!(game.set_adv_lvl(1).unwrap());
+    assert_eq!(game.get_adv_exp(), Some(0));
+
+    // Skill experience includes the multiplier, so Chain Lightning's cap is doubled.
+    let table = game.tables.skill();
+    let max = util::exp_for_level(200, table).unwrap();
+    assert!(game.set_skill_exp(3, max * 2).unwrap());
+    assert_eq!(
+      game.trained_skills(None, &SkillRegistry::new())[0].level,
+      SkillLevel::Level(200)
+    );
+    assert!(game.set_skill_exp(3, max * 2 + 1).is_err());
+    assert!(game.set_skill_exp(5, 2000).unwrap());
+    assert_eq!(game.get_skill_exp(5), Some(2000));
+    assert!(game.set_skill_exp(99999, 1).is_err());
+
+    let changes: Vec<_> = game
+      .pending_changes()
+      .iter()
+      .map(ToString::to_string)
+      .collect();
+    assert_eq!(
+      changes[0],
+      "[Other] Adventurer Experience: 1,000 -> 123,456"
+    );
+    assert_eq!(changes[2], "[Other] Adventurer Level: 28 -> 1");
+    assert_eq!(
+      changes[4],
+      "[Skill] Air's Embrace Experience: none -> 2,000"
+    );
+  }
+
+  #[test]
+  fn test_pathological_saves() {
+    // Quoted values compare by number.
+    let text = save().bank_gold(5).quoted_numbers().build();
+    let mut game = GameData::from_text(text).unwrap();
+    assert_eq!(game.get_gold(), Some(1234));
+    assert_eq!(game.get_prd_lvl(), 2);
+    assert!(!game.set_prd_lvl(2).unwrap());
+    assert!(!game.set_gold(1234).unwrap());
+
+    // Quoted values stay quoted when changed.
+    assert!(game.set_adv_lvl(10).unwrap());
+    assert!(game.set_gold(500).unwrap());
+    assert!(game.set_bank_gold(600).unwrap());
+    let report = game
+      .adjust_all_skills(&SkillRegistry::new(), 1, 0..=200)
+      .unwrap();
+    assert!(!report.skills.is_empty());
+    let text = game.store_to_string().unwrap();
+    let game = GameData::from_text(text.clone()).unwrap();
+    let ae = game.tables.level()[9];
+    assert!(text.contains(&format!(r#""ae":"{ae}""#)));
+    assert!(text.contains(r#""g":"500""#));
+    assert!(text.contains(r#""g":"600""#));
+    let sk2 = game.character[SK2].as_object().unwrap();
+    assert!(sk2.values().all(|skill| skill[X].is_string()));
+
+    // The backpack is required.
+    let text = save().without_backpack().build();
+    assert!(GameData::from_text(text).is_err());
+
+    // Bag contents live in their own ItemStore record.
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/Bone", 3)])
+      .build();
+    let game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.get_inventory_items().len(), 3);
+    assert_eq!(game.store_to_string().unwrap(), text);
+  }
 
-fn collection_tag(collection: &str) -> String {
-  format!(r#"<collection name="{collection}">"#)
-}
+  #[test]
+  fn test_quoted_item_numbers() {
+    let text = save().build();
+    let quoted = text
+      .replace(r#""hp":40.0,"php":50.0"#, r#""hp":"40.0","php":" 50 ""#)
+      .replace(r#""qn":50"#, r#""qn":"50""#);
+    assert_ne!(quoted, text);
 
-fn record_tag(id: &str) -> String {
-  format!(r#"<record Id="{id}">"#)
-}
+    let describe = |game: &GameData| -> Vec<_> {
+      game
+        .get_inventory_items()
+        .into_iter()
+        .map(|mut item| {
+          let dur = item.durability_mut().map(|dur| (dur.minor, dur.major));
+          (item.id().to_owned(), *item.count_mut(), dur)
+        })
+        .collect()
+    };
+    let game = GameData::from_text(text).unwrap();
+    let mut quoted_game = GameData::from_text(quoted.clone()).unwrap();
+    assert_eq!(describe(&quoted_game), describe(&game));
 
-const fn record_end() -> &'static str {
-  "</record>"
-}
+    // Unchanged values are left alone, and changed ones stay quoted.
+    let mut items = quoted_game.get_inventory_items();
+    assert!(!quoted_game.set_inventory_items(&items).unwrap());
+    assert_eq!(quoted_game.store_to_string().unwrap(), quoted);
+    for item in &mut items {
+      *item.count_mut() += 1;
+      if let Some(dur) = item.durability_mut() {
+        dur.minor = 45.0;
+      }
+    }
+    assert!(quoted_game.set_inventory_items(&items).unwrap());
+    let stored = quoted_game.store_to_string().unwrap();
+    assert!(stored.contains(r#""hp":"45.0","php":" 50 ""#));
+    assert!(stored.contains(r#""qn":"51""#));
+  }
 
-fn get_json_range(text: &str, collection: &str, id: &str) -> Option<Range<usize>> {
-  // Find the collection tag.
-  let find = collection_tag(collection);
-  let start = text.find(&find)? + find.len();
-  let text = &text[start..];
+  #[test]
+  fn test_number_text() {
+    let text = save().build().replacen(
+      r#"{"an":"Items/Crafting/IronOre","#,
+      r#"{"an":"Items/Crafting/IronOre","big":123456789012345678901234567890,"precise":0.30000000000000000000001,"#,
+      1,
+    );
+    let text = text.replacen(r#""hp":40.0,"php":50.0"#, r#""hp":40,"php":50"#, 1);
 
-  // From that point, find the record tag.
-  let find = record_tag(id);
-  let pos = text.find(&find)? + find.len();
-  let text = &text[pos..];
-  let start = start + pos;
+    // Untouched numbers are stored exactly as they were.
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    let mut items = game.get_inventory_items();
+    let longsword = items
+      .iter()
+      .position(|item| item.name() == "Longsword")
+      .unwrap();
+    let dur = items[longsword].durability_mut().unwrap();
+    assert_eq!((dur.minor, dur.major), (40.0, 50.0));
+    assert!(!game.set_inventory_items(&items).unwrap());
+    assert_eq!(game.store_to_string().unwrap(), text);
 
-  // Find the record end tag.
-  let pos = text.find(record_end())?;
-  let end = start + pos;
+    let ore = items
+      .iter()
+      .position(|item| item.name() == "IronOre")
+      .unwrap();
+    *items[ore].count_mut() = 60;
+    assert!(game.set_inventory_items(&items).unwrap());
+    let stored = game.store_to_string().unwrap();
+    assert!(stored.contains(
+      r#""big":123456789012345678901234567890,"precise":0.30000000000000000000001,"qn":60"#
+    ));
+    assert!(stored.contains(r#""hp":40,"php":50"#));
 
-  Some(start..end)
-}
+    // Integers written as floats.
+    let val: Value =
+      serde_json::from_str("[1440, 1440.0, 1.5, 1e3, 123456789012345678901234567890]").unwrap();
+    let vals: Vec<Option<i64>> = val
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(json_num::to_i64)
+      .collect();
+    assert_eq!(vals, [Some(1440), Some(1440), None, Some(1000), None]);
+  }
 
-fn get_json(text: &str, collection: &str, id: &str) -> Result<Value, Cow<'static, str>> {
-  if let Some(range) = get_json_range(text, collection, id) {
-    let text = &text[range];
-    match serde_json::from_str::<Value>(text) {
-      Ok(val) if val.is_object() => return Ok(val),
-      Err(err) => return Err(Cow::from(err.to_string())),
-      _ => (),
+  #[test]
+  fn test_key_order() {
+    let text = save().build();
+    let avatar = SaveBuilder::avatar_id();
+    let (range, _) = get_json_range(&text, CHARACTER_SHEET, &avatar).unwrap();
+    let date = r#"{"$date":1600000000000}"#;
+    let sheet = format!(
+      r#"{{"sk2":{{"7":{{"x":1440,"t":{date},"m":0}},"3":{{"x":1440,"m":0,"t":{date}}}}},"pe":1000,"zz":[],"ae":4641}}"#
+    );
+    let text = format!("{}{sheet}{}", &text[..range.start], &text[range.end..]);
+
+    // Unmodified records keep the original key order.
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    assert!(game.set_gold(99).unwrap());
+    let stored = game.store_to_string().unwrap();
+    let (range, _) = get_json_range(&stored, CHARACTER_SHEET, &avatar).unwrap();
+    assert_eq!(&stored[range], sheet);
+
+    // New skills are added at the end and removing one leaves the rest in place.
+    let mut groups = game.get_skills(SkillCategory::Adventurer);
+    for skill in &mut groups[0].skills {
+      skill.level = match skill.info.id {
+        3 => 0,
+        7 => skill.level,
+        _ => 10,
+      };
     }
+    assert!(game.set_skills(&groups).unwrap());
+    let ids: Vec<&String> = game.character[SK2].as_object().unwrap().keys().collect();
+    assert_eq!(ids[0], "7");
+    assert!(!ids.contains(&&String::from("3")));
+    let keys: Vec<&String> = game.character.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["sk2", "pe", "zz", "ae"]);
   }
 
-  let err = format!("Unable to get '{collection}' collection");
-  Err(Cow::from(err))
-}
+  #[test]
+  fn test_no_skills() {
+    let text = SaveBuilder::new().avatar("Barugon").build();
+    let mut game = GameData::from_text(text).unwrap();
+    let mut groups = game.get_skills(SkillCategory::Adventurer);
+    let skill = &mut groups[0].skills[0];
+    assert_eq!(skill.level, 0);
+    skill.level = 10;
+    assert!(game.set_skills(&groups).unwrap());
+
+    // The new skill gets a synthesized date.
+    let id = groups[0].skills[0].info.id.to_string();
+    let skill = &game.character[SK2][id];
+    assert_eq!(skill[X], util::SKILL_EXP[9] * 20);
+    assert!(skill[T][DATE].as_i64().unwrap() > 1600000000000);
+  }
 
-fn set_json(
-  text: &str,
-  collection: &str,
-  id: &str,
-  val: &Value,
-) -> Result<String, Cow<'static, str>> {
-  if let Some(range) = get_json_range(text, collection, id) {
-    // Convert the value to JSON text.
-    let json = val.to_string();
+  #[test]
+  fn test_snapshot() {
+    let mut game = GameData::from_text(save().build()).unwrap();
+    let snapshot = game.snapshot();
+    let text = snapshot.store_to_string().unwrap();
+    let path = temp_path("snapshot");
 
-    // Concatenate the XML with the new JSON.
-    let parts = [&text[..range.start], &json, &text[range.end..]];
-    let mut result = String::with_capacity(parts[0].len() + parts[1].len() + parts[2].len());
-    result.push_str(parts[0]);
-    result.push_str(parts[1]);
-    result.push_str(parts[2]);
-    return Ok(result);
+    // Store the snapshot while the skills are being changed.
+    let thread = {
+      let path = path.clone();
+      std::thread::spawn(move || snapshot.store_as(path))
+    };
+
+    let mut groups = game.get_skills(SkillCategory::Adventurer);
+    for group in &mut groups {
+      for skill in &mut group.skills {
+        skill.level = 100;
+      }
+    }
+    assert!(game.set_skills(&groups).unwrap());
+    assert!(game.set_gold(1).unwrap());
+    thread.join().unwrap().unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    let stored = GameData::load(&path).unwrap();
+    assert_eq!(stored.get_gold(), Some(1234));
+    assert_ne!(game.store_to_string().unwrap(), text);
   }
 
-  let err = format!("Unable to set '{collection}' collection");
-  Err(Cow::from(err))
-}
+  #[test]
+  fn test_compare_with_stats() {
+    let game = GameData::from_text(save().build()).unwrap();
+    let registry = SkillRegistry::new();
+    let mut stats = StatsSnapshot {
+      adv_lvl: Some(5),
+      prd_lvl: Some(3),
+      gold: Some(1234),
+      ..Default::default()
+    };
+    stats.values.insert("ChainLightning".into(), 25.0);
+    stats.values.insert("blink".into(), 10.0);
+    stats.values.insert("AirResistance".into(), 12.5);
 
-fn find_date(val: &Value) -> Result<Value, Cow<'static, str>> {
-  if let Value::Object(obj) = val {
-    for (_, val) in obj {
-      if let Some(val) = val.get(T) {
-        return Ok(val.clone());
+    let diffs = game.compare_with_stats(&stats, &registry);
+    let diff = |name: &str, save, stats| StatDiff {
+      name: name.into(),
+      save,
+      stats,
+    };
+    assert_eq!(
+      diffs,
+      vec![
+        diff("Producer Level", 2, 3),
+        diff("Chain Lightning", 23, 25),
+        diff("Blink", 0, 10),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_count_by_name() {
+    let text = save()
+      .item("Items/Crafting/Iron Ore", 5)
+      .item("Items/Crafting/iron ore", 10)
+      .bag(
+        "Items/Bags/Pouch",
+        &[("Items/Crafting/IronOre", 7), ("Items/Crafting/Bone", 3)],
+      )
+      .build();
+    let game = GameData::from_text(text).unwrap();
+    let search = Search::String {
+      find: "ore".into(),
+      ignore_case: true,
+      anchor: LineAnchor::None,
+    };
+
+    let counts = game.count_by_name(&search, false, ItemNameKind::Raw);
+    assert_eq!(
+      counts,
+      vec![("IronOre".into(), 50), ("Iron Ore".into(), 15)]
+    );
+
+    let counts = game.count_by_name(&search, true, ItemNameKind::Raw);
+    assert_eq!(
+      counts,
+      vec![("IronOre".into(), 57), ("Iron Ore".into(), 15)]
+    );
+
+    // "IronOre" is shown as "Iron Ore".
+    let counts = game.count_by_name(&search, true, ItemNameKind::Display);
+    assert_eq!(counts, vec![("Iron Ore".into(), 72)]);
+  }
+
+  #[test]
+  fn test_item_display_names() {
+    use crate::item_names::parse_item_names;
+
+    let text = save()
+      .item("Items/Crafting/CraftingOreIronOre", 5)
+      .item("Items/Deco/DecoWoodenChair", 1)
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    let names = |game: &GameData| -> Vec<String> {
+      let mut items = game.get_inventory_items();
+      sort_items(&mut items, false, ItemNameKind::Display);
+      items
+        .iter()
+        .map(|item| item.display_name().into())
+        .collect()
+    };
+    assert_eq!(
+      names(&game),
+      ["Iron Ore", "Iron Ore", "Longsword", "Wooden Chair"]
+    );
+
+    // Local names are kept when reloading.
+    let mut item_names = ItemNames::default();
+    item_names.merge(parse_item_names("DecoWoodenChair,Chair (Wooden)").unwrap());
+    game.set_item_names(item_names);
+    assert_eq!(names(&game)[0], "Chair (Wooden)");
+    assert_eq!(names(&game.clone())[0], "Chair (Wooden)");
+  }
+
+  #[test]
+  fn test_set_items_count_by_name() {
+    let text = save()
+      .item("Items/Crafting/CraftingOreIronOre", 5)
+      .bag("Items/Bags/Ore Pouch", &[("Items/Crafting/IronOre", 7)])
+      .durable_item("Items/Weapons/Iron Sword", 50.0, 50.0)
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    let counts = |game: &GameData| -> Vec<u64> {
+      let items = game.get_inventory_items();
+      items.iter().map(|item| item.cnt).collect()
+    };
+    let before = counts(&game);
+
+    // A dry run doesn't change anything.
+    let opts = MatchOpts {
+      dry_run: true,
+      ..Default::default()
+    };
+    let changes = game.set_items_count_by_name("Iron Ore", 100, opts).unwrap();
+    assert_eq!(changes.items.len(), 2);
+    assert_eq!(changes.items[0].1, 50);
+    assert_eq!(changes.items[1].1, 5);
+    assert!(changes.skipped.is_empty());
+    assert_eq!(counts(&game), before);
+    assert!(!game.is_modified());
+
+    // Exact names only match whole names.
+    let opts = MatchOpts::default();
+    let changes = game.set_items_count_by_name("iron ore", 100, opts).unwrap();
+    assert!(changes.items.is_empty());
+
+    // Containers and items with durability are skipped.
+    let opts = MatchOpts {
+      mode: NameMatch::Substring,
+      ignore_case: true,
+      dry_run: false,
+    };
+    let changes = game.set_items_count_by_name("ore", 100, opts).unwrap();
+    assert_eq!(changes.items.len(), 2);
+    assert!(changes.items.iter().all(|item| item.2 == 100));
+    assert_eq!(changes.skipped.len(), 1);
+    assert_eq!(changes.skipped[0].1, SkipReason::Container);
+    let changes = game.set_items_count_by_name("iron", 100, opts).unwrap();
+    assert_eq!(changes.skipped[0].1, SkipReason::NotStackable);
+    assert!(game.is_modified());
+
+    assert!(game.set_items_count_by_name("ore", 0, opts).is_err());
+  }
+
+  #[test]
+  fn test_set_durability_percent() {
+    let text = save().durable_item("Items/Armor/Helm", 10.0, 80.0).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let items = game.get_inventory_items();
+    let ids: Vec<&str> = items.iter().map(|item| item.id()).collect();
+    assert!(game.set_durability_percent(&ids, 100.5).is_err());
+    assert_eq!(game.set_durability_percent(&ids, 50.0).unwrap(), 2);
+    assert_eq!(game.set_durability_percent(&ids, 50.0).unwrap(), 0);
+
+    let text = game.store_to_string().unwrap();
+    assert!(text.contains(r#""an":"Items/Weapons/Longsword","hp":25.0,"php":50.0"#));
+    assert!(text.contains(r#""an":"Items/Armor/Helm","hp":40.0,"php":80.0"#));
+
+    // Items in bags are changed too.
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Armor/Boots", 1)])
+      .build()
+      .replace(
+        r#""an":"Items/Armor/Boots","qn":1"#,
+        r#""an":"Items/Armor/Boots","hp":20.0,"php":60.0,"qn":1"#,
+      );
+    let mut game = GameData::from_text(text).unwrap();
+    let (items, _) = game.get_inventory_tree();
+    let ids: Vec<&str> = items.iter().map(|item| item.id()).collect();
+    assert_eq!(game.set_durability_percent(&ids, 100.0).unwrap(), 2);
+    let changes = game.pending_changes();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[1].to_string(), "[Item] Boots: 20.0 -> 60.0");
+
+    let text = game.store_to_string().unwrap();
+    assert!(text.contains(r#""an":"Items/Weapons/Longsword","hp":50.0,"php":50.0"#));
+    assert!(text.contains(r#""an":"Items/Armor/Boots","hp":60.0,"php":60.0"#));
+  }
+
+  #[test]
+  fn test_sort_items() {
+    let text = save()
+      .item("Items/Potions/Potion of Healing 10", 1)
+      .item("Items/Potions/potion of Healing 2", 1)
+      .build();
+    let game = GameData::from_text(text).unwrap();
+    let names =
+      |items: &[Item]| -> Vec<String> { items.iter().map(|item| item.name().to_owned()).collect() };
+
+    let mut items = game.get_inventory_items();
+    sort_items(&mut items, false, ItemNameKind::Raw);
+    let expected = [
+      "IronOre",
+      "Longsword",
+      "Potion of Healing 10",
+      "potion of Healing 2",
+    ];
+    assert_eq!(names(&items), expected);
+
+    sort_items(&mut items, true, ItemNameKind::Raw);
+    let expected = [
+      "IronOre",
+      "Longsword",
+      "potion of Healing 2",
+      "Potion of Healing 10",
+    ];
+    assert_eq!(names(&items), expected);
+
+    // Sorting by display name puts "Iron Ore" first either way.
+    sort_items(&mut items, true, ItemNameKind::Display);
+    assert_eq!(items[0].display_name(), "Iron Ore");
+    assert_eq!(items[3].display_name(), "Potion of Healing 10");
+  }
+
+  #[test]
+  fn test_duplicate_items() {
+    let text = save()
+      .item("Items/Crafting/IronOre", 5)
+      .durable_item("Items/Weapons/Longsword", 50.0, 50.0)
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/IronOre", 7)])
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    assert_eq!(game.duplicate_items(false).len(), 2);
+
+    let groups = game.duplicate_items(true);
+    assert_eq!(groups.len(), 2);
+    let ore = &groups[0];
+    assert_eq!(ore.asset, "Items/Crafting/IronOre");
+    assert_eq!(ore.instances.len(), 3);
+    assert!(ore.mergeable());
+
+    // Equipment can't be merged.
+    let swords = &groups[1];
+    assert!(!swords.mergeable());
+    assert!(game.merge_stacks(swords).is_err());
+
+    // Nothing changes if a stack is missing, even the first one.
+    for idx in [0, 2] {
+      let mut missing = ore.clone();
+      missing.instances[idx].id = "missing".into();
+      assert!(game.merge_stacks(&missing).is_err());
+      assert!(game.pending_changes().is_empty());
+      assert_eq!(game.duplicate_items(true).len(), 2);
+    }
+
+    // The stack in the bag is merged too.
+    assert_eq!(game.merge_stacks(ore).unwrap(), 2);
+    let search = Search::String {
+      find: "IronOre".into(),
+      ignore_case: false,
+      anchor: LineAnchor::None,
+    };
+    assert_eq!(
+      game.count_by_name(&search, true, ItemNameKind::Raw),
+      vec![("IronOre".into(), 62)]
+    );
+    assert_eq!(game.duplicate_items(true).len(), 1);
+
+    // It's one edit.
+    assert_eq!(game.pending_changes().len(), 2);
+    assert!(game.undo());
+    assert!(game.pending_changes().is_empty());
+    assert_eq!(game.duplicate_items(true).len(), 2);
+
+    // Equipment with the same durability can't be merged either.
+    let text = SaveBuilder::new()
+      .avatar("Barugon")
+      .durable_item("Items/Weapons/Longsword", 50.0, 50.0)
+      .durable_item("Items/Weapons/Longsword", 50.0, 50.0)
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    let groups = game.duplicate_items(false);
+    assert_eq!(groups.len(), 1);
+    assert!(!groups[0].mergeable());
+    assert!(game.merge_stacks(&groups[0]).is_err());
+  }
+
+  #[test]
+  fn test_add_remove_items() {
+    let text = save()
+      .durable_item("Items/Weapons/Axe", 10.0, 20.0)
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/IronOre", 7)])
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    let find = |game: &GameData, id: &str| {
+      let items = game.get_inventory_items();
+      items.into_iter().find(|item| item.id() == id)
+    };
+
+    assert!(game.add_inventory_item("Axe", 1).is_err());
+    assert!(game.add_inventory_item("Items/", 1).is_err());
+    assert!(game
+      .add_inventory_item("Items/Crafting/IronOre", 0)
+      .is_err());
+    assert!(game
+      .add_inventory_item("Items/Crafting/IronOre", 40000)
+      .is_err());
+    let dur = Durability {
+      minor: 5.0,
+      major: 1.0,
+    };
+    let added = game.add_inventory_item_with("Items/Weapons/Axe", 1, Some(dur));
+    assert!(added.is_err());
+    assert!(!game.is_modified());
+
+    // New copies of an item get its full durability.
+    let ore = game
+      .add_inventory_item("Items/Crafting/IronOre", 25)
+      .unwrap();
+    let axe = game.add_inventory_item("Items/Weapons/Axe", 1).unwrap();
+    assert_ne!(ore, axe);
+    assert_eq!(find(&game, &ore).unwrap().cnt, 25);
+    assert_eq!(find(&game, &ore).unwrap().dur, None);
+    let dur = Durability {
+      minor: 20.0,
+      major: 20.0,
+    };
+    assert_eq!(find(&game, &axe).unwrap().dur, Some(dur));
+
+    let path = temp_path("add_remove_items.sota");
+    game.store_as(&path).unwrap();
+    let mut game = GameData::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(find(&game, &axe).unwrap().name(), "Axe");
+
+    // Containers must be empty.
+    let pouch = game
+      .get_inventory_items()
+      .into_iter()
+      .find(|item| item.bag)
+      .unwrap();
+    assert!(game.remove_inventory_item(pouch.id()).is_err());
+    assert!(game.remove_inventory_item("missing").is_err());
+
+    let items = game.get_inventory_items();
+    game.remove_inventory_item(&ore).unwrap();
+    assert!(find(&game, &ore).is_none());
+    assert_eq!(game.get_inventory_items().len(), items.len() - 1);
+
+    // Stale items are skipped.
+    game.set_inventory_items(&items).unwrap();
+    assert!(find(&game, &ore).is_none());
+
+    // Removing can be undone.
+    let changes = game.pending_changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].name, "Iron Ore");
+    game.revert_change(changes[0].id).unwrap();
+    assert_eq!(find(&game, &ore).unwrap().cnt, 25);
+    assert!(game.pending_changes().is_empty());
+
+    // Removing an empty container removes its store too.
+    let text = save().bag("Items/Bags/Box", &[]).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let items = game.get_inventory_items();
+    let id = items.iter().find(|item| item.bag).unwrap().id().to_owned();
+    let bag = game.inventory[IN][&id][IN][BAG]
+      .as_str()
+      .unwrap()
+      .to_owned();
+    assert_eq!(find_records(&game.text, ITEM_STORE, &bag).len(), 1);
+    game.remove_inventory_item(&id).unwrap();
+    let names: Vec<_> = game.pending_changes().into_iter().map(|c| c.name).collect();
+    assert_eq!(names, ["Box", "Box Contents"]);
+    let stored = game.store_to_string().unwrap();
+    assert!(find_records(&stored, ITEM_STORE, &bag).is_empty());
+    let patch = game.pending_patch();
+    let path = format!("/{ITEM_STORE}/{bag}");
+    assert!(patch
+      .as_array()
+      .unwrap()
+      .contains(&serde_json::json!({ "op": "remove", "path": path })));
+    game.undo();
+    assert!(game.pending_changes().is_empty());
+    let stored = game.store_to_string().unwrap();
+    assert_eq!(find_records(&stored, ITEM_STORE, &bag).len(), 1);
+  }
+
+  #[test]
+  fn test_inventory_tree() {
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/Flax", 5)])
+      .bag("Items/Bags/Sack", &[("Items/Crafting/Bone", 3)])
+      .bag("Items/Bags/Box", &[])
+      .build();
+    let mut game = GameData::from_text(text).unwrap();
+    let id_of = |items: &[Item], name: &str| {
+      let item = items.iter().find(|item| item.name() == name).unwrap();
+      item.id().to_owned()
+    };
+    let items = game.get_inventory_items();
+    let (pouch, sack, bx) = (
+      id_of(&items, "Pouch"),
+      id_of(&items, "Sack"),
+      id_of(&items, "Box"),
+    );
+
+    // Put the sack in the pouch, a bag that holds the pouch's contents in the sack, and lose the
+    // box's contents.
+    let inv = Arc::make_mut(&mut game.inventory);
+    let sack_val = inv[IN]
+      .as_object_mut()
+      .unwrap()
+      .shift_remove(&sack)
+      .unwrap();
+    let pouch_store = inv[IN][&pouch][IN][BAG].as_str().unwrap().to_owned();
+    let sack_store = sack_val[IN][BAG].as_str().unwrap().to_owned();
+    inv[IN][&bx][IN][BAG] = "ffffffffffffffffffffffff".into();
+    let mut store = game.item_store(&pouch_store).unwrap().into_owned();
+    store[IN][&sack] = sack_val;
+    Arc::make_mut(&mut game.stores).insert(pouch_store.clone(), store);
+    let mut store = game.item_store(&sack_store).unwrap().into_owned();
+    let looped = serde_json::json!({ IN: { AN: "Items/Bags/Loop", BAG: pouch_store, QN: 1 } });
+    store[IN]["0000000000000000000000ff"] = looped;
+    Arc::make_mut(&mut game.stores).insert(sack_store, store);
+    let mut game = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    assert_eq!(game.get_inventory_items().len(), 4);
+
+    // Bags are followed by their contents.
+    let (items, warnings) = game.get_inventory_tree();
+    let names: Vec<_> = items.iter().map(Item::name).collect();
+    assert_eq!(
+      names,
+      [
+        "IronOre",
+        "Longsword",
+        "Pouch",
+        "Flax",
+        "Sack",
+        "Bone",
+        "Loop",
+        "Box"
+      ]
+    );
+    assert_eq!(items[3].parent_id(), Some(pouch.as_str()));
+    assert_eq!(items[4].parent_id(), Some(pouch.as_str()));
+    assert_eq!(items[5].parent_id(), Some(sack.as_str()));
+    assert_eq!(items[7].parent_id(), None);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].ends_with("already listed"));
+    assert!(warnings[1].contains(&bx));
+
+    // Changes are written to the bag's contents.
+    let mut items = items;
+    *items[5].count_mut() = 10;
+    assert!(game.set_inventory_items(&items).unwrap());
+    assert!(!game.set_inventory_items(&items).unwrap());
+    let bone = |game: &GameData| game.get_inventory_tree().0[5].cnt;
+    assert_eq!(bone(&game), 10);
+    let stored = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    assert_eq!(bone(&stored), 10);
+
+    let changes = game.pending_changes();
+    assert_eq!(changes.len(), 1);
+    game.revert_change(changes[0].id).unwrap();
+    assert_eq!(bone(&game), 3);
+  }
+
+  #[test]
+  fn test_container_file() {
+    let dir = std::env::temp_dir().join("cota_test_container_file");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/Flax", 5)])
+      .build();
+    let game = GameData::from_text(text).unwrap();
+    let items = game.get_inventory_items();
+    let pouch = items.iter().find(|item| item.is_container()).unwrap();
+    let path = dir.join("pouch.json");
+    game.export_container(pouch.id(), &path).unwrap();
+    let err = game.export_container(items[0].id(), &path).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!("Item {} is not a container", items[0].id())
+    );
+
+    // Import the pouch twice, once inside the other, with new IDs each time.
+    let text = SaveBuilder::new()
+      .bag("Items/Bags/Chest", &[("Items/Crafting/Bone", 3)])
+      .build();
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    let outer = game
+      .import_container(&path, &ContainerTarget::Backpack)
+      .unwrap();
+    let target = ContainerTarget::Container(outer.clone());
+    let inner = game.import_container(&path, &target).unwrap();
+    assert_ne!(outer, pouch.id());
+    assert_ne!(outer, inner);
+    assert_eq!(game.pending_changes().len(), 4);
+
+    // The new stores are added by the patch.
+    let mut fresh = GameData::from_text(text).unwrap();
+    fresh.apply_patch(&game.pending_patch()).unwrap();
+    assert_eq!(
+      fresh.store_to_string().unwrap(),
+      game.store_to_string().unwrap()
+    );
+
+    // Nested contents survive another round trip through a file and a stored save-game.
+    let path = dir.join("nested.json");
+    game.export_container(&outer, &path).unwrap();
+    let mut game = GameData::from_text(SaveBuilder::new().build()).unwrap();
+    game
+      .import_container(&path, &ContainerTarget::Backpack)
+      .unwrap();
+    let game = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    let search = Search::String {
+      find: "Flax".into(),
+      ignore_case: false,
+      anchor: LineAnchor::None,
+    };
+    assert_eq!(
+      game.count_by_name(&search, true, ItemNameKind::Raw),
+      [("Flax".into(), 10)]
+    );
+    assert_eq!(game.count_by_name(&search, false, ItemNameKind::Raw), []);
+
+    let mut game = GameData::from_text(SaveBuilder::new().build()).unwrap();
+    let target = ContainerTarget::Container(String::from("missing"));
+    let err = game.import_container(&path, &target).unwrap_err();
+    assert_eq!(err.to_string(), "Item missing is not a container");
+
+    // Other versions and repeated IDs are rejected.
+    let mut file: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    file["version"] = 2.into();
+    fs::write(&path, file.to_string()).unwrap();
+    let err = game.import_container(&path, &ContainerTarget::Backpack);
+    let err = err.unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Container file version 2 is not supported, expected 1"
+    );
+
+    file["version"] = CONTAINER_VERSION.into();
+    let store = file["stores"]
+      .as_object()
+      .unwrap()
+      .keys()
+      .next()
+      .unwrap()
+      .clone();
+    file["id"] = store.clone().into();
+    fs::write(&path, file.to_string()).unwrap();
+    let err = game.import_container(&path, &ContainerTarget::Backpack);
+    assert_eq!(
+      err.unwrap_err().to_string(),
+      format!("Container file has more than one '{store}'")
+    );
+    assert!(game.pending_changes().is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_pending_changes() {
+    let mut game = GameData::from_text(save().build()).unwrap();
+    assert!(game.set_gold(12345).unwrap());
+    assert!(game.set_adv_lvl(6).unwrap());
+
+    let mut groups = game.get_skills(SkillCategory::Adventurer);
+    let skill = groups[0].skills.iter_mut().find(|skill| skill.info.id == 3);
+    skill.unwrap().level = 30;
+    assert!(game.set_skills(&groups).unwrap());
+
+    let mut items = game.get_inventory_items();
+    *items[0].count_mut() = 1050;
+    items[1].durability_mut().unwrap().minor = 45.0;
+    assert!(game.set_inventory_items(&items).unwrap());
+
+    let changes: Vec<String> = game
+      .journal
+      .describe(&Locale::en)
+      .iter()
+      .map(|change| change.to_string())
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        "[Gold] Gold: 1,234 -> 12,345",
+        "[Other] Adventurer Level: 5 -> 6",
+        "[Skill] Chain Lightning: 23 -> 30",
+        "[Item] Iron Ore: 50 -> 1,050",
+        "[Item] Longsword: 40.0 -> 45.0",
+      ]
+    );
+
+    // Store the changes next to the save-game.
+    let dir = std::env::temp_dir().join("cota_test_pending_changes");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("My.Save.sota");
+    let options = StoreOptions {
+      write_changes: true,
+      ..Default::default()
+    };
+    game.store_as_with(&path, options).unwrap();
+    let changes = fs::read_to_string(dir.join("My.Save.sota.changes.txt")).unwrap();
+    assert_eq!(changes.lines().count(), 5);
+
+    // Or next to the backup if one is made.
+    game.set_backup(true, 0);
+    let options = StoreOptions {
+      force: true,
+      ..options
+    };
+    game.store_as_with(&path, options).unwrap();
+    let backup = timestamped_backups(&path).remove(0);
+    let name = backup.file_name().unwrap().to_string_lossy();
+    let changes = fs::read_to_string(dir.join(format!("{name}.changes.txt"))).unwrap();
+    assert_eq!(changes.lines().count(), 5);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_store_unchanged() {
+    let text = save().build();
+    let mut game = load_text("store_unchanged", &text);
+    let path = game.get_file_path();
+    let mtime = || fs::metadata(&path).unwrap().modified().unwrap();
+    let loaded = mtime();
+    assert!(!game.is_modified());
+    assert_eq!(game.store().unwrap(), StoreOutcome::Unchanged);
+    assert_eq!(mtime(), loaded);
+
+    // Setting the same value isn't a change.
+    assert!(!game.set_gold(1234).unwrap());
+    assert_eq!(game.store().unwrap(), StoreOutcome::Unchanged);
+    assert_eq!(mtime(), loaded);
+
+    // Storing elsewhere always writes, and then storing there again doesn't.
+    let copy = temp_path("store_unchanged_copy");
+    let _ = fs::remove_file(&copy);
+    assert_eq!(game.store_as(&copy).unwrap(), StoreOutcome::Written);
+    assert_eq!(fs::read_to_string(&copy).unwrap(), text);
+    assert_eq!(game.store().unwrap(), StoreOutcome::Unchanged);
+
+    // Changes are written once.
+    assert!(game.set_gold(5).unwrap());
+    assert!(game.is_modified());
+    assert_eq!(game.store_as(&path).unwrap(), StoreOutcome::Written);
+    assert!(!game.is_modified());
+    assert_eq!(game.store_as(&path).unwrap(), StoreOutcome::Unchanged);
+
+    // Forced.
+    let options = StoreOptions {
+      force: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      game.store_as_with(&path, options).unwrap(),
+      StoreOutcome::Written
+    );
+  }
+
+  #[test]
+  fn test_trained_skills() {
+    let registry = SkillRegistry::new();
+    let text = save()
+      .skill(5, 1440)
+      .skill(857, 1440)
+      .skill(99999, 1440)
+      .build();
+    let game = GameData::from_text(text).unwrap();
+    let skills = game.trained_skills(None, &registry);
+    assert_eq!(skills.len(), 4);
+
+    // Unknown skills are listed without a name.
+    let unknown = skills.iter().find(|skill| skill.id == 99999).unwrap();
+    assert_eq!((unknown.category, unknown.name), (None, None));
+    let level = util::level_for_exp(1440, game.tables.skill());
+    assert_eq!(unknown.level, SkillLevel::Level(level));
+
+    // Chain Lightning has a multiplier of 2, so 1440 experience is a lower level.
+    let adventurer = game.trained_skills(Some(SkillCategory::Adventurer), &registry);
+    let ids: Vec<_> = adventurer.iter().map(|skill| skill.id).collect();
+    assert_eq!(ids, [3, 5]);
+    let chain = &adventurer[0];
+    assert_eq!(chain.name, Some("Chain Lightning"));
+    assert_eq!(chain.group, Some("Air"));
+    assert_eq!(chain.exp, Some(1440));
+    let level = util::level_for_exp(720, game.tables.skill());
+    assert_eq!(chain.level, SkillLevel::Level(level));
+    assert_eq!(chain.time.unwrap().timestamp_millis(), 1600000000000);
+
+    let producer = game.trained_skills(Some(SkillCategory::Producer), &registry);
+    assert_eq!(producer.len(), 1);
+    assert_eq!(producer[0].category, Some(SkillCategory::Producer));
+  }
+
+  #[test]
+  fn test_set_group_lvl() {
+    let text = save().skill(5, 1440).skill(857, 1440).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let air = skill_info::parse_skill_info_groups(SkillCategory::Adventurer)
+      .into_iter()
+      .find(|group| group.name == "Air")
+      .unwrap();
+    let size = air.skills.len();
+
+    // Each skill gets its own multiplier.
+    let changed = game.set_group_lvl(&air, 100).unwrap();
+    assert_eq!(changed.len(), size);
+    assert!(changed.iter().all(|skill| skill.after == 100));
+    for info in &air.skills {
+      assert_eq!(game.skill_level(info), SkillLevel::Level(100));
+    }
+    assert!(game.set_group_lvl(&air, 100).unwrap().is_empty());
+    assert_eq!(game.pending_changes().len(), size);
+
+    // Out of range changes nothing.
+    assert!(game
+      .set_category_lvl(SkillCategory::Adventurer, 500)
+      .is_err());
+    assert_eq!(game.pending_changes().len(), size);
+
+    // Level 0 removes the skills, but only the trained ones change.
+    let changed = game.set_category_lvl(SkillCategory::Adventurer, 0).unwrap();
+    assert_eq!(changed.len(), size);
+    assert!(changed.iter().all(|skill| skill.before == 100));
+    assert_eq!(game.trained_skill_ids(), ["857"]);
+    let changed = game.set_category_lvl(SkillCategory::Producer, 0).unwrap();
+    assert_eq!(changed.len(), 1);
+    assert!(game.trained_skill_ids().is_empty());
+  }
+
+  #[test]
+  fn test_skill_build() {
+    let text = save().skill(5, 1440).skill(857, 1440).build();
+    let game = GameData::from_text(text).unwrap();
+    let build = game.export_build(SkillCategory::Producer);
+    assert_eq!(build.skills.keys().collect::<Vec<_>>(), [&857]);
+
+    // Levels are the ones that the skill lists show.
+    let mut build = game.export_build(SkillCategory::Adventurer);
+    let shown: BTreeMap<u32, i32> = game
+      .get_skills(SkillCategory::Adventurer)
+      .iter()
+      .flat_map(|group| &group.skills)
+      .filter(|skill| skill.level > 0)
+      .map(|skill| (skill.info.id, skill.level))
+      .collect();
+    assert_eq!(build.skills, shown);
+    assert_eq!(build.skills.len(), 2);
+
+    // Skills at level 0 are removed and other skills are kept.
+    let text = SaveBuilder::new()
+      .skill(5, 100)
+      .skill(7, 1440)
+      .skill(9, 1440)
+      .build();
+    let mut fresh = GameData::from_text(text).unwrap();
+    build.skills.insert(7, 0);
+    assert!(fresh.apply_build(&build).unwrap());
+    assert!(!fresh.apply_build(&build).unwrap());
+    let applied = fresh.export_build(SkillCategory::Adventurer).skills;
+    assert_eq!(applied.keys().collect::<Vec<_>>(), [&3, &5, &9]);
+    assert_eq!(applied[&5], build.skills[&5]);
+
+    // Unknown skills and bad levels change nothing.
+    let mut bad = build.clone();
+    bad.skills.extend([(857, 10), (99999, 1)]);
+    let err = fresh.apply_build(&bad).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown adventurer skill IDs: 857, 99999");
+    let mut bad = build.clone();
+    bad.skills.extend([(9, 10), (3, 500)]);
+    assert!(fresh.apply_build(&bad).is_err());
+    assert_eq!(
+      fresh.export_build(SkillCategory::Adventurer).skills,
+      applied
+    );
+  }
+
+  #[test]
+  fn test_character_template() {
+    let registry = SkillRegistry::new();
+    let game = GameData::from_text(save().skill(4, 100).build()).unwrap();
+    let template = game.export_template(&registry);
+    assert_eq!(template.gold, Some(1234));
+    assert_eq!(template.adv_lvl, Some(game.get_adv_lvl()));
+    assert_eq!(template.plan.as_ref().unwrap().len(), 2);
+
+    // Round trip through JSON.
+    let json = serde_json::to_string(&template).unwrap();
+    let template: CharacterTemplate = serde_json::from_str(&json).unwrap();
+
+    let text = SaveBuilder::new().gold(5).skill(7, 1440).build();
+    let mut fresh = GameData::from_text(text).unwrap();
+    let report = fresh.apply_template(&template, &registry, None).unwrap();
+    assert!(report.unknown.is_empty());
+    assert_eq!(report.changes, 5);
+    assert_eq!(fresh.export_template(&registry).gold, Some(1234));
+    assert_eq!(fresh.get_adv_lvl(), game.get_adv_lvl());
+    assert_eq!(fresh.get_prd_lvl(), game.get_prd_lvl());
+    for id in [3, 4] {
+      let info = registry.find_id(id).unwrap().2;
+      assert_eq!(fresh.skill_level(info), game.skill_level(info));
+    }
+
+    // Skills that aren't planned are kept.
+    assert!(fresh.trained_skill_ids().contains(&String::from("7")));
+
+    // Missing values are skipped, and unknown skills reported.
+    let template: CharacterTemplate =
+      serde_json::from_str(r#"{"prd_lvl":20,"plan":{"3":50,"9999":10}}"#).unwrap();
+    let report = fresh.apply_template(&template, &registry, None).unwrap();
+    assert_eq!(
+      report,
+      ApplyReport {
+        changes: 2,
+        unknown: vec![9999]
+      }
+    );
+    assert_eq!(fresh.get_gold(), Some(1234));
+
+    // A failure changes nothing.
+    let template = CharacterTemplate {
+      gold: Some(1),
+      adv_lvl: Some(500),
+      ..Default::default()
+    };
+    let stored = fresh.store_to_string().unwrap();
+    let err = fresh
+      .apply_template(&template, &registry, None)
+      .unwrap_err();
+    assert_eq!(err.to_string(), "Adventurer level 500 is not within 1-200");
+    assert_eq!(fresh.store_to_string().unwrap(), stored);
+
+    // Canceling keeps the skills that were already set.
+    let template: CharacterTemplate =
+      serde_json::from_str(r#"{"gold":7,"plan":{"3":60,"4":70,"5":80}}"#).unwrap();
+    let progress = util::CancelAfter::new(2);
+    let err = fresh.apply_template(&template, &registry, Some(&progress));
+    let report = ApplyReport {
+      changes: 3,
+      unknown: Vec::new(),
+    };
+    assert_eq!(err, Err(BatchError::Canceled(report)));
+    assert_eq!(progress.reports(), [(0, 3), (1, 3), (2, 3)]);
+    assert_eq!(fresh.get_gold(), Some(7));
+    let level = |id| fresh.skill_level(registry.find_id(id).unwrap().2).level();
+    assert_eq!((level(3), level(4), level(5)), (Some(60), Some(70), None));
+  }
+
+  #[test]
+  fn test_clone() {
+    let text = save().bank_gold(10).build();
+    let mut game = load_text("clone", &text);
+    assert!(game.set_gold(99).unwrap());
+    let stored = game.store_to_string().unwrap();
+
+    // Wreck the clone.
+    let mut clone = game.clone();
+    assert_eq!(clone.store_to_string().unwrap(), stored);
+    assert!(clone.set_gold(1).unwrap());
+    assert!(clone.set_bank_gold(2).unwrap());
+    assert!(clone.set_adv_lvl(1).unwrap());
+    let mut groups = clone.get_skills(SkillCategory::Adventurer);
+    for skill in groups.iter_mut().flat_map(|group| group.skills.iter_mut()) {
+      skill.level = 0;
+    }
+    assert!(clone.set_skills(&groups).unwrap());
+    let mut items = clone.get_inventory_items();
+    for item in &mut items {
+      *item.count_mut() = 1;
+    }
+    assert!(clone.set_inventory_items(&items).unwrap());
+    let id = clone.pending_changes().last().unwrap().id;
+    clone.revert_change(id).unwrap();
+    let copy = temp_path("clone_copy");
+    clone.store_as(&copy).unwrap();
+
+    // The original is untouched.
+    assert_eq!(game.store_to_string().unwrap(), stored);
+    assert_eq!(game.pending_changes().len(), 1);
+    assert_eq!(game.get_file_path(), temp_path("clone"));
+    assert_eq!(fs::read_to_string(game.get_file_path()).unwrap(), text);
+    assert!(game.is_modified());
+    assert_ne!(fs::read_to_string(&copy).unwrap(), stored);
+  }
+
+  #[test]
+  fn test_store_conflict() {
+    let text = save().gold(100).build();
+    let mut game = load_text("store_conflict", &text);
+    let path = game.get_file_path();
+    assert_eq!(game.check_conflict(), Ok(()));
+
+    // Writing the same contents isn't a conflict.
+    fs::write(&path, &text).unwrap();
+    assert!(game.set_gold(200).unwrap());
+    game.store().unwrap();
+
+    // Our own store isn't a conflict either.
+    assert!(game.set_gold(300).unwrap());
+    game.store().unwrap();
+
+    // The game stored over it.
+    let external = text.replace(r#""g":100"#, r#""g":12345"#);
+    fs::write(&path, &external).unwrap();
+    assert!(game.set_gold(400).unwrap());
+    assert_eq!(game.check_conflict().unwrap_err().path, path);
+    assert!(game.store().is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), external);
+
+    // Storing elsewhere is fine.
+    game
+      .store_copy_as(temp_path("store_conflict_copy"))
+      .unwrap();
+
+    // Overwrite anyway.
+    let options = StoreOptions {
+      force: true,
+      ..Default::default()
+    };
+    game.store_as_with(&path, options).unwrap();
+    assert_eq!(game.check_conflict(), Ok(()));
+
+    // Reload and lose the changes.
+    fs::write(&path, &external).unwrap();
+    assert!(game.set_gold(500).unwrap());
+    assert!(game.check_conflict().is_err());
+    game.reload().unwrap();
+    assert_eq!(game.get_gold(), Some(12345));
+    assert!(game.pending_changes().is_empty());
+    assert!(!game.is_modified());
+    assert_eq!(game.check_conflict(), Ok(()));
+
+    // A failed reload keeps the changes.
+    assert!(game.set_gold(600).unwrap());
+    fs::write(&path, "<Data>").unwrap();
+    assert!(game.reload().is_err());
+    assert_eq!(game.get_gold(), Some(600));
+    assert_eq!(game.pending_changes().len(), 1);
+    assert!(game.is_modified());
+  }
+
+  #[test]
+  fn test_is_stale() {
+    let text = save().build();
+    let mut game = load_text("is_stale", &text);
+    let path = game.get_file_path();
+    assert!(!game.is_stale().unwrap());
+    assert!(!GameData::from_text(text.clone())
+      .unwrap()
+      .is_stale()
+      .unwrap());
+
+    // Touching the file doesn't make it stale.
+    let later = SystemTime::now() + std::time::Duration::from_secs(60);
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_modified(later).unwrap();
+    drop(file);
+    assert!(!game.is_stale().unwrap());
+    assert!(game.set_gold(5).unwrap());
+    game.store().unwrap();
+
+    // Changing it does, and storing over it then needs to be forced.
+    fs::write(&path, text.replace(r#""g":1234"#, r#""g":99"#)).unwrap();
+    assert!(game.is_stale().unwrap());
+    assert!(game.set_gold(6).unwrap());
+    assert!(matches!(game.store(), Err(Error::Conflict(_))));
+    game.reload().unwrap();
+    assert!(!game.is_stale().unwrap());
+    assert_eq!(game.get_gold(), Some(99));
+
+    fs::remove_file(&path).unwrap();
+    assert!(game.is_stale().is_err());
+  }
+
+  #[test]
+  fn test_verify_store() {
+    let mut game = GameData::from_text(save().skill(3, 1440).build()).unwrap();
+    assert!(game.set_gold(500).unwrap());
+    let snapshot = game.snapshot();
+    let text = snapshot.store_to_string().unwrap();
+    assert_eq!(snapshot.verify_text(text.clone()), Ok(()));
+
+    let err = snapshot.verify_text(text.replace(r#""g":500"#, r#""g":5"#));
+    assert_eq!(err.unwrap_err(), "gold is 5 instead of 500");
+    let err = snapshot.verify_text(text.replace(r#""sk2":{"3":"#, r#""sk2":{},"z":{"3":"#));
+    assert_eq!(err.unwrap_err(), "skill count is 0 instead of 1");
+    assert!(snapshot.verify_text(String::new()).is_err());
+
+    // Storing verifies by default.
+    let path = temp_path("verify_store");
+    game.store_as(&path).unwrap();
+    assert_eq!(GameData::load(&path).unwrap().get_gold(), Some(500));
+
+    // A new file that doesn't verify leaves the old one alone.
+    let data = std::fs::read(&path).unwrap();
+    let err = write_journaled(&path, b"{}", |temp| {
+      let reason = snapshot.verify_file(temp).unwrap_err();
+      Err(Error::Verification(VerificationFailed {
+        path: path.clone(),
+        reason,
+      }))
+    });
+    assert!(matches!(err, Err(Error::Verification(_))));
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    assert!(!suffixed(&path, TEMP_SUFFIX).exists());
+    assert!(!suffixed(&path, JOURNAL_SUFFIX).exists());
+  }
+
+  #[test]
+  fn test_revert_change() {
+    let mut game = GameData::from_text(save().build()).unwrap();
+    let text = game.store_to_string().unwrap();
+    assert!(game.set_gold(2000).unwrap());
+    assert!(game.set_bank_gold(500).unwrap());
+    assert!(game.set_prd_lvl(3).unwrap());
+    assert!(game.set_gold(3000).unwrap());
+
+    // The first gold change conflicts with the second.
+    let changes = game.pending_changes();
+    assert!(game.revert_change(changes[0].id).is_err());
+
+    assert!(game.revert_change(changes[3].id).is_ok());
+    assert!(game.revert_change(changes[3].id).is_err());
+    assert_eq!(game.get_gold(), Some(2000));
+    assert!(game.revert_change(changes[0].id).is_ok());
+
+    // Keep the producer level.
+    assert!(game.revert_change(changes[1].id).is_ok());
+    assert_eq!(game.get_bank_gold(), None);
+    assert_eq!(game.get_prd_lvl(), 3);
+    assert_eq!(game.pending_changes().len(), 1);
+
+    assert!(game.revert_change(changes[2].id).is_ok());
+    assert_eq!(game.store_to_string().unwrap(), text);
+  }
+
+  #[test]
+  fn test_undo_redo() {
+    let text = save().build();
+    let mut game = load_text("undo_redo", &text);
+    let original = game.store_to_string().unwrap();
+    assert!(!game.can_undo() && !game.undo());
+    assert!(!game.can_redo() && !game.redo());
+
+    assert!(game.set_gold(2000).unwrap());
+    assert!(game.set_skill_exp(3, 100).unwrap());
+    let mut items = game.get_inventory_items();
+    for item in &mut items {
+      *item.count_mut() = 9;
+    }
+    assert!(game.set_inventory_items(&items).unwrap());
+    let edited = game.store_to_string().unwrap();
+
+    // Both items are one edit.
+    assert!(game.undo());
+    let counts: Vec<_> = game
+      .get_inventory_items()
+      .iter()
+      .map(|item| item.cnt)
+      .collect();
+    assert_eq!(counts, [50, 1]);
+    assert_eq!(game.get_skill_exp(3), Some(100));
+    assert!(game.undo());
+    assert_eq!(game.get_skill_exp(3), Some(1440));
+    assert_eq!(game.pending_changes().len(), 1);
+
+    assert!(game.redo() && game.redo());
+    assert!(!game.can_redo());
+    assert_eq!(game.store_to_string().unwrap(), edited);
+    assert_eq!(game.pending_changes().len(), 4);
+
+    // A new change drops what was undone.
+    assert!(game.undo());
+    assert!(game.set_adv_exp(10).unwrap());
+    assert!(!game.can_redo());
+    while game.undo() {}
+    assert_eq!(game.store_to_string().unwrap(), original);
+    assert!(game.pending_changes().is_empty());
+
+    // Reverted changes aren't undone again.
+    assert!(game.set_gold(1).unwrap());
+    assert!(game.set_bank_gold(2).unwrap());
+    let id = game.pending_changes()[0].id;
+    game.revert_change(id).unwrap();
+    assert!(game.undo());
+    assert!(!game.undo());
+    assert_eq!(game.store_to_string().unwrap(), original);
+
+    // The history is capped, and dropped on reload.
+    for gold in 0..300 {
+      assert!(game.set_gold(gold).unwrap());
+    }
+    let mut undone = 0;
+    while game.undo() {
+      undone += 1;
+    }
+    assert_eq!(undone, crate::journal::MAX_UNDO);
+    assert_eq!(game.get_gold(), Some(43));
+    assert!(game.redo());
+    game.reload().unwrap();
+    assert!(!game.can_undo() && !game.can_redo());
+  }
+
+  #[test]
+  fn test_patch() {
+    let text = save().build();
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    assert_eq!(game.pending_patch(), serde_json::json!([]));
+
+    assert!(game.set_adv_lvl(6).unwrap());
+    assert!(game.set_bank_gold(500).unwrap());
+    let mut items = game.get_inventory_items();
+    *items[0].count_mut() = 60;
+    assert!(game.set_inventory_items(&items).unwrap());
+
+    let patch = game.pending_patch();
+    let avatar = SaveBuilder::avatar_id();
+    assert_eq!(patch[0]["op"], "replace");
+    assert_eq!(patch[0]["path"], format!("/CharacterSheet/{avatar}/ae"));
+    assert_eq!(patch.as_array().unwrap().len(), 3);
+
+    // Export, reload, apply and there's no difference.
+    let mut fresh = GameData::from_text(text.clone()).unwrap();
+    fresh.apply_patch(&patch).unwrap();
+    assert_eq!(fresh.pending_patch(), patch);
+    assert_eq!(
+      fresh.store_to_string().unwrap(),
+      game.store_to_string().unwrap()
+    );
+
+    // Failed patches change nothing.
+    let bad = serde_json::json!([
+      { "op": "replace", "path": format!("/UserGold/{USER_ID}/g"), "value": 1 },
+      { "op": "replace", "path": format!("/CharacterSheet/{avatar}/nope"), "value": 1 },
+    ]);
+    assert!(fresh.apply_patch(&bad).is_err());
+    assert_eq!(fresh.get_gold(), Some(1234));
+
+    // The patch is one edit after the earlier ones.
+    let mut fresh = GameData::from_text(text).unwrap();
+    let level = fresh.get_adv_lvl();
+    assert!(fresh.set_gold(10).unwrap());
+    fresh.apply_patch(&patch).unwrap();
+    assert_eq!(fresh.pending_changes().len(), 4);
+    assert!(fresh.undo());
+    assert_eq!(fresh.pending_changes().len(), 1);
+    assert_eq!(fresh.get_adv_lvl(), level);
+    assert!(fresh.undo());
+    assert_eq!(fresh.get_gold(), Some(1234));
+    assert!(fresh.redo() && fresh.redo());
+    assert_eq!(fresh.get_adv_lvl(), 6);
+    assert_eq!(fresh.get_gold(), Some(10));
+
+    // Bags' stores are patched too.
+    let text = save()
+      .bag("Items/Bags/Pouch", &[("Items/Crafting/Flax", 5)])
+      .build();
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    let (mut items, _) = game.get_inventory_tree();
+    let flax = items.iter_mut().find(|item| item.name() == "Flax").unwrap();
+    *flax.count_mut() = 50;
+    assert!(game.set_inventory_items(&items).unwrap());
+    let patch = game.pending_patch();
+    let path = patch[0]["path"].as_str().unwrap();
+    assert!(path.starts_with("/ItemStore/") && !path.contains(&game.backpack));
+
+    let mut fresh = GameData::from_text(text).unwrap();
+    fresh.apply_patch(&patch).unwrap();
+    assert_eq!(fresh.pending_patch(), patch);
+    assert_eq!(
+      fresh.store_to_string().unwrap(),
+      game.store_to_string().unwrap()
+    );
+  }
+
+  #[test]
+  fn test_validate() {
+    let registry = SkillRegistry::new();
+    let game = GameData::from_text(
+      save()
+        .bag("Items/Bags/Pouch", &[("Items/Crafting/Flax", 5)])
+        .build(),
+    )
+    .unwrap();
+    assert_eq!(game.validate(&registry), []);
+
+    let text = save()
+      .gold(-5)
+      .bank_gold(5_000_000_000)
+      .skill(3, -10)
+      .skill(4, i64::MAX / 2)
+      .skill(9999, 100)
+      .item("Items/Crafting/Flax", 2)
+      .durable_item("Items/Weapons/Axe", 60.0, 50.0)
+      .build();
+    let flax = "5ad7aa2a0f0e5b3c2c7d1e05";
+    let text = text.replace(r#""qn":2"#, r#""qn":-2"#);
+
+    // A bag whose ItemStore record is missing.
+    let axe = "5ad7aa2a0f0e5b3c2c7d1e06";
+    let text = text.replace(
+      r#""an":"Items/Weapons/Axe","#,
+      r#""an":"Items/Weapons/Axe","bag":"missing","#,
+    );
+    let game = GameData::from_text(text).unwrap();
+    let issues = game.validate(&registry);
+    let avatar = SaveBuilder::avatar_id();
+    let backpack = SaveBuilder::backpack_id();
+    let expected = [
+      (Severity::Error, format!("UserGold/{USER_ID}/g")),
+      (Severity::Warning, format!("UserBank/{USER_ID}/g")),
+      (Severity::Error, format!("CharacterSheet/{avatar}/sk2/3")),
+      (Severity::Warning, format!("CharacterSheet/{avatar}/sk2/4")),
+      (
+        Severity::Warning,
+        format!("CharacterSheet/{avatar}/sk2/9999"),
+      ),
+      (Severity::Error, format!("ItemStore/{backpack}/in/{flax}")),
+      (Severity::Warning, format!("ItemStore/{backpack}/in/{axe}")),
+      (Severity::Error, format!("ItemStore/{backpack}/in/{axe}")),
+    ];
+    let found: Vec<_> = issues
+      .iter()
+      .map(|issue| (issue.severity, issue.location.clone()))
+      .collect();
+    assert_eq!(found, expected);
+    assert_eq!(
+      issues[5].to_string(),
+      format!("Error: Flax has a negative count: -2 (ItemStore/{backpack}/in/{flax})")
+    );
+    assert!(issues[3].to_string().contains(" is above level 200: "));
+
+    // Skill levels are checked against the limits.
+    let mut game = GameData::from_text(save().build()).unwrap();
+    game.set_limits(Limits {
+      skill_level: 0..=1,
+      ..Limits::default()
+    });
+    let issues = game.validate(&registry);
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].to_string().contains(" is above level 1: 1440 "));
+  }
+
+  #[test]
+  fn test_adjust_all_skills() {
+    let registry = SkillRegistry::new();
+    let text = save().skill(9999, 100).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let report = game.adjust_all_skills(&registry, 10, 0..=30).unwrap();
+    assert_eq!(report.unknown, ["9999"]);
+    assert_eq!(report.skills.len(), 1);
+    assert_eq!((report.skills[0].before, report.skills[0].after), (23, 30));
+    assert_eq!(game.pending_changes().len(), 1);
+
+    // Clamp down.
+    let report = game.adjust_all_skills(&registry, 0, 1..=20).unwrap();
+    assert_eq!((report.skills[0].before, report.skills[0].after), (30, 20));
+
+    // A single level range.
+    let report = game.adjust_all_skills(&registry, -5, 15..=15).unwrap();
+    assert_eq!((report.skills[0].before, report.skills[0].after), (20, 15));
+    assert_eq!(game.pending_changes().len(), 3);
+
+    // Nothing changes.
+    let report = game.adjust_all_skills(&registry, 500, 0..=15).unwrap();
+    assert_eq!((report.skills[0].before, report.skills[0].after), (15, 15));
+    assert_eq!(game.pending_changes().len(), 3);
+  }
+
+  #[test]
+  fn test_level_range() {
+    let mut game = GameData::from_text(save().build()).unwrap();
+    for lvl in [0, 201, -5] {
+      let err = game.set_adv_lvl(lvl).unwrap_err();
+      let expected = OutOfRange {
+        name: "Adventurer",
+        value: lvl,
+        range: 1..=200,
+      };
+      assert!(matches!(&err, Error::OutOfRange(err) if *err == expected));
+      assert_eq!(
+        err.to_string(),
+        format!("Adventurer level {lvl} is not within 1-200")
+      );
+      let err = game.set_prd_lvl(lvl).unwrap_err();
+      assert_eq!(
+        err.to_string(),
+        format!("Producer level {lvl} is not within 1-200")
+      );
+    }
+    assert!(game.pending_changes().is_empty());
+    assert!(game.set_adv_lvl(1).unwrap());
+    assert!(game.set_prd_lvl(200).unwrap());
+
+    #[allow(clippy::reversed_empty_ranges)]
+    let err = game.adjust_all_skills(&SkillRegistry::new(), 1, 10..=5);
+    assert_eq!(err.unwrap_err().to_string(), "Invalid level range 10-5");
+
+    // A bad skill level leaves all the skills unchanged.
+    let text = game.store_to_string().unwrap();
+    let changes = game.pending_changes().len();
+    for lvl in [201, 250, -1] {
+      let mut groups = game.get_skills(SkillCategory::Adventurer);
+      groups[0].skills[0].level = 50;
+      groups[0].skills[1].level = 0;
+      groups[0].skills[2].level = lvl;
+      let name = groups[0].skills[2].info.name;
+      let err = game.set_skills(&groups).unwrap_err();
+      assert_eq!(
+        err.to_string(),
+        format!("{name} level {lvl} is not within 0-200")
+      );
+    }
+    assert_eq!(game.store_to_string().unwrap(), text);
+    assert_eq!(game.pending_changes().len(), changes);
+  }
+
+  #[test]
+  fn test_malformed_skills() {
+    let registry = SkillRegistry::new();
+    let text = save().skill(4, 1440).build();
+    let mut game = GameData::from_text(text).unwrap();
+    let mut groups = game.get_skills(SkillCategory::Adventurer);
+    let avatar = SaveBuilder::avatar_id();
+    let patch = |path: &str| {
+      serde_json::json!([{
+        "op": "replace",
+        "path": format!("/CharacterSheet/{avatar}/{path}"),
+        "value": "x",
+      }])
+    };
+
+    // A skill that isn't an object.
+    game.apply_patch(&patch("sk2/3")).unwrap();
+    let set_level = |groups: &mut [SkillLvlGroup], level| {
+      let mut skills = groups.iter_mut().flat_map(|group| group.skills.iter_mut());
+      skills.find(|skill| skill.info.id == 3).unwrap().level = level;
+    };
+    set_level(&mut groups, 50);
+    let err = game.set_skills(&groups).unwrap_err();
+    let section = Section::CharacterSheet;
+    assert!(matches!(&err, Error::MalformedSection(err) if err.section == section));
+    assert_eq!(
+      err.to_string(),
+      "CharacterSheet is malformed: skill 3 is not an object"
+    );
+
+    // Skills that aren't an object.
+    game.apply_patch(&patch("sk2")).unwrap();
+    let err = "CharacterSheet is malformed: sk2 is not an object";
+    assert_eq!(game.set_skills(&groups).unwrap_err().to_string(), err);
+    set_level(&mut groups, 0);
+    assert_eq!(game.set_skills(&groups).unwrap_err().to_string(), err);
+    let adjusted = game.adjust_all_skills(&registry, 1, 0..=200);
+    assert_eq!(adjusted.unwrap_err().to_string(), err);
+    assert_eq!(game.pending_changes().len(), 2);
+
+    // Experience that isn't a number is replaced.
+    game.apply_patch(&patch("ae")).unwrap();
+    assert_eq!(game.get_adv_lvl(), 1);
+    assert!(game.set_adv_lvl(10).unwrap());
+    assert_eq!(game.get_adv_lvl(), 10);
+  }
+
+  #[test]
+  fn test_load_avatar() {
+    // A second avatar with its own backpack.
+    let other = "5ad7aa2a0f0e5b3c2c7d9999";
+    let backpack = "5ad7aa2a0f0e5b3c2c7d9998";
+    let mut text = save().build();
+    let mut add = |collection: &str, id: &str, json: &str| {
+      let tag = format!(r#"<collection name="{collection}">"#);
+      let record = format!(r#"<record Id="{id}">{json}</record>"#);
+      let pos = text.find(&tag).unwrap() + tag.len();
+      text.insert_str(pos, &record);
+    };
+    add(CHARACTER, other, &format!(r#"{{"mainbp":"{backpack}"}}"#));
+    add(CHARACTER_NAME, other, r#"{"fn":"Crafter"}"#);
+    add(CHARACTER_SHEET, other, r#"{"ae":0,"pe":1440,"sk2":{}}"#);
+    add(
+      ITEM_STORE,
+      backpack,
+      r#"{"in":{"1":{"in":{"an":"Hammer","qn":1}}}}"#,
+    );
+
+    let path = temp_path("load_avatar");
+    fs::write(&path, &text).unwrap();
+    let avatar = SaveBuilder::avatar_id();
+    let avatars = GameData::list_avatars(&path).unwrap();
+    let names: Vec<_> = avatars
+      .iter()
+      .map(|info| (info.id.as_str(), info.name.as_str()))
+      .collect();
+    assert_eq!(names, [(other, "Crafter"), (avatar.as_str(), "Barugon")]);
+
+    // The current avatar is loaded by default.
+    let game = GameData::load(&path).unwrap();
+    assert_eq!(game.avatar_id(), avatar);
+    assert_eq!(game.avatars(), avatars);
+
+    let mut game = GameData::load_avatar(&path, other).unwrap();
+    assert_eq!(game.avatar_name(), "Crafter");
+    assert_eq!(game.backpack, backpack);
+    assert!(game.inventory[IN].get("1").is_some());
+    game.set_adv_lvl(10).unwrap();
+    game.store().unwrap();
+
+    // Only the loaded avatar's records change.
+    let main_backpack = SaveBuilder::backpack_id();
+    let stored = fs::read_to_string(&path).unwrap();
+    let record = |text: &str, collection, id| {
+      let range = find_records(text, collection, id).pop().unwrap();
+      text[range].to_owned()
+    };
+    for (collection, id) in [
+      (CHARACTER_SHEET, avatar.as_str()),
+      (ITEM_STORE, main_backpack.as_str()),
+      (USER, USER_ID),
+      (USER_GOLD, USER_ID),
+    ] {
+      assert_eq!(
+        record(&stored, collection, id),
+        record(&text, collection, id)
+      );
+    }
+    assert_ne!(
+      record(&stored, CHARACTER_SHEET, other),
+      record(&text, CHARACTER_SHEET, other)
+    );
+
+    // Reloading keeps the avatar.
+    game.reload().unwrap();
+    assert_eq!(game.avatar_id(), other);
+    assert_eq!(game.get_adv_lvl(), 10);
+
+    let err = GameData::load_avatar(&path, "missing").err().unwrap();
+    assert!(matches!(
+      err,
+      Error::MissingRecord {
+        collection: CHARACTER,
+        ..
       }
+    ));
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_compare_avatars() {
+    let registry = SkillRegistry::new();
+
+    // A second avatar without a backpack.
+    let other = "5ad7aa2a0f0e5b3c2c7d9999";
+    let sheet = r#"{"ae":0,"pe":1440,"sk2":{"3":{"x":1440},"4":{"x":1440}}}"#;
+    let text = save().build().replace(
+      "</collection><collection name=\"ItemStore\">",
+      &format!(
+        r#"<record Id="{other}">{sheet}</record></collection><collection name="ItemStore">"#
+      ),
+    );
+    let text = text.replace(
+      "</collection><collection name=\"Character\">",
+      &format!(r#"<record Id="{other}">{{"fn":"Crafter"}}</record></collection><collection name="Character">"#),
+    );
+
+    let game = GameData::from_text(text).unwrap();
+    let avatar = SaveBuilder::avatar_id();
+    let cmp = game.compare_avatars(&avatar, other, &registry).unwrap();
+    assert_eq!(cmp.a.name, "Barugon");
+    assert_eq!(cmp.b.name, "Crafter");
+    let prd_lvl = util::level_for_exp(1440, game.tables.level());
+    assert_eq!(
+      (cmp.a.prd_lvl, cmp.b.prd_lvl),
+      (game.get_prd_lvl(), prd_lvl)
+    );
+    assert_eq!(cmp.a.items, Some(2));
+    assert_eq!(cmp.b.items, None);
+    assert_eq!(cmp.shared_gold, Some(1234));
+
+    // Chain Lightning is the same, but only the second avatar has skill 4.
+    let name = registry.find_id(4).unwrap().2.name;
+    let skill = SkillAdjustment {
+      id: 4,
+      name,
+      before: 0,
+      after: 23,
+    };
+    assert_eq!(cmp.skills, [skill]);
+
+    assert!(game.compare_avatars(&avatar, "missing", &registry).is_err());
+    let same = game.compare_avatars(&avatar, &avatar, &registry).unwrap();
+    assert!(same.skills.is_empty());
+  }
+
+  #[test]
+  fn test_swap_skills() {
+    let registry = SkillRegistry::new();
+
+    // Add a second avatar with a different build.
+    let other = "5ad7aa2a0f0e5b3c2c7d9999";
+    let sheet =
+      r#"{"ae":10,"sk2":{"4":{"m":0,"t":{"$date":1600000000000},"x":1000},"9999":{"x":5}}}"#;
+    let text = save().skill(9999, 100).build().replace(
+      "</collection><collection name=\"ItemStore\">",
+      &format!(
+        r#"<record Id="{other}">{sheet}</record></collection><collection name="ItemStore">"#
+      ),
+    );
+    let text = text.replace(
+      "</collection><collection name=\"Character\">",
+      &format!(r#"<record Id="{other}">{{"fn":"Crafter"}}</record></collection><collection name="Character">"#),
+    );
+
+    let mut game = GameData::from_text(text.clone()).unwrap();
+    let avatar = SaveBuilder::avatar_id();
+    assert!(game
+      .swap_skills(&avatar, &avatar, &registry, false)
+      .is_err());
+    assert!(game
+      .swap_skills(&avatar, "missing", &registry, false)
+      .is_err());
+
+    game.swap_skills(&avatar, other, &registry, false).unwrap();
+    let sk2 = &game.character[SK2];
+    assert!(sk2.get("3").is_none());
+    assert_eq!(sk2["4"]["x"], 1000);
+    assert_eq!(sk2["9999"]["x"], 100);
+    assert_eq!(game.character[AE], 4641);
+
+    let changes: Vec<String> = game
+      .pending_changes()
+      .iter()
+      .map(|change| change.to_string())
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        "[Skill] Skills from Crafter (Barugon): 1 -> 1",
+        "[Skill] Skills from Barugon (Crafter): 1 -> 1",
+      ]
+    );
+
+    // Both sheets are stored.
+    let stored = GameData::from_text(game.store_to_string().unwrap()).unwrap();
+    let sheet = stored.sheet(other).unwrap();
+    assert_eq!(sheet[SK2]["3"]["x"], 1440);
+    assert_eq!(sheet[SK2]["9999"]["x"], 5);
+    assert_eq!(stored.character[SK2]["4"]["x"], 1000);
+
+    // The patch covers both sheets.
+    let mut fresh = GameData::from_text(text).unwrap();
+    fresh.apply_patch(&game.pending_patch()).unwrap();
+    assert_eq!(
+      fresh.store_to_string().unwrap(),
+      game.store_to_string().unwrap()
+    );
+
+    // Swap back, including pooled experience. The producer experience that only one avatar has
+    // moves without leaving a null behind.
+    game.swap_skills(other, &avatar, &registry, true).unwrap();
+    assert_eq!(game.character[SK2]["3"]["x"], 1440);
+    assert_eq!(game.character[AE], 10);
+    assert!(game.character.get(PE).is_none());
+    assert_eq!(game.sheet(other).unwrap()[PE], 1000);
+
+    let changes: Vec<String> = game.pending_changes()[2..]
+      .iter()
+      .map(|change| change.to_string())
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        "[Skill] Skills from Barugon (Crafter): 1 -> 1",
+        "[Skill] Skills from Crafter (Barugon): 1 -> 1",
+        "[Other] Adventurer Experience (Crafter): 10 -> 4,641",
+        "[Other] Adventurer Experience (Barugon): 4,641 -> 10",
+        "[Other] Producer Experience (Crafter): none -> 1,000",
+        "[Other] Producer Experience (Barugon): 1,000 -> none",
+      ]
+    );
+
+    // It's one edit.
+    assert!(game.undo());
+    assert_eq!(game.character[PE], 1000);
+    assert_eq!(game.pending_changes().len(), 2);
+  }
+
+  #[test]
+  fn test_export_skills_csv() {
+    let registry = SkillRegistry::new();
+    let text = save().skill(2501, 20 * 720).skill(9999, 1440).build();
+    let game = GameData::from_text(text).unwrap();
+    let path = std::env::temp_dir().join("cota_test_export_skills.csv");
+    game.export_skills_csv(&registry, &path).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let expected = concat!(
+      "Air,Air Magic Specialization,20,2501,23\n",
+      "Air,Chain Lightning,2,3,23\n",
+      "Unknown,,1,9999,30\n",
+    );
+    assert_eq!(text, expected);
+
+    // Without the level column it parses like a skill file.
+    let text: String = text
+      .lines()
+      .map(|line| format!("{}\n", line.rsplit_once(',').unwrap().0))
+      .collect();
+    let groups = skill_info::parse_skill_csv(Box::leak(text.into_boxed_str()));
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].skills[1].name, "Chain Lightning");
+    assert_eq!(groups[0].skills[1].mul, 2.0);
+    assert_eq!(groups[1].name, "Unknown");
+    assert_eq!(groups[1].skills[0].id, 9999);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_skill_level() {
+    let registry = SkillRegistry::new();
+    let text = save().skill(4, 100).skill(5, 100).build();
+    let text = text.replace(r#""x":100}"#, r#""x":"abc"}"#);
+    let text = text.replacen(r#","x":"abc""#, "", 1);
+    let game = GameData::from_text(text).unwrap();
+
+    let info = |id| registry.find_id(id).unwrap().2;
+    assert_eq!(game.skill_level(info(3)), SkillLevel::Level(23));
+    assert_eq!(
+      game.skill_level(info(4)),
+      SkillLevel::Invalid("no experience".into())
+    );
+    assert_eq!(
+      game.skill_level(info(5)),
+      SkillLevel::Invalid(r#"invalid experience: "abc""#.into())
+    );
+    assert_eq!(game.skill_level(info(6)), SkillLevel::Untrained);
+
+    let issues = game.validate(&registry);
+    assert_eq!(issues.len(), 2);
+    assert!(issues.iter().all(|issue| issue.severity == Severity::Error));
+  }
+
+  #[test]
+  fn test_tree_summary() {
+    let registry = SkillRegistry::new();
+    let text = save().skill(1, 100).skill(221, 200).skill(8, 50).build();
+    let game = GameData::from_text(text).unwrap();
+    let trees = game.tree_summary(&registry);
+    assert_eq!(trees.len(), 1);
+
+    // Gust is tier 1, Discharge 2, Rapid Discharge 2 and Chain Lightning 4.
+    let air = &trees[0];
+    assert_eq!(air.group, "Air");
+    assert_eq!(air.trained_by_tier, [1, 2, 0, 1]);
+    assert_eq!(air.trained(), 4);
+    assert_eq!(air.highest_tier, 4);
+    assert_eq!(air.exp, 1440 + 100 + 200 + 50);
+  }
+
+  #[test]
+  fn test_skill_lvl_f64() {
+    let registry = SkillRegistry::new();
+    let info = registry.find_id(3).unwrap().2;
+    let mul = info.mul;
+
+    // The whole part always matches the level, for random experience.
+    let mut seed = 4321u32;
+    let last = *util::SKILL_EXP.last().unwrap() as f64 * mul;
+    for _ in 0..500 {
+      seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+      let exp = (seed as f64 / u32::MAX as f64 * last * 1.1) as i64;
+      let game = GameData::from_text(SaveBuilder::new().skill(3, exp).build()).unwrap();
+      let lvl = game.get_skill_lvl_f64(3, mul).unwrap();
+      assert_eq!(
+        lvl.floor() as i32,
+        game.skill_level(info).level().unwrap(),
+        "{exp}"
+      );
+      assert!(lvl <= 200.0);
     }
+
+    // Halfway to level 11 (the experience is scaled by the multiplier).
+    let half = (util::SKILL_EXP[9] + util::SKILL_EXP[10]) as f64 / 2.0 * mul;
+    let game = GameData::from_text(SaveBuilder::new().skill(3, half as i64).build()).unwrap();
+    let lvl = game.get_skill_lvl_f64(3, mul).unwrap();
+    assert!((lvl - 10.5).abs() < 0.01, "{lvl}");
+    assert_eq!(game.get_skill_lvl_f64(4, mul), None);
+
+    let game = GameData::from_text(SaveBuilder::new().skill(3, -10).build()).unwrap();
+    assert_eq!(game.get_skill_lvl_f64(3, mul), None);
+
+    // Adventurer and producer levels work the same way.
+    let table = game.tables.level();
+    let game = GameData::from_text(save().adv_exp(table[19]).prd_exp(-5).build()).unwrap();
+    assert_eq!(game.get_adv_lvl_f64(), 20.0);
+    assert_eq!(game.get_prd_lvl_f64(), 1.0);
+  }
+
+  #[test]
+  fn test_skill_consistency() {
+    let registry = SkillRegistry::new();
+    let game = GameData::from_text(save().build()).unwrap();
+    assert_eq!(game.skill_consistency(&registry), []);
+
+    // Chain Lightning is level 23.
+    let text = save().ranked_skill(3, 1440, 22).build();
+    let game = GameData::from_text(text).unwrap();
+    assert_eq!(game.skill_consistency(&registry), []);
+
+    let text = save().ranked_skill(3, 1440, 30).build();
+    let game = GameData::from_text(text).unwrap();
+    let found = game.skill_consistency(&registry);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].id, found[0].level, found[0].rank), (3, 23, 30));
+
+    let issues = game.validate(&registry);
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].message.ends_with("has rank 30"));
+  }
+
+  #[test]
+  fn test_report() {
+    let game = GameData::from_text(save().bank_gold(5000).build()).unwrap();
+    let registry = SkillRegistry::new();
+    let text = game.report_with_locale(&registry, ReportFormat::Markdown, &Locale::en);
+    assert_eq!(
+      text,
+      concat!(
+        "# Barugon\n\n",
+        "- Date: 2020-09-13\n",
+        "- Gold: 1,234\n",
+        "- Bank: 5,000\n",
+        "- Adventurer Level: 5\n",
+        "- Producer Level: 2\n",
+        "\n## Adventurer Skills\n\n",
+        "| Tree | Skill | Level |\n",
+        "| --- | --- | ---: |\n",
+        "| Air | Chain Lightning | 23 |\n",
+        "\n## Skill Trees\n\n",
+        "| Tree | Skills | Highest Tier | Experience |\n",
+        "| --- | ---: | ---: | ---: |\n",
+        "| Air | 1 | 4 | 1,440 |\n",
+        "\n## Inventory\n\n",
+        "| Item | Count |\n",
+        "| --- | ---: |\n",
+        "| IronOre | 50 |\n",
+        "| Longsword | 1 |\n",
+      )
+    );
+
+    let text = game.report_with_locale(&registry, ReportFormat::Json, &Locale::en);
+    let json: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "adventurer_level": 5,
+        "bank": 5000,
+        "date": "2020-09-13",
+        "gold": 1234,
+        "inventory": [
+          { "count": 50, "name": "IronOre" },
+          { "count": 1, "name": "Longsword" },
+        ],
+        "name": "Barugon",
+        "producer_level": 2,
+        "skills": [
+          { "category": "Adventurer", "group": "Air", "level": 23, "name": "Chain Lightning" },
+        ],
+        "skill_trees": [
+          {
+            "category": "Adventurer",
+            "exp": 1440,
+            "group": "Air",
+            "highest_tier": 4,
+            "trained_by_tier": [0, 0, 0, 1],
+          },
+        ],
+      })
+    );
   }
 
-  Err(Cow::from("Unable to find a save date"))
+  #[test]
+  fn test_content_hash() {
+    assert_eq!(hash_bytes(b""), 0xcbf29ce484222325);
+    assert_eq!(hash_bytes(b"a"), 0xaf63dc4c8601ec8c);
+
+    let game = load_text("content_hash", &save().build());
+    assert_eq!(game.content_hash(), 0x5a45911b376c8c51);
+    assert_eq!(
+      hash_file(game.get_file_path()).unwrap(),
+      game.content_hash()
+    );
+  }
 }
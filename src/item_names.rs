@@ -0,0 +1,178 @@
+use std::{borrow::Cow, collections::HashMap, fmt, fs, path::Path};
+
+/// Prefixes of asset names that don't belong in display names.
+const KNOWN_PREFIXES: [&str; 2] = ["Crafting", "Deco"];
+
+/// Display names for item assets, from the built-in table plus any local overrides.
+#[derive(Clone, Debug)]
+pub struct ItemNames {
+  names: HashMap<String, String>,
+}
+
+impl Default for ItemNames {
+  fn default() -> Self {
+    let text = include_str!("../res/item_names.csv");
+    let names = parse_item_names(text).expect("The built-in item names are invalid");
+    Self { names }
+  }
+}
+
+impl ItemNames {
+  const FILE: &'static str = "item_names.csv";
+
+  /// Load the built-in names and merge `item_names.csv` from the specified folder, if it exists.
+  /// Names in the file replace the built-in ones.
+  pub fn load_or_default(dir: impl AsRef<Path>) -> Result<Self, Cow<'static, str>> {
+    let mut item_names = Self::default();
+    let path = dir.as_ref().join(Self::FILE);
+    if !path.exists() {
+      return Ok(item_names);
+    }
+
+    let err = |err: &dyn fmt::Display| Cow::from(format!("{}: {err}", path.display()));
+    let text = fs::read_to_string(&path).map_err(|e| err(&e))?;
+    item_names.merge(parse_item_names(&text).map_err(|e| err(&e))?);
+    Ok(item_names)
+  }
+
+  /// Add names, replacing any that are already there.
+  pub fn merge(&mut self, names: HashMap<String, String>) {
+    self.names.extend(names);
+  }
+
+  /// Get the display name for an asset name (e.g. "CraftingOreIronOre"). Names that aren't in the
+  /// table are made with [`heuristic_name`].
+  pub fn display_name<'a>(&'a self, asset: &str) -> Cow<'a, str> {
+    match self.names.get(asset) {
+      Some(name) => Cow::Borrowed(name),
+      None => Cow::Owned(heuristic_name(asset)),
+    }
+  }
+}
+
+/// A line in an item names file that isn't `asset,name`. Line numbers start at one.
+#[derive(Debug)]
+pub struct InvalidLine(usize);
+
+impl fmt::Display for InvalidLine {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {} is not 'asset,name'", self.0)
+  }
+}
+
+impl std::error::Error for InvalidLine {}
+
+/// Parse `asset,name` lines. Blank lines and lines starting with '#' are skipped.
+pub fn parse_item_names(text: &str) -> Result<HashMap<String, String>, InvalidLine> {
+  let mut names = HashMap::new();
+  for (index, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let Some((asset, name)) = line.split_once(',') else {
+      return Err(InvalidLine(index + 1));
+    };
+
+    let (asset, name) = (asset.trim(), name.trim());
+    if asset.is_empty() || name.is_empty() {
+      return Err(InvalidLine(index + 1));
+    }
+    names.insert(asset.into(), name.into());
+  }
+  Ok(names)
+}
+
+/// Make a display name from an asset name by removing a known prefix and splitting the words, e.g.
+/// "DecoWoodenChair2" is "Wooden Chair 2".
+pub fn heuristic_name(asset: &str) -> String {
+  let mut name = asset;
+  for prefix in KNOWN_PREFIXES {
+    // Only remove whole words.
+    if let Some(rest) = name.strip_prefix(prefix) {
+      if rest.starts_with(char::is_uppercase) {
+        name = rest;
+        break;
+      }
+    }
+  }
+
+  let chars: Vec<char> = name.chars().collect();
+  let mut text = String::with_capacity(name.len() + 8);
+  for (idx, &ch) in chars.iter().enumerate() {
+    if ch == '_' || ch.is_whitespace() {
+      if !text.is_empty() && !text.ends_with(' ') {
+        text.push(' ');
+      }
+      continue;
+    }
+
+    if let Some(&prev) = idx.checked_sub(1).map(|idx| &chars[idx]) {
+      let next = chars.get(idx + 1);
+      let word = ch.is_uppercase() && (prev.is_lowercase() || prev.is_ascii_digit());
+
+      // The last capital of an acronym starts the next word ("NPCShop" is "NPC Shop").
+      let acronym =
+        ch.is_uppercase() && prev.is_uppercase() && next.is_some_and(|ch| ch.is_lowercase());
+      let number = ch.is_ascii_digit() && prev.is_alphabetic();
+      if (word || acronym || number) && !text.ends_with(' ') {
+        text.push(' ');
+      }
+    }
+    text.push(ch);
+  }
+
+  text.truncate(text.trim_end().len());
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_heuristic_name() {
+    assert_eq!(heuristic_name("Longsword"), "Longsword");
+    assert_eq!(heuristic_name("CraftingOreCopper"), "Ore Copper");
+    assert_eq!(heuristic_name("DecoWoodenChair2"), "Wooden Chair 2");
+    assert_eq!(heuristic_name("NPCShopSign"), "NPC Shop Sign");
+    assert_eq!(heuristic_name("Potion_of_Healing"), "Potion of Healing");
+
+    // Prefixes are only removed if they're whole words.
+    assert_eq!(heuristic_name("Decoration"), "Decoration");
+    assert_eq!(heuristic_name("Crafting"), "Crafting");
+  }
+
+  #[test]
+  fn test_item_names() {
+    // Mapped names are used instead of the heuristic.
+    let names = ItemNames::default();
+    assert_eq!(names.display_name("CraftingOreIronOre"), "Iron Ore");
+    assert_eq!(heuristic_name("CraftingOreIronOre"), "Ore Iron Ore");
+    assert_eq!(names.display_name("IronIngot"), "Iron Ingot");
+
+    assert!(parse_item_names("IronOre").is_err());
+    assert!(parse_item_names("IronOre,").is_err());
+    assert_eq!(parse_item_names("# asset,name\n\n").unwrap().len(), 0);
+
+    // Local names replace the built-in ones.
+    let dir = std::env::temp_dir().join("cota_test_item_names");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(ItemNames::FILE);
+    fs::write(
+      &path,
+      "CraftingOreIronOre, Ore (Iron)\nLongsword,Sword, Long\n",
+    )
+    .unwrap();
+    let names = ItemNames::load_or_default(&dir).unwrap();
+    assert_eq!(names.display_name("CraftingOreIronOre"), "Ore (Iron)");
+    assert_eq!(names.display_name("Longsword"), "Sword, Long");
+    assert_eq!(names.display_name("IronIngot"), "Iron Ingot");
+
+    fs::write(&path, "CraftingOreIronOre\n").unwrap();
+    let err = ItemNames::load_or_default(&dir).err().unwrap();
+    assert!(err.ends_with("line 1 is not 'asset,name'"));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
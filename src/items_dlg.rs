@@ -63,7 +63,7 @@ impl ItemsDlg {
                 for item in items {
                   body.row(row_size, |mut row| {
                     row.col(|ui| {
-                      ui.label(RichText::from(item.name()).color(NAME_COLOR));
+                      ui.label(RichText::from(item.display_name()).color(NAME_COLOR));
                     });
                     row.col(|ui| {
                       if !item.is_container() {
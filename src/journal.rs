@@ -0,0 +1,292 @@
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::{collections::VecDeque, fmt};
+
+/// Most edits that can be undone.
+pub const MAX_UNDO: usize = 256;
+
+/// Identifies a change in the journal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChangeId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeCategory {
+  Gold,
+  Skill,
+  Item,
+  Other,
+}
+
+impl fmt::Display for ChangeCategory {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let text = match self {
+      ChangeCategory::Gold => "Gold",
+      ChangeCategory::Skill => "Skill",
+      ChangeCategory::Item => "Item",
+      ChangeCategory::Other => "Other",
+    };
+    write!(f, "{text}")
+  }
+}
+
+/// A value as shown in a change description.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeValue {
+  None,
+  Int(i64),
+  Float(f64),
+}
+
+impl ChangeValue {
+  fn format(&self, locale: &Locale) -> String {
+    match self {
+      ChangeValue::None => String::from("none"),
+      ChangeValue::Int(val) => val.to_formatted_string(locale),
+      ChangeValue::Float(val) => {
+        // Format the whole part with separators and keep one decimal place.
+        let text = format!("{val:.1}");
+        let (whole, frac) = text.split_once('.').unwrap_or((&text, "0"));
+        let whole: i64 = whole.parse().unwrap_or(0);
+        let sign = if *val < 0.0 && whole == 0 { "-" } else { "" };
+        format!(
+          "{sign}{}{}{frac}",
+          whole.to_formatted_string(locale),
+          locale.decimal()
+        )
+      }
+    }
+  }
+}
+
+/// The part of the save-game that a change applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Field {
+  Gold,
+
+  /// The whole UserGold record, e.g. from a patch.
+  GoldRecord,
+  Bank,
+  AdvExp,
+  PrdExp,
+  Skill(u32),
+  Item(String),
+  Inventory,
+
+  /// A whole CharacterSheet record, by avatar ID.
+  Sheet(String),
+
+  /// A whole ItemStore record other than the backpack's, by ID. Its JSON is `None` until it's
+  /// changed.
+  Store(String),
+}
+
+impl Field {
+  /// Check if changes to the two fields affect the same data.
+  pub fn overlaps(&self, other: &Field) -> bool {
+    let sheet = |field: &Field| {
+      matches!(
+        field,
+        Field::AdvExp | Field::PrdExp | Field::Skill(_) | Field::Sheet(_)
+      )
+    };
+
+    self == other
+      || matches!(
+        (self, other),
+        (Field::Inventory, Field::Item(_))
+          | (Field::Item(_), Field::Inventory)
+          // Bags in the backpack refer to stores.
+          | (Field::Inventory, Field::Store(_))
+          | (Field::Store(_), Field::Inventory)
+          | (Field::Gold, Field::GoldRecord)
+          | (Field::GoldRecord, Field::Gold)
+      )
+      // Whole sheets might be the loaded avatar's.
+      || (matches!(self, Field::Sheet(_)) && sheet(other))
+      || (matches!(other, Field::Sheet(_)) && sheet(self))
+  }
+}
+
+/// A recorded change, with the field's JSON before the change (`None` if it was absent).
+#[derive(Clone, Debug)]
+pub(crate) struct Change {
+  pub id: ChangeId,
+  pub category: ChangeCategory,
+  pub name: String,
+  pub field: Field,
+  pub old: Option<Value>,
+  pub old_value: ChangeValue,
+  pub new_value: ChangeValue,
+}
+
+/// Changes made to a save-game since it was loaded, oldest first.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Journal {
+  changes: Vec<Change>,
+  next_id: u64,
+}
+
+impl Journal {
+  pub fn next_id(&mut self) -> ChangeId {
+    self.next_id += 1;
+    ChangeId(self.next_id)
+  }
+
+  pub fn push(&mut self, change: Change) {
+    self.changes.push(change);
+  }
+
+  pub fn len(&self) -> usize {
+    self.changes.len()
+  }
+
+  /// Remove the changes with the specified IDs, oldest first.
+  pub fn take(&mut self, ids: &[ChangeId]) -> Vec<Change> {
+    let changes = std::mem::take(&mut self.changes);
+    let (taken, kept) = changes
+      .into_iter()
+      .partition(|change| ids.contains(&change.id));
+    self.changes = kept;
+    taken
+  }
+
+  /// Remove a change, returning it. Fails with the description of the first later change to the
+  /// same data, if there is one.
+  pub fn remove(
+    &mut self,
+    id: ChangeId,
+    locale: &Locale,
+  ) -> Result<Change, Option<ChangeDescription>> {
+    let pos = self
+      .changes
+      .iter()
+      .position(|change| change.id == id)
+      .ok_or(None)?;
+    let field = &self.changes[pos].field;
+    if let Some(later) = self.changes[pos + 1..]
+      .iter()
+      .find(|later| later.field.overlaps(field))
+    {
+      return Err(Some(later.describe(locale)));
+    }
+    Ok(self.changes.remove(pos))
+  }
+
+  pub fn describe(&self, locale: &Locale) -> Vec<ChangeDescription> {
+    self
+      .changes
+      .iter()
+      .map(|change| change.describe(locale))
+      .collect()
+  }
+}
+
+/// Edits that can be undone and redone. An edit is all the changes made by one call, e.g. every
+/// skill set by `GameData::set_skills`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+  // IDs of each edit's changes, oldest edit first.
+  undo: VecDeque<Vec<ChangeId>>,
+
+  // Undone edits, each change with its field's JSON from before it was undone.
+  redo: Vec<Vec<(Change, Option<Value>)>>,
+
+  // Changes of the edit that's being made, and how many edits it's nested in.
+  open: Vec<ChangeId>,
+  depth: usize,
+}
+
+impl History {
+  /// Start an edit. Changes are added to it until the matching [`History::end`].
+  pub fn begin(&mut self) {
+    self.depth += 1;
+  }
+
+  pub fn end(&mut self) {
+    self.depth -= 1;
+    if self.depth == 0 && !self.open.is_empty() {
+      let edit = std::mem::take(&mut self.open);
+      self.push_undo(edit);
+    }
+  }
+
+  /// Add a change, which is an edit of its own unless one was started. Nothing can be redone after
+  /// this.
+  pub fn push(&mut self, id: ChangeId) {
+    self.redo.clear();
+    if self.depth > 0 {
+      self.open.push(id);
+    } else {
+      self.push_undo(vec![id]);
+    }
+  }
+
+  /// Add an edit that can be undone, dropping the oldest if there are too many.
+  pub fn push_undo(&mut self, edit: Vec<ChangeId>) {
+    self.undo.push_back(edit);
+    if self.undo.len() > MAX_UNDO {
+      self.undo.pop_front();
+    }
+  }
+
+  pub fn pop_undo(&mut self) -> Option<Vec<ChangeId>> {
+    self.undo.pop_back()
+  }
+
+  pub fn push_redo(&mut self, edit: Vec<(Change, Option<Value>)>) {
+    self.redo.push(edit);
+  }
+
+  pub fn pop_redo(&mut self) -> Option<Vec<(Change, Option<Value>)>> {
+    self.redo.pop()
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo.is_empty()
+  }
+
+  /// Drop a change that was reverted on its own, along with everything that can be redone.
+  pub fn forget(&mut self, id: ChangeId) {
+    self.redo.clear();
+    for edit in &mut self.undo {
+      edit.retain(|change| *change != id);
+    }
+    self.undo.retain(|edit| !edit.is_empty());
+  }
+}
+
+impl Change {
+  fn describe(&self, locale: &Locale) -> ChangeDescription {
+    ChangeDescription {
+      id: self.id,
+      category: self.category,
+      name: self.name.clone(),
+      old: self.old_value.format(locale),
+      new: self.new_value.format(locale),
+    }
+  }
+}
+
+/// Human readable description of a pending change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeDescription {
+  pub id: ChangeId,
+  pub category: ChangeCategory,
+  pub name: String,
+  pub old: String,
+  pub new: String,
+}
+
+impl fmt::Display for ChangeDescription {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "[{}] {}: {} -> {}",
+      self.category, self.name, self.old, self.new
+    )
+  }
+}
@@ -41,7 +41,7 @@ pub struct StatsIter<'a> {
 }
 
 impl StatsIter<'_> {
-  fn new(text: &str) -> StatsIter {
+  pub(crate) fn new(text: &str) -> StatsIter {
     StatsIter {
       iter: text.split_whitespace(),
     }
@@ -93,9 +93,8 @@ pub async fn get_avatars(log_path: PathBuf, cancel: Cancel) -> Vec<String> {
       return Vec::new();
     }
 
-    let filename = &filename[FILENAME_START.len() + 1..];
-    if let Some(pos) = filename.rfind('_') {
-      name_set.insert(&filename[..pos]);
+    if let Some(avatar) = get_log_file_avatar(filename) {
+      name_set.insert(avatar);
     }
   }
 
@@ -282,7 +281,7 @@ pub async fn find_log_entries(
     }
 
     let path = log_path.join(filename);
-    if let Ok(text) = fs::read_to_string(path) {
+    if let Some(text) = read_search_text(&path, &search) {
       if text.is_empty() || !verify_log_text(&text) {
         continue;
       }
@@ -353,6 +352,24 @@ pub async fn find_log_entries(
   text
 }
 
+/// Read a log file for searching. If the file isn't valid UTF-8 then only the matching lines are
+/// returned, decoded lossily.
+fn read_search_text(path: &Path, search: &Search) -> Option<String> {
+  match String::from_utf8(fs::read(path).ok()?) {
+    Ok(text) => Some(text),
+    Err(err) => {
+      let data = err.into_bytes();
+      let lines = search.find_lines_in_bytes(&data);
+      let mut text = String::new();
+      for line in lines {
+        text.push_str(&util::decode_lossy(line));
+        text.push('\n');
+      }
+      Some(text)
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct Span {
   pub begin: NaiveDateTime,
@@ -575,8 +592,15 @@ fn timestamp_to_file_date(ts: i64) -> String {
   dt.format("%Y-%m-%d").to_string()
 }
 
+/// Get the avatar name from a log filename.
+pub(crate) fn get_log_file_avatar(filename: &str) -> Option<&str> {
+  let name = filename.strip_prefix(FILENAME_START)?.strip_prefix('_')?;
+  let (avatar, _) = name.rsplit_once('_')?;
+  (!avatar.is_empty()).then_some(avatar)
+}
+
 /// Get a NaiveDate from a log filename.
-fn get_log_file_date(path: &Path) -> Option<NaiveDate> {
+pub(crate) fn get_log_file_date(path: &Path) -> Option<NaiveDate> {
   let filename = path.file_stem()?.to_str()?;
   let pos = filename.rfind('_')?;
   let text = &filename[pos + 1..];
@@ -584,7 +608,7 @@ fn get_log_file_date(path: &Path) -> Option<NaiveDate> {
 }
 
 /// Get the log entry date/time as a timestamp.
-fn get_log_timestamp(line: &str, file_date: NaiveDate) -> Option<i64> {
+pub(crate) fn get_log_timestamp(line: &str, file_date: NaiveDate) -> Option<i64> {
   let date = get_log_date(line)?;
   log_date_to_timestamp(&date[1..date.len() - 1], file_date)
 }
@@ -0,0 +1,359 @@
+use crate::{
+  config::Config,
+  log_data,
+  util::{self, BatchError, Cancel, DateRange, ProgressSink, Search},
+};
+use chrono::NaiveDate;
+use futures::{channel::mpsc::UnboundedSender, executor};
+use std::{
+  borrow::Cow,
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// Avatar bucket for log files whose names don't follow the usual format.
+pub const UNKNOWN_AVATAR: &str = "unknown";
+
+const FILENAME_END: &str = ".txt";
+const ADV_LVL: &str = "AdventurerLevel";
+const PRD_LVL: &str = "ProducerLevel";
+const GOLD: &str = "Gold";
+
+/// A chat-log file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFile {
+  pub path: PathBuf,
+  pub avatar: String,
+
+  /// Date from the filename, if it could be parsed.
+  pub date: Option<NaiveDate>,
+}
+
+/// A line that matched a log search.
+#[derive(Clone, Debug)]
+pub struct LogMatch {
+  pub file: PathBuf,
+
+  /// One-based line number.
+  pub line_no: usize,
+
+  /// Timestamp of the log entry, if the line has one.
+  pub timestamp: Option<i64>,
+  pub line: String,
+}
+
+/// The values from a `/stats` dump.
+#[derive(Clone, Debug, Default)]
+pub struct StatsSnapshot {
+  /// When `/stats` was used.
+  pub timestamp: i64,
+  pub adv_lvl: Option<i32>,
+  pub prd_lvl: Option<i32>,
+  pub gold: Option<i64>,
+
+  /// All the other values by name, including skill levels.
+  pub values: BTreeMap<String, f64>,
+}
+
+impl StatsSnapshot {
+  fn new(timestamp: i64, stats: &log_data::StatsData) -> Self {
+    let mut snapshot = Self {
+      timestamp,
+      ..Default::default()
+    };
+
+    for (name, value) in stats.iter() {
+      match name {
+        ADV_LVL => snapshot.adv_lvl = Some(value as i32),
+        PRD_LVL => snapshot.prd_lvl = Some(value as i32),
+        GOLD => snapshot.gold = Some(value as i64),
+        _ => {
+          snapshot.values.insert(name.into(), value);
+        }
+      }
+    }
+    snapshot
+  }
+}
+
+/// Find and parse the most recent `/stats` dump for an avatar.
+pub fn latest_stats(avatar: &str, dir: &Path) -> Result<Option<StatsSnapshot>, Cow<'static, str>> {
+  if !dir.is_dir() {
+    let err = format!("Unable to read folder '{}'", dir.display());
+    return Err(Cow::from(err));
+  }
+
+  let dir = dir.to_owned();
+  let avatar = avatar.to_owned();
+  let cancel = Cancel::default();
+  let future = log_data::get_stats_timestamps(dir.clone(), avatar.clone(), cancel.clone(), None);
+
+  // Timestamps are sorted most recent first.
+  let Some(&ts) = executor::block_on(future).first() else {
+    return Ok(None);
+  };
+
+  let stats = executor::block_on(log_data::get_stats(dir, avatar, ts, cancel));
+  if stats.is_empty() {
+    return Ok(None);
+  }
+
+  Ok(Some(StatsSnapshot::new(ts, &stats)))
+}
+
+/// Get the chat-log folder. The override is used if specified, otherwise the platform's default
+/// SotA chat-log folder is used.
+pub fn log_dir(custom: Option<&Path>) -> Option<PathBuf> {
+  if let Some(dir) = custom {
+    return Some(dir.to_owned());
+  }
+  Config::get_default_log_path()
+}
+
+/// List the log files in a folder, grouped by avatar and sorted by date. Files that don't follow
+/// the `SotAChatLog_<avatar>_<YYYY-MM-DD>.txt` format are listed under [`UNKNOWN_AVATAR`].
+pub fn list_log_files(dir: &Path) -> BTreeMap<String, Vec<LogFile>> {
+  let mut files: BTreeMap<String, Vec<LogFile>> = BTreeMap::new();
+  let entries = ok!(dir.read_dir(), files);
+
+  for entry in entries.flatten() {
+    let Ok(filename) = entry.file_name().into_string() else {
+      continue;
+    };
+
+    if !filename.ends_with(FILENAME_END) || !entry.path().is_file() {
+      continue;
+    }
+
+    let file = parse_log_filename(&filename, entry.path());
+    files.entry(file.avatar.clone()).or_default().push(file);
+  }
+
+  for list in files.values_mut() {
+    list.sort_unstable_by(|a, b| a.date.cmp(&b.date).then_with(|| a.path.cmp(&b.path)));
+  }
+
+  files
+}
+
+/// Keep the log files whose day is within a range. The date comes from the filename or, if the
+/// name doesn't have one, the file's modification time.
+pub fn filter_log_files(files: Vec<LogFile>, dates: &DateRange) -> Vec<LogFile> {
+  files
+    .into_iter()
+    .filter(|file| {
+      let date = file
+        .date
+        .or_else(|| util::file_mtime(&file.path).map(|ts| ts.date()));
+      dates.accepts_date(date)
+    })
+    .collect()
+}
+
+/// Search log files dated within a range, sending each matching line through `tx`. Returns the
+/// number of files searched, which is also in the error if `cancel` or `progress` is canceled or
+/// `tx` is closed.
+pub async fn search_logs(
+  files: Vec<LogFile>,
+  dates: DateRange,
+  search: Search,
+  tx: UnboundedSender<LogMatch>,
+  cancel: Cancel,
+  progress: Option<&dyn ProgressSink>,
+) -> Result<usize, BatchError<usize>> {
+  let files = filter_log_files(files, &dates);
+  let total = files.len();
+  for (done, file) in files.into_iter().enumerate() {
+    let label = file.path.file_name().and_then(|name| name.to_str());
+    if util::report_progress(progress, done, total, label.unwrap_or_default()) {
+      return Err(BatchError::Canceled(done));
+    }
+
+    if cancel.is_canceled() {
+      return Err(BatchError::Canceled(done));
+    }
+
+    let Ok(data) = fs::read(&file.path) else {
+      continue;
+    };
+
+    // Search the raw lines, so that files that aren't valid UTF-8 are searched as is. Only the
+    // matching lines are decoded.
+    let data = data.strip_suffix(b"\n").unwrap_or(&data);
+    for (index, line) in data.split(|byte| *byte == b'\n').enumerate() {
+      if cancel.is_canceled() {
+        return Err(BatchError::Canceled(done));
+      }
+
+      let line = line.strip_suffix(b"\r").unwrap_or(line);
+      if search.find_in_bytes(line).is_none() {
+        continue;
+      }
+
+      let line = util::decode_lossy(line);
+      let timestamp = file
+        .date
+        .and_then(|date| log_data::get_log_timestamp(&line, date));
+      let found = LogMatch {
+        file: file.path.clone(),
+        line_no: index + 1,
+        timestamp,
+        line: line.into_owned(),
+      };
+
+      // The receiver was dropped, so nobody is listening.
+      if tx.unbounded_send(found).is_err() {
+        return Err(BatchError::Canceled(done));
+      }
+    }
+  }
+  Ok(total)
+}
+
+fn parse_log_filename(filename: &str, path: PathBuf) -> LogFile {
+  let avatar = log_data::get_log_file_avatar(filename);
+  let date = util::parse_log_timestamp(filename).map(|ts| ts.date());
+  match avatar.zip(date) {
+    Some((avatar, date)) => LogFile {
+      path,
+      avatar: avatar.into(),
+      date: Some(date),
+    },
+    None => LogFile {
+      path,
+      avatar: UNKNOWN_AVATAR.into(),
+      date: None,
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::{CancelAfter, LineAnchor};
+  use futures::{channel::mpsc, executor, StreamExt};
+
+  #[test]
+  fn test_latest_stats() {
+    let dir = std::env::temp_dir().join("cota_test_latest_stats");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    assert!(latest_stats("Barugon", &dir).unwrap().is_none());
+
+    let text = concat!(
+      "[1/2/2024 9:15:00 PM]  AdventurerLevel: 80 ProducerLevel: 60 Gold: 100\n",
+      "[1/2/2024 9:16:00 PM]  AdventurerLevel: 81 ProducerLevel: 60 Gold: 200 ChainLightning: 40.5\n",
+      "[1/2/2024 9:17:00 PM] Barugon: hello\n",
+    );
+    fs::write(dir.join("SotAChatLog_Barugon_2024-01-02.txt"), text).unwrap();
+
+    let stats = latest_stats("Barugon", &dir).unwrap().unwrap();
+    assert_eq!(stats.timestamp, 1704230160);
+    assert_eq!(stats.adv_lvl, Some(81));
+    assert_eq!(stats.prd_lvl, Some(60));
+    assert_eq!(stats.gold, Some(200));
+    assert_eq!(stats.values.get("ChainLightning"), Some(&40.5));
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(latest_stats("Barugon", &dir).is_err());
+  }
+
+  #[test]
+  fn test_search_logs() {
+    let dir = std::env::temp_dir().join("cota_test_logs");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let text = "[1/2/2024 9:15:00 PM] Barugon: hello\n[1/2/2024 9:16:00 PM] Barugon: bye\n";
+    fs::write(dir.join("SotAChatLog_Barugon_2024-01-02.txt"), text).unwrap();
+    let text = "[1/3/2024 1:00:00 AM] Barugon: hello again\n";
+    fs::write(dir.join("SotAChatLog_Barugon_2024-01-03.txt"), text).unwrap();
+    fs::write(dir.join("SotAChatLog_Barugon.txt"), "hello\n").unwrap();
+    fs::write(dir.join("notes.txt"), "hello\n").unwrap();
+    fs::write(dir.join("readme.md"), "hello\n").unwrap();
+
+    let files = list_log_files(&dir);
+    assert_eq!(files.len(), 2);
+    assert_eq!(files["Barugon"].len(), 2);
+    assert_eq!(files[UNKNOWN_AVATAR].len(), 2);
+
+    // Only search the first day.
+    let date = NaiveDate::from_ymd_opt(2024, 1, 2)
+      .unwrap()
+      .and_hms_opt(0, 0, 0);
+    let dates = DateRange::new(date, date);
+    let search = Search::String {
+      find: "HELLO".into(),
+      ignore_case: true,
+      anchor: LineAnchor::None,
+    };
+    let (tx, rx) = mpsc::unbounded();
+    let logs = files["Barugon"].clone();
+    let searched = search_logs(logs, dates, search, tx, Cancel::default(), None);
+    assert_eq!(executor::block_on(searched), Ok(1));
+    let found: Vec<LogMatch> = executor::block_on(rx.collect());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].line_no, 1);
+    assert_eq!(found[0].timestamp, Some(1704230100));
+    assert!(found[0].line.ends_with("hello"));
+
+    // Canceled searches don't find anything.
+    let mut cancel = Cancel::default();
+    cancel.cancel();
+    let search = Search::String {
+      find: "hello".into(),
+      ignore_case: false,
+      anchor: LineAnchor::None,
+    };
+    let (tx, rx) = mpsc::unbounded();
+    let logs = files.values().flatten().cloned().collect();
+    let searched = search_logs(logs, DateRange::default(), search.clone(), tx, cancel, None);
+    assert_eq!(executor::block_on(searched), Err(BatchError::Canceled(0)));
+    assert_eq!(executor::block_on(rx.collect::<Vec<_>>()).len(), 0);
+
+    // Progress is reported before each file, and canceling it stops the search.
+    let (tx, _rx) = mpsc::unbounded();
+    let progress = CancelAfter::new(1);
+    let logs = files["Barugon"].clone();
+    let searched = search_logs(
+      logs,
+      DateRange::default(),
+      search,
+      tx,
+      Cancel::default(),
+      Some(&progress),
+    );
+    assert_eq!(executor::block_on(searched), Err(BatchError::Canceled(1)));
+    assert_eq!(progress.reports(), [(0, 2), (1, 2)]);
+
+    // Files that aren't valid UTF-8 are still searched.
+    let path = dir.join("SotAChatLog_Barugon_2024-01-04.txt");
+    fs::write(
+      &path,
+      b"[1/4/2024 1:00:00 AM] Barugon: caf\xe9\r\n[1/4/2024 1:01:00 AM] Barugon: hello\r\n",
+    )
+    .unwrap();
+    let search = Search::regex("^.*hello$").unwrap();
+    let (tx, rx) = mpsc::unbounded();
+    let logs = vec![parse_log_filename(
+      "SotAChatLog_Barugon_2024-01-04.txt",
+      path,
+    )];
+    let searched = search_logs(
+      logs,
+      DateRange::default(),
+      search,
+      tx,
+      Cancel::default(),
+      None,
+    );
+    assert_eq!(executor::block_on(searched), Ok(1));
+    let found: Vec<LogMatch> = executor::block_on(rx.collect());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].line_no, 2);
+    assert_eq!(found[0].timestamp, Some(1704330060));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -1,11 +1,14 @@
 // Don't show the console on Windows.
 #![windows_subsystem = "windows"]
+// The save-game modules have more API than the app uses.
+#![allow(dead_code)]
 
 #[macro_use]
 mod util;
 
 mod about_dlg;
 mod app;
+mod backups;
 mod chronometer;
 mod config;
 mod confirm_dlg;
@@ -13,19 +16,32 @@ mod dps_dlg;
 mod ethos;
 mod experience;
 mod farming;
+#[cfg(test)]
+mod fuzz;
 mod game_data;
+mod item_names;
 mod items_dlg;
+mod journal;
 mod log_data;
 mod log_dlg;
+mod logs;
 mod notes_dlg;
 mod offline;
+mod pin;
 mod plant_dlg;
 mod plant_info;
+#[cfg(test)]
+mod save_builder;
+mod save_dirs;
 mod search_dlg;
+mod skill_build;
 mod skill_info;
+mod skill_plan;
 mod stats;
 mod storage;
 mod towns_dlg;
+#[cfg(feature = "update-check")]
+mod update;
 
 use app::App;
 use config::Config;
@@ -36,6 +52,16 @@ use eframe::{
 use util::{APP_ICON, APP_TITLE};
 
 fn main() {
+  #[cfg(debug_assertions)]
+  if let Err(err) = util::validate_exp_tables() {
+    panic!("{err}");
+  }
+
+  #[cfg(debug_assertions)]
+  if let Err(err) = skill_info::SkillRegistry::new().validate() {
+    panic!("{err}");
+  }
+
   let config = Config::new().unwrap();
   let icon = image::load_from_memory(APP_ICON).unwrap();
   let icon = IconData {
@@ -1,25 +1,43 @@
 use self::inner::GameInfo;
 use crate::{
-  game_data::GameData,
+  config::Config,
+  game_data::{self, GameData},
+  item_names::ItemNames,
   items_dlg::ItemsDlg,
-  util::{AppState, Picture, APP_NAME, LVL_RANGE},
+  skill_info::SkillRegistry,
+  util::{self, AppState, ExpTables, Limits, Picture, APP_NAME},
 };
 use eframe::{egui, epaint::Color32};
 use egui::{Button, DragValue, RichText, Ui, WidgetText};
+use num_format::{Locale, ToFormattedString};
 use std::{borrow::Cow, path::PathBuf};
 
 pub struct Offline {
   load_icon: Picture,
   store_icon: Picture,
   items_dlg: ItemsDlg,
+  state: AppState,
   game: Option<GameInfo>,
   error: Option<Cow<'static, str>>,
   changed: bool,
   load_request: bool,
+
+  // The save-game was modified outside of CotA since it was last opened.
+  modified: bool,
+
+  // Path and content hash of the last stored save-game.
+  stored_hash: Option<(PathBuf, u64)>,
+
+  // Problems found when the save-game was loaded.
+  issues: Vec<game_data::ValidationIssue>,
+
+  // Skill information for validating save-games, built once.
+  registry: SkillRegistry,
+  locale: Locale,
 }
 
 impl Offline {
-  pub fn new(state: AppState) -> Self {
+  pub fn new(state: AppState, locale: Locale) -> Self {
     let load_icon = Picture::new(
       format!("{APP_NAME}_load_icon"),
       include_bytes!("../res/load.png"),
@@ -32,15 +50,25 @@ impl Offline {
     let error = None;
     let changed = false;
     let load_request = false;
+    let modified = false;
+    let stored_hash = None;
+    let issues = Vec::new();
+    let registry = SkillRegistry::new();
 
     Offline {
       load_icon,
       store_icon,
-      items_dlg: ItemsDlg::new(state),
+      items_dlg: ItemsDlg::new(state.clone()),
+      state,
       game,
       error,
       changed,
       load_request,
+      modified,
+      stored_hash,
+      issues,
+      registry,
+      locale,
     }
   }
 
@@ -83,7 +111,7 @@ impl Offline {
         ui.label(RichText::from("Adv Lvl").color(LABEL_COLOR));
         if let Some(game) = &mut self.game {
           let mut level = game.adv_level();
-          let widget = DragValue::new(&mut level).range(LVL_RANGE);
+          let widget = DragValue::new(&mut level).range(game.limits().level.clone());
           if ui.add(widget).changed() {
             game.set_adv_level(level);
             self.changed = game.changed();
@@ -97,7 +125,7 @@ impl Offline {
         ui.label(RichText::from("Prd Lvl").color(LABEL_COLOR));
         if let Some(game) = &mut self.game {
           let mut level = game.prd_level();
-          let widget = DragValue::new(&mut level).range(LVL_RANGE);
+          let widget = DragValue::new(&mut level).range(game.limits().level.clone());
           if ui.add(widget).changed() {
             game.set_prd_level(level);
             self.changed = game.changed();
@@ -112,12 +140,34 @@ impl Offline {
         if let Some(game) = &mut self.game {
           let mut gold = game.gold();
           let speed = (gold as f64 / 100.0).max(1.0);
-          let range = 0..=MAX_GOLD;
+          let range = 0..=game.limits().gold;
           let widget = DragValue::new(&mut gold).speed(speed).range(range);
-          if ui.add(widget).changed() {
+          let response = ui.add(widget);
+          if response.changed() {
             game.set_gold(gold);
             self.changed = game.changed();
           }
+          // Large totals are abbreviated, with the full amount after them.
+          let total = game.total_gold();
+          let short = util::abbreviate_i64(total, self.locale);
+          let full = total.to_formatted_string(&self.locale);
+          if short == full {
+            response.on_hover_text(format!("Total: {full}"));
+          } else {
+            response.on_hover_text(format!("Total: {short} ({full})"));
+          }
+
+          // Not all saves have bank gold.
+          if let Some(mut bank) = game.bank_gold() {
+            ui.label(RichText::from("Bank").color(LABEL_COLOR));
+            let speed = (bank as f64 / 100.0).max(1.0);
+            let range = 0..=game.limits().gold;
+            let widget = DragValue::new(&mut bank).speed(speed).range(range);
+            if ui.add(widget).changed() {
+              game.set_bank_gold(bank);
+              self.changed = game.changed();
+            }
+          }
         } else {
           ui.add_enabled_ui(false, |ui| {
             ui.add_sized(ui.spacing().interact_size, Button::new(RichText::default()));
@@ -128,12 +178,21 @@ impl Offline {
 
     ui.separator();
 
+    // Warn before any edits are made that they can't be stored.
+    if let Some(reason) = self.game.as_ref().and_then(|game| game.read_only_reason()) {
+      let text = format!("This save-game is read-only, changes can't be stored: {reason}");
+      ui.label(RichText::from(text).color(Color32::GOLD));
+      ui.separator();
+    }
+
     // Skills.
     if let Some(game) = &mut self.game {
       if game.show_skills(ui) {
         self.changed = game.changed();
       }
     }
+
+    self.state.set_dirty(self.changed);
   }
 
   pub fn show_status(&mut self, ui: &mut Ui) {
@@ -143,27 +202,47 @@ impl Offline {
       } else if let Some(game) = self.game.as_ref() {
         let file_name = game.get_file_name();
         let changed = if self.changed() { "*" } else { "" };
-        ui.label(format!(
-          "Editing {} - {}{}",
+        let modified = if self.modified {
+          " (modified since last opened)"
+        } else {
+          ""
+        };
+        let text = format!(
+          "Editing {} - {}{}{}",
           game.avatar_name(),
           changed,
-          file_name
-        ));
+          file_name,
+          modified
+        );
+
+        if self.issues.is_empty() {
+          ui.label(text);
+        } else {
+          let text = format!("{text} ({} problems found)", self.issues.len());
+          let issues: Vec<String> = self.issues.iter().map(|issue| issue.to_string()).collect();
+          ui.label(WidgetText::from(text).color(Color32::GOLD))
+            .on_hover_text(issues.join("\n"));
+        }
       }
     });
   }
 
   pub fn load(&mut self, path: PathBuf) -> bool {
     self.changed = false;
-    match GameData::load(path) {
+    self.modified = false;
+    match load_game_data(path) {
       Ok(game) => {
+        self.issues = game.validate(&self.registry);
         self.game = Some(GameInfo::new(game));
         self.error = None;
+        self.update_state();
         true
       }
       Err(err) => {
         self.game = None;
+        self.issues.clear();
         self.error = Some(err);
+        self.update_state();
         false
       }
     }
@@ -183,6 +262,8 @@ impl Offline {
       self.error = Some(err);
     } else {
       self.changed = false;
+      self.update_stored_hash();
+      self.update_state();
     }
   }
 
@@ -192,6 +273,24 @@ impl Offline {
       self.error = Some(err);
     } else {
       self.changed = false;
+      self.update_stored_hash();
+      self.update_state();
+    }
+  }
+
+  /// Take the path and content hash of the last stored save-game.
+  pub fn take_stored_hash(&mut self) -> Option<(PathBuf, u64)> {
+    self.stored_hash.take()
+  }
+
+  fn update_stored_hash(&mut self) {
+    let Some(path) = self.file_path() else { return };
+    match game_data::hash_file(&path) {
+      Ok(hash) => self.stored_hash = Some((path, hash)),
+      Err(err) => {
+        let err = format!("Unable to hash '{}': {err}", path.display());
+        self.error = Some(Cow::from(err));
+      }
     }
   }
 
@@ -199,6 +298,14 @@ impl Offline {
     let Some(game) = &mut self.game else { return };
     game.discard_changes();
     self.changed = false;
+    self.update_state();
+  }
+
+  // Publish the loaded file and its dirty state.
+  fn update_state(&mut self) {
+    let path = self.file_path();
+    self.state.set_current_file(path);
+    self.state.set_dirty(self.changed);
   }
 
   pub fn file_name(&self) -> Option<String> {
@@ -206,6 +313,17 @@ impl Offline {
     Some(game.get_file_name())
   }
 
+  /// Hash of the loaded save-game's contents.
+  pub fn content_hash(&self) -> Option<u64> {
+    let game = self.game.as_ref()?;
+    Some(game.content_hash())
+  }
+
+  /// Flag the save-game as modified outside of CotA since it was last opened.
+  pub fn set_modified(&mut self, modified: bool) {
+    self.modified = modified;
+  }
+
   pub fn file_path(&self) -> Option<PathBuf> {
     let game = self.game.as_ref()?;
     Some(game.get_file_path())
@@ -222,11 +340,23 @@ impl Offline {
   }
 }
 
-const MAX_GOLD: i32 = i32::MAX / 2;
+fn load_game_data(path: PathBuf) -> Result<GameData, Cow<'static, str>> {
+  let mut game = GameData::load(path)?;
+
+  // Use custom experience tables, limits and item names if there are any.
+  if let Some(dir) = Config::get_exp_tables_path() {
+    let tables = ExpTables::load_or_default(&dir)?;
+    game.set_limits(Limits::load_or_default(&dir, &tables)?);
+    game.set_item_names(ItemNames::load_or_default(&dir)?);
+    game.set_exp_tables(tables);
+  }
+
+  Ok(game)
+}
 
 mod inner {
   use crate::{
-    game_data::{GameData, Item, SkillLvl, SkillLvlGroup},
+    game_data::{self, GameData, Item, ItemNameKind, SkillLvl, SkillLvlGroup},
     skill_info::SkillCategory,
     util,
   };
@@ -330,17 +460,21 @@ mod inner {
     adv_lvl: i32,
     prd_lvl_cmp: i32,
     prd_lvl: i32,
-    gold_cmp: i32,
-    gold: i32,
+    gold_cmp: i64,
+    gold: i64,
+    bank_cmp: Option<i64>,
+    bank: Option<i64>,
   }
 
   impl GameInfo {
     pub fn new(data: GameData) -> Self {
       let skills = Skills::new(&data);
-      let items = data.get_inventory_items();
+      let mut items = data.get_inventory_items();
+      game_data::sort_items(&mut items, true, ItemNameKind::Display);
       let adv_lvl = data.get_adv_lvl();
       let prd_lvl = data.get_prd_lvl();
       let gold = data.get_gold().unwrap_or(0);
+      let bank = data.get_bank_gold();
 
       GameInfo {
         data,
@@ -352,6 +486,8 @@ mod inner {
         prd_lvl,
         gold_cmp: gold,
         gold,
+        bank_cmp: bank,
+        bank,
       }
     }
 
@@ -390,6 +526,7 @@ mod inner {
         SkillCategory::Adventurer => ("offline_adventurer_skills", &mut self.skills.adv),
         SkillCategory::Producer => ("offline_producer_skills", &mut self.skills.prd),
       };
+      let skill_range = self.data.limits().skill_level.clone();
 
       let mut changed = None;
       ui.vertical(|ui| {
@@ -436,10 +573,15 @@ mod inner {
                                 const SUBDUED_NAME_COLOR: Color32 = Color32::from_rgb(80, 120, 140);
                                 SUBDUED_NAME_COLOR
                               };
-                              ui.label(RichText::from(skill.info.name).color(color));
+                              let response =
+                                ui.label(RichText::from(skill.info.display_name()).color(color));
+                              if let Some(description) = &skill.info.description {
+                                response.on_hover_text(description.as_ref());
+                              }
                             });
                             row.col(|ui| {
-                              let widget = DragValue::new(&mut skill.level).range(0..=200);
+                              let widget =
+                                DragValue::new(&mut skill.level).range(skill_range.clone());
                               if ui.add(widget).changed() {
                                 changed = Some(skill.info.id);
                               }
@@ -484,6 +626,18 @@ mod inner {
       self.data.avatar_name()
     }
 
+    pub fn read_only_reason(&self) -> Option<String> {
+      self.data.read_only_reason()
+    }
+
+    pub fn content_hash(&self) -> u64 {
+      self.data.content_hash()
+    }
+
+    pub fn limits(&self) -> &util::Limits {
+      self.data.limits()
+    }
+
     pub fn items_mut(&mut self) -> &mut Vec<Item> {
       &mut self.items
     }
@@ -504,21 +658,33 @@ mod inner {
       self.prd_lvl = level
     }
 
-    pub fn gold(&self) -> i32 {
+    pub fn gold(&self) -> i64 {
       self.gold
     }
 
-    pub fn set_gold(&mut self, gold: i32) {
+    pub fn set_gold(&mut self, gold: i64) {
       self.gold = gold;
     }
 
+    pub fn bank_gold(&self) -> Option<i64> {
+      self.bank
+    }
+
+    pub fn set_bank_gold(&mut self, gold: i64) {
+      self.bank = Some(gold);
+    }
+
+    pub fn total_gold(&self) -> i64 {
+      self.gold.saturating_add(self.bank.unwrap_or(0))
+    }
+
     pub fn store(&mut self) -> Result<(), Cow<'static, str>> {
-      self.update_json();
+      self.update_json()?;
       let result = self.data.store();
       if result.is_ok() {
         self.accept_changes();
       }
-      result
+      result.map(|_| ()).map_err(Cow::from)
     }
 
     pub fn store_as(&mut self, path: PathBuf) -> Result<(), Cow<'static, str>> {
@@ -529,18 +695,19 @@ mod inner {
         path
       };
 
-      self.update_json();
+      self.update_json()?;
       let result = self.data.store_as(path);
       if result.is_ok() {
         self.accept_changes();
       }
-      result
+      result.map(|_| ()).map_err(Cow::from)
     }
 
     pub fn changed(&self) -> bool {
       self.adv_lvl != self.adv_lvl_cmp
         || self.prd_lvl != self.prd_lvl_cmp
         || self.gold_changed()
+        || self.bank_changed()
         || self.items_changed()
         || changed(&self.skills.adv)
         || changed(&self.skills.prd)
@@ -553,6 +720,7 @@ mod inner {
       self.adv_lvl = self.adv_lvl_cmp;
       self.prd_lvl = self.prd_lvl_cmp;
       self.gold = self.gold_cmp;
+      self.bank = self.bank_cmp;
       discard_changes(&mut self.skills.adv);
       discard_changes(&mut self.skills.prd);
     }
@@ -562,6 +730,9 @@ mod inner {
       if self.gold_changed() {
         self.gold_cmp = self.gold;
       }
+      if self.bank_changed() {
+        self.bank_cmp = self.bank;
+      }
 
       for item in &mut self.items {
         item.accept();
@@ -572,20 +743,28 @@ mod inner {
       accept_changes(&mut self.skills.prd);
     }
 
-    fn update_json(&mut self) {
-      self.data.set_inventory_items(&self.items);
-      self.data.set_adv_lvl(self.adv_lvl);
-      self.data.set_prd_lvl(self.prd_lvl);
-      self.data.set_gold(self.gold);
-      self.data.set_skills(&self.skills.adv);
-      self.data.set_skills(&self.skills.prd);
+    fn update_json(&mut self) -> Result<(), Cow<'static, str>> {
+      self.data.set_inventory_items(&self.items)?;
+      self.data.set_adv_lvl(self.adv_lvl)?;
+      self.data.set_prd_lvl(self.prd_lvl)?;
+      self.data.set_gold(self.gold)?;
+      if let Some(bank) = self.bank {
+        self.data.set_bank_gold(bank)?;
+      }
+      self.data.set_skills(&self.skills.adv)?;
+      self.data.set_skills(&self.skills.prd)?;
+      Ok(())
     }
 
     fn gold_changed(&self) -> bool {
-      if self.gold != self.gold_cmp {
-        return self.gold_cmp < self.gold || self.gold != super::MAX_GOLD;
+      gold_changed(self.gold, self.gold_cmp, self.data.limits().gold)
+    }
+
+    fn bank_changed(&self) -> bool {
+      match (self.bank, self.bank_cmp) {
+        (Some(bank), Some(bank_cmp)) => gold_changed(bank, bank_cmp, self.data.limits().gold),
+        (bank, bank_cmp) => bank != bank_cmp,
       }
-      false
     }
 
     fn items_changed(&self) -> bool {
@@ -636,7 +815,14 @@ mod inner {
     }
   }
 
-  fn changed(groups: &Vec<SkillLvlGroup>) -> bool {
+  fn gold_changed(gold: i64, gold_cmp: i64, max_gold: i64) -> bool {
+    if gold != gold_cmp {
+      return gold_cmp < gold || gold != max_gold;
+    }
+    false
+  }
+
+  fn changed(groups: &[SkillLvlGroup]) -> bool {
     for group in groups {
       if group.changed() {
         return true;
@@ -645,13 +831,13 @@ mod inner {
     false
   }
 
-  fn accept_changes(groups: &mut Vec<SkillLvlGroup>) {
+  fn accept_changes(groups: &mut [SkillLvlGroup]) {
     for group in groups {
       group.accept();
     }
   }
 
-  fn discard_changes(groups: &mut Vec<SkillLvlGroup>) {
+  fn discard_changes(groups: &mut [SkillLvlGroup]) {
     for group in groups {
       group.discard();
     }
@@ -0,0 +1,258 @@
+use crate::{
+  game_data::{hash_file, CharacterTemplate, GameData},
+  skill_info::SkillRegistry,
+  util::Cancel,
+};
+use std::{
+  collections::VecDeque,
+  path::{Path, PathBuf},
+  sync::mpsc,
+  thread::{self, JoinHandle},
+  time::{Duration, Instant, SystemTime},
+};
+
+/// Settings for [`run`].
+#[derive(Clone, Debug)]
+pub struct PinOptions {
+  /// How often the file is checked for changes.
+  pub interval: Duration,
+
+  /// Number of timestamped backups to keep. See [`GameData::set_backup`].
+  pub keep_backups: usize,
+
+  /// Changes in a minute before backing off, e.g. if something else is also rewriting the file.
+  pub max_per_minute: usize,
+  pub backoff: Duration,
+
+  /// Receives each cycle as it happens.
+  pub events: Option<mpsc::Sender<PinCycle>>,
+}
+
+impl Default for PinOptions {
+  fn default() -> Self {
+    Self {
+      interval: Duration::from_secs(1),
+      keep_backups: 5,
+      max_per_minute: 6,
+      backoff: Duration::from_secs(60),
+      events: None,
+    }
+  }
+}
+
+/// What happened when the file changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PinEvent {
+  /// The template was applied and stored, with the number of changes.
+  Applied(usize),
+
+  /// The file already matches the template.
+  Unchanged,
+
+  /// The file changed too often, so it's left alone for [`PinOptions::backoff`].
+  BackedOff,
+
+  /// Loading, applying or storing failed. It's tried again when the file next changes.
+  Failed(String),
+}
+
+/// One check of the file, from [`run`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PinCycle {
+  pub time: SystemTime,
+  pub event: PinEvent,
+}
+
+/// Everything that [`run`] did before it was canceled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PinReport {
+  pub cycles: Vec<PinCycle>,
+}
+
+impl PinReport {
+  /// Number of times the template was stored.
+  pub fn applied(&self) -> usize {
+    let applied = |cycle: &&PinCycle| matches!(cycle.event, PinEvent::Applied(_));
+    self.cycles.iter().filter(applied).count()
+  }
+}
+
+/// Keep a save-game pinned to a template: whenever the file changes (e.g. the game saves), it's
+/// reloaded, the template is applied and it's stored with backups. It's also applied once at the
+/// start. The file is only written between checks for `cancel`, and stores are journaled, so
+/// canceling never leaves it half-written.
+pub fn run(
+  path: PathBuf,
+  template: CharacterTemplate,
+  options: PinOptions,
+  cancel: Cancel,
+) -> JoinHandle<PinReport> {
+  thread::spawn(move || {
+    let registry = SkillRegistry::new();
+    let mut report = PinReport::default();
+    let mut recent = VecDeque::new();
+    let mut last_hash = None;
+    let mut last_stat = None;
+    let mut log = |event| {
+      let cycle = PinCycle {
+        time: SystemTime::now(),
+        event,
+      };
+      if let Some(events) = &options.events {
+        // Nobody is listening if the receiver was dropped.
+        let _ = events.send(cycle.clone());
+      }
+      report.cycles.push(cycle);
+    };
+
+    while !cancel.is_canceled() {
+      // Only read the whole file if its modification time or size changed.
+      let stat = file_stat(&path);
+      if stat.is_some() && stat == last_stat {
+        wait(options.interval, &cancel);
+        continue;
+      }
+
+      let hash = hash_file(&path).ok();
+      if hash.is_some() && hash != last_hash {
+        // Something else keeps rewriting the file, so stop fighting it for a while.
+        let now = Instant::now();
+        let minute = Duration::from_secs(60);
+        recent.retain(|time| now.duration_since(*time) < minute);
+        if recent.len() >= options.max_per_minute {
+          recent.clear();
+          log(PinEvent::BackedOff);
+          wait(options.backoff, &cancel);
+          continue;
+        }
+        recent.push_back(now);
+
+        // A failure isn't retried until the file changes again.
+        last_hash = hash;
+        last_stat = stat;
+        match apply(&path, &template, &registry, options.keep_backups) {
+          Ok(changes) => {
+            if changes > 0 {
+              last_stat = file_stat(&path);
+              last_hash = hash_file(&path).ok();
+              log(PinEvent::Applied(changes));
+            } else {
+              log(PinEvent::Unchanged);
+            }
+          }
+          Err(err) => log(PinEvent::Failed(err)),
+        }
+      } else if hash.is_some() {
+        // Touched but not changed.
+        last_stat = stat;
+      }
+
+      wait(options.interval, &cancel);
+    }
+
+    report
+  })
+}
+
+// Load the file, apply the template and store it if anything changed. Returns the number of
+// changes.
+fn apply(
+  path: &Path,
+  template: &CharacterTemplate,
+  registry: &SkillRegistry,
+  keep: usize,
+) -> Result<usize, String> {
+  let mut game = GameData::load(path).map_err(|err| err.to_string())?;
+  game.set_backup(true, keep);
+
+  let applied = game.apply_template(template, registry, None);
+  let changes = applied.map_err(|err| err.to_string())?.changes;
+  if changes > 0 {
+    game.store().map_err(|err| err.to_string())?;
+  }
+  Ok(changes)
+}
+
+// Get a file's modification time and size, which change whenever it's written.
+fn file_stat(path: &Path) -> Option<(SystemTime, u64)> {
+  let meta = std::fs::metadata(path).ok()?;
+  Some((meta.modified().ok()?, meta.len()))
+}
+
+// Sleep, waking up early if canceled.
+fn wait(duration: Duration, cancel: &Cancel) {
+  let instant = Instant::now();
+  while instant.elapsed() < duration && !cancel.is_canceled() {
+    let left = duration.saturating_sub(instant.elapsed());
+    thread::sleep(left.min(Duration::from_millis(50)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::save_builder::SaveBuilder;
+  use std::fs;
+
+  fn gold(path: &Path) -> Option<i64> {
+    GameData::load(path).ok()?.get_gold()
+  }
+
+  fn next_event(rx: &mpsc::Receiver<PinCycle>) -> PinEvent {
+    rx.recv_timeout(Duration::from_secs(10)).unwrap().event
+  }
+
+  #[test]
+  fn test_pin() {
+    let dir = std::env::temp_dir().join("cota_test_pin");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("SavedGame.sota");
+    let temp = dir.join("SavedGame.tmp");
+
+    // Replace the file all at once so that it's never seen half-written.
+    let game_save = |gold| {
+      fs::write(&temp, SaveBuilder::new().gold(gold).build()).unwrap();
+      fs::rename(&temp, &path).unwrap();
+    };
+    game_save(100);
+
+    let template = CharacterTemplate {
+      gold: Some(777),
+      ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let options = PinOptions {
+      interval: Duration::from_millis(10),
+      max_per_minute: 2,
+      backoff: Duration::from_secs(600),
+      events: Some(tx),
+      ..Default::default()
+    };
+    let mut cancel = Cancel::default();
+    let thread = run(path.clone(), template, options, cancel.clone());
+
+    // Applied at the start, and again when the game saves.
+    assert_eq!(next_event(&rx), PinEvent::Applied(1));
+    assert_eq!(gold(&path), Some(777));
+    game_save(5);
+    assert_eq!(next_event(&rx), PinEvent::Applied(1));
+    assert_eq!(gold(&path), Some(777));
+
+    // Backs off from the third change in a minute.
+    game_save(6);
+    assert_eq!(next_event(&rx), PinEvent::BackedOff);
+    assert_eq!(gold(&path), Some(6));
+
+    // Canceling doesn't wait for the back off.
+    cancel.cancel();
+    let report = thread.join().unwrap();
+    assert_eq!(report.applied(), 2);
+    assert_eq!(report.cycles.len(), 3);
+
+    let backups = fs::read_dir(&dir).unwrap().count() - 1;
+    assert_eq!(backups, 2);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,277 @@
+use serde_json::{json, Map, Value};
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="utf-8"?><Data>"#;
+const FOOTER: &str = "</Data>";
+const USER_ID: &str = "000000000000000000000001";
+const DATE: i64 = 1600000000000;
+
+struct BuilderItem {
+  asset: String,
+  count: u64,
+  durability: Option<(f64, f64)>,
+}
+
+impl BuilderItem {
+  fn to_json(&self, bag: Option<&str>) -> Value {
+    // Keys are added in alphabetical order.
+    let mut val = json!({ "an": self.asset });
+    if let Some(bag) = bag {
+      val["bag"] = bag.into();
+    }
+    if let Some((minor, major)) = self.durability {
+      val["hp"] = minor.into();
+      val["php"] = major.into();
+    }
+    val["qn"] = self.count.into();
+    json!({ "in": val })
+  }
+}
+
+/// Build synthetic save-game text for tests.
+///
+/// ```ignore
+/// let text = SaveBuilder::new()
+///   .avatar("Barugon")
+///   .gold(1234)
+///   .skill(3, 1440)
+///   .item("Items/Crafting/IronOre", 50)
+///   .build();
+/// let game = GameData::from_text(text).unwrap();
+/// ```
+pub struct SaveBuilder {
+  name: String,
+  adv_exp: i64,
+  prd_exp: i64,
+  gold: i64,
+  bank_gold: Option<i64>,
+  skills: Vec<(u32, i64, i64)>,
+  items: Vec<BuilderItem>,
+  bags: Vec<(BuilderItem, Vec<BuilderItem>)>,
+  backpack: bool,
+  quoted: bool,
+}
+
+impl Default for SaveBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SaveBuilder {
+  pub fn new() -> Self {
+    Self {
+      name: String::from("Avatar"),
+      adv_exp: 0,
+      prd_exp: 0,
+      gold: 0,
+      bank_gold: None,
+      skills: Vec::new(),
+      items: Vec::new(),
+      bags: Vec::new(),
+      backpack: true,
+      quoted: false,
+    }
+  }
+
+  /// Avatar ID used by the save-game.
+  pub fn avatar_id() -> String {
+    id(0)
+  }
+
+  /// Backpack ID used by the save-game.
+  pub fn backpack_id() -> String {
+    id(1)
+  }
+
+  /// Avatar name.
+  pub fn avatar(mut self, name: &str) -> Self {
+    self.name = name.into();
+    self
+  }
+
+  /// Pooled adventurer experience.
+  pub fn adv_exp(mut self, exp: i64) -> Self {
+    self.adv_exp = exp;
+    self
+  }
+
+  /// Pooled producer experience.
+  pub fn prd_exp(mut self, exp: i64) -> Self {
+    self.prd_exp = exp;
+    self
+  }
+
+  pub fn gold(mut self, gold: i64) -> Self {
+    self.gold = gold;
+    self
+  }
+
+  /// Add a UserBank record.
+  pub fn bank_gold(mut self, gold: i64) -> Self {
+    self.bank_gold = Some(gold);
+    self
+  }
+
+  /// Add a trained skill. Without skills, `sk2` is empty.
+  pub fn skill(mut self, id: u32, exp: i64) -> Self {
+    self.skills.push((id, exp, 0));
+    self
+  }
+
+  /// Add a trained skill with the rank (`m`) set.
+  pub fn ranked_skill(mut self, id: u32, exp: i64, rank: i64) -> Self {
+    self.skills.push((id, exp, rank));
+    self
+  }
+
+  /// Add an item to the backpack.
+  pub fn item(mut self, asset: &str, count: u64) -> Self {
+    let asset = asset.into();
+    let durability = None;
+    self.items.push(BuilderItem {
+      asset,
+      count,
+      durability,
+    });
+    self
+  }
+
+  /// Add an item with durability to the backpack.
+  pub fn durable_item(mut self, asset: &str, minor: f64, major: f64) -> Self {
+    let asset = asset.into();
+    let durability = Some((minor, major));
+    self.items.push(BuilderItem {
+      asset,
+      count: 1,
+      durability,
+    });
+    self
+  }
+
+  /// Add a bag to the backpack. The bag's contents are in their own ItemStore record.
+  pub fn bag(mut self, asset: &str, items: &[(&str, u64)]) -> Self {
+    let bag = BuilderItem {
+      asset: asset.into(),
+      count: 1,
+      durability: None,
+    };
+    let items = items
+      .iter()
+      .map(|(asset, count)| BuilderItem {
+        asset: (*asset).into(),
+        count: *count,
+        durability: None,
+      })
+      .collect();
+    self.bags.push((bag, items));
+    self
+  }
+
+  /// Leave out the backpack's ItemStore record.
+  pub fn without_backpack(mut self) -> Self {
+    self.backpack = false;
+    self
+  }
+
+  /// Store experience, gold and skill experience as JSON strings.
+  pub fn quoted_numbers(mut self) -> Self {
+    self.quoted = true;
+    self
+  }
+
+  pub fn build(&self) -> String {
+    let avatar = Self::avatar_id();
+    let backpack = Self::backpack_id();
+    let mut next_id = 2;
+    let mut next_id = || {
+      next_id += 1;
+      id(next_id - 1)
+    };
+
+    // Backpack and bag contents.
+    let mut contents = Map::new();
+    for item in &self.items {
+      contents.insert(next_id(), item.to_json(None));
+    }
+
+    let mut stores = Vec::new();
+    for (bag, items) in &self.bags {
+      let bag_id = next_id();
+      let store_id = next_id();
+      contents.insert(bag_id, bag.to_json(Some(&store_id)));
+
+      let mut bag_contents = Map::new();
+      for item in items {
+        bag_contents.insert(next_id(), item.to_json(None));
+      }
+      stores.push((store_id, json!({ "in": bag_contents })));
+    }
+
+    if self.backpack {
+      stores.insert(0, (backpack.clone(), json!({ "in": contents })));
+    }
+
+    let mut sk2 = Map::new();
+    for (id, exp, rank) in &self.skills {
+      let skill = json!({
+        "m": rank,
+        "t": { "$date": DATE },
+        "x": self.number(*exp),
+      });
+      sk2.insert(id.to_string(), skill);
+    }
+
+    let sheet = json!({
+      "ae": self.number(self.adv_exp),
+      "pe": self.number(self.prd_exp),
+      "sk2": sk2,
+    });
+
+    let mut text = String::from(HEADER);
+    push_collection(
+      &mut text,
+      "User",
+      &[(USER_ID.into(), json!({ "dc": avatar }))],
+    );
+    push_collection(
+      &mut text,
+      "CharacterName",
+      &[(avatar.clone(), json!({ "fn": self.name }))],
+    );
+    push_collection(
+      &mut text,
+      "Character",
+      &[(avatar.clone(), json!({ "mainbp": backpack }))],
+    );
+    push_collection(&mut text, "CharacterSheet", &[(avatar, sheet)]);
+    push_collection(&mut text, "ItemStore", &stores);
+    let gold = json!({ "g": self.number(self.gold) });
+    push_collection(&mut text, "UserGold", &[(USER_ID.into(), gold)]);
+    if let Some(bank) = self.bank_gold {
+      let bank = json!({ "g": self.number(bank) });
+      push_collection(&mut text, "UserBank", &[(USER_ID.into(), bank)]);
+    }
+    text.push_str(FOOTER);
+    text
+  }
+
+  fn number(&self, val: i64) -> Value {
+    if self.quoted {
+      val.to_string().into()
+    } else {
+      val.into()
+    }
+  }
+}
+
+fn id(idx: u32) -> String {
+  format!("5ad7aa2a0f0e5b3c2c7d{:04x}", 0x1e01 + idx)
+}
+
+fn push_collection(text: &mut String, name: &str, records: &[(String, Value)]) {
+  text.push_str(&format!(r#"<collection name="{name}">"#));
+  for (id, val) in records {
+    text.push_str(&format!(r#"<record Id="{id}">{val}</record>"#));
+  }
+  text.push_str("</collection>");
+}
@@ -0,0 +1,436 @@
+use crate::game_data::SOTA_EXT;
+use std::{
+  ffi::OsString,
+  fmt, fs,
+  path::{Path, PathBuf},
+};
+
+/// Steam's app ID for Shroud of the Avatar, which names its Proton prefix.
+const STEAM_APP_ID: &str = "326160";
+
+/// How the game that uses a save-game folder was installed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstallKind {
+  Standalone,
+  Steam,
+
+  /// Run with Wine, outside of Steam.
+  Wine,
+}
+
+impl fmt::Display for InstallKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let text = match self {
+      InstallKind::Standalone => "Standalone",
+      InstallKind::Steam => "Steam",
+      InstallKind::Wine => "Wine",
+    };
+    write!(f, "{text}")
+  }
+}
+
+/// A folder with save-games in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveDir {
+  pub path: PathBuf,
+  pub kind: InstallKind,
+}
+
+/// Get the folders that contain save-games, in the order that they're most likely to be used.
+pub fn default_save_dirs() -> Vec<SaveDir> {
+  let var = |name: &str| std::env::var_os(name);
+  let os = std::env::consts::OS;
+
+  let mut steam = Vec::new();
+
+  #[cfg(windows)]
+  steam.extend(registry_steam_root());
+
+  steam.extend(steam_roots(os, var));
+  find_save_dirs(os, var, &steam)
+}
+
+/// Find the candidate save-game folders for `os` that exist and have save-games in them.
+/// Environment variables are looked up with `var` and `steam` has the Steam folders to look in.
+fn find_save_dirs(
+  os: &str,
+  var: impl Fn(&str) -> Option<OsString>,
+  steam: &[PathBuf],
+) -> Vec<SaveDir> {
+  let mut found: Vec<SaveDir> = Vec::new();
+  for candidate in save_dir_candidates(os, var, &steam_libraries(steam)) {
+    if !found.iter().any(|dir| dir.path == candidate.path) && has_save_games(&candidate.path) {
+      found.push(candidate);
+    }
+  }
+  found
+}
+
+fn save_dir_candidates(
+  os: &str,
+  var: impl Fn(&str) -> Option<OsString>,
+  libraries: &[PathBuf],
+) -> Vec<SaveDir> {
+  let path = |name: &str| var(name).filter(|val| !val.is_empty()).map(PathBuf::from);
+  let sota = |dir: PathBuf| {
+    dir
+      .join("Portalarium")
+      .join("Shroud of the Avatar")
+      .join("SavedGames")
+  };
+
+  // Windows style roaming app data within a Wine or Proton prefix.
+  let roaming = |prefix: PathBuf, user: &str| {
+    let dir = prefix.join("drive_c").join("users").join(user);
+    sota(dir.join("AppData").join("Roaming"))
+  };
+
+  // The game uses its usual folder when Steam runs it natively.
+  let native = if libraries.iter().any(|dir| has_game_manifest(dir)) {
+    InstallKind::Steam
+  } else {
+    InstallKind::Standalone
+  };
+
+  let dir = |path, kind| SaveDir { path, kind };
+  let home = path("HOME");
+  let mut dirs = Vec::new();
+  match os {
+    "windows" => {
+      dirs.extend(path("APPDATA").map(|app_data| dir(sota(app_data), native)));
+      if let Some(profile) = path("USERPROFILE") {
+        let app_data = profile.join("AppData").join("Roaming");
+        dirs.push(dir(sota(app_data), native));
+      }
+
+      // Portable installs keep their data next to the game.
+      for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+        if let Some(programs) = path(var) {
+          let path = programs.join("Shroud of the Avatar").join("SavedGames");
+          dirs.push(dir(path, InstallKind::Standalone));
+        }
+      }
+    }
+    "macos" => {
+      if let Some(home) = home {
+        let support = home.join("Library").join("Application Support");
+        dirs.push(dir(sota(support), native));
+      }
+    }
+    _ => {
+      let config = path("XDG_CONFIG_HOME").or_else(|| Some(home.as_ref()?.join(".config")));
+      dirs.extend(config.map(|config| dir(sota(config), native)));
+
+      // Proton keeps a prefix for the game in the library that it's installed in.
+      for library in libraries {
+        let prefix = library
+          .join("steamapps")
+          .join("compatdata")
+          .join(STEAM_APP_ID)
+          .join("pfx");
+        dirs.push(dir(roaming(prefix, "steamuser"), InstallKind::Steam));
+      }
+
+      let user = var("USER").and_then(|user| user.into_string().ok());
+      if let Some(user) = &user {
+        let prefixes = [path("WINEPREFIX"), home.map(|home| home.join(".wine"))];
+        for prefix in prefixes.into_iter().flatten() {
+          dirs.push(dir(roaming(prefix, user), InstallKind::Wine));
+        }
+      }
+    }
+  }
+  dirs
+}
+
+fn has_save_games(dir: &Path) -> bool {
+  let Ok(entries) = dir.read_dir() else {
+    return false;
+  };
+
+  entries.flatten().any(|entry| {
+    let path = entry.path();
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    ext.is_some_and(|ext| ext.eq_ignore_ascii_case(SOTA_EXT)) && path.is_file()
+  })
+}
+
+/// Check if the game is installed in a Steam library.
+fn has_game_manifest(library: &Path) -> bool {
+  let name = format!("appmanifest_{STEAM_APP_ID}.acf");
+  library.join("steamapps").join(name).is_file()
+}
+
+/// Get the usual Steam folders for `os`.
+fn steam_roots(os: &str, var: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+  let path = |name: &str| var(name).filter(|val| !val.is_empty()).map(PathBuf::from);
+  match os {
+    "windows" => ["ProgramFiles(x86)", "ProgramFiles"]
+      .into_iter()
+      .filter_map(path)
+      .map(|dir| dir.join("Steam"))
+      .collect(),
+    "macos" => path("HOME")
+      .map(|home| {
+        home
+          .join("Library")
+          .join("Application Support")
+          .join("Steam")
+      })
+      .into_iter()
+      .collect(),
+    _ => {
+      let Some(home) = path("HOME") else {
+        return Vec::new();
+      };
+
+      let flatpak = home
+        .join(".var")
+        .join("app")
+        .join("com.valvesoftware.Steam");
+      vec![
+        home.join(".steam").join("steam"),
+        home.join(".local").join("share").join("Steam"),
+        flatpak.join(".local").join("share").join("Steam"),
+      ]
+    }
+  }
+}
+
+/// Get the Steam folder from the registry.
+#[cfg(windows)]
+fn registry_steam_root() -> Option<PathBuf> {
+  use std::os::windows::process::CommandExt;
+
+  // Don't flash a console window.
+  const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+  let output = std::process::Command::new("reg")
+    .args(["query", r"HKCU\Software\Valve\Steam", "/v", "SteamPath"])
+    .creation_flags(CREATE_NO_WINDOW)
+    .output()
+    .ok()?;
+
+  let text = String::from_utf8_lossy(&output.stdout);
+  let line = text
+    .lines()
+    .find(|line| line.trim_start().starts_with("SteamPath"))?;
+  let (_, path) = line.split_once("REG_SZ")?;
+  Some(PathBuf::from(path.trim()))
+}
+
+/// Get the existing Steam libraries, including the Steam folders themselves.
+fn steam_libraries(roots: &[PathBuf]) -> Vec<PathBuf> {
+  let mut libraries = Vec::new();
+  let mut add = |dir: &Path| {
+    // Some of the usual folders are links to the others.
+    let Ok(dir) = fs::canonicalize(dir) else {
+      return;
+    };
+
+    if !libraries.contains(&dir) {
+      libraries.push(dir);
+    }
+  };
+
+  for root in roots {
+    add(root);
+
+    // A missing or malformed library list just means that there aren't any other libraries.
+    let path = root.join("steamapps").join("libraryfolders.vdf");
+    let Ok(text) = fs::read_to_string(path) else {
+      continue;
+    };
+
+    for library in parse_library_folders(&text).unwrap_or_default() {
+      add(&library);
+    }
+  }
+  libraries
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VdfToken {
+  Text(String),
+  Open,
+  Close,
+}
+
+/// Get the library paths from Steam's `libraryfolders.vdf`. Returns `None` if it's malformed.
+fn parse_library_folders(text: &str) -> Option<Vec<PathBuf>> {
+  let mut tokens = vdf_tokens(text)?.into_iter().peekable();
+  let mut paths = Vec::new();
+  let mut depth = 0usize;
+  while let Some(token) = tokens.next() {
+    match token {
+      VdfToken::Open => depth += 1,
+      VdfToken::Close => depth = depth.checked_sub(1)?,
+      VdfToken::Text(key) => match tokens.peek()? {
+        VdfToken::Text(_) => {
+          let Some(VdfToken::Text(val)) = tokens.next() else {
+            unreachable!();
+          };
+
+          // Libraries are `"N" { "path" "..." }`, or `"N" "..."` in older files.
+          if (depth == 2 && key == "path") || (depth == 1 && key.parse::<u32>().is_ok()) {
+            paths.push(PathBuf::from(val));
+          }
+        }
+        VdfToken::Open => (),
+        VdfToken::Close => return None,
+      },
+    }
+  }
+  (depth == 0).then_some(paths)
+}
+
+/// Split VDF text into quoted strings and braces.
+fn vdf_tokens(text: &str) -> Option<Vec<VdfToken>> {
+  let mut tokens = Vec::new();
+  let mut chars = text.chars().peekable();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '{' => tokens.push(VdfToken::Open),
+      '}' => tokens.push(VdfToken::Close),
+      '/' if chars.peek() == Some(&'/') => {
+        for ch in chars.by_ref() {
+          if ch == '\n' {
+            break;
+          }
+        }
+      }
+      '"' => {
+        let mut val = String::new();
+        loop {
+          match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+              'n' => val.push('\n'),
+              't' => val.push('\t'),
+              ch => val.push(ch),
+            },
+            ch => val.push(ch),
+          }
+        }
+        tokens.push(VdfToken::Text(val));
+      }
+      ch if ch.is_whitespace() => (),
+      _ => return None,
+    }
+  }
+  Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_default_save_dirs() {
+    let root = std::env::temp_dir().join("cota_test_save_dirs");
+    let _ = fs::remove_dir_all(&root);
+    let home = root.join("home");
+    let native = home
+      .join(".config")
+      .join("Portalarium")
+      .join("Shroud of the Avatar")
+      .join("SavedGames");
+    let wine = home
+      .join(".wine/drive_c/users/barugon")
+      .join("AppData/Roaming/Portalarium/Shroud of the Avatar/SavedGames");
+    fs::create_dir_all(&native).unwrap();
+    fs::create_dir_all(&wine).unwrap();
+    fs::write(wine.join("SavedGame.SOTA"), "").unwrap();
+
+    let env = |name: &str| match name {
+      "HOME" => Some(OsString::from(&home)),
+      "USER" => Some(OsString::from("barugon")),
+      _ => None,
+    };
+
+    // The native folder doesn't have any save-games yet.
+    let steam = steam_roots("linux", env);
+    let dirs = find_save_dirs("linux", env, &steam);
+    assert_eq!(dirs.len(), 1);
+    assert_eq!(dirs[0].kind, InstallKind::Wine);
+
+    fs::write(native.join("SavedGame.sota"), "").unwrap();
+    let dirs = find_save_dirs("linux", env, &steam);
+    assert_eq!(dirs[0].path, native);
+    assert_eq!(dirs[0].kind, InstallKind::Standalone);
+    assert_eq!(dirs[1].path, wine);
+
+    // Nothing is found without the environment.
+    assert!(find_save_dirs("linux", |_| None, &[]).is_empty());
+    assert!(save_dir_candidates("windows", |_| None, &[]).is_empty());
+
+    let app_data = root.join("AppData").join("Roaming");
+    let env = |name: &str| (name == "APPDATA").then(|| OsString::from(&app_data));
+    let dirs = save_dir_candidates("windows", env, &[]);
+    assert_eq!(dirs.len(), 1);
+    assert!(dirs[0].path.starts_with(&app_data));
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_steam_save_dirs() {
+    let root = std::env::temp_dir().join("cota_test_steam_dirs");
+    let _ = fs::remove_dir_all(&root);
+    let home = root.join("home");
+    let steam = home.join(".local").join("share").join("Steam");
+    let library = root.join("games").join("SteamLibrary");
+    let proton = library
+      .join("steamapps/compatdata/326160/pfx/drive_c/users/steamuser")
+      .join("AppData/Roaming/Portalarium/Shroud of the Avatar/SavedGames");
+    fs::create_dir_all(steam.join("steamapps")).unwrap();
+    fs::create_dir_all(&proton).unwrap();
+    fs::write(proton.join("SavedGame.sota"), "").unwrap();
+    fs::write(library.join("steamapps/appmanifest_326160.acf"), "").unwrap();
+
+    let vdf = format!(
+      "\"libraryfolders\"\n{{\n  \"0\"\n  {{\n    \"path\" \"{}\"\n  }}\n  \"1\"\n  {{\n    \"path\" \"{}\"\n    \"apps\" {{ \"326160\" \"1\" }}\n  }}\n}}\n",
+      steam.display(),
+      library.display()
+    );
+    fs::write(steam.join("steamapps/libraryfolders.vdf"), vdf).unwrap();
+
+    let env = |name: &str| (name == "HOME").then(|| OsString::from(&home));
+    let dirs = find_save_dirs("linux", env, &steam_roots("linux", env));
+    assert_eq!(dirs.len(), 1);
+    assert_eq!(dirs[0].kind, InstallKind::Steam);
+    assert!(dirs[0].path.ends_with("SavedGames"));
+    assert!(dirs[0]
+      .path
+      .starts_with(fs::canonicalize(&library).unwrap()));
+
+    // The library list is ignored if it's malformed.
+    fs::write(
+      steam.join("steamapps/libraryfolders.vdf"),
+      "\"libraryfolders\" {",
+    )
+    .unwrap();
+    assert!(find_save_dirs("linux", env, &steam_roots("linux", env)).is_empty());
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_parse_library_folders() {
+    let text = r#"
+      // Older files list the libraries directly.
+      "LibraryFolders"
+      {
+        "TimeNextStatsReport" "1700000000"
+        "1" "D:\\SteamLibrary"
+      }
+    "#;
+    let paths = parse_library_folders(text).unwrap();
+    assert_eq!(paths, [PathBuf::from(r"D:\SteamLibrary")]);
+
+    assert_eq!(parse_library_folders(r#""a" { "b" "#), None);
+    assert_eq!(parse_library_folders(r#""a" { } }"#), None);
+    assert_eq!(parse_library_folders(r#""a" { "path" }"#), None);
+    assert_eq!(parse_library_folders("a { }"), None);
+    assert_eq!(parse_library_folders(""), Some(Vec::new()));
+  }
+}
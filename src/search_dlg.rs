@@ -1,10 +1,9 @@
-use crate::util::{AppState, Search};
+use crate::util::{AppState, LineAnchor, Search};
 use eframe::{
-  egui::{Context, Key, Layout, RichText, Window},
+  egui::{ComboBox, Context, Key, Layout, RichText, Window},
   emath::{Align, Align2},
   epaint::Color32,
 };
-use regex::Regex;
 
 pub struct SearchDlg {
   state: AppState,
@@ -13,6 +12,7 @@ pub struct SearchDlg {
   error: String,
   search: Option<Search>,
   search_type: SearchType,
+  anchor: LineAnchor,
   visible: bool,
   focus: bool,
 }
@@ -27,6 +27,7 @@ impl SearchDlg {
       error: String::new(),
       search: None,
       search_type: SearchType::Default,
+      anchor: LineAnchor::None,
       visible: false,
       focus: false,
     }
@@ -88,6 +89,17 @@ impl SearchDlg {
                   _ => SearchType::NoCase,
                 };
               }
+
+              // Line anchors are for literal searches; regular expressions have `^` and `$`.
+              ui.add_enabled_ui(self.search_type != SearchType::Regex, |ui| {
+                ComboBox::from_id_source("search_anchor_combo")
+                  .selected_text(self.anchor.label())
+                  .show_ui(ui, |ui| {
+                    for anchor in LineAnchor::ALL {
+                      ui.selectable_value(&mut self.anchor, anchor, anchor.label());
+                    }
+                  });
+              });
             });
           });
         });
@@ -123,12 +135,16 @@ impl SearchDlg {
           let ignore_case = self.search_type == SearchType::NoCase;
           let mut find = String::new();
           std::mem::swap(&mut find, &mut self.text);
-          Some(Search::String { find, ignore_case })
+          Some(Search::String {
+            find,
+            ignore_case,
+            anchor: self.anchor,
+          })
         }
-        SearchType::Regex => match Regex::new(&self.text) {
-          Ok(regex) => {
+        SearchType::Regex => match Search::regex(&self.text) {
+          Ok(search) => {
             self.text.clear();
-            Some(Search::Regex(regex))
+            Some(search)
           }
           Err(err) => {
             self.text = format!("{err:?}");
@@ -0,0 +1,137 @@
+use crate::skill_info::{self, SkillCategory};
+use serde::{Deserialize, Serialize};
+use std::{
+  borrow::Cow,
+  collections::{BTreeMap, HashSet},
+  fmt, fs,
+  path::Path,
+};
+
+/// Skill levels for one category, in a form that can be shared as a JSON file. See
+/// [`GameData::export_build`](crate::game_data::GameData::export_build).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Build {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  pub category: SkillCategory,
+
+  /// Skill levels by skill ID. Level 0 removes the skill when the build is applied.
+  pub skills: BTreeMap<u32, i32>,
+}
+
+impl Build {
+  pub fn new(category: SkillCategory) -> Self {
+    Self {
+      name: None,
+      category,
+      skills: BTreeMap::new(),
+    }
+  }
+
+  /// Read a build from a JSON file.
+  pub fn load(path: &Path) -> Result<Self, Cow<'static, str>> {
+    let err = |err: &dyn fmt::Display| Cow::from(format!("{}: {err}", path.display()));
+    let text = fs::read_to_string(path).map_err(|e| err(&e))?;
+    serde_json::from_str(&text).map_err(|e| err(&e))
+  }
+
+  /// Write the build to a JSON file.
+  pub fn save(&self, path: &Path) -> Result<(), Cow<'static, str>> {
+    let text = serde_json::to_string_pretty(self).unwrap();
+    if let Err(err) = fs::write(path, text) {
+      let err = format!("Unable to write '{}': {err}", path.display());
+      return Err(Cow::from(err));
+    }
+
+    Ok(())
+  }
+
+  /// Check that every skill is in the skill CSV for the build's category.
+  pub fn validate(&self) -> Result<(), UnknownSkills> {
+    let groups = skill_info::parse_skill_info_groups(self.category);
+    let known: HashSet<u32> = groups
+      .iter()
+      .flat_map(|group| group.skills.iter().map(|info| info.id))
+      .collect();
+
+    let ids: Vec<u32> = self
+      .skills
+      .keys()
+      .copied()
+      .filter(|id| !known.contains(id))
+      .collect();
+    if ids.is_empty() {
+      return Ok(());
+    }
+
+    Err(UnknownSkills {
+      category: self.category,
+      ids,
+    })
+  }
+}
+
+/// Skill IDs in a [`Build`] that aren't in the skill CSV for its category.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownSkills {
+  pub category: SkillCategory,
+  pub ids: Vec<u32>,
+}
+
+impl fmt::Display for UnknownSkills {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let category = match self.category {
+      SkillCategory::Adventurer => "adventurer",
+      SkillCategory::Producer => "producer",
+    };
+    let ids: Vec<String> = self.ids.iter().map(u32::to_string).collect();
+    write!(f, "Unknown {category} skill IDs: {}", ids.join(", "))
+  }
+}
+
+impl std::error::Error for UnknownSkills {}
+
+impl From<UnknownSkills> for Cow<'static, str> {
+  fn from(err: UnknownSkills) -> Self {
+    Cow::from(err.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_file() {
+    let dir = std::env::temp_dir().join("cota_test_skill_build");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut build = Build::new(SkillCategory::Producer);
+    build.name = Some(String::from("Miner"));
+    build.skills.extend([(857, 40), (855, 0)]);
+    build.validate().unwrap();
+
+    let path = dir.join("miner.json");
+    build.save(&path).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.contains(r#""category": "producer""#));
+    assert_eq!(Build::load(&path).unwrap(), build);
+
+    // The name is optional.
+    fs::write(
+      &path,
+      r#"{"category":"adventurer","skills":{"3":10,"857":1}}"#,
+    )
+    .unwrap();
+    let build = Build::load(&path).unwrap();
+    assert_eq!(build.name, None);
+    let err = build.validate().unwrap_err();
+    assert_eq!(err.ids, [857]);
+
+    fs::write(&path, r#"{"category":"crafter","skills":{}}"#).unwrap();
+    assert!(Build::load(&path).is_err());
+    assert!(Build::load(&dir.join("missing.json")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -1,4 +1,18 @@
-#[derive(Clone, Copy, Debug)]
+use crate::{
+  game_data::GameData,
+  util::{self, BatchError, ProgressSink},
+};
+use num_format::Locale;
+use serde::{Deserialize, Serialize};
+use std::{
+  borrow::Cow,
+  collections::{BTreeSet, HashMap, HashSet},
+  fmt,
+};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SkillCategory {
   Adventurer,
   Producer,
@@ -16,6 +30,27 @@ pub struct SkillInfo {
   pub mul: f64,
   pub id: u32,
   pub reqs: Vec<Requires>,
+
+  /// What the skill does, if the CSV has it.
+  pub description: Option<Cow<'static, str>>,
+
+  /// The name in the user's language, if there's a translation.
+  pub localized: Option<&'static str>,
+
+  /// The tier within the tree, if the CSV has it. See [`SkillRegistry::tier`].
+  pub tier: Option<u32>,
+}
+
+impl SkillInfo {
+  /// The name to show, which is the localized name if there is one.
+  pub fn display_name(&self) -> &'static str {
+    self.localized.unwrap_or(self.name)
+  }
+
+  /// Check if the skill's English or localized name matches.
+  fn name_matches(&self, pred: impl Fn(&str) -> bool) -> bool {
+    pred(self.name) || self.localized.is_some_and(pred)
+  }
 }
 
 #[derive(Default)]
@@ -33,12 +68,70 @@ impl SkillInfoGroup {
   }
 }
 
-/// Parse the CSV for adventurer or producer skills.
+/// Localized skill names by locale name. Each is a CSV of `id,name` lines, e.g.
+/// `("de", include_str!("../res/skills_de.csv"))`. Locales are only added once their names are
+/// translated.
+const LOCALIZED_NAMES: &[(&str, &str)] = &[];
+
+/// Parse the CSV for adventurer or producer skills, with names for the user's locale. The skills
+/// in each group are sorted by the names that are shown (see [`util::natural_cmp`]).
 pub fn parse_skill_info_groups(category: SkillCategory) -> Vec<SkillInfoGroup> {
   let text = match category {
     SkillCategory::Adventurer => include_str!("../res/adventurer_skills.csv"),
     SkillCategory::Producer => include_str!("../res/producer_skills.csv"),
   };
+
+  let mut groups = parse_skill_csv(text);
+  if let Some(names) = localized_names(&util::get_locale()) {
+    localize(&mut groups, &names);
+  }
+
+  for group in &mut groups {
+    let skills = &mut group.skills;
+    skills.sort_by(|a, b| util::natural_cmp(a.display_name(), b.display_name()));
+  }
+  groups
+}
+
+/// Get the skill names for a locale, falling back to the language without the region ("de" for
+/// "de-AT"). Returns `None` for English or if there's no translation.
+fn localized_names(locale: &Locale) -> Option<HashMap<u32, &'static str>> {
+  let name = locale.name();
+  let lang = name.split('-').next().unwrap_or(name);
+  let find = |key: &str| LOCALIZED_NAMES.iter().find(|(name, _)| *name == key);
+  let (_, text) = find(name).or_else(|| find(lang))?;
+  Some(parse_localized_names(text))
+}
+
+/// Parse localized names (`id,name`). Blank lines and lines starting with '#' are skipped.
+pub(crate) fn parse_localized_names(text: &'static str) -> HashMap<u32, &'static str> {
+  let mut names = HashMap::new();
+  for line in text.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if let Some((id, name)) = line.split_once(',') {
+      if let Ok(id) = id.trim().parse() {
+        names.insert(id, name.trim());
+      }
+    }
+  }
+  names
+}
+
+/// Set the localized names of the skills that have one.
+pub(crate) fn localize(groups: &mut [SkillInfoGroup], names: &HashMap<u32, &'static str>) {
+  for group in groups {
+    for info in &mut group.skills {
+      info.localized = names.get(&info.id).copied();
+    }
+  }
+}
+
+/// Parse skill CSV text (`group,name,mul,id[,tier=N][,req_id,req_lvl]...[,description]`), which
+/// must be sorted by group. The description can be quoted so that it can contain commas.
+pub(crate) fn parse_skill_csv(text: &'static str) -> Vec<SkillInfoGroup> {
   let mut skill_groups = Vec::new();
   let mut skill_group = SkillInfoGroup::default();
 
@@ -46,7 +139,24 @@ pub fn parse_skill_info_groups(category: SkillCategory) -> Vec<SkillInfoGroup> {
   let mut tmp_reqs = Vec::new();
 
   for line in text.lines() {
-    let mut fields = line.split(',');
+    let mut fields = split_csv_line(line);
+    let tier = match fields.get(4).and_then(|field| field.strip_prefix("tier=")) {
+      Some(tier) => {
+        let tier = tier.parse().unwrap();
+        fields.remove(4);
+        Some(tier)
+      }
+      None => None,
+    };
+
+    // Requirements are in pairs, so an odd field out at the end is the description.
+    let description = if fields.len() > 4 && fields.len() % 2 == 1 {
+      fields.pop().map(unquote)
+    } else {
+      None
+    };
+
+    let mut fields = fields.into_iter();
     if let Some(group) = fields.next() {
       // CSVs are pre-sorted, so we just need to create a new group on group change.
       if group != skill_group.name {
@@ -74,6 +184,9 @@ pub fn parse_skill_info_groups(category: SkillCategory) -> Vec<SkillInfoGroup> {
         mul,
         id,
         reqs,
+        description,
+        localized: None,
+        tier,
       });
     }
   }
@@ -84,3 +197,476 @@ pub fn parse_skill_info_groups(category: SkillCategory) -> Vec<SkillInfoGroup> {
 
   skill_groups
 }
+
+/// Split a CSV line on commas that aren't in quotes. Fields are not unquoted.
+fn split_csv_line(line: &'static str) -> Vec<&'static str> {
+  let mut fields = Vec::new();
+  let mut quoted = false;
+  let mut start = 0;
+  for (pos, ch) in line.char_indices() {
+    match ch {
+      '"' => quoted = !quoted,
+      ',' if !quoted => {
+        fields.push(&line[start..pos]);
+        start = pos + 1;
+      }
+      _ => (),
+    }
+  }
+
+  fields.push(&line[start..]);
+  fields
+}
+
+/// Remove the quotes from a CSV field and unescape doubled quotes.
+fn unquote(field: &'static str) -> Cow<'static, str> {
+  let Some(field) = field
+    .strip_prefix('"')
+    .and_then(|field| field.strip_suffix('"'))
+  else {
+    return Cow::Borrowed(field);
+  };
+
+  if field.contains("\"\"") {
+    Cow::Owned(field.replace("\"\"", "\""))
+  } else {
+    Cow::Borrowed(field)
+  }
+}
+
+/// Result of [`SkillRegistry::audit_against`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryAudit {
+  /// Registry skills that none of the saves have trained.
+  pub unused: Vec<u32>,
+
+  /// Registry skills whose level disagrees with the stored rank in any save, which suggests a bad
+  /// multiplier.
+  pub inconsistent: Vec<u32>,
+
+  /// Trained skills that aren't in the registry.
+  pub unknown: Vec<String>,
+}
+
+/// Two skills whose names have the same lookup key. See [`name_key`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameConflict {
+  pub key: String,
+  pub ids: (u32, u32),
+}
+
+impl fmt::Display for NameConflict {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (first, second) = self.ids;
+    write!(
+      f,
+      "Skills {first} and {second} both have the lookup name '{}'",
+      self.key
+    )
+  }
+}
+
+impl std::error::Error for NameConflict {}
+
+/// Make the key that a skill name is looked up by, so that names typed or pasted differently still
+/// match. The name is NFKC normalized, accents are removed, apostrophes and dashes are unified, the
+/// case is ignored and runs of whitespace are single spaces. "Fenté" and "FENTE" have the same key.
+pub fn name_key(name: &str) -> String {
+  let mut key = String::with_capacity(name.len());
+  let chars = name.nfkc().collect::<String>();
+  for ch in chars.nfd().filter(|ch| !is_combining_mark(*ch)) {
+    let ch = match ch {
+      '\u{2018}' | '\u{2019}' | '\u{201B}' | '\u{2032}' | '\u{02BC}' | '`' | '\u{00B4}' => '\'',
+      '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+      ch => ch,
+    };
+    key.extend(ch.to_uppercase());
+  }
+  key.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Adventurer and producer skill info, parsed once and shared.
+pub struct SkillRegistry {
+  adventurer: Vec<SkillInfoGroup>,
+  producer: Vec<SkillInfoGroup>,
+
+  /// Where each English and localized name is, by [`name_key`].
+  names: HashMap<String, (SkillCategory, usize, usize)>,
+
+  /// Names that have the same key as another skill's, which can't be looked up.
+  conflicts: Vec<NameConflict>,
+}
+
+impl Default for SkillRegistry {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SkillRegistry {
+  pub fn new() -> Self {
+    Self::from_groups(
+      parse_skill_info_groups(SkillCategory::Adventurer),
+      parse_skill_info_groups(SkillCategory::Producer),
+    )
+  }
+
+  pub(crate) fn from_groups(
+    adventurer: Vec<SkillInfoGroup>,
+    producer: Vec<SkillInfoGroup>,
+  ) -> Self {
+    let mut names = HashMap::new();
+    let mut conflicts = Vec::new();
+    let mut ids = HashMap::new();
+    for (category, groups) in [
+      (SkillCategory::Adventurer, &adventurer),
+      (SkillCategory::Producer, &producer),
+    ] {
+      for (group_idx, group) in groups.iter().enumerate() {
+        for (skill_idx, info) in group.skills.iter().enumerate() {
+          for name in [Some(info.name), info.localized].into_iter().flatten() {
+            let key = name_key(name);
+            match ids.get(&key) {
+              // A skill's English and localized names can be the same.
+              Some(&id) if id != info.id => conflicts.push(NameConflict {
+                key,
+                ids: (id, info.id),
+              }),
+              Some(_) => (),
+              None => {
+                ids.insert(key.clone(), info.id);
+                names.insert(key, (category, group_idx, skill_idx));
+              }
+            }
+          }
+        }
+      }
+    }
+
+    Self {
+      adventurer,
+      producer,
+      names,
+      conflicts,
+    }
+  }
+
+  /// Make sure that every skill can be found by name. See [`name_key`].
+  pub fn validate(&self) -> Result<(), NameConflict> {
+    match self.conflicts.first() {
+      Some(conflict) => Err(conflict.clone()),
+      None => Ok(()),
+    }
+  }
+
+  pub fn groups(&self, category: SkillCategory) -> &[SkillInfoGroup] {
+    match category {
+      SkillCategory::Adventurer => &self.adventurer,
+      SkillCategory::Producer => &self.producer,
+    }
+  }
+
+  /// Find a skill by its English or localized name, ignoring case, accents and the kind of
+  /// apostrophe or dash. See [`name_key`].
+  pub fn find(&self, name: &str) -> Option<(SkillCategory, &SkillInfoGroup, &SkillInfo)> {
+    let &(category, group, skill) = self.names.get(&name_key(name))?;
+    let group = &self.groups(category)[group];
+    Some((category, group, &group.skills[skill]))
+  }
+
+  /// Find the skills whose English or localized names contain the text, ignoring case, accents and
+  /// the kind of apostrophe or dash.
+  pub fn find_partial(&self, text: &str) -> Vec<(SkillCategory, &SkillInfoGroup, &SkillInfo)> {
+    let text = name_key(text);
+    let contains = |name: &str| name_key(name).contains(&text);
+    let mut found = Vec::new();
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in self.groups(category) {
+        for info in &group.skills {
+          if info.name_matches(contains) {
+            found.push((category, group, info));
+          }
+        }
+      }
+    }
+    found
+  }
+
+  /// Get the name to show for a skill, which is the localized name if there is one. Unknown skills
+  /// have an empty name.
+  pub fn display_name(&self, id: u32) -> &str {
+    self
+      .find_id(id)
+      .map_or("", |(_, _, info)| info.display_name())
+  }
+
+  /// Get a skill's tier within its tree, starting at 1. Skills without a tier in the CSV are one
+  /// tier above the highest of their requirements in the same tree.
+  pub fn tier(&self, info: &SkillInfo) -> u32 {
+    self.tier_at_depth(info, 0)
+  }
+
+  fn tier_at_depth(&self, info: &SkillInfo, depth: usize) -> u32 {
+    if let Some(tier) = info.tier {
+      return tier;
+    }
+
+    // Guard against requirements that loop.
+    if depth > 32 {
+      return 1;
+    }
+
+    let Some((_, group, _)) = self.find_id(info.id) else {
+      return 1;
+    };
+
+    let reqs = info.reqs.iter();
+    let reqs = reqs.filter_map(|req| group.skills.iter().find(|skill| skill.id == req.id));
+    let highest = reqs.map(|req| self.tier_at_depth(req, depth + 1)).max();
+    highest.unwrap_or(0) + 1
+  }
+
+  /// Get a skill's description, if it has one.
+  pub fn description(&self, id: u32) -> Option<&str> {
+    self.find_id(id)?.2.description.as_deref()
+  }
+
+  /// Check the registry against a number of save-games, looking for stale entries. If `progress` is
+  /// canceled then the audit of the saves checked so far is returned in the error.
+  pub fn audit_against(
+    &self,
+    saves: &[GameData],
+    progress: Option<&dyn ProgressSink>,
+  ) -> Result<RegistryAudit, BatchError<RegistryAudit>> {
+    let mut trained = HashSet::new();
+    let mut inconsistent = BTreeSet::new();
+    let mut unknown = BTreeSet::new();
+    let mut canceled = false;
+    for (done, save) in saves.iter().enumerate() {
+      if util::report_progress(progress, done, saves.len(), save.avatar_name()) {
+        canceled = true;
+        break;
+      }
+
+      for id in save.trained_skill_ids() {
+        match id.parse().ok().filter(|id| self.find_id(*id).is_some()) {
+          Some(id) => {
+            trained.insert(id);
+          }
+          None => {
+            unknown.insert(id);
+          }
+        }
+      }
+
+      for skill in save.skill_consistency(self) {
+        inconsistent.insert(skill.id);
+      }
+    }
+
+    let mut unused = Vec::new();
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in self.groups(category) {
+        let ids = group.skills.iter().map(|info| info.id);
+        unused.extend(ids.filter(|id| !trained.contains(id)));
+      }
+    }
+    unused.sort_unstable();
+
+    let audit = RegistryAudit {
+      unused,
+      inconsistent: inconsistent.into_iter().collect(),
+      unknown: unknown.into_iter().collect(),
+    };
+
+    if canceled {
+      return Err(BatchError::Canceled(audit));
+    }
+    Ok(audit)
+  }
+
+  /// Find a skill by ID.
+  pub fn find_id(&self, id: u32) -> Option<(SkillCategory, &SkillInfoGroup, &SkillInfo)> {
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in self.groups(category) {
+        if let Some(info) = group.skills.iter().find(|info| info.id == id) {
+          return Some((category, group, info));
+        }
+      }
+    }
+    None
+  }
+
+  /// Find a skill by its `/stats` name, which might have the spaces and punctuation removed.
+  pub fn find_stat(&self, name: &str) -> Option<(SkillCategory, &SkillInfoGroup, &SkillInfo)> {
+    if let Some(found) = self.find(name) {
+      return Some(found);
+    }
+
+    let compact = |text: &str| -> String {
+      let key = name_key(text);
+      key.chars().filter(|ch| ch.is_alphanumeric()).collect()
+    };
+    let name = compact(name);
+    let equal = |text: &str| compact(text) == name;
+    for category in [SkillCategory::Adventurer, SkillCategory::Producer] {
+      for group in self.groups(category) {
+        for info in &group.skills {
+          if info.name_matches(equal) {
+            return Some((category, group, info));
+          }
+        }
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_audit_against() {
+    use crate::save_builder::SaveBuilder;
+
+    let registry = SkillRegistry::new();
+    let saves = [
+      SaveBuilder::new().skill(3, 1440).skill(9999, 10).build(),
+      SaveBuilder::new()
+        .ranked_skill(7, 1440, 100)
+        .skill(9998, 10)
+        .build(),
+    ];
+    let saves: Vec<GameData> = saves
+      .into_iter()
+      .map(|text| GameData::from_text(text).unwrap())
+      .collect();
+
+    let audit = registry.audit_against(&saves, None).unwrap();
+    assert!(!audit.unused.contains(&3));
+    assert!(!audit.unused.contains(&7));
+    assert!(audit.unused.contains(&5));
+    assert_eq!(audit.inconsistent, [7]);
+    assert_eq!(audit.unknown, ["9998", "9999"]);
+
+    // A canceled audit has the saves checked so far.
+    let progress = util::CancelAfter::new(1);
+    let Err(BatchError::Canceled(audit)) = registry.audit_against(&saves, Some(&progress)) else {
+      panic!("The audit wasn't canceled");
+    };
+    assert_eq!(progress.reports(), [(0, 2), (1, 2)]);
+    assert!(audit.unused.contains(&7));
+    assert_eq!(audit.unknown, ["9999"]);
+  }
+
+  #[test]
+  fn test_parse_skill_csv() {
+    let text = concat!(
+      "Air,Blink,1,7,6,40\n",
+      "Air,Chain Lightning,2,3,2,40,\"Lightning arcs, hitting \"\"nearby\"\" foes.\"\n",
+      "Air,Dash,1,6,tier=2,Run faster\n",
+      "Fire,Fireball,1,8,tier=3,7,20\n",
+    );
+    let groups = parse_skill_csv(text);
+    assert_eq!(groups.len(), 2);
+
+    let skills = &groups[0].skills;
+    assert_eq!(skills[0].description, None);
+    assert_eq!(skills[0].reqs.len(), 1);
+    assert_eq!(
+      skills[1].description.as_deref(),
+      Some(r#"Lightning arcs, hitting "nearby" foes."#)
+    );
+    assert_eq!((skills[1].reqs[0].id, skills[1].reqs[0].lvl), (2, 40));
+    assert_eq!(skills[2].description.as_deref(), Some("Run faster"));
+    assert!(skills[2].reqs.is_empty());
+    assert_eq!(skills[2].tier, Some(2));
+    assert_eq!(skills[0].tier, None);
+    assert_eq!(groups[1].skills[0].id, 8);
+    assert_eq!(groups[1].skills[0].tier, Some(3));
+    assert_eq!(groups[1].skills[0].reqs.len(), 1);
+
+    // The embedded skills don't have descriptions yet.
+    let registry = SkillRegistry::new();
+    assert_eq!(registry.description(3), None);
+  }
+
+  #[test]
+  fn test_tier() {
+    let registry = SkillRegistry::new();
+    let tier = |id| registry.tier(registry.find_id(id).unwrap().2);
+
+    // Gust, Discharge, Lightning and then Chain Lightning.
+    assert_eq!(tier(1), 1);
+    assert_eq!(tier(221), 2);
+    assert_eq!(tier(2), 3);
+    assert_eq!(tier(3), 4);
+
+    // Tiers in the CSV are used as they are.
+    let mut info = registry.find_id(3).unwrap().2.clone();
+    info.tier = Some(2);
+    assert_eq!(registry.tier(&info), 2);
+  }
+
+  #[test]
+  fn test_localized_names() {
+    let names = parse_localized_names("# id,name\n\n3, Kettenblitz\nbad\n7,Blinzeln\n");
+    assert_eq!(names.len(), 2);
+
+    let mut adventurer = parse_skill_info_groups(SkillCategory::Adventurer);
+    localize(&mut adventurer, &names);
+    let producer = parse_skill_info_groups(SkillCategory::Producer);
+    let registry = SkillRegistry::from_groups(adventurer, producer);
+    assert_eq!(registry.display_name(3), "Kettenblitz");
+    assert_eq!(
+      registry.display_name(5),
+      registry.find_id(5).unwrap().2.name
+    );
+    assert_eq!(registry.display_name(9999), "");
+
+    // Both the English and localized names are found.
+    assert_eq!(registry.find("kettenblitz").unwrap().2.id, 3);
+    assert_eq!(registry.find("Chain Lightning").unwrap().2.id, 3);
+    assert_eq!(registry.find_stat("KETTENBLITZ").unwrap().2.id, 3);
+    let found = registry.find_partial("blinz");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].2.display_name(), "Blinzeln");
+
+    // English doesn't need translating, and locales without translations use it.
+    assert!(localized_names(&Locale::en).is_none());
+    assert!(localized_names(&Locale::de_AT).is_none());
+  }
+
+  #[test]
+  fn test_name_key() {
+    assert_eq!(name_key("Fenté"), name_key("fente"));
+    assert_eq!(name_key("Ward\u{2019}s  Edge"), "WARD'S EDGE");
+    assert_eq!(name_key("Self\u{2013}Heal"), name_key("self-heal"));
+    assert_eq!(name_key("\u{FB01}re"), "FIRE");
+    assert_eq!(name_key("Straße"), "STRASSE");
+
+    // Names are found however they're written, and the CSV name is kept.
+    let text = "Air,Fenté,1,900\nAir,Ward's Edge,1,901\nFire,Self-Heal,1,902\n";
+    let registry = SkillRegistry::from_groups(parse_skill_csv(text), Vec::new());
+    assert_eq!(registry.validate(), Ok(()));
+    assert_eq!(registry.find("FENTE").unwrap().2.name, "Fenté");
+    assert_eq!(registry.find("ward\u{2018}s edge").unwrap().2.id, 901);
+    assert_eq!(registry.find_stat("WardsEdge").unwrap().2.id, 901);
+    assert_eq!(registry.find_stat("SelfHeal").unwrap().2.id, 902);
+    assert_eq!(registry.find_partial("fent").len(), 1);
+    assert_eq!(registry.find_partial("self\u{2011}h").len(), 1);
+
+    // Names that only differ by an accent can't both be found.
+    let text = "Air,Fente,1,900\nFire,Fenté,1,901\n";
+    let registry = SkillRegistry::from_groups(parse_skill_csv(text), Vec::new());
+    let conflict = NameConflict {
+      key: String::from("FENTE"),
+      ids: (900, 901),
+    };
+    assert_eq!(registry.validate(), Err(conflict));
+    assert_eq!(registry.find("fenté").unwrap().2.id, 900);
+
+    assert_eq!(SkillRegistry::new().validate(), Ok(()));
+  }
+}
@@ -0,0 +1,374 @@
+use crate::{
+  log_data::{self, StatsIter},
+  skill_info::{SkillCategory, SkillRegistry},
+  util::{self, Limits},
+};
+use std::{borrow::Cow, fmt};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedSkill {
+  pub category: SkillCategory,
+  pub group: &'static str,
+  pub name: &'static str,
+  pub id: u32,
+  pub level: i32,
+}
+
+/// A problem with a line of pasted skill levels. Line numbers start at one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LineError {
+  /// The skill name isn't recognized.
+  Unknown { line: usize, name: String },
+
+  /// The skill name matches more than one skill.
+  Ambiguous {
+    line: usize,
+    name: String,
+    candidates: Vec<&'static str>,
+  },
+
+  /// The level is missing or isn't a whole number within the skill level range.
+  BadLevel { line: usize, text: String },
+}
+
+impl fmt::Display for LineError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LineError::Unknown { line, name } => write!(f, "Line {line}: unknown skill '{name}'"),
+      LineError::Ambiguous {
+        line,
+        name,
+        candidates,
+      } => write!(
+        f,
+        "Line {line}: '{name}' could be {}",
+        candidates.join(", ")
+      ),
+      LineError::BadLevel { line, text } => write!(f, "Line {line}: invalid level in '{text}'"),
+    }
+  }
+}
+
+/// Errors from [`SkillPlan::from_lines`], one for each bad line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanImportError {
+  pub errors: Vec<LineError>,
+}
+
+impl fmt::Display for PlanImportError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (index, err) in self.errors.iter().enumerate() {
+      if index > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{err}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for PlanImportError {}
+
+/// Skills read by [`SkillPlan::from_stats_text`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatsImport {
+  pub plan: SkillPlan,
+
+  /// Names that aren't skills, e.g. attributes and levels, in the order they were found.
+  pub unknown: Vec<String>,
+}
+
+/// A set of skill levels, in the order they were given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SkillPlan {
+  skills: Vec<PlannedSkill>,
+}
+
+impl SkillPlan {
+  /// Parse the skills from the game's `/stats` output, either pasted as "Skill Name: level" lines
+  /// or as the single line that's written to the chat log.
+  ///
+  /// Lines without a level are ignored. Other stats, such as attributes, are returned with any
+  /// unrecognized skill names in [`StatsImport::unknown`]. Levels outside of the skill level range
+  /// of `limits` are an error.
+  pub fn from_stats_text(
+    text: &str,
+    registry: &SkillRegistry,
+    limits: &Limits,
+  ) -> Result<StatsImport, Cow<'static, str>> {
+    let mut import = StatsImport::default();
+
+    for line in text.lines() {
+      let line = log_data::get_log_text(line);
+
+      // The chat log has every stat on one line, with the spaces taken out of the names.
+      let stats: Vec<(String, f64)> = if line.matches(':').count() > 1 {
+        let stats = StatsIter::new(line);
+        stats
+          .map(|(name, value)| (name.to_owned(), value))
+          .collect()
+      } else {
+        let Some((name, level)) = line.rsplit_once(':') else {
+          continue;
+        };
+
+        // The game prints levels with decimals.
+        let Ok(level) = util::replace_decimal(level.trim()).parse::<f64>() else {
+          continue;
+        };
+
+        // Collapse decorative whitespace.
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        vec![(name, level)]
+      };
+
+      for (name, level) in stats {
+        if name.is_empty() {
+          continue;
+        }
+
+        let Some((category, group, info)) = registry.find_stat(&name) else {
+          import.unknown.push(name);
+          continue;
+        };
+
+        let level = level.floor() as i32;
+        if !limits.skill_level.contains(&level) {
+          return Err(Cow::from(format!(
+            "Invalid level for {}: {level}",
+            info.name
+          )));
+        }
+
+        import.plan.set(PlannedSkill {
+          category,
+          group: group.name,
+          name: info.name,
+          id: info.id,
+          level,
+        });
+      }
+    }
+
+    Ok(import)
+  }
+
+  /// Parse lines of skill names followed by whole levels, e.g. "Heavy Armor 80". Names are matched
+  /// ignoring case and spacing, or by part of the name if that's unique. All the bad lines are
+  /// returned in the error. Levels must be within the skill level range of `limits`.
+  pub fn from_lines(
+    text: &str,
+    registry: &SkillRegistry,
+    limits: &Limits,
+  ) -> Result<Self, PlanImportError> {
+    let mut plan = Self::default();
+    let mut errors = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+      let line_no = index + 1;
+      let line = line.trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      let bad_level = || LineError::BadLevel {
+        line: line_no,
+        text: line.into(),
+      };
+
+      let Some((name, level)) = line.rsplit_once(char::is_whitespace) else {
+        errors.push(bad_level());
+        continue;
+      };
+
+      let level = match level.parse::<i32>() {
+        Ok(level) if limits.skill_level.contains(&level) => level,
+        _ => {
+          errors.push(bad_level());
+          continue;
+        }
+      };
+
+      // Collapse tabs and repeated spaces.
+      let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+      let found = match registry.find_stat(&name) {
+        Some(found) => found,
+        None => {
+          let mut candidates = registry.find_partial(&name);
+          match candidates.len() {
+            1 => candidates.remove(0),
+            0 => {
+              errors.push(LineError::Unknown {
+                line: line_no,
+                name,
+              });
+              continue;
+            }
+            _ => {
+              let candidates = candidates.iter().map(|(_, _, info)| info.name).collect();
+              errors.push(LineError::Ambiguous {
+                line: line_no,
+                name,
+                candidates,
+              });
+              continue;
+            }
+          }
+        }
+      };
+
+      let (category, group, info) = found;
+      plan.set(PlannedSkill {
+        category,
+        group: group.name,
+        name: info.name,
+        id: info.id,
+        level,
+      });
+    }
+
+    if !errors.is_empty() {
+      return Err(PlanImportError { errors });
+    }
+
+    Ok(plan)
+  }
+
+  /// Export the skills in the `/stats` text format.
+  pub fn to_stats_text(&self) -> String {
+    let mut text = String::new();
+    for skill in &self.skills {
+      text += &format!("{}: {}\n", skill.name, skill.level);
+    }
+    text
+  }
+
+  pub fn skills(&self) -> &[PlannedSkill] {
+    &self.skills
+  }
+
+  // Add a skill. Later skills replace earlier ones.
+  fn set(&mut self, skill: PlannedSkill) {
+    if let Some(planned) = self
+      .skills
+      .iter_mut()
+      .find(|planned| planned.id == skill.id)
+    {
+      *planned = skill;
+    } else {
+      self.skills.push(skill);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_stats_text() {
+    let registry = SkillRegistry::new();
+    let limits = Limits::default();
+    let import = |text| SkillPlan::from_stats_text(text, &registry, &limits);
+    let text = "  chain   lightning :  23.75\nBlink: 40,0\n\nAdventurer Skills\n";
+    let plan = import(text).unwrap().plan;
+    assert_eq!(plan.skills().len(), 2);
+    assert_eq!(plan.skills()[0].name, "Chain Lightning");
+    assert_eq!(plan.skills()[0].level, 23);
+    assert_eq!(plan.skills()[1].group, "Air");
+    assert_eq!(plan.skills()[1].level, 40);
+
+    // Round trip.
+    let text = plan.to_stats_text();
+    assert_eq!(text, "Chain Lightning: 23\nBlink: 40\n");
+    assert_eq!(import(&text).unwrap().plan, plan);
+
+    // Other stats and unknown names are returned with the skills.
+    let text = "Strength: 50\nBlink: 40\nFrobnicate: 10\nWiggle: 2.5\n";
+    let result = import(text).unwrap();
+    assert_eq!(result.unknown, ["Strength", "Frobnicate", "Wiggle"]);
+    assert_eq!(result.plan.skills().len(), 1);
+    assert_eq!(result.plan.skills()[0].name, "Blink");
+
+    // The single line in the chat log.
+    let text =
+      "[1/2/2024 9:16:00 PM]  AdventurerLevel: 81 Strength: 50 ChainLightning: 40.5 Blink: 12";
+    let result = import(text).unwrap();
+    assert_eq!(result.unknown, ["AdventurerLevel", "Strength"]);
+    let skills: Vec<_> = result
+      .plan
+      .skills()
+      .iter()
+      .map(|skill| (skill.name, skill.level))
+      .collect();
+    assert_eq!(skills, [("Chain Lightning", 40), ("Blink", 12)]);
+
+    // Levels are checked against the limits.
+    assert_eq!(
+      import("Blink: 201").unwrap_err(),
+      "Invalid level for Blink: 201"
+    );
+    let limits = Limits {
+      skill_level: 0..=250,
+      ..Limits::default()
+    };
+    let result = SkillPlan::from_stats_text("Blink: 201", &registry, &limits).unwrap();
+    assert_eq!(result.plan.skills()[0].level, 201);
+  }
+
+  #[test]
+  fn test_from_lines() {
+    let registry = SkillRegistry::new();
+    let text =
+      "chain  lightning\t40\n\n  BLINK 100\nAir Magic Spec 20\nblink 90\nAir\u{2019}s Embrace 30\n";
+    let plan = SkillPlan::from_lines(text, &registry, &Limits::default()).unwrap();
+    let skills: Vec<_> = plan
+      .skills()
+      .iter()
+      .map(|skill| (skill.name, skill.level))
+      .collect();
+    assert_eq!(
+      skills,
+      [
+        ("Chain Lightning", 40),
+        ("Blink", 90),
+        ("Air Magic Specialization", 20),
+        ("Air's Embrace", 30),
+      ]
+    );
+
+    // Each bad line is reported.
+    let text = "Blink 40\nFrobnicate 10\nBlink\nBlink 2.5\nBlink 201\nSpecialization 20\n";
+    let err = SkillPlan::from_lines(text, &registry, &Limits::default()).unwrap_err();
+    assert_eq!(err.errors.len(), 5);
+    assert_eq!(
+      err.errors[0],
+      LineError::Unknown {
+        line: 2,
+        name: "Frobnicate".into()
+      }
+    );
+    assert!(matches!(err.errors[1], LineError::BadLevel { line: 3, .. }));
+    assert!(matches!(err.errors[2], LineError::BadLevel { line: 4, .. }));
+    assert!(matches!(err.errors[3], LineError::BadLevel { line: 5, .. }));
+    let LineError::Ambiguous {
+      line, candidates, ..
+    } = &err.errors[4]
+    else {
+      panic!("expected an ambiguous name");
+    };
+    assert_eq!(*line, 6);
+    assert!(candidates.contains(&"Air Magic Specialization"));
+    assert!(err
+      .to_string()
+      .starts_with("Line 2: unknown skill 'Frobnicate'\nLine 3: invalid level"));
+
+    // Raised limits allow higher levels.
+    let limits = Limits {
+      skill_level: 0..=250,
+      ..Limits::default()
+    };
+    let plan = SkillPlan::from_lines("Blink 201", &registry, &limits).unwrap();
+    assert_eq!(plan.skills()[0].level, 201);
+  }
+}
@@ -0,0 +1,239 @@
+use crate::util::{Cancel, APP_NAME};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  fmt, fs,
+  path::{Path, PathBuf},
+  sync::mpsc::{self, RecvTimeoutError},
+  thread,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/Barugon/cota/releases/latest";
+const CACHE_FILENAME: &str = "update_check.json";
+const CACHE_SECS: u64 = 24 * 60 * 60;
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A release that's newer than the running version.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseInfo {
+  pub version: String,
+  pub url: String,
+  pub notes: String,
+}
+
+/// Why an update check failed. None of these are serious, so the UI can ignore them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpdateError {
+  Canceled,
+  Network(String),
+
+  /// GitHub refused the request because too many were made.
+  RateLimited,
+  InvalidResponse(String),
+}
+
+impl fmt::Display for UpdateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UpdateError::Canceled => write!(f, "The update check was canceled"),
+      UpdateError::Network(err) => write!(f, "Unable to check for updates: {err}"),
+      UpdateError::RateLimited => write!(f, "Too many update checks, try again later"),
+      UpdateError::InvalidResponse(err) => write!(f, "Invalid release information: {err}"),
+    }
+  }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// The result of the last check, so that GitHub is asked at most once a day.
+#[derive(Serialize, Deserialize)]
+struct CachedCheck {
+  /// When the check was made, in seconds since the Unix epoch.
+  checked: u64,
+  current: String,
+  release: Option<ReleaseInfo>,
+}
+
+/// Check GitHub for a release that's newer than `current`, which is normally
+/// [`APP_VERSION`](crate::util::APP_VERSION). The request is made on a background thread and the
+/// result is cached for a day in the app's data folder.
+pub fn check_for_update(
+  current: &str,
+  cancel: &Cancel,
+) -> Result<Option<ReleaseInfo>, UpdateError> {
+  let cache = cache_path();
+  let now = unix_now();
+  if let Some(release) = cache
+    .as_deref()
+    .and_then(|path| read_cache(path, current, now))
+  {
+    return Ok(release);
+  }
+
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    // The receiver is gone if the check was canceled.
+    let _ = tx.send(fetch_latest());
+  });
+
+  let json = loop {
+    if cancel.is_canceled() {
+      return Err(UpdateError::Canceled);
+    }
+
+    match rx.recv_timeout(Duration::from_millis(100)) {
+      Ok(result) => break result?,
+      Err(RecvTimeoutError::Timeout) => continue,
+      Err(RecvTimeoutError::Disconnected) => {
+        let err = String::from("the request was dropped");
+        return Err(UpdateError::Network(err));
+      }
+    }
+  };
+
+  let release = newer_release(current, &json)?;
+  if let Some(path) = cache {
+    let check = CachedCheck {
+      checked: now,
+      current: current.into(),
+      release: release.clone(),
+    };
+    write_cache(&path, &check);
+  }
+  Ok(release)
+}
+
+fn fetch_latest() -> Result<Value, UpdateError> {
+  let response = ureq::get(RELEASES_URL)
+    .timeout(TIMEOUT)
+    .set("Accept", "application/vnd.github+json")
+    .set("User-Agent", APP_NAME)
+    .call();
+
+  match response {
+    Ok(response) => response
+      .into_json()
+      .map_err(|err| UpdateError::InvalidResponse(err.to_string())),
+    Err(ureq::Error::Status(429, _)) => Err(UpdateError::RateLimited),
+    Err(ureq::Error::Status(403, response))
+      if response.header("x-ratelimit-remaining") == Some("0") =>
+    {
+      Err(UpdateError::RateLimited)
+    }
+    Err(ureq::Error::Status(code, _)) => Err(UpdateError::Network(format!("HTTP status {code}"))),
+    Err(ureq::Error::Transport(err)) => Err(UpdateError::Network(err.to_string())),
+  }
+}
+
+/// Get the release from GitHub's release JSON if it's newer than `current`.
+fn newer_release(current: &str, json: &Value) -> Result<Option<ReleaseInfo>, UpdateError> {
+  let invalid = |err: String| UpdateError::InvalidResponse(err);
+  let current = parse_version(current).map_err(invalid)?;
+  let Some(tag) = json.get("tag_name").and_then(Value::as_str) else {
+    return Err(invalid(String::from("there's no tag name")));
+  };
+
+  let version = parse_version(tag).map_err(invalid)?;
+  if version <= current {
+    return Ok(None);
+  }
+
+  let text = |key| json.get(key).and_then(Value::as_str).unwrap_or_default();
+  Ok(Some(ReleaseInfo {
+    version: version.to_string(),
+    url: text("html_url").into(),
+    notes: text("body").into(),
+  }))
+}
+
+/// Parse a version, allowing a leading 'v' as release tags often have one.
+fn parse_version(text: &str) -> Result<semver::Version, String> {
+  let trimmed = text.trim();
+  let trimmed = trimmed.strip_prefix(['v', 'V']).unwrap_or(trimmed);
+  semver::Version::parse(trimmed).map_err(|err| format!("'{text}' is not a version ({err})"))
+}
+
+fn cache_path() -> Option<PathBuf> {
+  dirs::data_dir().map(|path| path.join(APP_NAME).join(CACHE_FILENAME))
+}
+
+/// Get the cached result if it's less than a day old and for the same version.
+fn read_cache(path: &Path, current: &str, now: u64) -> Option<Option<ReleaseInfo>> {
+  let text = fs::read_to_string(path).ok()?;
+  let check: CachedCheck = serde_json::from_str(&text).ok()?;
+  let fresh = check.checked <= now && now - check.checked < CACHE_SECS;
+  (fresh && check.current == current).then_some(check.release)
+}
+
+fn write_cache(path: &Path, check: &CachedCheck) {
+  // Failing to cache only means that the next check asks GitHub again.
+  if let Some(dir) = path.parent() {
+    let _ = fs::create_dir_all(dir);
+  }
+  if let Ok(text) = serde_json::to_string(check) {
+    let _ = fs::write(path, text);
+  }
+}
+
+fn unix_now() -> u64 {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH);
+  now.map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn test_newer_release() {
+    let json = json!({
+      "tag_name": "v5.7.0",
+      "html_url": "https://github.com/Barugon/cota/releases/tag/v5.7.0",
+      "body": "Fixes",
+    });
+    let release = newer_release("5.6.6", &json).unwrap().unwrap();
+    assert_eq!(release.version, "5.7.0");
+    assert_eq!(release.notes, "Fixes");
+    assert_eq!(newer_release("5.7.0", &json), Ok(None));
+    assert_eq!(newer_release("5.10.0", &json), Ok(None));
+
+    // Pre-releases are older than the release.
+    assert!(newer_release("5.7.0-beta.1", &json).unwrap().is_some());
+
+    let json = json!({"tag_name": "latest"});
+    assert!(matches!(
+      newer_release("5.6.6", &json),
+      Err(UpdateError::InvalidResponse(_))
+    ));
+    assert!(newer_release("5.6.6", &json!({})).is_err());
+  }
+
+  #[test]
+  fn test_update_cache() {
+    let path = std::env::temp_dir()
+      .join("cota_test_update")
+      .join(CACHE_FILENAME);
+    let _ = fs::remove_file(&path);
+    assert_eq!(read_cache(&path, "5.6.6", 1000), None);
+
+    let release = ReleaseInfo {
+      version: "5.7.0".into(),
+      url: String::new(),
+      notes: String::new(),
+    };
+    let check = CachedCheck {
+      checked: 1000,
+      current: "5.6.6".into(),
+      release: Some(release.clone()),
+    };
+    write_cache(&path, &check);
+    assert_eq!(read_cache(&path, "5.6.6", 1000), Some(Some(release)));
+    assert_eq!(read_cache(&path, "5.7.0", 1000), None);
+    assert_eq!(read_cache(&path, "5.6.6", 1000 + CACHE_SECS), None);
+    assert_eq!(read_cache(&path, "5.6.6", 999), None);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+  }
+}
@@ -1,21 +1,34 @@
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use clipboard::{ClipboardContext, ClipboardProvider};
 use eframe::{
   egui::{Context, Image, TextStyle, Ui},
   epaint::{ColorImage, TextureHandle, TextureId, Vec2},
 };
-use num_format::Locale;
+use num_format::{Locale, ToFormattedString};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
+  borrow::Cow,
   cell::RefCell,
+  cmp, fmt, fs,
+  io::{self, BufRead},
   mem,
   ops::{Range, RangeInclusive},
+  path::{Path, PathBuf},
   sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
   },
 };
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// The app only uses default_save_dirs.
+#[allow(unused_imports)]
+pub use crate::save_dirs::{default_save_dirs, InstallKind, SaveDir};
+// Nothing in the app checks for updates unless it's asked to.
+#[cfg(feature = "update-check")]
+#[allow(unused_imports)]
+pub use crate::update::{check_for_update, ReleaseInfo, UpdateError};
 
 pub const APP_ICON: &[u8] = include_bytes!("../res/icon.png");
 pub const APP_NAME: &str = env!("CARGO_PKG_NAME");
@@ -27,6 +40,18 @@ pub const LEVEL_EXP: &[i64] = include!("../res/level_exp_values.rs");
 pub const SKILL_EXP: &[i64] = include!("../res/skill_exp_values.rs");
 pub const LVL_RANGE: RangeInclusive<i32> = 1..=200;
 
+/// Skill levels, where zero is untrained.
+pub const SKILL_LVL_RANGE: RangeInclusive<i32> = 0..=200;
+
+/// Most gold that can be set, carried or banked.
+pub const MAX_GOLD: i64 = i32::MAX as i64 / 2;
+
+/// Most items in one stack. The game keeps item counts as 16 bit numbers.
+pub const MAX_STACK: u64 = i16::MAX as u64;
+
+/// Number of entries in the built-in experience tables, and the fewest a custom table can have.
+pub const EXP_TABLE_LEN: usize = 200;
+
 /// Number of seconds in an hour (one in-game day).
 pub const HOUR_SECS: i64 = 60 * 60;
 
@@ -151,6 +176,348 @@ pub fn floor_search<T: Ord>(value: T, values: &[T]) -> Option<usize> {
   }
 }
 
+/// Where a value falls in a sorted table, from [`table_position`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TablePos {
+  /// Index of the last entry that's at or below the value.
+  pub index: usize,
+
+  /// How far the value is toward the next entry, from 0 up to (but not including) 1, or exactly 1
+  /// past the last entry.
+  pub fraction: f64,
+}
+
+/// Find the entry of a sorted table that a value falls on or after, and how far it is toward the
+/// next entry. Values below the first entry are at index 0 with no fraction, and values above the
+/// last entry are at the last index with a fraction of 1. If entries are equal then the value is
+/// after the last of them. An empty table is always at index 0.
+pub fn table_position(value: i64, table: &[i64]) -> TablePos {
+  let count = table.partition_point(|entry| *entry <= value);
+  let Some(index) = count.checked_sub(1) else {
+    return TablePos {
+      index: 0,
+      fraction: 0.0,
+    };
+  };
+
+  let low = table[index];
+  let fraction = match table.get(index + 1) {
+    // The differences can be too large for i64.
+    Some(&next) => {
+      let offset = value as i128 - low as i128;
+      let size = next as i128 - low as i128;
+      bucket_fraction(offset as f64, size as f64)
+    }
+    None if value > low => 1.0,
+    None => 0.0,
+  };
+  TablePos { index, fraction }
+}
+
+/// Get `offset / size` as a fraction that's less than 1, even when rounded.
+fn bucket_fraction(offset: f64, size: f64) -> f64 {
+  (offset / size).clamp(0.0, 1.0f64.next_down())
+}
+
+/// Get the level for an amount of experience from a table of the experience needed for each level.
+/// Anything at or below the first entry is level 1 and anything at or above the last is the last
+/// level (200 for the standard tables).
+pub fn level_for_exp(exp: i64, table: &[i64]) -> i32 {
+  table_position(exp, table).index as i32 + 1
+}
+
+/// Get the level with the progress toward the next one as a fraction, e.g. 99.72. The whole part is
+/// the same as [`level_for_exp`] for the truncated experience, and the last level is the highest.
+pub fn fractional_level(exp: f64, table: &[i64]) -> f64 {
+  let pos = table_position(exp as i64, table);
+  let lvl = pos.index as f64 + 1.0;
+  let Some(&next) = table.get(pos.index + 1) else {
+    return lvl;
+  };
+
+  // Use the experience before truncation for the fraction. Adding the level can round up too.
+  let low = table[pos.index] as f64;
+  let fraction = bucket_fraction(exp - low, next as f64 - low);
+  (lvl + fraction).min((lvl + 1.0).next_down())
+}
+
+/// Get the experience needed for a level, which is `None` if the level isn't in the table.
+pub fn exp_for_level(lvl: i32, table: &[i64]) -> Option<i64> {
+  let idx = usize::try_from(lvl).ok()?.checked_sub(1)?;
+  table.get(idx).copied()
+}
+
+/// Generate an experience table from the curve `floor(previous * 1.1 + add)`.
+const fn exp_curve(add: i64) -> [i64; EXP_TABLE_LEN] {
+  let mut table = [0; EXP_TABLE_LEN];
+  let mut idx = 1;
+  while idx < EXP_TABLE_LEN {
+    table[idx] = table[idx - 1] * 11 / 10 + add;
+    idx += 1;
+  }
+  table
+}
+
+const LEVEL_EXP_CURVE: [i64; EXP_TABLE_LEN] = exp_curve(1000);
+const SKILL_EXP_CURVE: [i64; EXP_TABLE_LEN] = exp_curve(10);
+
+#[derive(Debug, PartialEq)]
+pub enum TableError {
+  /// The table has too few entries.
+  Length { table: &'static str, len: usize },
+
+  /// The value at the index is negative or not greater than the previous value.
+  Order { table: &'static str, index: usize },
+
+  /// The value at the index doesn't match the experience curve.
+  Curve {
+    table: &'static str,
+    index: usize,
+    expected: i64,
+    actual: i64,
+  },
+}
+
+impl fmt::Display for TableError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TableError::Length { table, len } => {
+        write!(
+          f,
+          "{table} has {len} entries, expected at least {EXP_TABLE_LEN}"
+        )
+      }
+      TableError::Order { table, index } => {
+        write!(f, "{table}[{index}] is not greater than the previous value")
+      }
+      TableError::Curve {
+        table,
+        index,
+        expected,
+        actual,
+      } => write!(f, "{table}[{index}] is {actual}, expected {expected}"),
+    }
+  }
+}
+
+impl std::error::Error for TableError {}
+
+/// Make sure the experience tables are sorted (for `table_position`) and match their curves.
+pub fn validate_exp_tables() -> Result<(), TableError> {
+  validate_exp_table("LEVEL_EXP", LEVEL_EXP)?;
+  validate_exp_table("SKILL_EXP", SKILL_EXP)?;
+  validate_exp_curve("LEVEL_EXP", LEVEL_EXP, &LEVEL_EXP_CURVE)?;
+  validate_exp_curve("SKILL_EXP", SKILL_EXP, &SKILL_EXP_CURVE)
+}
+
+/// Make sure an experience table has enough entries and is strictly increasing from zero. Tables
+/// can be longer than the built-in ones for shards with raised level caps.
+fn validate_exp_table(table: &'static str, values: &[i64]) -> Result<(), TableError> {
+  if values.len() < EXP_TABLE_LEN {
+    let len = values.len();
+    return Err(TableError::Length { table, len });
+  }
+
+  let mut prev = -1;
+  for (index, value) in values.iter().enumerate() {
+    if *value <= prev {
+      return Err(TableError::Order { table, index });
+    }
+    prev = *value;
+  }
+
+  Ok(())
+}
+
+fn validate_exp_curve(
+  table: &'static str,
+  values: &[i64],
+  curve: &[i64],
+) -> Result<(), TableError> {
+  for (index, (actual, expected)) in values.iter().zip(curve).enumerate() {
+    if actual != expected {
+      return Err(TableError::Curve {
+        table,
+        index,
+        expected: *expected,
+        actual: *actual,
+      });
+    }
+  }
+
+  Ok(())
+}
+
+/// Experience tables used for level calculations.
+#[derive(Clone)]
+pub struct ExpTables {
+  level: Cow<'static, [i64]>,
+  skill: Cow<'static, [i64]>,
+}
+
+impl Default for ExpTables {
+  fn default() -> Self {
+    Self {
+      level: Cow::Borrowed(LEVEL_EXP),
+      skill: Cow::Borrowed(SKILL_EXP),
+    }
+  }
+}
+
+impl ExpTables {
+  const LEVEL_FILE: &'static str = "level_exp.json";
+  const SKILL_FILE: &'static str = "skill_exp.json";
+
+  /// Load replacement tables from `level_exp.json` and `skill_exp.json` in the specified folder.
+  /// The built-in tables are used for files that don't exist.
+  pub fn load_or_default(dir: impl AsRef<Path>) -> Result<Self, Cow<'static, str>> {
+    let dir = dir.as_ref();
+    let mut tables = Self::default();
+    if let Some(level) = load_exp_table(dir, Self::LEVEL_FILE)? {
+      tables.level = Cow::Owned(level);
+    }
+    if let Some(skill) = load_exp_table(dir, Self::SKILL_FILE)? {
+      tables.skill = Cow::Owned(skill);
+    }
+    Ok(tables)
+  }
+
+  /// Adventurer and producer level experience.
+  pub fn level(&self) -> &[i64] {
+    &self.level
+  }
+
+  /// Skill level experience.
+  pub fn skill(&self) -> &[i64] {
+    &self.skill
+  }
+}
+
+/// Level and gold caps. Shards with modified rules can raise them with longer experience tables and
+/// `limits.json`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+  /// Adventurer and producer levels.
+  pub level: RangeInclusive<i32>,
+
+  /// Skill levels, where zero is untrained.
+  pub skill_level: RangeInclusive<i32>,
+
+  /// Most gold that can be carried or banked.
+  pub gold: i64,
+
+  /// Most items that can be in one stack.
+  pub stack: u64,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Self {
+      level: LVL_RANGE,
+      skill_level: SKILL_LVL_RANGE,
+      gold: MAX_GOLD,
+      stack: MAX_STACK,
+    }
+  }
+}
+
+/// The contents of `limits.json`. Caps that aren't specified come from the experience tables.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsFile {
+  max_level: Option<i32>,
+  max_skill_level: Option<i32>,
+  max_gold: Option<i64>,
+}
+
+impl Limits {
+  const FILE: &'static str = "limits.json";
+
+  /// Get the limits for experience tables, which allow a level for each entry.
+  pub fn for_tables(tables: &ExpTables) -> Self {
+    let max = |table: &[i64]| i32::try_from(table.len()).unwrap_or(i32::MAX);
+    Self {
+      level: 1..=max(tables.level()),
+      skill_level: 0..=max(tables.skill()),
+      gold: MAX_GOLD,
+      stack: MAX_STACK,
+    }
+  }
+
+  /// Get the limits for the experience tables loaded from a folder (see
+  /// [`ExpTables::load_or_default`]), lowered or with a different gold cap by `limits.json` in the
+  /// same folder. Level caps can't be more than the tables allow.
+  pub fn load_or_default(
+    dir: impl AsRef<Path>,
+    tables: &ExpTables,
+  ) -> Result<Self, Cow<'static, str>> {
+    let mut limits = Self::for_tables(tables);
+    let path = dir.as_ref().join(Self::FILE);
+    if !path.exists() {
+      return Ok(limits);
+    }
+
+    let err = |err: &dyn fmt::Display| Cow::from(format!("{}: {err}", path.display()));
+    let text = fs::read_to_string(&path).map_err(|e| err(&e))?;
+    let file: LimitsFile = serde_json::from_str(&text).map_err(|e| err(&e))?;
+    if let Some(max) = file.max_level {
+      let end = *limits.level.end();
+      limits.level = 1..=check_cap("max_level", max, 1, end).map_err(|e| err(&e))?;
+    }
+    if let Some(max) = file.max_skill_level {
+      let end = *limits.skill_level.end();
+      limits.skill_level = 0..=check_cap("max_skill_level", max, 1, end).map_err(|e| err(&e))?;
+    }
+    if let Some(max) = file.max_gold {
+      limits.gold = check_cap("max_gold", max, 0, i64::MAX).map_err(|e| err(&e))?;
+    }
+    Ok(limits)
+  }
+}
+
+/// A cap in `limits.json` that's out of range.
+#[derive(Debug)]
+pub struct InvalidCap {
+  name: &'static str,
+  value: i64,
+  range: RangeInclusive<i64>,
+}
+
+impl fmt::Display for InvalidCap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (start, end) = (self.range.start(), self.range.end());
+    write!(f, "{} is {}, expected {start}-{end}", self.name, self.value)
+  }
+}
+
+impl std::error::Error for InvalidCap {}
+
+fn check_cap<T>(name: &'static str, value: T, min: T, max: T) -> Result<T, InvalidCap>
+where
+  T: Copy + PartialOrd + Into<i64>,
+{
+  if (min..=max).contains(&value) {
+    return Ok(value);
+  }
+
+  let (value, range) = (value.into(), min.into()..=max.into());
+  Err(InvalidCap { name, value, range })
+}
+
+/// Load an experience table from a JSON file in `dir`. Returns `None` if the file doesn't exist.
+fn load_exp_table(dir: &Path, file: &'static str) -> Result<Option<Vec<i64>>, Cow<'static, str>> {
+  let path = dir.join(file);
+  if !path.exists() {
+    return Ok(None);
+  }
+
+  let err = |err: &dyn fmt::Display| Cow::from(format!("{}: {err}", path.display()));
+  let text = fs::read_to_string(&path).map_err(|e| err(&e))?;
+  let values: Vec<i64> = serde_json::from_str(&text).map_err(|e| err(&e))?;
+  validate_exp_table(file, &values).map_err(|e| err(&e))?;
+  Ok(Some(values))
+}
+
 pub fn set_clipboard_contents(text: String) {
   let mut ctx: ClipboardContext = ok!(ClipboardProvider::new());
   err!(ctx.set_contents(text));
@@ -184,6 +551,21 @@ struct State {
 
   /// Disable the main UI.
   disabled: AtomicBool,
+
+  /// Path of the loaded save-game.
+  current_file: Mutex<Option<PathBuf>>,
+
+  /// The loaded save-game has unsaved changes.
+  dirty: AtomicBool,
+
+  /// Incremented whenever the current file or dirty state changes.
+  generation: AtomicU64,
+
+  /// Progress of the running batch operation, if there is one.
+  progress: Mutex<Option<Progress>>,
+
+  /// Stop the running batch operation.
+  progress_canceled: AtomicBool,
 }
 
 #[derive(Clone, Default)]
@@ -209,6 +591,65 @@ impl AppState {
   pub fn is_disabled(&self) -> bool {
     self.state.disabled.load(Ordering::Relaxed)
   }
+
+  /// Set the path of the loaded save-game.
+  pub fn set_current_file(&mut self, path: Option<PathBuf>) {
+    let mut current_file = self.state.current_file.lock().unwrap();
+    if *current_file != path {
+      *current_file = path;
+      self.state.generation.fetch_add(1, Ordering::Release);
+    }
+  }
+
+  #[must_use]
+  pub fn current_file(&self) -> Option<PathBuf> {
+    self.state.current_file.lock().unwrap().clone()
+  }
+
+  /// Flag the loaded save-game as having unsaved changes.
+  pub fn set_dirty(&mut self, dirty: bool) {
+    if self.state.dirty.swap(dirty, Ordering::AcqRel) != dirty {
+      self.state.generation.fetch_add(1, Ordering::Release);
+    }
+  }
+
+  #[must_use]
+  pub fn is_dirty(&self) -> bool {
+    self.state.dirty.load(Ordering::Acquire)
+  }
+
+  /// Counter that changes whenever the current file or dirty state changes.
+  #[must_use]
+  pub fn generation(&self) -> u64 {
+    self.state.generation.load(Ordering::Acquire)
+  }
+
+  /// Get the progress of the running batch operation, if there is one.
+  #[must_use]
+  pub fn progress(&self) -> Option<Progress> {
+    self.state.progress.lock().unwrap().clone()
+  }
+
+  /// Ask the running batch operation to stop.
+  pub fn cancel_progress(&mut self) {
+    self.state.progress_canceled.store(true, Ordering::Relaxed);
+  }
+
+  /// Clear the progress when a batch operation finishes.
+  pub fn clear_progress(&mut self) {
+    *self.state.progress.lock().unwrap() = None;
+    self.state.progress_canceled.store(false, Ordering::Relaxed);
+  }
+}
+
+impl ProgressSink for AppState {
+  fn report(&self, done: usize, total: usize, label: &str) {
+    *self.state.progress.lock().unwrap() = Some(Progress::new(done, total, label));
+  }
+
+  fn is_canceled(&self) -> bool {
+    self.state.progress_canceled.load(Ordering::Relaxed)
+  }
 }
 
 #[derive(Clone, Default)]
@@ -227,54 +668,249 @@ impl Cancel {
   }
 }
 
-fn find_ignore_case(text: &str, find: &str) -> Option<Range<usize>> {
+/// How far a batch operation has got.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+  pub done: usize,
+  pub total: usize,
+
+  /// What's being worked on, e.g. a skill or avatar name.
+  pub label: String,
+}
+
+impl Progress {
+  fn new(done: usize, total: usize, label: &str) -> Self {
+    Self {
+      done,
+      total,
+      label: label.into(),
+    }
+  }
+}
+
+/// Receives progress from long-running batch operations, which stop between items if it's
+/// canceled.
+pub trait ProgressSink {
+  /// Called before each item with the number of items already done.
+  fn report(&self, done: usize, total: usize, label: &str);
+
+  /// Check if the operation should stop.
+  fn is_canceled(&self) -> bool {
+    false
+  }
+}
+
+impl ProgressSink for mpsc::Sender<Progress> {
+  fn report(&self, done: usize, total: usize, label: &str) {
+    // Nobody is listening if the receiver was dropped.
+    let _ = self.send(Progress::new(done, total, label));
+  }
+}
+
+/// Report progress, if there's a sink, and check if the operation was canceled.
+pub fn report_progress(
+  progress: Option<&dyn ProgressSink>,
+  done: usize,
+  total: usize,
+  label: &str,
+) -> bool {
+  let Some(progress) = progress else {
+    return false;
+  };
+
+  progress.report(done, total, label);
+  progress.is_canceled()
+}
+
+/// Why a batch operation stopped before finishing.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BatchError<R> {
+  /// The operation was canceled, with a report of what was done before that.
+  Canceled(R),
+  Failed(Cow<'static, str>),
+}
+
+impl<R> From<Cow<'static, str>> for BatchError<R> {
+  fn from(err: Cow<'static, str>) -> Self {
+    BatchError::Failed(err)
+  }
+}
+
+impl<R> From<BatchError<R>> for Cow<'static, str> {
+  fn from(err: BatchError<R>) -> Self {
+    match err {
+      BatchError::Canceled(_) => Cow::from(err.to_string()),
+      BatchError::Failed(err) => err,
+    }
+  }
+}
+
+impl<R> fmt::Display for BatchError<R> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BatchError::Canceled(_) => write!(f, "The operation was canceled"),
+      BatchError::Failed(err) => write!(f, "{err}"),
+    }
+  }
+}
+
+impl<R: fmt::Debug> std::error::Error for BatchError<R> {}
+
+/// Progress sink that cancels after a number of reports, for testing batch operations.
+#[cfg(test)]
+pub(crate) struct CancelAfter {
+  limit: usize,
+  reports: RefCell<Vec<(usize, usize)>>,
+}
+
+#[cfg(test)]
+impl CancelAfter {
+  pub fn new(limit: usize) -> Self {
+    let reports = RefCell::default();
+    Self { limit, reports }
+  }
+
+  /// The `done` and `total` of each report.
+  pub fn reports(&self) -> Vec<(usize, usize)> {
+    self.reports.borrow().clone()
+  }
+}
+
+#[cfg(test)]
+impl ProgressSink for CancelAfter {
+  fn report(&self, done: usize, total: usize, _label: &str) {
+    self.reports.borrow_mut().push((done, total));
+  }
+
+  fn is_canceled(&self) -> bool {
+    self.reports.borrow().len() > self.limit
+  }
+}
+
+pub(crate) fn find_ignore_case(text: &str, find: &str) -> Option<Range<usize>> {
   if text.is_empty() || find.is_empty() {
     return None;
   }
 
-  struct ToCaseNext<I: Iterator> {
-    next: usize,
-    iter: I,
+  // Try each character position, comparing the uppercase characters.
+  let find = find.to_uppercase();
+  'start: for (start, _) in text.char_indices() {
+    let mut find_iter = find.chars();
+    for (index, ch) in text[start..].char_indices() {
+      let end = start + index + ch.len_utf8();
+      for upper_ch in ch.to_uppercase() {
+        match find_iter.next() {
+          Some(find_ch) if find_ch == upper_ch => (),
+          Some(_) => continue 'start,
+
+          // The match ended part way through a character that has more than one uppercase
+          // character (e.g. 'ß' is "SS").
+          None => return Some(start..end),
+        }
+      }
+
+      if find_iter.as_str().is_empty() {
+        return Some(start..end);
+      }
+    }
   }
+  None
+}
+
+/// Regular expression flags for [`Search::regex_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegexOptions {
+  /// `^` and `$` match at the start and end of lines.
+  pub multi_line: bool,
 
-  impl<I: Iterator> Iterator for ToCaseNext<I> {
-    type Item = (usize, I::Item);
+  /// `.` matches `\n`.
+  pub dot_matches_new_line: bool,
+  pub case_insensitive: bool,
 
-    fn next(&mut self) -> Option<Self::Item> {
-      Some((self.next, self.iter.next()?))
+  /// Match Unicode characters instead of bytes. On by default.
+  pub unicode: bool,
+}
+
+impl Default for RegexOptions {
+  fn default() -> Self {
+    Self {
+      multi_line: false,
+      dot_matches_new_line: false,
+      case_insensitive: false,
+      unicode: true,
     }
   }
+}
 
-  // Iterator that returns the byte position of the next character
-  // and the current character converted to uppercase.
-  let mut text_iter = text.char_indices().flat_map(|(index, ch)| ToCaseNext {
-    next: index + ch.len_utf8(),
-    iter: ch.to_uppercase(),
-  });
+impl RegexOptions {
+  /// Get the options as inline flags, such as `(?ms)`, or an empty string for the defaults.
+  fn flags(&self) -> String {
+    let mut on = String::new();
+    let mut off = String::new();
+    for (flag, set, default) in [
+      ('i', self.case_insensitive, false),
+      ('m', self.multi_line, false),
+      ('s', self.dot_matches_new_line, false),
+      ('u', self.unicode, true),
+    ] {
+      if set != default {
+        if set { &mut on } else { &mut off }.push(flag);
+      }
+    }
 
-  let find = find.to_uppercase();
-  let mut find_iter = find.chars();
-  let mut start = 0;
-  let mut end = 0;
+    match (on.is_empty(), off.is_empty()) {
+      (true, true) => String::new(),
+      (false, true) => format!("(?{on})"),
+      _ => format!("(?{on}-{off})"),
+    }
+  }
+}
 
-  loop {
-    // If we made it to the end of find_iter then it's a match.
-    let Some(find_ch) = find_iter.next() else {
-      return Some(start..end);
-    };
+/// Where in a line a literal match must be.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineAnchor {
+  /// Anywhere in a line.
+  #[default]
+  None,
+  Start,
+  End,
 
-    // Exit if we arrive at the end of text_iter.
-    let (next, upper_ch) = text_iter.next()?;
+  /// The match must be the whole line.
+  WholeLine,
+}
+
+impl LineAnchor {
+  pub const ALL: [LineAnchor; 4] = [
+    LineAnchor::None,
+    LineAnchor::Start,
+    LineAnchor::End,
+    LineAnchor::WholeLine,
+  ];
 
-    // Set the end to the next character.
-    end = next;
+  pub fn label(self) -> &'static str {
+    match self {
+      LineAnchor::None => "Anywhere",
+      LineAnchor::Start => "Line Start",
+      LineAnchor::End => "Line End",
+      LineAnchor::WholeLine => "Whole Line",
+    }
+  }
 
-    if upper_ch != find_ch {
-      // Characters don't match, reset find_iter.
-      find_iter = find.chars();
+  /// Check the bytes around a match in `data`. A match that ends before "\r\n" is at the end of
+  /// its line.
+  fn accepts(self, data: &[u8], found: &Range<usize>) -> bool {
+    let at_start = || found.start == 0 || data[found.start - 1] == b'\n';
+    let at_end = || {
+      let rest = &data[found.end..];
+      rest.is_empty() || rest.starts_with(b"\n") || rest.starts_with(b"\r\n")
+    };
 
-      // Set the start to the next character.
-      start = next;
+    match self {
+      LineAnchor::None => true,
+      LineAnchor::Start => at_start(),
+      LineAnchor::End => at_end(),
+      LineAnchor::WholeLine => at_start() && at_end(),
     }
   }
 }
@@ -282,23 +918,51 @@ fn find_ignore_case(text: &str, find: &str) -> Option<Range<usize>> {
 #[derive(Clone)]
 pub enum Search {
   /// Search for the specified string.
-  String { find: String, ignore_case: bool },
+  String {
+    find: String,
+    ignore_case: bool,
+    anchor: LineAnchor,
+  },
 
-  /// Use regular expression for pattern matching.
-  Regex(Regex),
+  /// Use regular expression for pattern matching. See [`Search::regex`].
+  Regex {
+    regex: Regex,
+
+    /// The same expression compiled for text that isn't UTF-8.
+    bytes: regex::bytes::Regex,
+  },
 }
 
 impl Search {
+  pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+    Self::regex_with(pattern, RegexOptions::default())
+  }
+
+  /// Compile a regular expression with options. The options are kept in the pattern as inline
+  /// flags, so that the bytes searches use them too.
+  pub fn regex_with(pattern: &str, options: RegexOptions) -> Result<Self, regex::Error> {
+    let pattern = format!("{}{pattern}", options.flags());
+    Ok(Search::Regex {
+      regex: Regex::new(&pattern)?,
+      bytes: regex::bytes::Regex::new(&pattern)?,
+    })
+  }
+
   pub fn find_in(&self, text: &str) -> Option<Range<usize>> {
     match self {
-      Search::String { find, ignore_case } => {
+      Search::String {
+        find,
+        ignore_case,
+        anchor: LineAnchor::None,
+      } => {
         if *ignore_case {
           return find_ignore_case(text, find);
         } else if let Some(pos) = text.find(find) {
           return Some(pos..pos + find.len());
         }
       }
-      Search::Regex(regex) => {
+      Search::String { .. } => return self.matches(text).next(),
+      Search::Regex { regex, .. } => {
         if let Some(pos) = regex.find(text) {
           return Some(pos.start()..pos.end());
         }
@@ -306,6 +970,204 @@ impl Search {
     }
     None
   }
+
+  /// Find all the matches in `text`, which don't overlap.
+  pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+    self.matches(text).collect()
+  }
+
+  /// Count the matches in `text`, which is the same as `find_all(text).len()`.
+  pub fn count_in(&self, text: &str) -> usize {
+    self.matches(text).count()
+  }
+
+  /// Count the matches in `text`, stopping once there are more than `limit`.
+  pub fn count_up_to(&self, text: &str, limit: usize) -> MatchCount {
+    let count = self.matches(text).take(limit.saturating_add(1)).count();
+    MatchCount::new(count, limit)
+  }
+
+  /// Count the matches in the lines read from `reader`, stopping once there are more than `limit`
+  /// if there is one. Lines are searched as with [`Search::find_in_bytes`], so matches can't span
+  /// lines.
+  pub fn count_in_reader(
+    &self,
+    mut reader: impl BufRead,
+    limit: Option<usize>,
+  ) -> io::Result<MatchCount> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let finder = self.bytes_finder();
+
+    let mut count = 0;
+    let mut line = Vec::new();
+    while count <= limit {
+      line.clear();
+      if reader.read_until(b'\n', &mut line)? == 0 {
+        break;
+      }
+
+      let data = line.strip_suffix(b"\n").unwrap_or(&line);
+      let data = data.strip_suffix(b"\r").unwrap_or(data);
+      count += finder.count(data, limit.saturating_add(1) - count);
+    }
+    Ok(MatchCount::new(count, limit))
+  }
+
+  fn matches<'a>(&'a self, text: &'a str) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+    match self {
+      Search::String { find, .. } if find.is_empty() => Box::new(std::iter::empty()),
+      Search::String {
+        find,
+        ignore_case,
+        anchor,
+      } => {
+        let mut pos = 0;
+        Box::new(std::iter::from_fn(move || loop {
+          let rest = &text[pos..];
+          let found = if *ignore_case {
+            find_ignore_case(rest, find)
+          } else {
+            rest
+              .find(find.as_str())
+              .map(|start| start..start + find.len())
+          }?;
+
+          let found = pos + found.start..pos + found.end;
+          if anchor.accepts(text.as_bytes(), &found) {
+            pos = found.end;
+            return Some(found);
+          }
+
+          // A rejected match might overlap one that's accepted, so try again from the next
+          // character.
+          pos = found.start + text[found.start..].chars().next().map_or(1, char::len_utf8);
+        }))
+      }
+      Search::Regex { regex, .. } => Box::new(regex.find_iter(text).map(|found| found.range())),
+    }
+  }
+
+  /// Search data that might not be valid UTF-8. Literal searches ignore case for ASCII only.
+  pub fn find_in_bytes(&self, data: &[u8]) -> Option<Range<usize>> {
+    self.bytes_finder().find(data)
+  }
+
+  /// Get the lines of `data` that match, in order.
+  pub fn find_lines_in_bytes<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+    let finder = self.bytes_finder();
+
+    data
+      .split(|byte| *byte == b'\n')
+      .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+      .filter(|line| finder.find(line).is_some())
+      .collect()
+  }
+
+  fn bytes_finder(&self) -> BytesFinder<'_> {
+    match self {
+      Search::String {
+        find,
+        ignore_case,
+        anchor,
+      } => BytesFinder::String {
+        find: find.as_bytes(),
+        ignore_case: *ignore_case,
+        anchor: *anchor,
+      },
+      Search::Regex { bytes, .. } => BytesFinder::Regex(bytes),
+    }
+  }
+}
+
+/// The number of matches, from [`Search::count_up_to`] or [`Search::count_in_reader`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchCount {
+  Exact(usize),
+
+  /// There are more matches than the limit, so counting stopped.
+  MoreThan(usize),
+}
+
+impl MatchCount {
+  fn new(count: usize, limit: usize) -> Self {
+    if count > limit {
+      MatchCount::MoreThan(limit)
+    } else {
+      MatchCount::Exact(count)
+    }
+  }
+}
+
+enum BytesFinder<'a> {
+  String {
+    find: &'a [u8],
+    ignore_case: bool,
+    anchor: LineAnchor,
+  },
+  Regex(&'a regex::bytes::Regex),
+}
+
+impl BytesFinder<'_> {
+  fn find(&self, data: &[u8]) -> Option<Range<usize>> {
+    self.find_from(data, 0)
+  }
+
+  /// Find the first match that starts at or after `start`. Line anchors look at the bytes before
+  /// `start`, so it's not the same as searching `&data[start..]`.
+  fn find_from(&self, data: &[u8], start: usize) -> Option<Range<usize>> {
+    match self {
+      BytesFinder::String {
+        find,
+        ignore_case,
+        anchor,
+      } => {
+        if find.is_empty() {
+          return None;
+        }
+
+        let pos = data[start..]
+          .windows(find.len())
+          .enumerate()
+          .position(|(pos, window)| {
+            let pos = start + pos;
+            let found = if *ignore_case {
+              window.eq_ignore_ascii_case(find)
+            } else {
+              window == *find
+            };
+            found && anchor.accepts(data, &(pos..pos + find.len()))
+          })?;
+        Some(start + pos..start + pos + find.len())
+      }
+      BytesFinder::Regex(regex) => {
+        let pos = regex.find_at(data, start)?;
+        Some(pos.start()..pos.end())
+      }
+    }
+  }
+
+  /// Count the matches in `data`, up to `limit`.
+  fn count(&self, data: &[u8], limit: usize) -> usize {
+    if let BytesFinder::Regex(regex) = self {
+      return regex.find_iter(data).take(limit).count();
+    }
+
+    let mut pos = 0;
+    let mut count = 0;
+    while count < limit {
+      let Some(found) = self.find_from(data, pos) else {
+        break;
+      };
+      pos = found.end;
+      count += 1;
+    }
+    count
+  }
+}
+
+/// Decode a line for display, replacing invalid UTF-8 sequences.
+pub fn decode_lossy(line: &[u8]) -> Cow<'_, str> {
+  String::from_utf8_lossy(line)
 }
 
 /// Return the byte distance between `text` and `sub`.
@@ -318,9 +1180,28 @@ pub fn offset(text: &str, sub: &str) -> Option<usize> {
   None
 }
 
-/// Get the system's locale.
+/// Get the locale for number formatting. As with other programs, `LC_ALL`, `LC_NUMERIC` and `LANG`
+/// take precedence over the system's locale.
 pub fn get_locale() -> Locale {
-  if let Some(name) = sys_locale::get_locale() {
+  let var = |name: &str| std::env::var(name).ok();
+  select_locale(var, sys_locale::get_locale)
+}
+
+/// Select the locale from the first of `LC_ALL`, `LC_NUMERIC` and `LANG` that's set, falling back to
+/// `system`.
+fn select_locale(
+  var: impl Fn(&str) -> Option<String>,
+  system: impl FnOnce() -> Option<String>,
+) -> Locale {
+  let name = ["LC_ALL", "LC_NUMERIC", "LANG"]
+    .into_iter()
+    .filter_map(var)
+    .find(|name| !name.is_empty())
+    .or_else(system);
+
+  if let Some(name) = name {
+    // Remove the encoding (".UTF-8") and modifier ("@euro").
+    let name = name.split(['.', '@']).next().unwrap_or_default();
     let name = name.replace('_', "-");
     let names = Locale::available_names();
     let uname = name.to_uppercase();
@@ -346,6 +1227,101 @@ pub fn get_locale() -> Locale {
   Locale::en
 }
 
+/// Compare text so that runs of digits are ordered by their numeric value ("Potion 2" before
+/// "Potion 10"). Everything else is compared ignoring case and accents, so "Äpfel" sorts with the
+/// other A words.
+pub fn natural_cmp(a: &str, b: &str) -> cmp::Ordering {
+  let (mut a_rest, mut b_rest) = (a, b);
+  loop {
+    let (ac, bc) = match (a_rest.chars().next(), b_rest.chars().next()) {
+      (Some(ac), Some(bc)) => (ac, bc),
+      // Break ties with a plain comparison so that the order is consistent.
+      (None, None) => return a.cmp(b),
+      (None, Some(_)) => return cmp::Ordering::Less,
+      (Some(_), None) => return cmp::Ordering::Greater,
+    };
+
+    if ac.is_ascii_digit() && bc.is_ascii_digit() {
+      let (a_num, a_next) = split_digits(a_rest);
+      let (b_num, b_next) = split_digits(b_rest);
+
+      // Compare by length and then digits so that long runs can't overflow.
+      let a_num = a_num.trim_start_matches('0');
+      let b_num = b_num.trim_start_matches('0');
+      let ord = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+      if ord.is_ne() {
+        return ord;
+      }
+
+      a_rest = a_next;
+      b_rest = b_next;
+    } else {
+      let ord = cmp_folded(ac, bc);
+      if ord.is_ne() {
+        return ord;
+      }
+
+      a_rest = &a_rest[ac.len_utf8()..];
+      b_rest = &b_rest[bc.len_utf8()..];
+    }
+  }
+}
+
+// Compare characters ignoring case and accents.
+fn cmp_folded(a: char, b: char) -> cmp::Ordering {
+  if a.is_ascii() && b.is_ascii() {
+    return a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase());
+  }
+
+  let fold = |ch: char| {
+    let base = std::iter::once(ch)
+      .nfd()
+      .filter(|ch| !is_combining_mark(*ch));
+    base.flat_map(char::to_lowercase)
+  };
+  fold(a).cmp(fold(b))
+}
+
+/// Split the leading digits from the rest of the text.
+fn split_digits(text: &str) -> (&str, &str) {
+  let len = text.find(|ch: char| !ch.is_ascii_digit());
+  text.split_at(len.unwrap_or(text.len()))
+}
+
+/// Format a number with a k, M or B suffix and up to two decimal places, e.g. "1.2M" or "3.45B".
+/// Numbers below 10,000 are formatted in full. Halves are rounded away from zero.
+pub fn abbreviate_i64(value: i64, locale: Locale) -> String {
+  const UNITS: [(u64, &str); 3] = [(1_000, "k"), (1_000_000, "M"), (1_000_000_000, "B")];
+
+  let abs = value.unsigned_abs();
+  if abs < 10_000 {
+    return value.to_formatted_string(&locale);
+  }
+
+  // Round to hundredths of the unit, moving up a unit if rounding reaches 1,000.
+  let mut rounded = 0;
+  let mut suffix = "";
+  for (div, unit) in UNITS {
+    let div = div as u128;
+    rounded = (abs as u128 * 100 + div / 2) / div;
+    suffix = unit;
+    if rounded < 100_000 {
+      break;
+    }
+  }
+
+  let whole = (rounded / 100) as u64;
+  let frac = format!("{:02}", rounded % 100);
+  let frac = frac.trim_end_matches('0');
+  let sign = if value < 0 { locale.minus_sign() } else { "" };
+  let whole = whole.to_formatted_string(&locale);
+  if frac.is_empty() {
+    format!("{sign}{whole}{suffix}")
+  } else {
+    format!("{sign}{whole}{}{frac}{suffix}", locale.decimal())
+  }
+}
+
 /// Replace a single occurrence of a comma or arabic decimal with a period.
 pub fn replace_decimal(text: &str) -> String {
   text.replacen([',', '\u{66b}'], ".", 1)
@@ -365,6 +1341,85 @@ pub fn timestamp_to_string(ts: Option<i64>) -> String {
   dt.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+/// Get the date from a log filename (`SotAChatLog_<avatar>_<YYYY-MM-DD>.txt`) as midnight of
+/// that day.
+pub fn parse_log_timestamp(name: &str) -> Option<NaiveDateTime> {
+  let name = name.strip_suffix(".txt").unwrap_or(name);
+  let (_, date) = name.rsplit_once('_')?;
+  let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+  date.and_hms_opt(0, 0, 0)
+}
+
+/// Inclusive date/time range for filtering files. Unbounded ends match everything.
+#[derive(Clone, Debug)]
+pub struct DateRange {
+  begin: Option<NaiveDateTime>,
+  end: Option<NaiveDateTime>,
+  include_unknown: bool,
+}
+
+impl Default for DateRange {
+  fn default() -> Self {
+    Self {
+      begin: None,
+      end: None,
+      include_unknown: true,
+    }
+  }
+}
+
+impl DateRange {
+  pub fn new(begin: Option<NaiveDateTime>, end: Option<NaiveDateTime>) -> Self {
+    Self {
+      begin,
+      end,
+      include_unknown: false,
+    }
+  }
+
+  /// Whether files with an unknown date are included.
+  pub fn with_unknown(mut self, include: bool) -> Self {
+    self.include_unknown = include;
+    self
+  }
+
+  #[must_use]
+  pub fn contains(&self, ts: NaiveDateTime) -> bool {
+    self.begin.is_none_or(|begin| ts >= begin) && self.end.is_none_or(|end| ts <= end)
+  }
+
+  /// Check a date that might not be known.
+  #[must_use]
+  pub fn accepts(&self, ts: Option<NaiveDateTime>) -> bool {
+    match ts {
+      Some(ts) => self.contains(ts),
+      None => self.include_unknown,
+    }
+  }
+
+  /// Check if any part of a day is within the range, for files that cover a whole day.
+  #[must_use]
+  pub fn contains_date(&self, date: NaiveDate) -> bool {
+    self.begin.is_none_or(|begin| date >= begin.date())
+      && self.end.is_none_or(|end| date <= end.date())
+  }
+
+  /// Check a day that might not be known.
+  #[must_use]
+  pub fn accepts_date(&self, date: Option<NaiveDate>) -> bool {
+    match date {
+      Some(date) => self.contains_date(date),
+      None => self.include_unknown,
+    }
+  }
+}
+
+/// Get a file's modification time.
+pub fn file_mtime(path: &Path) -> Option<NaiveDateTime> {
+  let time = fs::metadata(path).ok()?.modified().ok()?;
+  Some(DateTime::<Utc>::from(time).naive_utc())
+}
+
 /// Get the size (thickness) of a scrollbar.
 pub fn scroll_bar_size(ui: &Ui) -> f32 {
   let spacing = ui.spacing();
@@ -381,10 +1436,219 @@ pub fn text_size(ui: &Ui) -> f32 {
   TextStyle::Body.resolve(ui.style()).size
 }
 
+/// Read numbers from save-game JSON, which sometimes has them quoted (e.g. `"qn":"5"`). Quoted
+/// numbers can have whitespace around them, but must be nothing but the number.
+pub mod json_num {
+  use serde_json::Value;
+
+  pub fn to_i64(val: &Value) -> Option<i64> {
+    match val {
+      // Numbers keep their original text, which might be written as a float (e.g. `1440.0`).
+      Value::Number(num) => num.as_i64().or_else(|| whole(num.as_f64()?)),
+      Value::String(text) => {
+        let text = text.trim();
+        text.parse().ok().or_else(|| whole(parse_f64(text)?))
+      }
+      _ => None,
+    }
+  }
+
+  pub fn to_u64(val: &Value) -> Option<u64> {
+    match val {
+      Value::Number(num) => num.as_u64(),
+      Value::String(text) => text.trim().parse().ok(),
+      _ => None,
+    }
+    .or_else(|| u64::try_from(to_i64(val)?).ok())
+  }
+
+  /// Like [`to_i64`], but also accepts whole numbers with digit grouping, e.g. "1,234,567".
+  pub fn to_i64_grouped(val: &Value) -> Option<i64> {
+    to_i64(val).or_else(|| ungroup(val.as_str()?.trim())?.parse().ok())
+  }
+
+  pub fn to_f64(val: &Value) -> Option<f64> {
+    match val {
+      Value::Number(num) => num.as_f64(),
+      Value::String(text) => parse_f64(text.trim()),
+      _ => None,
+    }
+  }
+
+  // JSON numbers can't be NaN or infinite, so neither can quoted ones.
+  fn parse_f64(text: &str) -> Option<f64> {
+    text.parse().ok().filter(|val: &f64| val.is_finite())
+  }
+
+  // Remove the separators from a whole number with groups of three digits. The separator must be
+  // the same throughout, and periods aren't allowed since they could be a decimal point.
+  fn ungroup(text: &str) -> Option<String> {
+    let digits = |text: &str| !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit());
+    let (sign, text) = match text.strip_prefix('-') {
+      Some(rest) => ("-", rest),
+      None => ("", text),
+    };
+
+    let sep = text.chars().find(|ch| !ch.is_ascii_digit())?;
+    if !matches!(sep, ',' | '_' | '\'' | ' ' | '\u{a0}' | '\u{202f}') {
+      return None;
+    }
+
+    let mut groups = text.split(sep);
+    let first = groups.next()?;
+    if !digits(first) || first.len() > 3 {
+      return None;
+    }
+
+    let mut number = format!("{sign}{first}");
+    for group in groups {
+      if !digits(group) || group.len() != 3 {
+        return None;
+      }
+      number += group;
+    }
+    Some(number)
+  }
+
+  fn whole(val: f64) -> Option<i64> {
+    (val.fract() == 0.0 && val.abs() < i64::MAX as f64).then_some(val as i64)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  #[test]
+  fn test_json_num() {
+    use serde_json::json;
+    assert_eq!(json_num::to_i64(&json!(" 42 ")), Some(42));
+    assert_eq!(json_num::to_i64(&json!("-7.0")), Some(-7));
+    assert_eq!(json_num::to_i64(&json!("42x")), None);
+    assert_eq!(json_num::to_i64(&json!("1.5")), None);
+    assert_eq!(json_num::to_i64(&json!(true)), None);
+    assert_eq!(json_num::to_i64(&json!("1,234")), None);
+    assert_eq!(
+      json_num::to_i64_grouped(&json!("3,000,000,000")),
+      Some(3_000_000_000)
+    );
+    assert_eq!(json_num::to_i64_grouped(&json!("-1 234")), Some(-1234));
+    assert_eq!(json_num::to_i64_grouped(&json!("1\u{a0}234")), Some(1234));
+    assert_eq!(json_num::to_i64_grouped(&json!(" 42 ")), Some(42));
+    assert_eq!(json_num::to_i64_grouped(&json!("1.234")), None);
+    assert_eq!(json_num::to_i64_grouped(&json!("1,23")), None);
+    assert_eq!(json_num::to_i64_grouped(&json!("1234,567")), None);
+    assert_eq!(json_num::to_i64_grouped(&json!("1,234 567")), None);
+    assert_eq!(json_num::to_i64_grouped(&json!(",123")), None);
+    assert_eq!(
+      json_num::to_u64(&json!("18446744073709551615")),
+      Some(u64::MAX)
+    );
+    assert_eq!(json_num::to_u64(&json!(5.0)), Some(5));
+    assert_eq!(json_num::to_u64(&json!("-5")), None);
+    assert_eq!(json_num::to_f64(&json!("\t40.5\n")), Some(40.5));
+    assert_eq!(json_num::to_f64(&json!(40)), Some(40.0));
+    assert_eq!(json_num::to_f64(&json!("NaN")), None);
+    assert_eq!(json_num::to_f64(&json!("inf")), None);
+    assert_eq!(json_num::to_f64(&json!("")), None);
+  }
+
+  #[test]
+  fn test_select_locale() {
+    let select = |vars: &[(&str, &str)], system: Option<&str>| {
+      let var = |name: &str| {
+        let found = vars.iter().find(|(key, _)| *key == name);
+        found.map(|(_, val)| val.to_string())
+      };
+      select_locale(var, || system.map(String::from))
+    };
+
+    let system = Some("en-US");
+    assert_eq!(select(&[], system), Locale::en);
+    assert_eq!(select(&[], Some("fr-FR")), Locale::fr);
+    assert_eq!(select(&[("LANG", "de_DE.UTF-8")], system), Locale::de);
+
+    // LC_ALL is first, then LC_NUMERIC and LANG.
+    let vars = [
+      ("LANG", "fr_FR.UTF-8"),
+      ("LC_NUMERIC", "de_DE@euro"),
+      ("LC_ALL", "it_IT.UTF-8@euro"),
+    ];
+    assert_eq!(select(&vars, system), Locale::it);
+    assert_eq!(select(&vars[..2], system), Locale::de);
+
+    // Empty variables are ignored and unknown locales are English.
+    assert_eq!(
+      select(&[("LC_ALL", ""), ("LANG", "fr_FR")], system),
+      Locale::fr
+    );
+    assert_eq!(select(&[("LANG", "C.UTF-8")], Some("fr-FR")), Locale::en);
+    assert_eq!(select(&[], None), Locale::en);
+  }
+
+  #[test]
+  fn test_natural_cmp() {
+    use cmp::Ordering::{Equal, Greater, Less};
+    assert_eq!(
+      natural_cmp("Potion of Healing 2", "Potion of Healing 10"),
+      Less
+    );
+    assert_eq!(natural_cmp("potion 10", "Potion 9"), Greater);
+    assert_eq!(natural_cmp("Potion", "Potion 1"), Less);
+    assert_eq!(natural_cmp("a2b", "a2b"), Equal);
+
+    // Leading zeros don't change the value, but ties are broken consistently.
+    assert_eq!(natural_cmp("Item 007", "Item 8"), Less);
+    assert_eq!(natural_cmp("Item 007", "Item 7"), Less);
+    assert_eq!(natural_cmp("Item 7", "Item 007"), Greater);
+
+    // Longer than any integer type.
+    let big = "Item 123456789012345678901234567890";
+    let bigger = "Item 123456789012345678901234567891";
+    assert_eq!(natural_cmp(big, bigger), Less);
+    assert_eq!(natural_cmp("Item 99999999999999999999999", bigger), Less);
+
+    // Case is ignored for non-ASCII text too.
+    assert_eq!(natural_cmp("épée 2", "ÉPÉE 10"), Less);
+    assert_eq!(natural_cmp("Ölfass 3", "ölfass 20"), Less);
+    assert_eq!(natural_cmp("Äpfel", "Zwiebel"), Less);
+    assert_eq!(natural_cmp("Öl", "Pfeffer"), Less);
+    assert_eq!(natural_cmp("Epee", "Épée"), Less);
+    assert_eq!(natural_cmp("Éclair", "Epee"), Less);
+
+    let mut names = vec!["Potion 10", "potion 2", "Potion 1", "Elixir"];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(names, ["Elixir", "Potion 1", "potion 2", "Potion 10"]);
+  }
+
+  #[test]
+  fn test_abbreviate_i64() {
+    let values = [
+      (0, "0"),
+      (9_999, "9,999"),
+      (-9_999, "-9,999"),
+      (10_000, "10k"),
+      (12_345, "12.35k"),
+      (12_344, "12.34k"),
+      (-12_345, "-12.35k"),
+      (999_994, "999.99k"),
+      (999_995, "1M"),
+      (1_200_000, "1.2M"),
+      (3_450_000_000, "3.45B"),
+      (-1_234_567, "-1.23M"),
+      (999_999_999_999, "1,000B"),
+      (i64::MAX, "9,223,372,036.85B"),
+      (i64::MIN, "-9,223,372,036.85B"),
+    ];
+    for (value, text) in values {
+      assert_eq!(abbreviate_i64(value, Locale::en), text, "{value}");
+    }
+
+    assert_eq!(abbreviate_i64(9_999, Locale::de), "9.999");
+    assert_eq!(abbreviate_i64(1_200_000, Locale::de), "1,2M");
+    assert_eq!(abbreviate_i64(-12_345, Locale::de), "-12,35k");
+  }
+
   #[test]
   fn test_replace_decimal() {
     assert_eq!("123.4", replace_decimal("123.4"));
@@ -404,6 +1668,232 @@ mod tests {
     assert_eq!("123456789", remove_separators("123\u{a0}456\u{a0}789"));
   }
 
+  #[test]
+  fn test_level_for_exp() {
+    for table in [LEVEL_EXP, SKILL_EXP] {
+      // Each level starts at its entry and ends just before the next.
+      for (idx, &exp) in table.iter().enumerate() {
+        let lvl = idx as i32 + 1;
+        assert_eq!(level_for_exp(exp, table), lvl);
+        assert_eq!(exp_for_level(lvl, table), Some(exp));
+        if idx > 0 {
+          assert_eq!(level_for_exp(exp - 1, table), lvl - 1);
+        }
+      }
+
+      assert_eq!(level_for_exp(i64::MIN, table), 1);
+      assert_eq!(level_for_exp(-1, table), 1);
+      assert_eq!(level_for_exp(table[1] - 1, table), 1);
+      assert_eq!(level_for_exp(table[199] + 1, table), 200);
+      assert_eq!(level_for_exp(i64::MAX, table), 200);
+      assert_eq!(exp_for_level(0, table), None);
+      assert_eq!(exp_for_level(-1, table), None);
+      assert_eq!(exp_for_level(201, table), None);
+
+      // Fractional levels stay in their level, even just before the next one.
+      for (idx, &exp) in table.iter().enumerate().skip(1) {
+        let lvl = fractional_level(exp as f64 - 0.001, table);
+        assert_eq!(lvl.floor() as usize, idx, "{lvl}");
+        assert_eq!(fractional_level(exp as f64, table), idx as f64 + 1.0);
+      }
+      assert_eq!(fractional_level(-5.0, table), 1.0);
+      assert_eq!(fractional_level(table[199] as f64 * 2.0, table), 200.0);
+    }
+  }
+
+  #[test]
+  fn test_table_position() {
+    let pos = |index, fraction| TablePos { index, fraction };
+    let table = [0, 10, 20, 20, 20, 40];
+
+    // Below the first entry and above the last.
+    assert_eq!(table_position(i64::MIN, &table), pos(0, 0.0));
+    assert_eq!(table_position(-1, &table), pos(0, 0.0));
+    assert_eq!(table_position(40, &table), pos(5, 0.0));
+    assert_eq!(table_position(41, &table), pos(5, 1.0));
+    assert_eq!(table_position(i64::MAX, &table), pos(5, 1.0));
+
+    // Each entry starts a bucket, and the fraction grows through it.
+    assert_eq!(table_position(0, &table), pos(0, 0.0));
+    assert_eq!(table_position(5, &table), pos(0, 0.5));
+    assert_eq!(table_position(9, &table), pos(0, 0.9));
+    assert_eq!(table_position(10, &table), pos(1, 0.0));
+    assert_eq!(table_position(19, &table), pos(1, 0.9));
+
+    // Equal entries are empty buckets, so the value is after the last of them.
+    assert_eq!(table_position(20, &table), pos(4, 0.0));
+    assert_eq!(table_position(30, &table), pos(4, 0.5));
+
+    // Entries that are nearly equal.
+    let table = [0, i64::MAX - 1, i64::MAX];
+    assert_eq!(table_position(i64::MAX - 1, &table), pos(1, 0.0));
+    assert!(table_position(i64::MAX - 2, &table).fraction < 1.0);
+    assert_eq!(table_position(i64::MAX, &table), pos(2, 0.0));
+
+    assert_eq!(table_position(5, &[]), pos(0, 0.0));
+    assert_eq!(table_position(5, &[5]), pos(0, 0.0));
+    assert_eq!(table_position(6, &[5]), pos(0, 1.0));
+
+    // Every value in the real tables agrees with the level.
+    for table in [LEVEL_EXP, SKILL_EXP] {
+      for window in table.windows(2) {
+        for exp in [window[0], window[0] + 1, window[1] - 1] {
+          let pos = table_position(exp, table);
+          assert_eq!(pos.index as i32 + 1, level_for_exp(exp, table));
+          assert_eq!(table[pos.index], window[0]);
+          assert!((0.0..1.0).contains(&pos.fraction));
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_validate_exp_tables() {
+    assert_eq!(validate_exp_tables(), Ok(()));
+
+    let mut values = LEVEL_EXP_CURVE;
+    values[10] = values[9];
+    let result = validate_exp_table("test", &values);
+    assert_eq!(
+      result,
+      Err(TableError::Order {
+        table: "test",
+        index: 10
+      })
+    );
+
+    values[10] = -values[10];
+    let result = validate_exp_curve("test", &values, &LEVEL_EXP_CURVE);
+    let (expected, actual) = (LEVEL_EXP_CURVE[10], -LEVEL_EXP_CURVE[9]);
+    assert_eq!(
+      result,
+      Err(TableError::Curve {
+        table: "test",
+        index: 10,
+        expected,
+        actual
+      })
+    );
+
+    let result = validate_exp_table("test", &values[1..]);
+    assert_eq!(
+      result,
+      Err(TableError::Length {
+        table: "test",
+        len: 199
+      })
+    );
+  }
+
+  #[test]
+  fn test_exp_tables() {
+    let dir = std::env::temp_dir().join("cota_test_exp_tables");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    // Missing files use the built-in tables.
+    let tables = ExpTables::load_or_default(&dir).unwrap();
+    assert_eq!(tables.level(), LEVEL_EXP);
+    assert_eq!(tables.skill(), SKILL_EXP);
+
+    let skill: Vec<i64> = (0..200).map(|val| val * 20).collect();
+    fs::write(
+      dir.join("skill_exp.json"),
+      serde_json::to_string(&skill).unwrap(),
+    )
+    .unwrap();
+    let tables = ExpTables::load_or_default(&dir).unwrap();
+    assert_eq!(tables.level(), LEVEL_EXP);
+    assert_eq!(tables.skill(), skill);
+
+    // Too short.
+    fs::write(dir.join("level_exp.json"), "[0, 1000, 2100]").unwrap();
+    assert!(ExpTables::load_or_default(&dir).is_err());
+
+    // Not increasing.
+    let mut level = skill.clone();
+    level.swap(5, 6);
+    fs::write(
+      dir.join("level_exp.json"),
+      serde_json::to_string(&level).unwrap(),
+    )
+    .unwrap();
+    let err = ExpTables::load_or_default(&dir).err().unwrap();
+    assert!(err.ends_with("level_exp.json[6] is not greater than the previous value"));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_progress_sink() {
+    let mut state = AppState::default();
+    assert_eq!(state.progress(), None);
+    assert!(!report_progress(Some(&state), 1, 3, "Barugon"));
+    let progress = Progress::new(1, 3, "Barugon");
+    assert_eq!(state.progress(), Some(progress.clone()));
+
+    state.cancel_progress();
+    assert!(report_progress(Some(&state), 2, 3, "Other"));
+    state.clear_progress();
+    assert_eq!(state.progress(), None);
+    assert!(!state.is_canceled());
+
+    let (tx, rx) = mpsc::channel();
+    assert!(!report_progress(Some(&tx), 1, 3, "Barugon"));
+    assert_eq!(rx.try_recv(), Ok(progress));
+    assert!(!report_progress(None, 1, 3, ""));
+
+    let err = BatchError::<()>::Failed(Cow::from("Invalid"));
+    assert_eq!(Cow::from(err), "Invalid");
+    assert_eq!(
+      Cow::from(BatchError::Canceled(())),
+      "The operation was canceled"
+    );
+  }
+
+  #[test]
+  fn test_limits() {
+    let dir = std::env::temp_dir().join("cota_test_limits");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let tables = ExpTables::default();
+    assert_eq!(Limits::for_tables(&tables), Limits::default());
+    assert_eq!(
+      Limits::load_or_default(&dir, &tables),
+      Ok(Limits::default())
+    );
+
+    // Longer tables raise the level caps.
+    let level: Vec<i64> = (0..250).map(|val| val * 1000).collect();
+    fs::write(
+      dir.join("level_exp.json"),
+      serde_json::to_string(&level).unwrap(),
+    )
+    .unwrap();
+    let tables = ExpTables::load_or_default(&dir).unwrap();
+    let limits = Limits::load_or_default(&dir, &tables).unwrap();
+    assert_eq!(limits.level, 1..=250);
+    assert_eq!(limits.skill_level, SKILL_LVL_RANGE);
+
+    // The file can lower the caps, but not raise them past the tables.
+    let path = dir.join("limits.json");
+    fs::write(&path, r#"{"max_level": 220, "max_gold": 1000}"#).unwrap();
+    let limits = Limits::load_or_default(&dir, &tables).unwrap();
+    assert_eq!(limits.level, 1..=220);
+    assert_eq!(limits.gold, 1000);
+
+    fs::write(&path, r#"{"max_skill_level": 201}"#).unwrap();
+    let err = Limits::load_or_default(&dir, &tables).err().unwrap();
+    assert!(err.ends_with("max_skill_level is 201, expected 1-200"));
+    fs::write(&path, r#"{"max_gold": -1}"#).unwrap();
+    assert!(Limits::load_or_default(&dir, &tables).is_err());
+    fs::write(&path, r#"{"level": 250}"#).unwrap();
+    assert!(Limits::load_or_default(&dir, &tables).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
   #[test]
   fn test_find_ignore_case() {
     let text = "Test for 'tschüß' in this text";
@@ -429,4 +1919,262 @@ mod tests {
     let result = find_ignore_case(text, "abc");
     assert_eq!(result, Some(8..11));
   }
+
+  #[test]
+  fn test_app_state() {
+    let state = AppState::default();
+    assert_eq!(state.generation(), 0);
+
+    let mut worker = state.clone();
+    let path = PathBuf::from("SaveGame.sota");
+    let thread = {
+      let path = path.clone();
+      std::thread::spawn(move || {
+        for _ in 0..100 {
+          worker.set_dirty(true);
+          worker.set_dirty(false);
+        }
+        worker.set_current_file(Some(path.clone()));
+        worker.set_current_file(Some(path));
+        worker.set_dirty(true);
+      })
+    };
+
+    let mut last = state.generation();
+    while !thread.is_finished() {
+      let generation = state.generation();
+      assert!(generation >= last);
+      last = generation;
+    }
+    thread.join().unwrap();
+
+    // Setting the same value again doesn't bump the generation.
+    assert_eq!(state.generation(), 202);
+    assert_eq!(state.current_file(), Some(path));
+    assert!(state.is_dirty());
+  }
+
+  #[test]
+  fn test_date_range() {
+    let ts = parse_log_timestamp("SotAChatLog_Sir Barugon_2024-01-02.txt").unwrap();
+    assert_eq!(ts.to_string(), "2024-01-02 00:00:00");
+    assert!(parse_log_timestamp("SotAChatLog_Barugon.txt").is_none());
+
+    // Both ends are inclusive, right down to midnight.
+    let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+    let range = DateRange::new(day(2).and_hms_opt(0, 0, 0), day(3).and_hms_opt(0, 0, 0));
+    assert!(range.contains(ts));
+    assert!(range.contains(day(3).and_hms_opt(0, 0, 0).unwrap()));
+    assert!(!range.contains(day(3).and_hms_opt(0, 0, 1).unwrap()));
+    assert!(!range.contains(day(1).and_hms_opt(23, 59, 59).unwrap()));
+
+    assert!(!range.accepts(None));
+    assert!(range.with_unknown(true).accepts(None));
+    assert!(DateRange::default().accepts(Some(ts)));
+
+    // A day is in the range if any part of it is.
+    let range = DateRange::new(day(2).and_hms_opt(10, 0, 0), day(3).and_hms_opt(9, 0, 0));
+    assert!(!range.contains(ts));
+    assert!(range.contains_date(day(2)));
+    assert!(range.contains_date(day(3)));
+    assert!(!range.contains_date(day(1)));
+    assert!(!range.contains_date(day(4)));
+    assert!(!range.accepts_date(None));
+  }
+
+  #[test]
+  fn test_find_in_bytes() {
+    let data = b"\xffabc\r\nxyz ABC\n\xfe\xfd";
+    let search = Search::String {
+      find: "abc".into(),
+      ignore_case: false,
+      anchor: LineAnchor::None,
+    };
+    assert_eq!(search.find_in_bytes(data), Some(1..4));
+    assert_eq!(search.find_lines_in_bytes(data), vec![&b"\xffabc"[..]]);
+
+    let search = Search::String {
+      find: "abc".into(),
+      ignore_case: true,
+      anchor: LineAnchor::None,
+    };
+    assert_eq!(search.find_lines_in_bytes(data).len(), 2);
+
+    let search = Search::regex("z +A").unwrap();
+    assert_eq!(search.find_in_bytes(data), Some(8..11));
+    let lines = search.find_lines_in_bytes(data);
+    assert_eq!(lines, vec![&b"xyz ABC"[..]]);
+    assert_eq!(decode_lossy(b"\xffabc"), "\u{fffd}abc");
+  }
+
+  #[test]
+  fn test_count_matches() {
+    // Make up texts and compare the counts with the matches that are found.
+    let mut seed = 12345u32;
+    let mut random = |max: usize| {
+      seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+      (seed >> 16) as usize % max
+    };
+
+    let alphabets: [&[char]; 2] = [
+      &['a', 'A', 'b', ' ', '\n', '\u{e9}', '\u{c9}', '\u{df}', 'S'],
+      &['a', 'A', 'b', ' ', '\n'],
+    ];
+    let literals = ["a", "aA", "\u{e9}", "b a", "ss", ""];
+    let patterns = ["a+", "[ab]", "(?i)\u{e9}", "(?m)^a", "a*", "b?"];
+    let mut searches = Vec::new();
+    for find in literals {
+      for ignore_case in [false, true] {
+        for anchor in LineAnchor::ALL {
+          let find = find.into();
+          searches.push(Search::String {
+            find,
+            ignore_case,
+            anchor,
+          });
+        }
+      }
+    }
+    for pattern in patterns {
+      searches.push(Search::regex(pattern).unwrap());
+    }
+
+    for _ in 0..200 {
+      let ascii = random(2) == 1;
+      let alphabet = alphabets[ascii as usize];
+      let len = random(40);
+      let text: String = (0..len).map(|_| alphabet[random(alphabet.len())]).collect();
+      for search in &searches {
+        let found = search.find_all(&text);
+        assert_eq!(search.count_in(&text), found.len(), "{text:?}");
+        assert_eq!(
+          search.count_up_to(&text, found.len()),
+          MatchCount::Exact(found.len())
+        );
+        if let Some(limit) = found.len().checked_sub(1) {
+          assert_eq!(
+            search.count_up_to(&text, limit),
+            MatchCount::MoreThan(limit)
+          );
+        }
+
+        // Lines are searched separately, and case is only ignored for ASCII, when streaming.
+        if ascii {
+          let lines = text.lines().map(|line| search.count_in(line)).sum();
+          let count = search.count_in_reader(text.as_bytes(), None).unwrap();
+          assert_eq!(count, MatchCount::Exact(lines), "{text:?}");
+          if lines > 0 {
+            let count = search.count_in_reader(text.as_bytes(), Some(lines - 1));
+            assert_eq!(count.unwrap(), MatchCount::MoreThan(lines - 1));
+          }
+        }
+      }
+    }
+
+    let search = Search::String {
+      find: "aa".into(),
+      ignore_case: false,
+      anchor: LineAnchor::None,
+    };
+    assert_eq!(search.find_all("aaaaa"), [0..2, 2..4]);
+  }
+
+  #[test]
+  fn test_line_anchor() {
+    let search = |find: &str, ignore_case, anchor| Search::String {
+      find: find.into(),
+      ignore_case,
+      anchor,
+    };
+
+    // The first and last lines don't have line breaks around them.
+    let text = "gold\r\nold gold\nGold\r\ngolden\nold";
+    let start = search("gold", false, LineAnchor::Start);
+    assert_eq!(start.find_all(text), [0..4, 21..25]);
+    assert_eq!(search("GOLD", true, LineAnchor::Start).count_in(text), 3);
+
+    // Matches before "\r\n" are at the end of their lines.
+    let end = search("old", false, LineAnchor::End);
+    assert_eq!(end.find_all(text), [1..4, 11..14, 16..19, 28..31]);
+    let whole = search("gold", true, LineAnchor::WholeLine);
+    assert_eq!(whole.find_all(text), [0..4, 15..19]);
+    assert_eq!(whole.find_in("golden gold"), None);
+
+    // A rejected match doesn't hide an overlapping one.
+    let end = search("aa", false, LineAnchor::End);
+    assert_eq!(end.find_in("aaa"), Some(1..3));
+
+    // The bytes searches agree.
+    let data = text.as_bytes();
+    assert_eq!(whole.find_in_bytes(data), Some(0..4));
+    let lines = whole.find_lines_in_bytes(data);
+    assert_eq!(lines, vec![&b"gold"[..], &b"Gold"[..]]);
+    assert_eq!(end.find_in_bytes(b"aaa\r\n"), Some(1..3));
+    let count = start.count_in_reader(data, None).unwrap();
+    assert_eq!(count, MatchCount::Exact(2));
+  }
+
+  #[test]
+  fn test_regex_options() {
+    use crate::save_builder::SaveBuilder;
+
+    // Anchor on a record's line.
+    let text = SaveBuilder::new().gold(1234).build();
+    let text = text.replace("<record", "\n  <record");
+    let pattern = r#"^\s*<record Id="000000000000000000000001">\{"g":\d+\}"#;
+    let search = Search::regex(pattern).unwrap();
+    assert_eq!(search.find_in(&text), None);
+    assert_eq!(search.find_in_bytes(text.as_bytes()), None);
+
+    let options = RegexOptions {
+      multi_line: true,
+      ..Default::default()
+    };
+    let search = Search::regex_with(pattern, options).unwrap();
+    let found = search.find_in(&text).unwrap();
+    assert!(text[found.clone()].ends_with(r#"{"g":1234}"#));
+    assert_eq!(search.find_in_bytes(text.as_bytes()), Some(found));
+    assert_eq!(search.find_lines_in_bytes(text.as_bytes()).len(), 1);
+
+    // Match across lines, ignoring case.
+    let options = RegexOptions {
+      dot_matches_new_line: true,
+      case_insensitive: true,
+      ..Default::default()
+    };
+    let search = Search::regex_with("<DATA>.*</data>", options).unwrap();
+    assert!(search.find_in(&text).is_some());
+    assert!(search.find_in_bytes(text.as_bytes()).is_some());
+    assert!(Search::regex("<DATA>.*</data>")
+      .unwrap()
+      .find_in(&text)
+      .is_none());
+
+    // Without Unicode, word characters are ASCII only.
+    let options = RegexOptions {
+      unicode: false,
+      ..Default::default()
+    };
+    assert_eq!(options.flags(), "(?-u)");
+    let search = Search::regex_with(r"\w+", options).unwrap();
+    assert_eq!(search.find_in("\u{e9}1"), Some(2..3));
+    assert_eq!(search.find_in_bytes("\u{e9}1".as_bytes()), Some(2..3));
+    let search = Search::regex(r"\w+").unwrap();
+    assert_eq!(search.find_in("\u{e9}1"), Some(0..3));
+
+    let options = RegexOptions {
+      multi_line: true,
+      dot_matches_new_line: true,
+      case_insensitive: true,
+      unicode: false,
+    };
+    assert_eq!(options.flags(), "(?ims-u)");
+    let json = serde_json::to_string(&options).unwrap();
+    assert_eq!(
+      serde_json::from_str::<RegexOptions>(&json).unwrap(),
+      options
+    );
+    let options: RegexOptions = serde_json::from_str("{}").unwrap();
+    assert_eq!(options, RegexOptions::default());
+  }
 }